@@ -48,7 +48,12 @@ pin_project! {
 }
 
 /// Converts any `impl Body` into a `impl Stream` of just its DATA frames.
-#[cfg(any(feature = "stream", feature = "multipart",))]
+#[cfg(any(
+    feature = "stream",
+    feature = "multipart",
+    feature = "sse",
+    feature = "json"
+))]
 pub(crate) struct DataStream<B>(pub(crate) B);
 
 impl Body {
@@ -96,7 +101,16 @@ impl Body {
         Body::stream(stream)
     }
 
-    #[cfg(any(feature = "stream", feature = "multipart", feature = "blocking"))]
+    #[cfg(any(
+        feature = "stream",
+        feature = "multipart",
+        feature = "blocking",
+        feature = "json",
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
     pub(crate) fn stream<S>(stream: S) -> Body
     where
         S: futures_core::stream::TryStream + Send + 'static,
@@ -433,7 +447,12 @@ where
 
 // ===== impl DataStream =====
 
-#[cfg(any(feature = "stream", feature = "multipart",))]
+#[cfg(any(
+    feature = "stream",
+    feature = "multipart",
+    feature = "sse",
+    feature = "json"
+))]
 impl<B> futures_core::Stream for DataStream<B>
 where
     B: HttpBody<Data = Bytes> + Unpin,
@@ -2,22 +2,59 @@
 use std::any::Any;
 use std::future::Future;
 use std::net::IpAddr;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
-use std::task::{Context, Poll};
-use std::time::Duration;
+#[cfg(unix)]
+use std::sync::Mutex;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, convert::TryInto, net::SocketAddr};
 use std::{fmt, str};
 
-use super::decoder::Accepts;
+use super::body::ResponseBody;
+use super::decoder::{Accepts, Limits};
+#[cfg(any(
+    feature = "gzip",
+    feature = "brotli",
+    feature = "zstd",
+    feature = "deflate"
+))]
+use super::encoder::Encoding;
+use super::progress;
 use super::request::{Request, RequestBuilder};
 use super::response::Response;
+use super::resume::{RawResponseFuture, Resume};
 use super::Body;
 #[cfg(feature = "http3")]
+use crate::async_impl::h3_client::alt_svc::{AltSvcCache, Alternative};
+#[cfg(feature = "http3")]
 use crate::async_impl::h3_client::connect::{H3ClientConfig, H3Connector};
 #[cfg(feature = "http3")]
-use crate::async_impl::h3_client::{H3Client, H3ResponseFuture};
-use crate::config::{RequestConfig, RequestTimeout};
+use crate::async_impl::h3_client::{AltResponse, H3Client};
+#[cfg(feature = "digest-auth")]
+use crate::auth::digest;
+use crate::auth::CredentialProvider;
+use crate::cache::{self, CacheService};
+#[cfg(any(
+    feature = "gzip",
+    feature = "brotli",
+    feature = "zstd",
+    feature = "deflate"
+))]
+use crate::config::RequestCompression;
+#[cfg(feature = "digest-auth")]
+use crate::config::RequestDigestAuth;
+#[cfg(feature = "websocket")]
+use crate::config::RequestHttp1Only;
+#[cfg(unix)]
+use crate::config::RequestUnixSocket;
+use crate::config::{
+    RequestConfig, RequestDownloadProgress, RequestMaxResponseBodySize, RequestResumable,
+    RequestRetry, RequestTimeout, RequestUploadProgress,
+};
 use crate::connect::{
     sealed::{Conn, Unnameable},
     BoxedConnectorLayer, BoxedConnectorService, Connector, ConnectorBuilder,
@@ -26,15 +63,23 @@ use crate::connect::{
 use crate::cookie;
 #[cfg(feature = "hickory-dns")]
 use crate::dns::hickory::HickoryDnsResolver;
-use crate::dns::{gai::GaiResolver, DnsResolverWithOverrides, DynResolver, Resolve};
+use crate::dns::{gai::GaiResolver, DnsResolverWithOverrides, DynResolver, IpFamily, Resolve};
 use crate::error::{self, BoxError};
+use crate::event::{self, EventListener, ResponseTimings};
+#[cfg(feature = "har")]
+use crate::har::{self, HarRecorder};
 use crate::into_url::try_uri;
 use crate::proxy::Matcher as ProxyMatcher;
 use crate::redirect::{self, remove_sensitive_headers};
+#[cfg(feature = "replay")]
+use crate::replay::{Cassette, ReplayService};
+use crate::retry;
 #[cfg(feature = "__rustls")]
 use crate::tls::CertificateRevocationList;
 #[cfg(feature = "__tls")]
 use crate::tls::{self, TlsBackend};
+#[cfg(feature = "tracing")]
+use crate::trace;
 #[cfg(feature = "__tls")]
 use crate::Certificate;
 #[cfg(any(feature = "native-tls", feature = "__rustls"))]
@@ -42,14 +87,16 @@ use crate::Identity;
 use crate::{IntoUrl, Method, Proxy, StatusCode, Url};
 
 use bytes::Bytes;
+use futures_util::future;
 use http::header::{
-    Entry, HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH,
-    CONTENT_TYPE, LOCATION, PROXY_AUTHORIZATION, RANGE, REFERER, TRANSFER_ENCODING, USER_AGENT,
+    Entry, HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_ENCODING,
+    CONTENT_LENGTH, CONTENT_TYPE, LOCATION, PROXY_AUTHORIZATION, RANGE, REFERER, TRANSFER_ENCODING,
+    USER_AGENT,
 };
 use http::uri::Scheme;
-use http::Uri;
-use hyper_util::client::legacy::connect::HttpConnector;
-use log::debug;
+use http::{Extensions, Uri, Version};
+use hyper_util::client::legacy::connect::{capture_connection, HttpConnector};
+use log::{debug, trace};
 #[cfg(feature = "default-tls")]
 use native_tls_crate::TlsConnector;
 use pin_project_lite::pin_project;
@@ -58,10 +105,8 @@ use quinn::TransportConfig;
 #[cfg(feature = "http3")]
 use quinn::VarInt;
 use tokio::time::Sleep;
-use tower::util::BoxCloneSyncServiceLayer;
-use tower::{Layer, Service};
-
-type HyperResponseFuture = hyper_util::client::legacy::ResponseFuture;
+use tower::util::{BoxCloneSyncService, BoxCloneSyncServiceLayer};
+use tower::{Layer, Service, ServiceExt};
 
 /// An asynchronous `Client` to make Requests with.
 ///
@@ -108,6 +153,11 @@ struct Config {
     tls_sni: bool,
     connect_timeout: Option<Duration>,
     connection_verbose: bool,
+    event_listener: Option<Arc<dyn EventListener>>,
+    #[cfg(feature = "tracing")]
+    trace_propagator: Option<Arc<dyn trace::Propagator>>,
+    #[cfg(feature = "har")]
+    har_recorder: Option<Arc<HarRecorder>>,
     pool_idle_timeout: Option<Duration>,
     pool_max_idle_per_host: usize,
     tcp_keepalive: Option<Duration>,
@@ -119,8 +169,25 @@ struct Config {
     auto_sys_proxy: bool,
     redirect_policy: redirect::Policy,
     referer: bool,
+    retry_policy: retry::Policy,
+    #[cfg(feature = "digest-auth")]
+    digest_auth: Option<digest::Credentials>,
+    credential_provider: Option<Arc<dyn CredentialProvider>>,
+    cache: Option<cache::Cache>,
+    #[cfg(feature = "replay")]
+    replay: Option<Cassette>,
     read_timeout: Option<Duration>,
     timeout: Option<Duration>,
+    max_response_body_size: Option<u64>,
+    max_decompressed_size: Option<u64>,
+    max_compression_ratio: Option<u32>,
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    compress: Option<Encoding>,
     #[cfg(feature = "__tls")]
     root_certs: Vec<Certificate>,
     #[cfg(feature = "__tls")]
@@ -140,6 +207,8 @@ struct Config {
     #[cfg(feature = "__tls")]
     tls: TlsBackend,
     connector_layers: Vec<BoxedConnectorLayer>,
+    layers: Vec<BoxedClientLayer>,
+    transport: Option<BoxedTransport>,
     http_version_pref: HttpVersionPref,
     http09_responses: bool,
     http1_title_case_headers: bool,
@@ -176,6 +245,8 @@ struct Config {
         target_os = "watchos",
     ))]
     interface: Option<String>,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
     nodelay: bool,
     #[cfg(feature = "cookies")]
     cookie_store: Option<Arc<dyn cookie::CookieStore>>,
@@ -198,8 +269,12 @@ struct Config {
     h3_max_field_section_size: Option<u64>,
     #[cfg(feature = "http3")]
     h3_send_grease: Option<bool>,
+    #[cfg(feature = "http3")]
+    h3_alt_svc: bool,
     dns_overrides: HashMap<String, Vec<SocketAddr>>,
     dns_resolver: Option<Arc<dyn Resolve>>,
+    ip_family: IpFamily,
+    happy_eyeballs_timeout: Option<Duration>,
 }
 
 impl Default for ClientBuilder {
@@ -229,6 +304,11 @@ impl ClientBuilder {
                 tls_sni: true,
                 connect_timeout: None,
                 connection_verbose: false,
+                event_listener: None,
+                #[cfg(feature = "tracing")]
+                trace_propagator: None,
+                #[cfg(feature = "har")]
+                har_recorder: None,
                 pool_idle_timeout: Some(Duration::from_secs(90)),
                 pool_max_idle_per_host: usize::MAX,
                 // TODO: Re-enable default duration once hyper's HttpConnector is fixed
@@ -240,8 +320,25 @@ impl ClientBuilder {
                 auto_sys_proxy: true,
                 redirect_policy: redirect::Policy::default(),
                 referer: true,
+                retry_policy: retry::Policy::default(),
+                #[cfg(feature = "digest-auth")]
+                digest_auth: None,
+                credential_provider: None,
+                cache: None,
+                #[cfg(feature = "replay")]
+                replay: None,
                 read_timeout: None,
                 timeout: None,
+                max_response_body_size: None,
+                max_decompressed_size: None,
+                max_compression_ratio: None,
+                #[cfg(any(
+                    feature = "gzip",
+                    feature = "brotli",
+                    feature = "zstd",
+                    feature = "deflate"
+                ))]
+                compress: None,
                 #[cfg(feature = "__tls")]
                 root_certs: Vec::new(),
                 #[cfg(feature = "__tls")]
@@ -263,6 +360,8 @@ impl ClientBuilder {
                 #[cfg(feature = "__tls")]
                 tls: TlsBackend::default(),
                 connector_layers: Vec::new(),
+                layers: Vec::new(),
+                transport: None,
                 http_version_pref: HttpVersionPref::All,
                 http09_responses: false,
                 http1_title_case_headers: false,
@@ -299,6 +398,8 @@ impl ClientBuilder {
                     target_os = "watchos",
                 ))]
                 interface: None,
+                #[cfg(unix)]
+                unix_socket: None,
                 nodelay: true,
                 hickory_dns: cfg!(feature = "hickory-dns"),
                 #[cfg(feature = "cookies")]
@@ -321,7 +422,11 @@ impl ClientBuilder {
                 h3_max_field_section_size: None,
                 #[cfg(feature = "http3")]
                 h3_send_grease: None,
+                #[cfg(feature = "http3")]
+                h3_alt_svc: false,
                 dns_resolver: None,
+                ip_family: IpFamily::Any,
+                happy_eyeballs_timeout: Some(Duration::from_millis(300)),
             },
         }
     }
@@ -369,6 +474,8 @@ impl ClientBuilder {
                 ));
             }
             DynResolver::new(resolver)
+                .with_event_listener(config.event_listener.clone())
+                .with_ip_family(config.ip_family)
         };
 
         let mut connector_builder = {
@@ -379,6 +486,7 @@ impl ClientBuilder {
 
             let mut http = HttpConnector::new_with_resolver(resolver.clone());
             http.set_connect_timeout(config.connect_timeout);
+            http.set_happy_eyeballs_timeout(config.happy_eyeballs_timeout);
 
             #[cfg(all(feature = "http3", feature = "__rustls"))]
             let build_h3_connector =
@@ -392,7 +500,13 @@ impl ClientBuilder {
                  h3_max_field_section_size,
                  h3_send_grease,
                  local_address,
+                 happy_eyeballs_timeout,
                  http_version_pref: &HttpVersionPref| {
+                    // the TLS config is shared with TCP connections, which
+                    // may negotiate other protocols
+                    let mut tls: rustls::ClientConfig = tls;
+                    tls.alpn_protocols = vec!["h3".into()];
+
                     let mut transport_config = TransportConfig::default();
 
                     if let Some(max_idle_timeout) = quic_max_idle_timeout {
@@ -432,6 +546,7 @@ impl ClientBuilder {
                         resolver,
                         tls,
                         local_address,
+                        happy_eyeballs_timeout,
                         transport_config,
                         h3_client_config,
                     );
@@ -567,6 +682,7 @@ impl ClientBuilder {
                             config.h3_max_field_section_size,
                             config.h3_send_grease,
                             config.local_address,
+                            config.happy_eyeballs_timeout,
                             &config.http_version_pref,
                         )?;
                     }
@@ -772,6 +888,7 @@ impl ClientBuilder {
                             config.h3_max_field_section_size,
                             config.h3_send_grease,
                             config.local_address,
+                            config.happy_eyeballs_timeout,
                             &config.http_version_pref,
                         )?;
                     }
@@ -831,6 +948,7 @@ impl ClientBuilder {
 
         connector_builder.set_timeout(config.connect_timeout);
         connector_builder.set_verbose(config.connection_verbose);
+        connector_builder.set_event_listener(config.event_listener.clone());
         connector_builder.set_keepalive(config.tcp_keepalive);
         connector_builder.set_keepalive_interval(config.tcp_keepalive_interval);
         connector_builder.set_keepalive_retries(config.tcp_keepalive_retries);
@@ -902,26 +1020,112 @@ impl ClientBuilder {
 
         let proxies_maybe_http_auth = proxies.iter().any(|p| p.maybe_has_http_auth());
 
+        #[cfg(unix)]
+        let unix_clients = UnixClients {
+            builder: builder.clone(),
+            connector: connector_builder.clone(),
+            connector_layers: config.connector_layers.clone(),
+            clients: Arc::default(),
+        };
+
+        #[cfg(feature = "websocket")]
+        let hyper_http1 = {
+            #[cfg_attr(not(feature = "http2"), allow(unused_mut))]
+            let mut builder = builder.clone();
+            #[cfg(feature = "http2")]
+            builder.http2_only(false);
+            let mut connector = connector_builder.clone();
+            connector.set_http1_only();
+            builder.build(connector.build(config.connector_layers.clone()))
+        };
+
+        let hyper_service = HyperService {
+            // Use match instead of map since config is partially moved,
+            // and it cannot be used in closure
+            #[cfg(feature = "http3")]
+            h3_client: match h3_connector {
+                Some(h3_connector) => Some(H3Client::new(h3_connector, config.pool_idle_timeout)),
+                None => None,
+            },
+            #[cfg(feature = "http3")]
+            alt_svc: if config.h3_alt_svc {
+                Some(AltSvcCache::default())
+            } else {
+                None
+            },
+            #[cfg(feature = "http3")]
+            proxies: proxies.clone(),
+            hyper: builder.build(connector_builder.build(config.connector_layers)),
+            #[cfg(feature = "websocket")]
+            hyper_http1,
+            transport: config.transport,
+            #[cfg(unix)]
+            unix_socket: RequestConfig::new(config.unix_socket.clone()),
+            #[cfg(unix)]
+            unix_clients,
+            accepts: config.accepts,
+            read_timeout: config.read_timeout,
+            max_response_body_size: RequestConfig::new(config.max_response_body_size),
+            limits: Limits {
+                body_size: None,
+                decompressed_size: config.max_decompressed_size,
+                compression_ratio: config.max_compression_ratio,
+            },
+            event_listener: config.event_listener.clone(),
+            #[cfg(feature = "har")]
+            har_recorder: config.har_recorder,
+        };
+
+        let simple = config.layers.is_empty() && config.cache.is_none();
+        #[cfg(feature = "replay")]
+        let simple = simple && config.replay.is_none();
+
+        let service = if simple {
+            ClientService::Simple(Box::new(hyper_service))
+        } else {
+            let mut service = BoxCloneSyncService::new(hyper_service);
+            #[cfg(feature = "replay")]
+            if let Some(cassette) = config.replay {
+                service = BoxCloneSyncService::new(ReplayService::new(cassette, service));
+            }
+            if let Some(cache) = config.cache {
+                service = BoxCloneSyncService::new(CacheService::new(cache, service));
+            }
+            for layer in config.layers {
+                service = layer.layer(service);
+            }
+            ClientService::WithLayers(service)
+        };
+
         Ok(Client {
             inner: Arc::new(ClientRef {
                 accepts: config.accepts,
                 #[cfg(feature = "cookies")]
                 cookie_store: config.cookie_store,
-                // Use match instead of map since config is partially moved,
-                // and it cannot be used in closure
-                #[cfg(feature = "http3")]
-                h3_client: match h3_connector {
-                    Some(h3_connector) => {
-                        Some(H3Client::new(h3_connector, config.pool_idle_timeout))
-                    }
-                    None => None,
-                },
-                hyper: builder.build(connector_builder.build(config.connector_layers)),
+                service,
                 headers: config.headers,
                 redirect_policy: config.redirect_policy,
                 referer: config.referer,
+                retry_policy: RequestConfig::new(Some(config.retry_policy)),
+                #[cfg(feature = "digest-auth")]
+                digest_auth: RequestConfig::new(config.digest_auth),
+                #[cfg(feature = "digest-auth")]
+                digest_challenges: digest::Challenges::default(),
+                credential_provider: config.credential_provider,
+                event_listener: config.event_listener,
+                #[cfg(feature = "tracing")]
+                trace_propagator: config.trace_propagator,
                 read_timeout: config.read_timeout,
                 request_timeout: RequestConfig::new(config.timeout),
+                #[cfg(any(
+                    feature = "gzip",
+                    feature = "brotli",
+                    feature = "zstd",
+                    feature = "deflate"
+                ))]
+                compress: RequestConfig::new(config.compress),
+                #[cfg(unix)]
+                unix_socket: RequestConfig::new(config.unix_socket),
                 proxies,
                 proxies_maybe_http_auth,
                 https_only: config.https_only,
@@ -1204,6 +1408,42 @@ impl ClientBuilder {
         }
     }
 
+    /// Compress request bodies with `encoding`, and set their
+    /// `Content-Encoding` header.
+    ///
+    /// A body with a known length keeps a `Content-Length` of its
+    /// compressed length, while a streaming body is sent chunked. Requests
+    /// that already have a `Content-Encoding` header are sent as is.
+    ///
+    /// This can be overridden for a request using
+    /// `RequestBuilder::compress()`.
+    ///
+    /// Default is to not compress request bodies.
+    ///
+    /// # Optional
+    ///
+    /// This requires one of the optional `gzip`, `brotli`, `zstd` or
+    /// `deflate` features to be enabled.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(any(
+            feature = "gzip",
+            feature = "brotli",
+            feature = "zstd",
+            feature = "deflate"
+        )))
+    )]
+    pub fn compress(mut self, encoding: Encoding) -> ClientBuilder {
+        self.config.compress = Some(encoding);
+        self
+    }
+
     // Redirect options
 
     /// Set a `RedirectPolicy` for this client.
@@ -1222,6 +1462,95 @@ impl ClientBuilder {
         self
     }
 
+    // Retry options
+
+    /// Set a `retry::Policy` for this client.
+    ///
+    /// Default only retries requests that the server refused before
+    /// processing them, up to a maximum of 2 times.
+    pub fn retry(mut self, policy: retry::Policy) -> ClientBuilder {
+        self.config.retry_policy = policy;
+        self
+    }
+
+    // Auth options
+
+    /// Enable HTTP digest authentication for all requests of this client.
+    ///
+    /// When a server answers with `401 Unauthorized` and a `Digest`
+    /// challenge, the request is sent again with an `Authorization` header
+    /// answering it, as described in RFC 7616. Requests with a body that
+    /// is not reusable can't be sent again, and are returned as they are.
+    ///
+    /// The challenge is then remembered, so later requests to the same
+    /// origin are authorized without first being rejected.
+    ///
+    /// Credentials are only used for the origin a request was made to,
+    /// never for an origin it was redirected to.
+    ///
+    /// `RequestBuilder::digest_auth()` overrides these credentials for a
+    /// single request.
+    #[cfg(feature = "digest-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "digest-auth")))]
+    pub fn digest_auth<U, P>(mut self, username: U, password: P) -> ClientBuilder
+    where
+        U: fmt::Display,
+        P: fmt::Display,
+    {
+        self.config.digest_auth = Some(digest::Credentials::new(username, password));
+        self
+    }
+
+    /// Set the `CredentialProvider` that authorizes the requests of this
+    /// client.
+    ///
+    /// It is asked for an `Authorization` value before each request is
+    /// sent, including redirects and retries, and asked for a new one when
+    /// the server answers `401 Unauthorized`. See the [`auth`](crate::auth)
+    /// module for details.
+    ///
+    /// Requests that already have an `Authorization` header, for example
+    /// from `RequestBuilder::bearer_auth()`, are sent as they are.
+    ///
+    /// Default is no provider.
+    pub fn credential_provider<P: CredentialProvider + 'static>(
+        mut self,
+        provider: Arc<P>,
+    ) -> ClientBuilder {
+        self.config.credential_provider = Some(provider as _);
+        self
+    }
+
+    // Cache options
+
+    /// Enable an HTTP cache for this client.
+    ///
+    /// Responses to `GET` requests are stored according to the rules of
+    /// RFC 9111, and reused or revalidated by later requests. See the
+    /// [`cache`](crate::cache) module for details.
+    ///
+    /// The cache sits underneath any `layer()`, so layers also see the
+    /// responses answered by the cache.
+    ///
+    /// Default is no cache.
+    pub fn cache(mut self, cache: cache::Cache) -> ClientBuilder {
+        self.config.cache = Some(cache);
+        self
+    }
+
+    /// Record the exchanges of this client to a `Cassette`, or replay them
+    /// from it.
+    ///
+    /// See the [`replay`](crate::replay) module for details.
+    ///
+    /// Default is no cassette.
+    #[cfg(feature = "replay")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "replay")))]
+    pub fn replay(mut self, cassette: Cassette) -> ClientBuilder {
+        self.config.replay = Some(cassette);
+        self
+    }
+
     // Proxy options
 
     /// Add a `Proxy` to the list of proxies the `Client` will use.
@@ -1273,6 +1602,48 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the maximum size of response bodies, in bytes, after
+    /// decompression.
+    ///
+    /// Reading a larger body fails with an error for which
+    /// [`Error::is_body_too_large`](crate::Error::is_body_too_large) is
+    /// true. A body with a larger `Content-Length` fails before any of it
+    /// is read. It can be overridden for a single request with
+    /// `RequestBuilder::max_response_body_size()`.
+    ///
+    /// Default is no limit.
+    pub fn max_response_body_size(mut self, max: u64) -> ClientBuilder {
+        self.config.max_response_body_size = Some(max);
+        self
+    }
+
+    /// Set the maximum size of decompressed response bodies, in bytes.
+    ///
+    /// Decompressing a larger body fails with an error for which
+    /// [`Error::is_decompression_limit`](crate::Error::is_decompression_limit)
+    /// is true. Bodies that aren't compressed are not limited.
+    ///
+    /// Default is no limit.
+    pub fn max_decompressed_size(mut self, max: u64) -> ClientBuilder {
+        self.config.max_decompressed_size = Some(max);
+        self
+    }
+
+    /// Set the maximum ratio of the decompressed size of response bodies to
+    /// their compressed size.
+    ///
+    /// A body that decompresses to `ratio` times its compressed size fails
+    /// with an error for which
+    /// [`Error::is_decompression_limit`](crate::Error::is_decompression_limit)
+    /// is true. The ratio is only checked once more than 1 MiB is
+    /// decompressed, as small bodies can be compressed a lot.
+    ///
+    /// Default is no limit.
+    pub fn max_compression_ratio(mut self, ratio: u32) -> ClientBuilder {
+        self.config.max_compression_ratio = Some(ratio);
+        self
+    }
+
     /// Set a timeout for only the connect phase of a `Client`.
     ///
     /// Default is `None`.
@@ -1297,6 +1668,49 @@ impl ClientBuilder {
         self
     }
 
+    /// Set an `EventListener` to be told about the network activity of
+    /// this client.
+    ///
+    /// It is called as hosts are resolved, connections are made and
+    /// acquired, and requests are sent and answered. See the
+    /// [`event`](crate::event) module for details.
+    ///
+    /// Default is no listener.
+    pub fn event_listener<L: EventListener + 'static>(mut self, listener: Arc<L>) -> ClientBuilder {
+        self.config.event_listener = Some(listener as _);
+        self
+    }
+
+    /// Set a `Propagator` to add the trace context to the headers of each
+    /// request sent by this client.
+    ///
+    /// It is given the span of every hop of a request, see the
+    /// [`trace`](crate::trace) module for details.
+    ///
+    /// Default is no propagator.
+    #[cfg(feature = "tracing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+    pub fn trace_propagator<P: trace::Propagator + 'static>(
+        mut self,
+        propagator: Arc<P>,
+    ) -> ClientBuilder {
+        self.config.trace_propagator = Some(propagator as _);
+        self
+    }
+
+    /// Record every request this client sends, and its response, with a
+    /// `HarRecorder`.
+    ///
+    /// See the [`har`](crate::har) module for details.
+    ///
+    /// Default is no recorder.
+    #[cfg(feature = "har")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "har")))]
+    pub fn har_recorder(mut self, recorder: Arc<HarRecorder>) -> ClientBuilder {
+        self.config.har_recorder = Some(recorder);
+        self
+    }
+
     // HTTP options
 
     /// Set an optional timeout for idle sockets being kept-alive.
@@ -1558,6 +1972,40 @@ impl ClientBuilder {
         self
     }
 
+    /// Send requests over the Unix socket at `path` instead of TCP.
+    ///
+    /// The URL of a request still gives its `Host` header and path, and
+    /// whether it uses TLS: `https` URLs make a TLS handshake over the socket,
+    /// with the host of the URL as server name. HTTP/1 and HTTP/2 are
+    /// supported, and proxies are not used. Redirects, even to another host,
+    /// are followed over the same socket.
+    ///
+    /// Each socket gets its own pool of connections. The socket can be
+    /// overridden for a single request with `RequestBuilder::unix_socket()`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::builder()
+    ///     .unix_socket("/var/run/docker.sock")
+    ///     .build()?;
+    /// let version = client
+    ///     .get("http://localhost/version")
+    ///     .send()
+    ///     .await?
+    ///     .text()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn unix_socket<P: AsRef<Path>>(mut self, path: P) -> ClientBuilder {
+        self.config.unix_socket = Some(path.as_ref().to_owned());
+        self
+    }
+
     /// Set that all sockets have `SO_KEEPALIVE` set with the supplied duration.
     ///
     /// If `None`, the option will not be set.
@@ -2052,6 +2500,50 @@ impl ClientBuilder {
         self
     }
 
+    /// Set which IP families to connect with, and which to try first.
+    ///
+    /// The resolved addresses of a host are filtered and ordered
+    /// accordingly, for both TCP and QUIC connections. This can be used to
+    /// avoid IPv6, or IPv4, on networks where it is broken.
+    ///
+    /// Default is `IpFamily::Any`, which keeps the order of the resolver.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[cfg(all(feature = "__rustls", not(feature = "__rustls-ring")))]
+    /// # let _ = rustls::crypto::ring::default_provider().install_default();
+    /// use reqwest::dns::IpFamily;
+    ///
+    /// let client = reqwest::Client::builder()
+    ///     .ip_family(IpFamily::PreferV4)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn ip_family(mut self, ip_family: IpFamily) -> ClientBuilder {
+        self.config.ip_family = ip_family;
+        self
+    }
+
+    /// Set the delay before trying the other IP family when connecting, as
+    /// described by [RFC 8305] ("Happy Eyeballs").
+    ///
+    /// The addresses of the family of the first resolved address are tried
+    /// first. If no connection is made within this delay, the addresses of the
+    /// other family are tried in parallel. If `None`, all addresses are tried
+    /// one after the other.
+    ///
+    /// Default is 300 milliseconds.
+    ///
+    /// [RFC 8305]: https://www.rfc-editor.org/rfc/rfc8305
+    pub fn happy_eyeballs_timeout<D>(mut self, timeout: D) -> ClientBuilder
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.config.happy_eyeballs_timeout = timeout.into();
+        self
+    }
+
     /// Whether to send data on the first flight ("early data") in TLS 1.3 handshakes
     /// for HTTP/3 connections.
     ///
@@ -2169,6 +2661,27 @@ impl ClientBuilder {
         self
     }
 
+    /// Enable upgrading to HTTP/3 with the `Alt-Svc` headers of responses.
+    ///
+    /// When an `https` origin advertises an HTTP/3 alternative service, as
+    /// with `Alt-Svc: h3=":443"`, later requests to it are sent over HTTP/3
+    /// until the advertisement expires. If no QUIC connection can be made
+    /// within a second, requests are sent over TCP instead, and the
+    /// alternative service is not used for a while.
+    ///
+    /// Requests with an explicit version other than HTTP/1.1, requests
+    /// sent through a proxy or a Unix socket, and all requests of a client
+    /// with a custom [`transport`](ClientBuilder::transport), are not
+    /// upgraded.
+    ///
+    /// Default is `false`.
+    #[cfg(feature = "http3")]
+    #[cfg_attr(docsrs, doc(cfg(all(reqwest_unstable, feature = "http3",))))]
+    pub fn http3_alt_svc(mut self, enabled: bool) -> ClientBuilder {
+        self.config.h3_alt_svc = enabled;
+        self
+    }
+
     /// Adds a new Tower [`Layer`](https://docs.rs/tower/latest/tower/trait.Layer.html) to the
     /// base connector [`Service`](https://docs.rs/tower/latest/tower/trait.Service.html) which
     /// is responsible for connection establishment.
@@ -2205,10 +2718,113 @@ impl ClientBuilder {
 
         self
     }
+
+    /// Adds a new Tower [`Layer`](https://docs.rs/tower/latest/tower/trait.Layer.html) to the
+    /// [`Service`](https://docs.rs/tower/latest/tower/trait.Service.html) which sends each
+    /// `Request` and receives its `Response`.
+    ///
+    /// The layered service is called once per hop: for the original request,
+    /// for every redirect that is followed, and for every retry. Each call
+    /// sees the `Request` that is about to be sent, and the `Response` (or
+    /// error) that came back for it, so the final call sees the final
+    /// `Response`.
+    ///
+    /// Each subsequent invocation of this function will wrap previous layers.
+    ///
+    /// The total `timeout` and redirect and retry policies sit outside of
+    /// all layers.
+    ///
+    /// Example usage:
+    /// ```
+    /// use reqwest::header::{HeaderValue, USER_AGENT};
+    ///
+    /// # #[cfg(not(feature = "rustls-tls-no-provider"))]
+    /// let client = reqwest::Client::builder()
+    ///     .layer(tower::util::MapRequestLayer::new(|mut req: reqwest::Request| {
+    ///         req.headers_mut()
+    ///             .insert(USER_AGENT, HeaderValue::from_static("my-agent"));
+    ///         req
+    ///     }))
+    ///     .layer(tower::limit::concurrency::ConcurrencyLimitLayer::new(8))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn layer<L>(mut self, layer: L) -> ClientBuilder
+    where
+        L: Layer<BoxedClientService> + Clone + Send + Sync + 'static,
+        L::Service:
+            Service<Request, Response = Response, Error = BoxError> + Clone + Send + Sync + 'static,
+        <L::Service as Service<Request>>::Future: Send + 'static,
+    {
+        let layer = BoxCloneSyncServiceLayer::new(layer);
+
+        self.config.layers.push(layer);
+
+        self
+    }
+
+    /// Sends requests with a Tower [`Service`](https://docs.rs/tower/latest/tower/trait.Service.html)
+    /// instead of over the network.
+    ///
+    /// The service is called with each hop of a request, in place of the
+    /// connection pool, so no DNS lookups, connections or TLS handshakes are
+    /// made, and connection options have no effect. Everything else still
+    /// applies: default headers, cookies, redirects, retries, decompression
+    /// of the response and timeouts.
+    ///
+    /// The requests have an absolute URI, and the method, headers and body
+    /// that would be handed to the HTTP connection, before any HTTP/1 or
+    /// HTTP/2 framing or TLS is applied. This makes it possible to assert on
+    /// them, and answer with canned responses, in unit tests:
+    ///
+    /// ```
+    /// use std::convert::Infallible;
+    ///
+    /// # #[cfg(not(feature = "rustls-tls-no-provider"))]
+    /// # async fn run() -> Result<(), reqwest::Error> {
+    /// let client = reqwest::Client::builder()
+    ///     .transport(tower::service_fn(|req: http::Request<reqwest::Body>| async move {
+    ///         assert_eq!(req.uri(), "http://api.test/users/1");
+    ///         Ok::<_, Infallible>(http::Response::new(r#"{"name":"Ferris"}"#.to_owned()))
+    ///     }))
+    ///     .build()?;
+    ///
+    /// let user = client.get("http://api.test/users/1").send().await?.text().await?;
+    /// assert_eq!(user, r#"{"name":"Ferris"}"#);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn transport<S, B>(mut self, transport: S) -> ClientBuilder
+    where
+        S: Service<http::Request<Body>, Response = http::Response<B>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Error: Into<BoxError>,
+        S::Future: Send + 'static,
+        B: http_body::Body<Data = Bytes> + Send + Sync + 'static,
+        B::Error: Into<BoxError>,
+    {
+        let transport = transport
+            .map_response(|res: http::Response<B>| res.map(super::body::boxed))
+            .map_err(Into::into);
+        self.config.transport = Some(BoxCloneSyncService::new(transport));
+        self
+    }
 }
 
+pub(crate) type BoxedClientService = BoxCloneSyncService<Request, Response, BoxError>;
+
+pub(crate) type BoxedClientLayer =
+    BoxCloneSyncServiceLayer<BoxedClientService, Request, Response, BoxError>;
+
 type HyperClient = hyper_util::client::legacy::Client<Connector, super::Body>;
 
+/// A service set with `ClientBuilder::transport()`, used instead of hyper.
+type BoxedTransport =
+    BoxCloneSyncService<http::Request<Body>, http::Response<ResponseBody>, BoxError>;
+
 impl Default for Client {
     fn default() -> Self {
         Self::new()
@@ -2303,6 +2919,41 @@ impl Client {
         RequestBuilder::new(self.clone(), req)
     }
 
+    /// Start building a WebSocket connection to a `ws` or `wss` URL.
+    ///
+    /// The opening handshake is sent like other requests of this client, so
+    /// it uses the same proxies, TLS configuration, cookies and default
+    /// headers.
+    ///
+    /// # Errors
+    ///
+    /// This method fails whenever the supplied `Url` cannot be parsed, or
+    /// is not a WebSocket or HTTP URL.
+    #[cfg(feature = "websocket")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+    pub fn websocket<U: IntoUrl>(&self, url: U) -> crate::websocket::WebSocketRequestBuilder {
+        let req = url
+            .into_url()
+            .and_then(crate::websocket::http_url)
+            .map(|url| Request::new(Method::GET, url));
+        crate::websocket::WebSocketRequestBuilder::new(RequestBuilder::new(self.clone(), req))
+    }
+
+    /// Start a download of a resource to a file.
+    ///
+    /// The resource is fetched in byte ranges over several connections at
+    /// once, if the server supports range requests. See the
+    /// [`download`](crate::download) module.
+    ///
+    /// # Errors
+    ///
+    /// The download fails whenever the supplied `Url` cannot be parsed.
+    #[cfg(feature = "download")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "download")))]
+    pub fn download<U: IntoUrl>(&self, url: U) -> crate::download::Download {
+        crate::download::Download::new(self.clone(), url.into_url())
+    }
+
     /// Executes a `Request`.
     ///
     /// A `Request` can be built manually with `Request::new()` or obtained
@@ -2323,7 +2974,8 @@ impl Client {
     }
 
     pub(super) fn execute_request(&self, req: Request) -> Pending {
-        let (method, url, mut headers, body, version, extensions) = req.pieces();
+        let started = Instant::now();
+        let (method, url, mut headers, body, version, mut extensions) = req.pieces();
         if url.scheme() != "http" && url.scheme() != "https" {
             return Pending::new_err(error::url_bad_scheme(url));
         }
@@ -2364,34 +3016,50 @@ impl Client {
             _ => return Pending::new_err(error::url_invalid_uri(url)),
         };
 
+        #[cfg(any(
+            feature = "gzip",
+            feature = "brotli",
+            feature = "zstd",
+            feature = "deflate"
+        ))]
+        let body = match (body, self.inner.compress.fetch(&extensions)) {
+            (Some(body), Some(&encoding)) if !headers.contains_key(CONTENT_ENCODING) => {
+                match body.try_reuse() {
+                    // a buffered body is compressed before the request is
+                    // sent, so that it can still be sent again
+                    (Some(bytes), _) => {
+                        let client = self.clone();
+                        return Pending::compressing(async move {
+                            let body = super::encoder::encode_bytes(encoding, bytes, &mut headers)
+                                .await
+                                .map_err(|err| error::builder(err).with_url(url.clone()))?;
+                            let mut req = Request::new(method, url);
+                            *req.headers_mut() = headers;
+                            *req.body_mut() = Some(body);
+                            *req.version_mut() = version;
+                            *req.extensions_mut() = extensions;
+                            client.execute_request(req).await
+                        });
+                    }
+                    (None, body) => Some(super::encoder::encode(encoding, body, &mut headers)),
+                }
+            }
+            (body, _) => body,
+        };
+
         let (reusable, body) = match body {
             Some(body) => {
                 let (reusable, body) = body.try_reuse();
-                (Some(reusable), body)
+                (Some(reusable), Some(body))
             }
-            None => (None, Body::empty()),
+            None => (None, None),
         };
 
-        self.proxy_auth(&uri, &mut headers);
-
-        let builder = hyper::Request::builder()
-            .method(method.clone())
-            .uri(uri)
-            .version(version);
+        self.proxy_auth(&uri, &mut headers, &extensions);
 
-        let in_flight = match version {
-            #[cfg(feature = "http3")]
-            http::Version::HTTP_3 if self.inner.h3_client.is_some() => {
-                let mut req = builder.body(body).expect("valid request parts");
-                *req.headers_mut() = headers.clone();
-                ResponseFuture::H3(self.inner.h3_client.as_ref().unwrap().request(req))
-            }
-            _ => {
-                let mut req = builder.body(body).expect("valid request parts");
-                *req.headers_mut() = headers.clone();
-                ResponseFuture::Default(self.inner.hyper.request(req))
-            }
-        };
+        #[cfg(feature = "digest-auth")]
+        self.inner
+            .digest_authorize(&method, &url, &mut headers, &extensions, &reusable);
 
         let total_timeout = self
             .inner
@@ -2401,38 +3069,73 @@ impl Client {
             .map(tokio::time::sleep)
             .map(Box::pin);
 
+        if let Some(ref total_timeout) = total_timeout {
+            extensions.insert(Deadline(total_timeout.deadline()));
+        }
+
+        let mut req = Request::new(method.clone(), url.clone());
+        *req.headers_mut() = headers.clone();
+        *req.body_mut() = body;
+        *req.version_mut() = version;
+        *req.extensions_mut() = extensions.clone();
+        let mut trace = TraceState::new(&method, &url);
+        let in_flight = trace.send(&self.inner, req);
+
         let read_timeout_fut = self
             .inner
             .read_timeout
             .map(tokio::time::sleep)
             .map(Box::pin);
 
+        let retry_policy = self
+            .inner
+            .retry_policy
+            .fetch(&extensions)
+            .cloned()
+            .unwrap_or_default();
+        retry_policy.deposit();
+
         Pending {
             inner: PendingInner::Request(PendingRequest {
                 method,
                 url,
                 headers,
                 body: reusable,
+                version,
+                extensions,
 
                 urls: Vec::new(),
 
                 retry_count: 0,
+                retry_policy,
+
+                auth: AuthState::default(),
+                trace,
 
                 client: self.inner.clone(),
 
+                started,
                 in_flight,
+                retry_delay: None,
                 total_timeout,
                 read_timeout_fut,
-                read_timeout: self.inner.read_timeout,
             }),
         }
     }
 
-    fn proxy_auth(&self, dst: &Uri, headers: &mut HeaderMap) {
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn proxy_auth(&self, dst: &Uri, headers: &mut HeaderMap, extensions: &Extensions) {
         if !self.inner.proxies_maybe_http_auth {
             return;
         }
 
+        // Requests over a Unix socket never go through a proxy, so they
+        // must not carry its credentials.
+        #[cfg(unix)]
+        if self.inner.unix_socket.fetch(extensions).is_some() {
+            return;
+        }
+
         // Only set the header here if the destination scheme is 'http',
         // since otherwise, the header will be included in the CONNECT tunnel
         // request instead.
@@ -2524,35 +3227,75 @@ impl Config {
             f.field("referer", &true);
         }
 
-        f.field("default_headers", &self.headers);
+        if !self.retry_policy.is_default() {
+            f.field("retry_policy", &self.retry_policy);
+        }
 
-        if self.http1_title_case_headers {
-            f.field("http1_title_case_headers", &true);
+        #[cfg(feature = "digest-auth")]
+        if let Some(ref credentials) = self.digest_auth {
+            f.field("digest_auth", credentials);
         }
 
-        if self.http1_allow_obsolete_multiline_headers_in_responses {
-            f.field("http1_allow_obsolete_multiline_headers_in_responses", &true);
+        if self.credential_provider.is_some() {
+            f.field("credential_provider", &true);
         }
 
-        if self.http1_ignore_invalid_headers_in_responses {
-            f.field("http1_ignore_invalid_headers_in_responses", &true);
+        if self.event_listener.is_some() {
+            f.field("event_listener", &true);
         }
 
-        if self.http1_allow_spaces_after_header_name_in_responses {
-            f.field("http1_allow_spaces_after_header_name_in_responses", &true);
+        #[cfg(feature = "tracing")]
+        if self.trace_propagator.is_some() {
+            f.field("trace_propagator", &true);
         }
 
-        if matches!(self.http_version_pref, HttpVersionPref::Http1) {
-            f.field("http1_only", &true);
+        #[cfg(feature = "har")]
+        if let Some(ref recorder) = self.har_recorder {
+            f.field("har_recorder", recorder);
         }
 
-        #[cfg(feature = "http2")]
-        if matches!(self.http_version_pref, HttpVersionPref::Http2) {
-            f.field("http2_prior_knowledge", &true);
+        if let Some(ref cache) = self.cache {
+            f.field("cache", cache);
         }
 
-        if let Some(ref d) = self.connect_timeout {
-            f.field("connect_timeout", d);
+        #[cfg(feature = "replay")]
+        if let Some(ref cassette) = self.replay {
+            f.field("replay", cassette);
+        }
+
+        if self.transport.is_some() {
+            f.field("transport", &true);
+        }
+
+        f.field("default_headers", &self.headers);
+
+        if self.http1_title_case_headers {
+            f.field("http1_title_case_headers", &true);
+        }
+
+        if self.http1_allow_obsolete_multiline_headers_in_responses {
+            f.field("http1_allow_obsolete_multiline_headers_in_responses", &true);
+        }
+
+        if self.http1_ignore_invalid_headers_in_responses {
+            f.field("http1_ignore_invalid_headers_in_responses", &true);
+        }
+
+        if self.http1_allow_spaces_after_header_name_in_responses {
+            f.field("http1_allow_spaces_after_header_name_in_responses", &true);
+        }
+
+        if matches!(self.http_version_pref, HttpVersionPref::Http1) {
+            f.field("http1_only", &true);
+        }
+
+        #[cfg(feature = "http2")]
+        if matches!(self.http_version_pref, HttpVersionPref::Http2) {
+            f.field("http2_prior_knowledge", &true);
+        }
+
+        if let Some(ref d) = self.connect_timeout {
+            f.field("connect_timeout", d);
         }
 
         if let Some(ref d) = self.timeout {
@@ -2579,6 +3322,33 @@ impl Config {
             f.field("interface", v);
         }
 
+        if let Some(ref v) = self.max_response_body_size {
+            f.field("max_response_body_size", v);
+        }
+
+        if let Some(ref v) = self.max_decompressed_size {
+            f.field("max_decompressed_size", v);
+        }
+
+        if let Some(ref v) = self.max_compression_ratio {
+            f.field("max_compression_ratio", v);
+        }
+
+        #[cfg(any(
+            feature = "gzip",
+            feature = "brotli",
+            feature = "zstd",
+            feature = "deflate"
+        ))]
+        if let Some(ref v) = self.compress {
+            f.field("compress", v);
+        }
+
+        #[cfg(unix)]
+        if let Some(ref v) = self.unix_socket {
+            f.field("unix_socket", v);
+        }
+
         if self.nodelay {
             f.field("tcp_nodelay", &true);
         }
@@ -2618,11 +3388,23 @@ impl Config {
             f.field("dns_overrides", &self.dns_overrides);
         }
 
+        if self.ip_family != IpFamily::Any {
+            f.field("ip_family", &self.ip_family);
+        }
+
+        if self.happy_eyeballs_timeout != Some(Duration::from_millis(300)) {
+            f.field("happy_eyeballs_timeout", &self.happy_eyeballs_timeout);
+        }
+
         #[cfg(feature = "http3")]
         {
             if self.tls_enable_early_data {
                 f.field("tls_enable_early_data", &true);
             }
+
+            if self.h3_alt_svc {
+                f.field("h3_alt_svc", &true);
+            }
         }
     }
 }
@@ -2632,13 +3414,29 @@ struct ClientRef {
     #[cfg(feature = "cookies")]
     cookie_store: Option<Arc<dyn cookie::CookieStore>>,
     headers: HeaderMap,
-    hyper: HyperClient,
-    #[cfg(feature = "http3")]
-    h3_client: Option<H3Client>,
+    service: ClientService,
     redirect_policy: redirect::Policy,
     referer: bool,
+    retry_policy: RequestConfig<RequestRetry>,
+    #[cfg(feature = "digest-auth")]
+    digest_auth: RequestConfig<RequestDigestAuth>,
+    #[cfg(feature = "digest-auth")]
+    digest_challenges: digest::Challenges,
+    credential_provider: Option<Arc<dyn CredentialProvider>>,
+    event_listener: Option<Arc<dyn EventListener>>,
+    #[cfg(feature = "tracing")]
+    trace_propagator: Option<Arc<dyn trace::Propagator>>,
     request_timeout: RequestConfig<RequestTimeout>,
     read_timeout: Option<Duration>,
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    compress: RequestConfig<RequestCompression>,
+    #[cfg(unix)]
+    unix_socket: RequestConfig<RequestUnixSocket>,
     proxies: Arc<Vec<ProxyMatcher>>,
     proxies_maybe_http_auth: bool,
     https_only: bool,
@@ -2672,12 +3470,120 @@ impl ClientRef {
 
         f.field("default_headers", &self.headers);
 
+        self.retry_policy.fmt_as_field(f);
+        #[cfg(feature = "digest-auth")]
+        self.digest_auth.fmt_as_field(f);
         self.request_timeout.fmt_as_field(f);
+        #[cfg(any(
+            feature = "gzip",
+            feature = "brotli",
+            feature = "zstd",
+            feature = "deflate"
+        ))]
+        self.compress.fmt_as_field(f);
+
+        if self.credential_provider.is_some() {
+            f.field("credential_provider", &true);
+        }
+
+        if self.event_listener.is_some() {
+            f.field("event_listener", &true);
+        }
+
+        #[cfg(feature = "tracing")]
+        if self.trace_propagator.is_some() {
+            f.field("trace_propagator", &true);
+        }
 
         if let Some(ref d) = self.read_timeout {
             f.field("read_timeout", d);
         }
     }
+
+    /// Send a single hop of a request, authorized by the credential provider.
+    ///
+    /// The provider is asked for every hop, so an `Authorization` value it
+    /// supplied never follows a redirect to another origin.
+    fn send(self: &Arc<Self>, mut req: Request) -> ResponseFuture {
+        let provider = match self.credential_provider {
+            Some(ref provider) if !req.headers().contains_key(AUTHORIZATION) => provider.clone(),
+            _ => return self.service.send(req),
+        };
+        let client = self.clone();
+
+        Box::pin(async move {
+            let url = req.url().clone();
+            let mut value = match provider.authorization(&url).await? {
+                Some(value) => value,
+                None => return client.service.send(req).await,
+            };
+            value.set_sensitive(true);
+
+            let again = req.try_clone();
+            req.headers_mut().insert(AUTHORIZATION, value.clone());
+            let res = client.service.send(req).await?;
+            if res.status() != StatusCode::UNAUTHORIZED {
+                return Ok(res);
+            }
+
+            let mut again = match again {
+                Some(again) => again,
+                None => {
+                    debug!("credentials for '{url}' were rejected, but body not reusable");
+                    return Ok(res);
+                }
+            };
+            match provider.refresh(&url, &value).await? {
+                Some(mut value) => {
+                    debug!("sending '{url}' again with refreshed credentials");
+                    value.set_sensitive(true);
+                    again.headers_mut().insert(AUTHORIZATION, value);
+                    client.service.send(again).await
+                }
+                None => Ok(res),
+            }
+        })
+    }
+
+    /// Authorize a request with the digest challenge last received from its
+    /// origin, if the request has digest credentials.
+    ///
+    /// An `Authorization` header of another scheme is left alone.
+    #[cfg(feature = "digest-auth")]
+    fn digest_authorize(
+        &self,
+        method: &Method,
+        url: &Url,
+        headers: &mut HeaderMap,
+        extensions: &Extensions,
+        body: &Option<Option<Bytes>>,
+    ) {
+        let credentials = match self.digest_auth.fetch(extensions) {
+            Some(credentials) => credentials,
+            None => return,
+        };
+        if let Some(value) = headers.get(AUTHORIZATION) {
+            if !value.as_bytes().starts_with(b"Digest ") {
+                return;
+            }
+        }
+        let value = self
+            .digest_challenges
+            .authorize(credentials, method, url, known_body(body));
+        if let Some(value) = value {
+            headers.insert(AUTHORIZATION, value);
+        }
+    }
+}
+
+/// The bytes of a request body, if they are known.
+#[cfg(feature = "digest-auth")]
+fn known_body(body: &Option<Option<Bytes>>) -> Option<&[u8]> {
+    match body {
+        Some(Some(body)) => Some(body),
+        Some(None) => None,
+        None => Some(&[]),
+    }
 }
 
 pin_project! {
@@ -2689,6 +3595,14 @@ pin_project! {
 
 enum PendingInner {
     Request(PendingRequest),
+    /// A request whose buffered body is compressed before it is sent.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    Compressing(Pin<Box<dyn Future<Output = Result<Response, crate::Error>> + Send>>),
     Error(Option<crate::Error>),
 }
 
@@ -2698,31 +3612,471 @@ pin_project! {
         url: Url,
         headers: HeaderMap,
         body: Option<Option<Bytes>>,
+        version: Version,
+        extensions: Extensions,
 
         urls: Vec<Url>,
 
         retry_count: usize,
+        retry_policy: retry::Policy,
+
+        auth: AuthState,
+        trace: TraceState,
 
         client: Arc<ClientRef>,
 
-        #[pin]
+        started: Instant,
         in_flight: ResponseFuture,
         #[pin]
+        retry_delay: Option<Pin<Box<Sleep>>>,
+        #[pin]
         total_timeout: Option<Pin<Box<Sleep>>>,
         #[pin]
         read_timeout_fut: Option<Pin<Box<Sleep>>>,
-        read_timeout: Option<Duration>,
     }
 }
 
-enum ResponseFuture {
-    Default(HyperResponseFuture),
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, BoxError>> + Send>>;
+
+/// How often the challenges of the current hop have been answered.
+#[derive(Default)]
+struct AuthState {
+    #[cfg(feature = "digest-auth")]
+    digest_attempts: usize,
+}
+
+/// The span of a request, and how many hops were sent for it.
+struct TraceState {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    hops: u32,
+}
+
+impl TraceState {
+    #[cfg(feature = "tracing")]
+    fn new(method: &Method, url: &Url) -> TraceState {
+        TraceState {
+            span: trace::request_span(method, url),
+            hops: 0,
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    fn new(_method: &Method, _url: &Url) -> TraceState {
+        TraceState {}
+    }
+
+    /// Send the next hop of the request, within a span of its own.
+    #[cfg(feature = "tracing")]
+    fn send(&mut self, client: &Arc<ClientRef>, mut req: Request) -> ResponseFuture {
+        let span = trace::hop_span(&self.span, req.method(), req.url(), self.hops);
+        self.hops += 1;
+        if let Some(ref propagator) = client.trace_propagator {
+            propagator.inject(&span, req.headers_mut());
+        }
+        Box::pin(trace::instrument(span, client.send(req)))
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    fn send(&mut self, client: &Arc<ClientRef>, req: Request) -> ResponseFuture {
+        client.send(req)
+    }
+
+    /// Record the outcome of the whole request.
+    #[cfg(feature = "tracing")]
+    fn finish(&self, res: &Result<Response, crate::Error>) {
+        match res {
+            Ok(res) => trace::record_response(&self.span, res.status(), res.version()),
+            Err(err) => trace::record_error(&self.span, err),
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    fn finish(&self, _res: &Result<Response, crate::Error>) {}
+}
+
+/// The deadline of the total request timeout.
+///
+/// It is passed along to the `HyperService` in the request extensions, so the
+/// body of whichever response ends up being returned is still bound by it.
+#[derive(Clone, Copy)]
+struct Deadline(tokio::time::Instant);
+
+/// The service each hop of a request is sent through.
+#[derive(Clone)]
+enum ClientService {
+    // no user-provided layers, use the concrete type
+    Simple(Box<HyperService>),
+    // at least one layer from `builder.layer()`, or the cache
+    WithLayers(BoxedClientService),
+}
+
+impl ClientService {
+    fn send(&self, req: Request) -> ResponseFuture {
+        match self {
+            ClientService::Simple(service) => service.request(req),
+            ClientService::WithLayers(service) => Box::pin(service.clone().oneshot(req)),
+        }
+    }
+}
+
+/// The innermost service, which sends a single request over HTTP/1, HTTP/2
+/// or HTTP/3, without following redirects or retrying.
+#[derive(Clone)]
+struct HyperService {
+    hyper: HyperClient,
+    /// A client whose connections only offer HTTP/1.1, for upgrades.
+    #[cfg(feature = "websocket")]
+    hyper_http1: HyperClient,
+    transport: Option<BoxedTransport>,
+    #[cfg(unix)]
+    unix_socket: RequestConfig<RequestUnixSocket>,
+    #[cfg(unix)]
+    unix_clients: UnixClients,
+    #[cfg(feature = "http3")]
+    h3_client: Option<H3Client>,
     #[cfg(feature = "http3")]
-    H3(H3ResponseFuture),
+    alt_svc: Option<AltSvcCache>,
+    #[cfg(feature = "http3")]
+    proxies: Arc<Vec<ProxyMatcher>>,
+    accepts: Accepts,
+    read_timeout: Option<Duration>,
+    max_response_body_size: RequestConfig<RequestMaxResponseBodySize>,
+    /// The decompression limits, and no body size limit.
+    limits: Limits,
+    event_listener: Option<Arc<dyn EventListener>>,
+    #[cfg(feature = "har")]
+    har_recorder: Option<Arc<HarRecorder>>,
+}
+
+impl Service<Request> for HyperService {
+    type Response = Response;
+    type Error = BoxError;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        self.request(req)
+    }
+}
+
+impl HyperService {
+    fn request(&self, req: Request) -> ResponseFuture {
+        #[cfg(feature = "har")]
+        if let Some(ref recorder) = self.har_recorder {
+            let entry = recorder.start(&req);
+            return Box::pin(har::record(entry, self.send(req)));
+        }
+
+        self.send(req)
+    }
+
+    fn send(&self, req: Request) -> ResponseFuture {
+        let url = req.url().clone();
+        let total_timeout = req
+            .extensions()
+            .get::<Deadline>()
+            .map(|deadline| Box::pin(tokio::time::sleep_until(deadline.0)));
+        let accepts = self.accepts;
+        let mut read_timeout = self.read_timeout;
+        let limits = Limits {
+            body_size: self.max_response_body_size.fetch(req.extensions()).copied(),
+            ..self.limits
+        };
+
+        let resume = match RequestConfig::<RequestResumable>::get(req.extensions()) {
+            Some(true) => {
+                let service = self.clone();
+                Resume::new(
+                    &req,
+                    read_timeout,
+                    Arc::new(move |req| service.send_raw(req)),
+                )
+            }
+            _ => None,
+        };
+        if resume.is_some() {
+            // each part of a resumable body has a read timeout of its own
+            read_timeout = None;
+        }
+        let download_progress =
+            RequestConfig::<RequestDownloadProgress>::get(req.extensions()).cloned();
+
+        let in_flight = self.send_raw(req);
+        Box::pin(async move {
+            let mut res = in_flight.await?;
+            if let Some(resume) = resume {
+                res = resume.wrap(res);
+            }
+            // the body is counted as received, before it is decoded
+            let received = download_progress
+                .as_ref()
+                .map(|_| Arc::new(AtomicU64::new(0)));
+            if let Some(ref received) = received {
+                let received = received.clone();
+                res = res.map(|body| super::body::boxed(progress::count(body, received)));
+            }
+            let total = res
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|len| len.to_str().ok())
+                .and_then(|len| len.parse().ok());
+            let res = Response::new(res, url, accepts, total_timeout, read_timeout, limits);
+            Ok(match (download_progress, received) {
+                (Some(progress), Some(received)) => res.wrap_body(move |body| {
+                    progress::track_download(body, progress, received, total)
+                }),
+                _ => res,
+            })
+        })
+    }
+
+    /// Send `req`, and return the response with its body as received.
+    fn send_raw(&self, req: Request) -> RawResponseFuture {
+        let (method, url, headers, mut body, version, extensions) = req.pieces();
+        if let Some(progress) = RequestConfig::<RequestUploadProgress>::get(&extensions) {
+            body = body.map(|body| {
+                // a multipart form only knows its length from its parts
+                let total = http_body::Body::size_hint(&body).exact().or_else(|| {
+                    headers
+                        .get(CONTENT_LENGTH)
+                        .and_then(|len| len.to_str().ok())
+                        .and_then(|len| len.parse().ok())
+                });
+                Body::wrap(progress::track(body, progress.clone(), total))
+            });
+        }
+
+        let uri = match try_uri(&url) {
+            Ok(uri) => uri,
+            Err(_) => return Box::pin(future::err(error::url_invalid_uri(url).into())),
+        };
+
+        let mut req = hyper::Request::builder()
+            .method(method)
+            .uri(uri)
+            .version(version)
+            .body(body.unwrap_or_else(Body::empty))
+            .expect("valid request parts");
+        *req.headers_mut() = headers;
+
+        let listener = self.event_listener.clone();
+
+        if let Some(ref transport) = self.transport {
+            let started = Instant::now();
+            let in_flight = transport.clone().oneshot(req);
+            return Box::pin(async move {
+                let mut res = in_flight.await?;
+                res.extensions_mut().insert(ResponseTimings::since(started));
+                Ok(received(res, listener, &url))
+            });
+        }
+
+        match version {
+            #[cfg(feature = "http3")]
+            http::Version::HTTP_3 if self.h3_client.is_some() => {
+                let started = Instant::now();
+                let in_flight = self.h3_client.as_ref().unwrap().request(req);
+                Box::pin(async move {
+                    let mut res = in_flight.await?;
+                    res.extensions_mut().insert(ResponseTimings::since(started));
+                    Ok(received(res, listener, &url))
+                })
+            }
+            _ => {
+                #[cfg(feature = "http3")]
+                if let Some((h3_client, alt)) = self.alternative(&req, &url, version, &extensions) {
+                    return self.send_alt(h3_client, alt, req, url);
+                }
+
+                let captured = capture_connection(&mut req);
+                #[cfg(feature = "http3")]
+                let alt_svc = self.alt_svc.clone();
+                let in_flight = self.hyper_request(req, &extensions);
+                Box::pin(async move {
+                    let (res, timings) =
+                        event::acquire(in_flight, captured, listener.as_deref(), &url).await;
+                    let mut res = res?.map(super::body::boxed);
+                    res.extensions_mut().insert(timings);
+                    #[cfg(feature = "http3")]
+                    if let Some(alt_svc) = alt_svc {
+                        alt_svc.update(&url, res.headers());
+                    }
+                    Ok(received(res, listener, &url))
+                })
+            }
+        }
+    }
+}
+
+#[cfg(feature = "http3")]
+impl HyperService {
+    /// The HTTP/3 alternative service to send `req` to instead of its
+    /// origin, if one was advertised and `req` can be upgraded.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn alternative(
+        &self,
+        req: &hyper::Request<Body>,
+        url: &Url,
+        version: Version,
+        extensions: &Extensions,
+    ) -> Option<(H3Client, Alternative)> {
+        let (alt_svc, h3_client) = match (&self.alt_svc, &self.h3_client) {
+            (Some(alt_svc), Some(h3_client)) => (alt_svc, h3_client),
+            _ => return None,
+        };
+        // a custom transport takes the place of every connection
+        if self.transport.is_some() || version != Version::HTTP_11 {
+            return None;
+        }
+        #[cfg(feature = "websocket")]
+        if RequestConfig::<RequestHttp1Only>::get(extensions) == Some(&true) {
+            return None;
+        }
+        #[cfg(unix)]
+        if self.unix_socket.fetch(extensions).is_some() {
+            return None;
+        }
+        // QUIC connections can't go through proxies
+        if self
+            .proxies
+            .iter()
+            .any(|p| p.intercept(req.uri()).is_some())
+        {
+            return None;
+        }
+        let alt = alt_svc.get(url)?;
+        Some((h3_client.clone(), alt))
+    }
+
+    /// Send `req` to `alt` over HTTP/3, falling back to TCP if no QUIC
+    /// connection can be made.
+    fn send_alt(
+        &self,
+        h3_client: H3Client,
+        alt: Alternative,
+        req: hyper::Request<Body>,
+        url: Url,
+    ) -> RawResponseFuture {
+        let hyper = self.hyper.clone();
+        let alt_svc = self
+            .alt_svc
+            .clone()
+            .expect("alternative needs an Alt-Svc cache");
+        let listener = self.event_listener.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let res = match h3_client.request_alt(req, alt).await {
+                AltResponse::Sent(res) => {
+                    let mut res = res?;
+                    res.extensions_mut().insert(ResponseTimings::since(started));
+                    res
+                }
+                AltResponse::Unavailable(mut req, err) => {
+                    debug!("HTTP/3 alternative service for {url} unavailable: {err}");
+                    alt_svc.mark_broken(&url);
+                    let captured = capture_connection(&mut req);
+                    let in_flight = hyper.request(req);
+                    let (res, timings) =
+                        event::acquire(in_flight, captured, listener.as_deref(), &url).await;
+                    let mut res = res?.map(super::body::boxed);
+                    res.extensions_mut().insert(timings);
+                    res
+                }
+            };
+            alt_svc.update(&url, res.headers());
+            Ok(received(res, listener, &url))
+        })
+    }
+}
+
+impl HyperService {
+    /// Send `req` with the hyper client for the Unix socket in `extensions`,
+    /// if any.
+    #[cfg(unix)]
+    fn hyper_request(
+        &self,
+        req: hyper::Request<Body>,
+        extensions: &Extensions,
+    ) -> hyper_util::client::legacy::ResponseFuture {
+        match self.unix_socket.fetch(extensions) {
+            Some(path) => self.unix_clients.get(path).request(req),
+            None => self.tcp_client(extensions).request(req),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn hyper_request(
+        &self,
+        req: hyper::Request<Body>,
+        extensions: &Extensions,
+    ) -> hyper_util::client::legacy::ResponseFuture {
+        self.tcp_client(extensions).request(req)
+    }
+
+    /// The hyper client for a request with `extensions` that isn't sent
+    /// over a Unix socket.
+    #[cfg_attr(not(feature = "websocket"), allow(unused_variables))]
+    fn tcp_client(&self, extensions: &Extensions) -> &HyperClient {
+        #[cfg(feature = "websocket")]
+        if RequestConfig::<RequestHttp1Only>::get(extensions) == Some(&true) {
+            return &self.hyper_http1;
+        }
+        &self.hyper
+    }
+}
+
+/// The hyper clients for Unix sockets, one for each socket, so that they
+/// don't share a connection pool.
+#[cfg(unix)]
+#[derive(Clone)]
+struct UnixClients {
+    builder: hyper_util::client::legacy::Builder,
+    connector: ConnectorBuilder,
+    connector_layers: Vec<BoxedConnectorLayer>,
+    clients: Arc<Mutex<HashMap<PathBuf, HyperClient>>>,
+}
+
+#[cfg(unix)]
+impl UnixClients {
+    fn get(&self, path: &Path) -> HyperClient {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.get(path) {
+            return client.clone();
+        }
+        let mut connector = self.connector.clone();
+        connector.set_unix_socket(Some(Arc::from(path)));
+        let client = self
+            .builder
+            .build(connector.build(self.connector_layers.clone()));
+        clients.insert(path.to_owned(), client.clone());
+        client
+    }
+}
+
+/// Tell the listener, if any, that the response `res` arrived, and have its
+/// body tell when it is done with the connection.
+fn received(
+    res: hyper::Response<ResponseBody>,
+    listener: Option<Arc<dyn EventListener>>,
+    url: &Url,
+) -> hyper::Response<ResponseBody> {
+    match listener {
+        Some(listener) => {
+            listener.response_headers_received(url, res.status());
+            let url = url.clone();
+            res.map(|body| super::body::boxed(event::Release::new(body, listener, url)))
+        }
+        None => res,
+    }
 }
 
 impl PendingRequest {
-    fn in_flight(self: Pin<&mut Self>) -> Pin<&mut ResponseFuture> {
+    fn in_flight(self: Pin<&mut Self>) -> &mut ResponseFuture {
         self.project().in_flight
     }
 
@@ -2742,101 +4096,128 @@ impl PendingRequest {
         self.project().headers
     }
 
-    #[cfg(any(feature = "http2", feature = "http3"))]
-    fn retry_error(mut self: Pin<&mut Self>, err: &(dyn std::error::Error + 'static)) -> bool {
-        use log::trace;
+    fn trace(self: Pin<&mut Self>) -> &mut TraceState {
+        self.project().trace
+    }
 
-        if !is_retryable_error(err) {
-            return false;
-        }
+    fn retry_delay(self: Pin<&mut Self>) -> Pin<&mut Option<Pin<Box<Sleep>>>> {
+        self.project().retry_delay
+    }
+
+    fn retry_error(self: Pin<&mut Self>, err: &(dyn std::error::Error + 'static)) -> bool {
+        let failure = match retry::Failure::from_error(err) {
+            Some(failure) => failure,
+            None => return false,
+        };
 
         trace!("can retry {err:?}");
+        self.retry(failure)
+    }
 
-        let body = match self.body {
-            Some(Some(ref body)) => Body::reusable(body.clone()),
-            Some(None) => {
-                debug!("error was retryable, but body not reusable");
+    fn retry_status(self: Pin<&mut Self>, res: &Response) -> bool {
+        self.retry(retry::Failure::Status(res.status(), res.headers()))
+    }
+
+    /// Answer a digest challenge by sending the request again.
+    #[cfg(feature = "digest-auth")]
+    fn digest_challenge(mut self: Pin<&mut Self>, res: &Response) -> bool {
+        if res.status() != StatusCode::UNAUTHORIZED {
+            return false;
+        }
+        let credentials = match self.client.digest_auth.fetch(&self.extensions) {
+            Some(credentials) => credentials.clone(),
+            None => return false,
+        };
+        if let Some(first) = self.urls.first() {
+            if first.origin() != self.url.origin() {
                 return false;
             }
-            None => Body::empty(),
+        }
+        let challenge = match digest::Challenge::from_headers(res.headers()) {
+            Some(challenge) => challenge,
+            None => return false,
         };
 
-        if self.retry_count >= 2 {
-            trace!("retry count too high");
-            return false;
+        // A second challenge means the credentials were rejected, unless
+        // only the nonce was stale.
+        match self.auth.digest_attempts {
+            0 => {}
+            1 if challenge.is_stale() => {}
+            _ => return false,
         }
-        self.retry_count += 1;
-
-        // If it parsed once, it should parse again
-        let uri = try_uri(&self.url).expect("URL was already validated as URI");
 
-        *self.as_mut().in_flight().get_mut() = match *self.as_mut().in_flight().as_ref() {
-            #[cfg(feature = "http3")]
-            ResponseFuture::H3(_) => {
-                let mut req = hyper::Request::builder()
-                    .method(self.method.clone())
-                    .uri(uri)
-                    .body(body)
-                    .expect("valid request parts");
-                *req.headers_mut() = self.headers.clone();
-                ResponseFuture::H3(
-                    self.client
-                        .h3_client
-                        .as_ref()
-                        .expect("H3 client must exists, otherwise we can't have a h3 request here")
-                        .request(req),
-                )
-            }
-            _ => {
-                let mut req = hyper::Request::builder()
-                    .method(self.method.clone())
-                    .uri(uri)
-                    .body(body)
-                    .expect("valid request parts");
-                *req.headers_mut() = self.headers.clone();
-                ResponseFuture::Default(self.client.hyper.request(req))
+        let body = match self.body {
+            Some(Some(ref body)) => Some(Body::reusable(body.clone())),
+            Some(None) => {
+                debug!("digest challenge for '{}', but body not reusable", self.url);
+                return false;
             }
+            None => None,
+        };
+        let value = self.client.digest_challenges.answer(
+            challenge,
+            &credentials,
+            &self.method,
+            &self.url,
+            known_body(&self.body),
+        );
+        let value = match value {
+            Some(value) => value,
+            None => return false,
         };
 
+        debug!("answering digest challenge for '{}'", self.url);
+        self.auth.digest_attempts += 1;
+        self.as_mut().headers().insert(AUTHORIZATION, value);
+        self.send(body);
+
         true
     }
-}
 
-#[cfg(any(feature = "http2", feature = "http3"))]
-fn is_retryable_error(err: &(dyn std::error::Error + 'static)) -> bool {
-    // pop the legacy::Error
-    let err = if let Some(err) = err.source() {
-        err
-    } else {
-        return false;
-    };
+    fn retry(mut self: Pin<&mut Self>, failure: retry::Failure<'_>) -> bool {
+        let body = match self.body {
+            Some(Some(ref body)) => Some(Body::reusable(body.clone())),
+            Some(None) => {
+                debug!("{failure:?} was retryable, but body not reusable");
+                return false;
+            }
+            None => None,
+        };
 
-    #[cfg(feature = "http3")]
-    if let Some(cause) = err.source() {
-        if let Some(err) = cause.downcast_ref::<h3::error::ConnectionError>() {
-            debug!("determining if HTTP/3 error {err} can be retried");
-            // TODO: Does h3 provide an API for checking the error?
-            return err.to_string().as_str() == "timeout";
+        let delay = match self
+            .retry_policy
+            .check(&self.method, self.retry_count, failure)
+        {
+            Some(delay) => delay,
+            None => return false,
+        };
+        self.retry_count += 1;
+
+        if !delay.is_zero() {
+            debug!("retrying '{}' in {delay:?}", self.url);
+            self.as_mut()
+                .retry_delay()
+                .set(Some(Box::pin(tokio::time::sleep(delay))));
         }
-    }
 
-    #[cfg(feature = "http2")]
-    if let Some(cause) = err.source() {
-        if let Some(err) = cause.downcast_ref::<h2::Error>() {
-            // They sent us a graceful shutdown, try with a new connection!
-            if err.is_go_away() && err.is_remote() && err.reason() == Some(h2::Reason::NO_ERROR) {
-                return true;
-            }
+        self.send(body);
 
-            // REFUSED_STREAM was sent from the server, which is safe to retry.
-            // https://www.rfc-editor.org/rfc/rfc9113.html#section-8.7-3.2
-            if err.is_reset() && err.is_remote() && err.reason() == Some(h2::Reason::REFUSED_STREAM)
-            {
-                return true;
-            }
-        }
+        true
+    }
+
+    /// Sends the next hop of this request, with the current method, URL and
+    /// headers.
+    fn send(mut self: Pin<&mut Self>, body: Option<Body>) {
+        let mut req = Request::new(self.method.clone(), self.url.clone());
+        *req.headers_mut() = self.headers.clone();
+        *req.body_mut() = body;
+        *req.version_mut() = self.version;
+        *req.extensions_mut() = self.extensions.clone();
+
+        let client = self.client.clone();
+        let in_flight = self.as_mut().trace().send(&client, req);
+        *self.as_mut().in_flight() = in_flight;
     }
-    false
 }
 
 impl Pending {
@@ -2846,6 +4227,21 @@ impl Pending {
         }
     }
 
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    fn compressing<F>(request: F) -> Pending
+    where
+        F: Future<Output = Result<Response, crate::Error>> + Send + 'static,
+    {
+        Pending {
+            inner: PendingInner::Compressing(Box::pin(request)),
+        }
+    }
+
     fn inner(self: Pin<&mut Self>) -> Pin<&mut PendingInner> {
         self.project().inner
     }
@@ -2857,7 +4253,21 @@ impl Future for Pending {
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let inner = self.inner();
         match inner.get_mut() {
-            PendingInner::Request(ref mut req) => Pin::new(req).poll(cx),
+            PendingInner::Request(ref mut req) => {
+                let res = ready!(Pin::new(&mut *req).poll(cx));
+                req.trace.finish(&res);
+                if let (Err(err), Some(listener)) = (&res, &req.client.event_listener) {
+                    listener.call_failed(err);
+                }
+                Poll::Ready(res)
+            }
+            #[cfg(any(
+                feature = "gzip",
+                feature = "brotli",
+                feature = "zstd",
+                feature = "deflate"
+            ))]
+            PendingInner::Compressing(ref mut request) => request.as_mut().poll(cx),
             PendingInner::Error(ref mut err) => Poll::Ready(Err(err
                 .take()
                 .expect("Pending error polled more than once"))),
@@ -2886,33 +4296,26 @@ impl Future for PendingRequest {
         }
 
         loop {
-            let res = match self.as_mut().in_flight().get_mut() {
-                ResponseFuture::Default(r) => match Pin::new(r).poll(cx) {
-                    Poll::Ready(Err(e)) => {
-                        #[cfg(feature = "http2")]
-                        if self.as_mut().retry_error(&e) {
-                            continue;
-                        }
-                        return Poll::Ready(Err(
-                            crate::error::request(e).with_url(self.url.clone())
-                        ));
-                    }
-                    Poll::Ready(Ok(res)) => res.map(super::body::boxed),
-                    Poll::Pending => return Poll::Pending,
-                },
-                #[cfg(feature = "http3")]
-                ResponseFuture::H3(r) => match Pin::new(r).poll(cx) {
-                    Poll::Ready(Err(e)) => {
-                        if self.as_mut().retry_error(&e) {
-                            continue;
-                        }
-                        return Poll::Ready(Err(
-                            crate::error::request(e).with_url(self.url.clone())
-                        ));
+            if let Some(delay) = self.as_mut().retry_delay().as_mut().as_pin_mut() {
+                if delay.poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.as_mut().retry_delay().set(None);
+            }
+
+            let mut res = match self.as_mut().in_flight().as_mut().poll(cx) {
+                Poll::Ready(Err(e)) => {
+                    if self.as_mut().retry_error(&*e) {
+                        continue;
                     }
-                    Poll::Ready(Ok(res)) => res,
-                    Poll::Pending => return Poll::Pending,
-                },
+                    return Poll::Ready(Err(match e.downcast::<crate::Error>() {
+                        Ok(e) if e.url().is_some() => *e,
+                        Ok(e) => e.with_url(self.url.clone()),
+                        Err(e) => crate::error::request(e).with_url(self.url.clone()),
+                    }));
+                }
+                Poll::Ready(Ok(res)) => res,
+                Poll::Pending => return Poll::Pending,
             };
 
             #[cfg(feature = "cookies")]
@@ -2925,6 +4328,16 @@ impl Future for PendingRequest {
                     }
                 }
             }
+
+            if self.as_mut().retry_status(&res) {
+                continue;
+            }
+
+            #[cfg(feature = "digest-auth")]
+            if self.as_mut().digest_challenge(&res) {
+                continue;
+            }
+
             let should_redirect = match res.status() {
                 StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
                     self.body = None;
@@ -3010,11 +4423,12 @@ impl Future for PendingRequest {
                             let mut headers =
                                 std::mem::replace(self.as_mut().headers(), HeaderMap::new());
 
+                            // The credential provider, if any, is asked again
+                            // for the new URL when the hop is sent.
                             remove_sensitive_headers(&mut headers, &self.url, &self.urls);
-                            let uri = try_uri(&self.url)?;
                             let body = match self.body {
-                                Some(Some(ref body)) => Body::reusable(body.clone()),
-                                _ => Body::empty(),
+                                Some(Some(ref body)) => Some(Body::reusable(body.clone())),
+                                _ => None,
                             };
 
                             // Add cookies from the cookie store.
@@ -3025,33 +4439,22 @@ impl Future for PendingRequest {
                                 }
                             }
 
-                            *self.as_mut().in_flight().get_mut() =
-                                match *self.as_mut().in_flight().as_ref() {
-                                    #[cfg(feature = "http3")]
-                                    ResponseFuture::H3(_) => {
-                                        let mut req = hyper::Request::builder()
-                                            .method(self.method.clone())
-                                            .uri(uri.clone())
-                                            .body(body)
-                                            .expect("valid request parts");
-                                        *req.headers_mut() = headers.clone();
-                                        std::mem::swap(self.as_mut().headers(), &mut headers);
-                                        ResponseFuture::H3(self.client.h3_client
-                        .as_ref()
-                        .expect("H3 client must exists, otherwise we can't have a h3 request here")
-                                            .request(req))
-                                    }
-                                    _ => {
-                                        let mut req = hyper::Request::builder()
-                                            .method(self.method.clone())
-                                            .uri(uri.clone())
-                                            .body(body)
-                                            .expect("valid request parts");
-                                        *req.headers_mut() = headers.clone();
-                                        std::mem::swap(self.as_mut().headers(), &mut headers);
-                                        ResponseFuture::Default(self.client.hyper.request(req))
-                                    }
-                                };
+                            #[cfg(feature = "digest-auth")]
+                            {
+                                self.auth = AuthState::default();
+                                if self.urls[0].origin() == self.url.origin() {
+                                    self.client.digest_authorize(
+                                        &self.method,
+                                        &self.url,
+                                        &mut headers,
+                                        &self.extensions,
+                                        &self.body,
+                                    );
+                                }
+                            }
+
+                            *self.as_mut().headers() = headers;
+                            self.as_mut().send(body);
 
                             continue;
                         }
@@ -3065,13 +4468,9 @@ impl Future for PendingRequest {
                 }
             }
 
-            let res = Response::new(
-                res,
-                self.url.clone(),
-                self.client.accepts,
-                self.total_timeout.take(),
-                self.read_timeout,
-            );
+            if let Some(timings) = res.extensions_mut().get_mut::<ResponseTimings>() {
+                timings.total = self.started.elapsed();
+            }
             return Poll::Ready(Ok(res));
         }
     }
@@ -3085,6 +4484,13 @@ impl fmt::Debug for Pending {
                 .field("method", &req.method)
                 .field("url", &req.url)
                 .finish(),
+            #[cfg(any(
+                feature = "gzip",
+                feature = "brotli",
+                feature = "zstd",
+                feature = "deflate"
+            ))]
+            PendingInner::Compressing(_) => f.debug_struct("Pending").finish_non_exhaustive(),
             PendingInner::Error(ref err) => f.debug_struct("Pending").field("error", err).finish(),
         }
     }
@@ -7,6 +7,8 @@ use std::fmt;
 ))]
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::task::{ready, Context, Poll};
 
 #[cfg(any(
@@ -59,6 +61,27 @@ use tokio_util::codec::{BytesCodec, FramedRead};
 use tokio_util::io::StreamReader;
 
 use super::body::ResponseBody;
+use crate::error::{BodyTooLarge, DecompressionLimit};
+#[cfg(any(
+    feature = "gzip",
+    feature = "zstd",
+    feature = "brotli",
+    feature = "deflate"
+))]
+use crate::error::{TooManyCodings, UnsupportedCoding};
+
+/// How much of a body can be decompressed before the compression ratio is
+/// checked, since small bodies can be compressed a lot.
+const RATIO_CHECK_THRESHOLD: u64 = 1024 * 1024;
+
+/// How many codings of a body are decoded at most, as in curl.
+#[cfg(any(
+    feature = "gzip",
+    feature = "zstd",
+    feature = "brotli",
+    feature = "deflate"
+))]
+const MAX_CODINGS: usize = 5;
 
 #[derive(Clone, Copy, Debug)]
 pub(super) struct Accepts {
@@ -87,11 +110,27 @@ impl Accepts {
     }
 }
 
+/// The limits of the size of a response body.
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct Limits {
+    /// The maximum size of the body, after decompression.
+    pub(super) body_size: Option<u64>,
+    /// The maximum size of a decompressed body.
+    pub(super) decompressed_size: Option<u64>,
+    /// The maximum ratio of the decompressed size to the compressed size.
+    pub(super) compression_ratio: Option<u32>,
+}
+
 /// A response decompressor over a non-blocking stream of chunks.
 ///
 /// The inner decoder may be constructed asynchronously.
 pub(crate) struct Decoder {
     inner: Inner,
+    limits: Limits,
+    /// The size of the body returned so far.
+    decoded: u64,
+    /// The size of the compressed body read so far, when decompressing.
+    encoded: Option<Arc<AtomicU64>>,
 }
 
 #[cfg(any(
@@ -138,6 +177,9 @@ enum Inner {
         feature = "deflate"
     ))]
     Pending(Pin<Box<Pending>>),
+
+    /// A decoded body wrapped by a client option that observes it.
+    Wrapped(Pin<Box<dyn HttpBody<Data = Bytes, Error = crate::Error> + Send + Sync>>),
 }
 
 #[cfg(any(
@@ -175,6 +217,28 @@ enum DecoderType {
     Deflate,
 }
 
+#[cfg(any(
+    feature = "gzip",
+    feature = "zstd",
+    feature = "brotli",
+    feature = "deflate"
+))]
+impl DecoderType {
+    fn from_coding(coding: &str, _accepts: Accepts) -> Option<DecoderType> {
+        match coding {
+            #[cfg(feature = "gzip")]
+            "gzip" | "x-gzip" if _accepts.gzip => Some(DecoderType::Gzip),
+            #[cfg(feature = "brotli")]
+            "br" if _accepts.brotli => Some(DecoderType::Brotli),
+            #[cfg(feature = "zstd")]
+            "zstd" if _accepts.zstd => Some(DecoderType::Zstd),
+            #[cfg(feature = "deflate")]
+            "deflate" if _accepts.deflate => Some(DecoderType::Deflate),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Debug for Decoder {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Decoder").finish()
@@ -184,9 +248,7 @@ impl fmt::Debug for Decoder {
 impl Decoder {
     #[cfg(feature = "blocking")]
     pub(crate) fn empty() -> Decoder {
-        Decoder {
-            inner: Inner::PlainText(empty()),
-        }
+        Decoder::plain_text(empty())
     }
 
     #[cfg(feature = "blocking")]
@@ -194,12 +256,33 @@ impl Decoder {
         IoStream(self)
     }
 
+    /// Wrap this decoder in a body that passes along its frames.
+    pub(super) fn wrap<B, F>(self, f: F) -> Decoder
+    where
+        F: FnOnce(Decoder) -> B,
+        B: HttpBody<Data = Bytes, Error = crate::Error> + Send + Sync + 'static,
+    {
+        Decoder {
+            inner: Inner::Wrapped(Box::pin(f(self))),
+            limits: Limits::default(),
+            decoded: 0,
+            encoded: None,
+        }
+    }
+
     /// A plain text decoder.
     ///
     /// This decoder will emit the underlying chunks as-is.
     fn plain_text(body: ResponseBody) -> Decoder {
+        Decoder::new(Inner::PlainText(body))
+    }
+
+    fn new(inner: Inner) -> Decoder {
         Decoder {
-            inner: Inner::PlainText(body),
+            inner,
+            limits: Limits::default(),
+            decoded: 0,
+            encoded: None,
         }
     }
 
@@ -210,12 +293,10 @@ impl Decoder {
     fn gzip(body: ResponseBody) -> Decoder {
         use futures_util::StreamExt;
 
-        Decoder {
-            inner: Inner::Pending(Box::pin(Pending(
-                IoStream(body).peekable(),
-                DecoderType::Gzip,
-            ))),
-        }
+        Decoder::new(Inner::Pending(Box::pin(Pending(
+            IoStream(body).peekable(),
+            DecoderType::Gzip,
+        ))))
     }
 
     /// A brotli decoder.
@@ -225,12 +306,10 @@ impl Decoder {
     fn brotli(body: ResponseBody) -> Decoder {
         use futures_util::StreamExt;
 
-        Decoder {
-            inner: Inner::Pending(Box::pin(Pending(
-                IoStream(body).peekable(),
-                DecoderType::Brotli,
-            ))),
-        }
+        Decoder::new(Inner::Pending(Box::pin(Pending(
+            IoStream(body).peekable(),
+            DecoderType::Brotli,
+        ))))
     }
 
     /// A zstd decoder.
@@ -240,12 +319,10 @@ impl Decoder {
     fn zstd(body: ResponseBody) -> Decoder {
         use futures_util::StreamExt;
 
-        Decoder {
-            inner: Inner::Pending(Box::pin(Pending(
-                IoStream(body).peekable(),
-                DecoderType::Zstd,
-            ))),
-        }
+        Decoder::new(Inner::Pending(Box::pin(Pending(
+            IoStream(body).peekable(),
+            DecoderType::Zstd,
+        ))))
     }
 
     /// A deflate decoder.
@@ -255,47 +332,58 @@ impl Decoder {
     fn deflate(body: ResponseBody) -> Decoder {
         use futures_util::StreamExt;
 
-        Decoder {
-            inner: Inner::Pending(Box::pin(Pending(
-                IoStream(body).peekable(),
-                DecoderType::Deflate,
-            ))),
-        }
+        Decoder::new(Inner::Pending(Box::pin(Pending(
+            IoStream(body).peekable(),
+            DecoderType::Deflate,
+        ))))
     }
 
+    /// A decoder of a single coding.
     #[cfg(any(
-        feature = "brotli",
-        feature = "zstd",
         feature = "gzip",
+        feature = "zstd",
+        feature = "brotli",
         feature = "deflate"
     ))]
-    fn detect_encoding(headers: &mut HeaderMap, encoding_str: &str) -> bool {
-        use http::header::{CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING};
-        use log::warn;
-
-        let mut is_content_encoded = {
-            headers
-                .get_all(CONTENT_ENCODING)
-                .iter()
-                .any(|enc| enc == encoding_str)
-                || headers
-                    .get_all(TRANSFER_ENCODING)
-                    .iter()
-                    .any(|enc| enc == encoding_str)
-        };
-        if is_content_encoded {
-            if let Some(content_length) = headers.get(CONTENT_LENGTH) {
-                if content_length == "0" {
-                    warn!("{encoding_str} response with content-length of 0");
-                    is_content_encoded = false;
-                }
-            }
-        }
-        if is_content_encoded {
-            headers.remove(CONTENT_ENCODING);
-            headers.remove(CONTENT_LENGTH);
+    fn of(decoder_type: DecoderType, body: ResponseBody) -> Decoder {
+        match decoder_type {
+            #[cfg(feature = "gzip")]
+            DecoderType::Gzip => Decoder::gzip(body),
+            #[cfg(feature = "brotli")]
+            DecoderType::Brotli => Decoder::brotli(body),
+            #[cfg(feature = "zstd")]
+            DecoderType::Zstd => Decoder::zstd(body),
+            #[cfg(feature = "deflate")]
+            DecoderType::Deflate => Decoder::deflate(body),
         }
-        is_content_encoded
+    }
+
+    /// The codings of the body, in the order they were applied.
+    ///
+    /// Transfer codings are applied after content codings. The `identity`
+    /// coding, and the `chunked` transfer coding removed by hyper, are left
+    /// out.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "zstd",
+        feature = "brotli",
+        feature = "deflate"
+    ))]
+    fn codings(headers: &HeaderMap) -> Vec<String> {
+        use http::header::{CONTENT_ENCODING, TRANSFER_ENCODING};
+
+        headers
+            .get_all(CONTENT_ENCODING)
+            .iter()
+            .chain(headers.get_all(TRANSFER_ENCODING))
+            .flat_map(|value| {
+                String::from_utf8_lossy(value.as_bytes())
+                    .split(',')
+                    .map(|coding| coding.trim().to_ascii_lowercase())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|coding| !coding.is_empty() && coding != "identity" && coding != "chunked")
+            .collect()
     }
 
     /// Constructs a Decoder from a hyper request.
@@ -303,52 +391,135 @@ impl Decoder {
     /// A decoder is just a wrapper around the hyper request that knows
     /// how to decode the content body of the request.
     ///
-    /// Uses the correct variant by inspecting the Content-Encoding header.
+    /// Uses the correct variant by inspecting the Content-Encoding header,
+    /// and checks that the decoded body stays within `limits`.
     pub(super) fn detect(
-        _headers: &mut HeaderMap,
+        headers: &mut HeaderMap,
         body: ResponseBody,
-        _accepts: Accepts,
+        accepts: Accepts,
+        limits: Limits,
     ) -> Decoder {
-        #[cfg(feature = "gzip")]
-        {
-            if _accepts.gzip && Decoder::detect_encoding(_headers, "gzip") {
-                return Decoder::gzip(body);
-            }
-        }
+        use http_body_util::BodyExt;
+
+        // count the compressed bytes to check the compression ratio
+        let encoded = Arc::new(AtomicU64::new(0));
+        let body = if limits.compression_ratio.is_some() {
+            let encoded = encoded.clone();
+            let counted = body.map_frame(move |frame| {
+                if let Some(data) = frame.data_ref() {
+                    encoded.fetch_add(data.len() as u64, Ordering::Relaxed);
+                }
+                frame
+            });
+            ResponseBody::new(counted)
+        } else {
+            body
+        };
 
-        #[cfg(feature = "brotli")]
-        {
-            if _accepts.brotli && Decoder::detect_encoding(_headers, "br") {
-                return Decoder::brotli(body);
-            }
+        let mut decoder = Decoder::select(headers, body, accepts);
+        if !matches!(decoder.inner, Inner::PlainText(_)) {
+            decoder.encoded = Some(encoded);
         }
+        decoder.limits = limits;
+        decoder
+    }
 
-        #[cfg(feature = "zstd")]
-        {
-            if _accepts.zstd && Decoder::detect_encoding(_headers, "zstd") {
-                return Decoder::zstd(body);
+    /// Selects a chain of decoders, one for each coding of the body.
+    ///
+    /// If none of the codings are accepted, the body is passed along as is.
+    /// Otherwise, a body with a coding that is not accepted, or with more
+    /// than `MAX_CODINGS` codings, fails to decode.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "zstd",
+        feature = "brotli",
+        feature = "deflate"
+    ))]
+    fn select(headers: &mut HeaderMap, body: ResponseBody, accepts: Accepts) -> Decoder {
+        use http::header::{CONTENT_ENCODING, CONTENT_LENGTH};
+        use log::warn;
+
+        let codings = Decoder::codings(headers);
+        if codings.is_empty() {
+            return Decoder::plain_text(body);
+        }
+        if let Some(content_length) = headers.get(CONTENT_LENGTH) {
+            if content_length == "0" {
+                warn!("{} response with content-length of 0", codings.join(", "));
+                return Decoder::plain_text(body);
             }
         }
 
-        #[cfg(feature = "deflate")]
-        {
-            if _accepts.deflate && Decoder::detect_encoding(_headers, "deflate") {
-                return Decoder::deflate(body);
+        let mut chain = Vec::with_capacity(codings.len());
+        let mut unsupported = None;
+        for coding in &codings {
+            match DecoderType::from_coding(coding, accepts) {
+                Some(decoder_type) => chain.push(decoder_type),
+                None => unsupported = Some(coding),
             }
         }
+        if chain.is_empty() {
+            return Decoder::plain_text(body);
+        }
+        if let Some(coding) = unsupported {
+            return Decoder::plain_text(failing(UnsupportedCoding(coding.clone())));
+        }
+        if chain.len() > MAX_CODINGS {
+            return Decoder::plain_text(failing(TooManyCodings(chain.len())));
+        }
+        headers.remove(CONTENT_ENCODING);
+        headers.remove(CONTENT_LENGTH);
+
+        // the last coding applied is the first one decoded
+        let mut chain = chain.into_iter().rev();
+        let first = chain.next().expect("chain is not empty");
+        chain.fold(Decoder::of(first, body), |decoder, decoder_type| {
+            Decoder::of(decoder_type, super::body::boxed(decoder))
+        })
+    }
 
+    #[cfg(not(any(
+        feature = "gzip",
+        feature = "zstd",
+        feature = "brotli",
+        feature = "deflate"
+    )))]
+    fn select(_headers: &mut HeaderMap, body: ResponseBody, _accepts: Accepts) -> Decoder {
         Decoder::plain_text(body)
     }
-}
 
-impl HttpBody for Decoder {
-    type Data = Bytes;
-    type Error = crate::Error;
+    /// Check that the body decoded so far, and the rest of it if its size
+    /// is known, is within the limits.
+    fn check_limits(&self) -> Result<(), crate::Error> {
+        let body_size = self.decoded.saturating_add(self.size_hint().lower());
+        if let Some(limit) = self.limits.body_size {
+            if body_size > limit {
+                return Err(crate::error::body(BodyTooLarge(limit)));
+            }
+        }
+        let encoded = match self.encoded {
+            Some(ref encoded) => encoded.load(Ordering::Relaxed),
+            None => return Ok(()),
+        };
+        if let Some(limit) = self.limits.decompressed_size {
+            if self.decoded > limit {
+                return Err(crate::error::decode(DecompressionLimit::Size(limit)));
+            }
+        }
+        if let Some(ratio) = self.limits.compression_ratio {
+            if self.decoded > RATIO_CHECK_THRESHOLD
+                && self.decoded / encoded.max(1) >= u64::from(ratio)
+            {
+                return Err(crate::error::decode(DecompressionLimit::Ratio(ratio)));
+            }
+        }
+        Ok(())
+    }
 
-    fn poll_frame(
-        mut self: Pin<&mut Self>,
+    fn poll_decoded(
+        &mut self,
         cx: &mut Context,
-    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+    ) -> Poll<Option<Result<Frame<Bytes>, crate::Error>>> {
         match self.inner {
             #[cfg(any(
                 feature = "brotli",
@@ -359,7 +530,7 @@ impl HttpBody for Decoder {
             Inner::Pending(ref mut future) => match Pin::new(future).poll(cx) {
                 Poll::Ready(Ok(inner)) => {
                     self.inner = inner;
-                    self.poll_frame(cx)
+                    self.poll_decoded(cx)
                 }
                 Poll::Ready(Err(e)) => Poll::Ready(Some(Err(crate::error::decode_io(e)))),
                 Poll::Pending => Poll::Pending,
@@ -369,6 +540,7 @@ impl HttpBody for Decoder {
                 Some(Err(err)) => Poll::Ready(Some(Err(crate::error::decode(err)))),
                 None => Poll::Ready(None),
             },
+            Inner::Wrapped(ref mut body) => body.as_mut().poll_frame(cx),
             #[cfg(feature = "gzip")]
             Inner::Gzip(ref mut decoder) => {
                 match ready!(Pin::new(&mut *decoder).poll_next(cx)) {
@@ -427,10 +599,46 @@ impl HttpBody for Decoder {
             }
         }
     }
+}
+
+impl HttpBody for Decoder {
+    type Data = Bytes;
+    type Error = crate::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        // a body known to be too large is not read at all
+        let checked = if self.decoded == 0 {
+            self.check_limits()
+        } else {
+            Ok(())
+        };
+        let checked = match checked {
+            Ok(()) => match ready!(self.poll_decoded(cx)) {
+                Some(Ok(frame)) => {
+                    if let Some(data) = frame.data_ref() {
+                        self.decoded += data.len() as u64;
+                    }
+                    self.check_limits().map(|()| frame)
+                }
+                other => return Poll::Ready(other),
+            },
+            Err(err) => Err(err),
+        };
+        if checked.is_err() {
+            // the rest of the body is dropped
+            self.inner = Inner::PlainText(empty());
+            self.encoded = None;
+        }
+        Poll::Ready(Some(checked))
+    }
 
     fn size_hint(&self) -> http_body::SizeHint {
         match self.inner {
             Inner::PlainText(ref body) => HttpBody::size_hint(body),
+            Inner::Wrapped(ref body) => body.size_hint(),
             // the rest are "unknown", so default
             #[cfg(any(
                 feature = "brotli",
@@ -471,13 +679,23 @@ fn poll_inner_should_be_empty(
     }
 }
 
+/// A body that fails to decode, because of its codings.
 #[cfg(any(
     feature = "gzip",
     feature = "zstd",
     feature = "brotli",
-    feature = "deflate",
-    feature = "blocking",
+    feature = "deflate"
 ))]
+fn failing<E>(err: E) -> ResponseBody
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    use http_body_util::{BodyExt, StreamBody};
+
+    let err: Box<dyn std::error::Error + Send + Sync> = Box::new(err);
+    StreamBody::new(futures_util::stream::iter(Some(Err(err)))).boxed()
+}
+
 fn empty() -> ResponseBody {
     use http_body_util::{combinators::BoxBody, BodyExt, Empty};
     BoxBody::new(Empty::new().map_err(|never| match never {}))
@@ -695,6 +913,31 @@ impl Default for Accepts {
 mod tests {
     use super::*;
 
+    #[cfg(any(
+        feature = "gzip",
+        feature = "zstd",
+        feature = "brotli",
+        feature = "deflate"
+    ))]
+    #[test]
+    fn codings_in_applied_order() {
+        use http::header::{HeaderValue, CONTENT_ENCODING, TRANSFER_ENCODING};
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            CONTENT_ENCODING,
+            HeaderValue::from_static("deflate, identity"),
+        );
+        headers.append(CONTENT_ENCODING, HeaderValue::from_static(" Br ,,zstd"));
+        headers.append(TRANSFER_ENCODING, HeaderValue::from_static("gzip, chunked"));
+        assert_eq!(
+            Decoder::codings(&headers),
+            ["deflate", "br", "zstd", "gzip"]
+        );
+
+        assert!(Decoder::codings(&HeaderMap::new()).is_empty());
+    }
+
     #[test]
     fn accepts_as_str() {
         fn format_accept_encoding(accepts: &Accepts) -> String {
@@ -0,0 +1,171 @@
+//! Compression of request bodies.
+
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+
+#[cfg(feature = "brotli")]
+use async_compression::tokio::bufread::BrotliEncoder;
+#[cfg(feature = "gzip")]
+use async_compression::tokio::bufread::GzipEncoder;
+#[cfg(feature = "deflate")]
+use async_compression::tokio::bufread::ZlibEncoder;
+#[cfg(feature = "zstd")]
+use async_compression::tokio::bufread::ZstdEncoder;
+use bytes::{Bytes, BytesMut};
+use futures_util::{future, TryStreamExt};
+use http::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH};
+use http_body_util::BodyDataStream;
+use tokio::io::{AsyncBufRead, AsyncRead};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use super::Body;
+
+/// A content coding used to compress a request body.
+///
+/// See [`RequestBuilder::compress`](crate::RequestBuilder::compress).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// The `gzip` coding.
+    #[cfg(feature = "gzip")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "gzip")))]
+    Gzip,
+    /// The `br` coding.
+    #[cfg(feature = "brotli")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "brotli")))]
+    Brotli,
+    /// The `zstd` coding.
+    #[cfg(feature = "zstd")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "zstd")))]
+    Zstd,
+    /// The `deflate` coding, which is zlib wrapped deflate data.
+    #[cfg(feature = "deflate")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "deflate")))]
+    Deflate,
+}
+
+impl Encoding {
+    /// Returns the value of the `Content-Encoding` header for this coding.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => "gzip",
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => "br",
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => "zstd",
+            #[cfg(feature = "deflate")]
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    fn encoder<R>(self, reader: R) -> Pin<Box<dyn AsyncRead + Send>>
+    where
+        R: AsyncBufRead + Send + 'static,
+    {
+        match self {
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => Box::pin(GzipEncoder::new(reader)),
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => Box::pin(BrotliEncoder::new(reader)),
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => Box::pin(ZstdEncoder::new(reader)),
+            #[cfg(feature = "deflate")]
+            Encoding::Deflate => Box::pin(ZlibEncoder::new(reader)),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Compresses a streaming `body` with `encoding` as it is sent, and updates
+/// the headers to match.
+///
+/// The length of the compressed body isn't known.
+pub(super) fn encode(encoding: Encoding, body: Body, headers: &mut HeaderMap) -> Body {
+    let chunks = BodyDataStream::new(body).map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+    set_headers(encoding, headers);
+    Body::stream(ReaderStream::new(
+        encoding.encoder(StreamReader::new(chunks)),
+    ))
+}
+
+/// Compresses all of the `bytes` of a buffered body with `encoding`, and
+/// updates the headers to match.
+///
+/// The compressed body keeps a known length, and can still be sent again.
+pub(super) async fn encode_bytes(
+    encoding: Encoding,
+    bytes: Bytes,
+    headers: &mut HeaderMap,
+) -> io::Result<Body> {
+    let encoded = ReaderStream::new(encoding.encoder(io::Cursor::new(bytes)))
+        .try_fold(BytesMut::new(), |mut encoded, chunk| {
+            encoded.extend_from_slice(&chunk);
+            future::ready(Ok(encoded))
+        })
+        .await?;
+    set_headers(encoding, headers);
+    Ok(Body::from(encoded.freeze()))
+}
+
+fn set_headers(encoding: Encoding, headers: &mut HeaderMap) {
+    headers.insert(
+        CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.as_str()),
+    );
+    // the length of the body is set when it is sent, if it is known
+    headers.remove(CONTENT_LENGTH);
+}
+
+#[cfg(all(test, feature = "gzip"))]
+mod tests {
+    use super::*;
+
+    use futures_util::{stream, StreamExt};
+    use http_body_util::BodyExt;
+
+    fn headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("11"));
+        headers
+    }
+
+    async fn gunzip(body: Body) -> Vec<u8> {
+        let encoded = body.collect().await.unwrap().to_bytes();
+        let mut decoded = Vec::new();
+        io::Read::read_to_end(
+            &mut flate2::read::GzDecoder::new(&encoded[..]),
+            &mut decoded,
+        )
+        .unwrap();
+        decoded
+    }
+
+    #[tokio::test]
+    async fn encode_buffered_body() {
+        let mut headers = headers();
+        let body = encode_bytes(Encoding::Gzip, Bytes::from("hello world"), &mut headers)
+            .await
+            .unwrap();
+        assert_eq!(headers[CONTENT_ENCODING], "gzip");
+        assert!(!headers.contains_key(CONTENT_LENGTH));
+        assert!(body.as_bytes().is_some());
+        assert_eq!(gunzip(body).await, b"hello world");
+    }
+
+    #[tokio::test]
+    async fn encode_streaming_body() {
+        let chunks = stream::iter(["hello", " ", "world"]).map(Ok::<_, io::Error>);
+        let mut headers = headers();
+        let body = encode(Encoding::Gzip, Body::stream(chunks), &mut headers);
+        assert_eq!(headers[CONTENT_ENCODING], "gzip");
+        assert!(body.as_bytes().is_none());
+        assert_eq!(gunzip(body).await, b"hello world");
+    }
+}
@@ -0,0 +1,217 @@
+//! The HTTP/3 alternative services of origins, advertised in `Alt-Svc`
+//! headers, as specified in [RFC 7838].
+//!
+//! [RFC 7838]: https://www.rfc-editor.org/rfc/rfc7838
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http::header::{HeaderMap, ALT_SVC};
+use log::trace;
+use url::Url;
+
+/// How long an alternative service is used when its `ma` is not given.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long an alternative service is not used after failing to connect.
+const BROKEN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// The HTTP/3 alternative services of `https` origins, until they expire.
+#[derive(Clone, Default)]
+pub(crate) struct AltSvcCache {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+/// Where an origin is also served over HTTP/3.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Alternative {
+    /// The host, or `None` for the host of the origin.
+    pub(crate) host: Option<String>,
+    pub(crate) port: u16,
+}
+
+struct Entry {
+    alternative: Alternative,
+    expires: Instant,
+    broken_until: Option<Instant>,
+}
+
+impl AltSvcCache {
+    /// Get the alternative service to use for the origin of `url`, if any.
+    pub(crate) fn get(&self, url: &Url) -> Option<Alternative> {
+        let origin = origin(url)?;
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get(&origin)?;
+        if entry.expires <= now {
+            entries.remove(&origin);
+            return None;
+        }
+        if entry.broken_until.map_or(false, |until| until > now) {
+            return None;
+        }
+        Some(entry.alternative.clone())
+    }
+
+    /// Remember the alternative service advertised in `headers` for the
+    /// origin of `url`, replacing the previous one.
+    ///
+    /// Responses without an `Alt-Svc` header keep the previous one.
+    pub(crate) fn update(&self, url: &Url, headers: &HeaderMap) {
+        let origin = match origin(url) {
+            Some(origin) => origin,
+            None => return,
+        };
+        let mut values = headers.get_all(ALT_SVC).iter().peekable();
+        if values.peek().is_none() {
+            return;
+        }
+        let advertised = values
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(parse);
+
+        let mut entries = self.entries.lock().unwrap();
+        match advertised {
+            Some((alternative, max_age)) => {
+                // keep remembering a failure to connect to the same service
+                let broken_until = entries
+                    .get(&origin)
+                    .filter(|entry| entry.alternative == alternative)
+                    .and_then(|entry| entry.broken_until);
+                trace!("alternative service for {origin}: {alternative:?}");
+                entries.insert(
+                    origin,
+                    Entry {
+                        alternative,
+                        expires: Instant::now() + max_age,
+                        broken_until,
+                    },
+                );
+            }
+            None => {
+                entries.remove(&origin);
+            }
+        }
+    }
+
+    /// Stop using the alternative service of the origin of `url` for a
+    /// while, after failing to connect to it.
+    pub(crate) fn mark_broken(&self, url: &Url) {
+        let origin = match origin(url) {
+            Some(origin) => origin,
+            None => return,
+        };
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(&origin) {
+            entry.broken_until = Some(Instant::now() + BROKEN_TIMEOUT);
+        }
+    }
+}
+
+/// The origin of `url` as `host:port`, if it is an `https` one.
+fn origin(url: &Url) -> Option<String> {
+    if url.scheme() != "https" {
+        return None;
+    }
+    let host = url.host_str()?.to_ascii_lowercase();
+    Some(format!("{}:{}", host, url.port_or_known_default()?))
+}
+
+/// Parse an `h3` alternative, such as `h3=":443"; ma=3600`, and how long
+/// it can be used.
+///
+/// Other protocols, and `clear`, give `None`.
+fn parse(value: &str) -> Option<(Alternative, Duration)> {
+    let mut params = value.split(';');
+    let (protocol, authority) = params.next()?.trim().split_once('=')?;
+    if protocol != "h3" {
+        return None;
+    }
+    let authority = authority.strip_prefix('"')?.strip_suffix('"')?;
+    let (host, port) = authority.rsplit_once(':')?;
+    let alternative = Alternative {
+        host: Some(host)
+            .filter(|host| !host.is_empty())
+            .map(str::to_owned),
+        port: port.parse().ok()?,
+    };
+
+    let mut max_age = DEFAULT_MAX_AGE;
+    for param in params {
+        if let Some((name, value)) = param.split_once('=') {
+            if name.trim() == "ma" {
+                max_age = Duration::from_secs(value.trim().trim_matches('"').parse().ok()?);
+            }
+        }
+    }
+    Some((alternative, max_age))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alt(host: Option<&str>, port: u16) -> Alternative {
+        Alternative {
+            host: host.map(str::to_owned),
+            port,
+        }
+    }
+
+    #[test]
+    fn parse_alternatives() {
+        assert_eq!(
+            parse(r#"h3=":443""#),
+            Some((alt(None, 443), DEFAULT_MAX_AGE))
+        );
+        assert_eq!(
+            parse(r#" h3="alt.example:8443"; ma=60; persist=1"#),
+            Some((alt(Some("alt.example"), 8443), Duration::from_secs(60)))
+        );
+        assert_eq!(
+            parse(r#"h3="[::1]:443";ma=0"#),
+            Some((alt(Some("[::1]"), 443), Duration::ZERO))
+        );
+        assert_eq!(parse(r#"h2=":443""#), None);
+        assert_eq!(parse("clear"), None);
+        assert_eq!(parse(r#"h3=":port""#), None);
+    }
+
+    #[test]
+    fn cache_updates() {
+        let cache = AltSvcCache::default();
+        let url = Url::parse("https://example.com/path").unwrap();
+        let mut headers = HeaderMap::new();
+
+        cache.update(&url, &headers);
+        assert_eq!(cache.get(&url), None);
+
+        headers.insert(ALT_SVC, r#"h3-29=":443", h3=":8443""#.parse().unwrap());
+        cache.update(&url, &headers);
+        assert_eq!(cache.get(&url), Some(alt(None, 8443)));
+        let other = Url::parse("https://example.com:444/").unwrap();
+        assert_eq!(cache.get(&other), None);
+
+        // plain `http` origins are not upgraded
+        let insecure = Url::parse("http://example.com/").unwrap();
+        cache.update(&insecure, &headers);
+        assert_eq!(cache.get(&insecure), None);
+
+        cache.mark_broken(&url);
+        assert_eq!(cache.get(&url), None);
+        cache.update(&url, &headers);
+        assert_eq!(cache.get(&url), None);
+
+        // clearing forgets the failure too
+        headers.insert(ALT_SVC, "clear".parse().unwrap());
+        cache.update(&url, &headers);
+        headers.insert(ALT_SVC, r#"h3=":8443""#.parse().unwrap());
+        cache.update(&url, &headers);
+        assert_eq!(cache.get(&url), Some(alt(None, 8443)));
+
+        headers.insert(ALT_SVC, r#"h3=":8443"; ma=0"#.parse().unwrap());
+        cache.update(&url, &headers);
+        assert_eq!(cache.get(&url), None);
+    }
+}
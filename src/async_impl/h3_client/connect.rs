@@ -1,3 +1,4 @@
+use crate::async_impl::h3_client::alt_svc::Alternative;
 use crate::async_impl::h3_client::dns::resolve;
 use crate::dns::DynResolver;
 use crate::error::BoxError;
@@ -11,6 +12,9 @@ use quinn::{ClientConfig, Endpoint, TransportConfig};
 use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::{self, Either};
 
 type H3Connection = (
     h3::client::Connection<Connection, Bytes>,
@@ -58,6 +62,7 @@ impl Default for H3ClientConfig {
 pub(crate) struct H3Connector {
     resolver: DynResolver,
     endpoint: Endpoint,
+    happy_eyeballs_timeout: Option<Duration>,
     client_config: H3ClientConfig,
 }
 
@@ -66,6 +71,7 @@ impl H3Connector {
         resolver: DynResolver,
         tls: rustls::ClientConfig,
         local_addr: Option<IpAddr>,
+        happy_eyeballs_timeout: Option<Duration>,
         transport_config: TransportConfig,
         client_config: H3ClientConfig,
     ) -> Result<H3Connector, BoxError> {
@@ -85,17 +91,32 @@ impl H3Connector {
         Ok(Self {
             resolver,
             endpoint,
+            happy_eyeballs_timeout,
             client_config,
         })
     }
 
-    pub async fn connect(&mut self, dest: Uri) -> Result<H3Connection, BoxError> {
-        let host = dest
+    /// Connect to `dest`, or to `alt` with the name of `dest` when given
+    /// an alternative service.
+    pub async fn connect(
+        &mut self,
+        dest: Uri,
+        alt: Option<&Alternative>,
+    ) -> Result<H3Connection, BoxError> {
+        let server_name = dest
             .host()
             .ok_or("destination must have a host")?
             .trim_start_matches('[')
             .trim_end_matches(']');
-        let port = dest.port_u16().unwrap_or(443);
+        let (host, port) = match alt {
+            Some(alt) => {
+                let host = alt.host.as_deref().map_or(server_name, |host| {
+                    host.trim_start_matches('[').trim_end_matches(']')
+                });
+                (host, alt.port)
+            }
+            None => (server_name, dest.port_u16().unwrap_or(443)),
+        };
 
         let addrs = if let Some(addr) = IpAddr::from_str(host).ok() {
             // If the host is already an IP address, skip resolving.
@@ -109,11 +130,54 @@ impl H3Connector {
             addrs.collect()
         };
 
-        self.remote_connect(addrs, host).await
+        self.remote_connect(addrs, server_name).await
     }
 
+    /// Connect to one of `addrs`, starting with those of the family of the
+    /// first one, and racing those of the other family after the Happy
+    /// Eyeballs timeout.
     async fn remote_connect(
-        &mut self,
+        &self,
+        mut addrs: Vec<SocketAddr>,
+        server_name: &str,
+    ) -> Result<H3Connection, BoxError> {
+        let preferred_v6 = addrs.first().map_or(false, SocketAddr::is_ipv6);
+        let fallback_addrs = addrs
+            .iter()
+            .filter(|addr| addr.is_ipv6() != preferred_v6)
+            .copied()
+            .collect::<Vec<_>>();
+        addrs.retain(|addr| addr.is_ipv6() == preferred_v6);
+
+        let delay = match self.happy_eyeballs_timeout {
+            Some(delay) if !fallback_addrs.is_empty() => delay,
+            _ => {
+                addrs.extend(fallback_addrs);
+                return self.connect_any(addrs, server_name).await;
+            }
+        };
+
+        let preferred = self.connect_any(addrs, server_name);
+        let delay = tokio::time::sleep(delay);
+        futures_util::pin_mut!(preferred, delay);
+        match future::select(preferred, delay).await {
+            Either::Left((Ok(conn), _)) => Ok(conn),
+            Either::Left((Err(_), _)) => self.connect_any(fallback_addrs, server_name).await,
+            Either::Right(((), preferred)) => {
+                let fallback = self.connect_any(fallback_addrs, server_name);
+                futures_util::pin_mut!(fallback);
+                match future::select(preferred, fallback).await {
+                    Either::Left((Ok(conn), _)) | Either::Right((Ok(conn), _)) => Ok(conn),
+                    Either::Left((Err(_), fallback)) => fallback.await,
+                    Either::Right((Err(_), preferred)) => preferred.await,
+                }
+            }
+        }
+    }
+
+    /// Connect to the first of `addrs` that accepts a connection.
+    async fn connect_any(
+        &self,
         addrs: Vec<SocketAddr>,
         server_name: &str,
     ) -> Result<H3Connection, BoxError> {
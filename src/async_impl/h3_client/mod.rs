@@ -1,10 +1,12 @@
 #![cfg(feature = "http3")]
 
+pub(crate) mod alt_svc;
 pub(crate) mod connect;
 pub(crate) mod dns;
 mod pool;
 
 use crate::async_impl::body::ResponseBody;
+use crate::async_impl::h3_client::alt_svc::Alternative;
 use crate::async_impl::h3_client::pool::{Key, Pool, PoolClient};
 use crate::error::{BoxError, Error, Kind};
 use crate::{error, Body};
@@ -17,6 +19,11 @@ use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
+/// How long connecting to an alternative service may take before the
+/// origin is used instead, so that a network dropping UDP doesn't stall
+/// requests.
+const ALT_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
 #[derive(Clone)]
 pub(crate) struct H3Client {
     pool: Pool,
@@ -31,7 +38,11 @@ impl H3Client {
         }
     }
 
-    async fn get_pooled_client(&mut self, key: Key) -> Result<PoolClient, BoxError> {
+    async fn get_pooled_client(
+        &mut self,
+        key: Key,
+        alt: Option<&Alternative>,
+    ) -> Result<PoolClient, BoxError> {
         if let Some(client) = self.pool.try_pool(&key) {
             trace!("getting client from pool with key {key:?}");
             return Ok(client);
@@ -53,7 +64,7 @@ impl H3Client {
             pool::Connecting::Acquired(lock) => lock,
         };
         trace!("connecting to {key:?}...");
-        let (driver, tx) = self.connector.connect(dest).await?;
+        let (driver, tx) = self.connector.connect(dest, alt).await?;
         trace!("saving new pooled connection to {key:?}");
         Ok(self.pool.new_connection(lock, driver, tx))
     }
@@ -63,7 +74,7 @@ impl H3Client {
         key: Key,
         req: Request<Body>,
     ) -> Result<Response<ResponseBody>, Error> {
-        let mut pooled = match self.get_pooled_client(key).await {
+        let mut pooled = match self.get_pooled_client(key, None).await {
             Ok(client) => client,
             Err(e) => return Err(error::request(e)),
         };
@@ -88,6 +99,47 @@ impl H3Client {
     }
 }
 
+/// The outcome of sending a request to an alternative service.
+pub(crate) enum AltResponse {
+    /// The request was sent, and this is its response or error.
+    Sent(Result<Response<ResponseBody>, Error>),
+    /// No connection could be made, so the request is given back to be
+    /// sent another way.
+    Unavailable(Request<Body>, BoxError),
+}
+
+impl H3Client {
+    /// Send `req` over a connection to `alt`, an alternative service of the
+    /// origin of `req`.
+    pub(crate) async fn request_alt(
+        mut self,
+        mut req: Request<Body>,
+        alt: Alternative,
+    ) -> AltResponse {
+        let key = match pool::extract_domain(req.uri_mut()) {
+            Ok(key) => key,
+            Err(e) => return AltResponse::Sent(Err(e)),
+        };
+        let connecting = self.get_pooled_client(key, Some(&alt));
+        let mut pooled = match tokio::time::timeout(ALT_CONNECT_TIMEOUT, connecting).await {
+            Ok(Ok(client)) => client,
+            Ok(Err(e)) => return AltResponse::Unavailable(req, e),
+            Err(_) => {
+                return AltResponse::Unavailable(
+                    req,
+                    "timed out connecting to the alternative service".into(),
+                )
+            }
+        };
+        AltResponse::Sent(
+            pooled
+                .send_request(req)
+                .await
+                .map_err(|e| Error::new(Kind::Request, Some(e))),
+        )
+    }
+}
+
 pub(crate) struct H3ResponseFuture {
     inner: Pin<Box<dyn Future<Output = Result<Response<ResponseBody>, Error>> + Send>>,
 }
@@ -0,0 +1,205 @@
+//! Newline-delimited JSON bodies, with one JSON value per line.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::BytesMut;
+use futures_core::Stream;
+use futures_util::{ready, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::body::DataStream;
+use super::decoder::Decoder;
+use super::Body;
+
+/// The longest line, unless set with [`JsonLines::max_line_length`].
+const DEFAULT_MAX_LINE_LENGTH: usize = 16 * 1024 * 1024;
+
+/// A `Stream` of the values of a newline-delimited JSON body, deserialized
+/// as the lines are received.
+///
+/// Created by [`Response::json_lines`](crate::Response::json_lines).
+#[must_use = "streams do nothing unless polled"]
+pub struct JsonLines<T> {
+    chunks: DataStream<Decoder>,
+    /// The line being received.
+    buf: BytesMut,
+    /// How much of `buf` is known to have no newline.
+    scanned: usize,
+    max_line_length: usize,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> JsonLines<T> {
+    pub(crate) fn new(chunks: DataStream<Decoder>) -> JsonLines<T> {
+        JsonLines {
+            chunks,
+            buf: BytesMut::new(),
+            scanned: 0,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Set the maximum length of a line, in bytes, without its newline.
+    ///
+    /// A longer line is an error of the stream, which then ends, so that a
+    /// body without newlines can't use up memory.
+    ///
+    /// Default is 16 MiB.
+    pub fn max_line_length(mut self, max: usize) -> JsonLines<T> {
+        self.max_line_length = max;
+        self
+    }
+
+    /// End the stream with an error for a line that is too long.
+    fn too_long(&mut self) -> crate::Error {
+        self.done = true;
+        self.buf.clear();
+        self.scanned = 0;
+        crate::error::decode(format!(
+            "line is longer than {} bytes",
+            self.max_line_length
+        ))
+    }
+}
+
+impl<T> Stream for JsonLines<T>
+where
+    T: DeserializeOwned,
+{
+    type Item = crate::Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let newline = self.buf[self.scanned..].iter().position(|&b| b == b'\n');
+            let line = match newline {
+                Some(pos) if self.scanned + pos > self.max_line_length => {
+                    return Poll::Ready(Some(Err(self.too_long())));
+                }
+                Some(pos) => {
+                    let end = self.scanned + pos + 1;
+                    self.scanned = 0;
+                    self.buf.split_to(end)
+                }
+                None if self.buf.len() > self.max_line_length => {
+                    return Poll::Ready(Some(Err(self.too_long())));
+                }
+                // the last line may not end with a newline
+                None if self.done => {
+                    self.scanned = 0;
+                    std::mem::take(&mut self.buf)
+                }
+                None => {
+                    self.scanned = self.buf.len();
+                    match ready!(self.chunks.poll_next_unpin(cx)) {
+                        Some(Ok(chunk)) => self.buf.extend_from_slice(&chunk),
+                        Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                        None => self.done = true,
+                    }
+                    continue;
+                }
+            };
+
+            if line.iter().all(u8::is_ascii_whitespace) {
+                if self.done && self.buf.is_empty() {
+                    return Poll::Ready(None);
+                }
+                continue;
+            }
+            return Poll::Ready(Some(
+                serde_json::from_slice(&line).map_err(crate::error::decode),
+            ));
+        }
+    }
+}
+
+impl<T> fmt::Debug for JsonLines<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonLines")
+            .field("max_line_length", &self.max_line_length)
+            .finish()
+    }
+}
+
+/// A body of the values of `items`, serialized as they are sent.
+pub(crate) fn body<S>(items: S) -> Body
+where
+    S: Stream + Send + 'static,
+    S::Item: Serialize,
+{
+    Body::stream(items.map(|item| {
+        let mut line = serde_json::to_vec(&item)?;
+        line.push(b'\n');
+        Ok::<_, serde_json::Error>(line)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error as _;
+
+    use futures_util::stream;
+
+    fn lines(chunks: &[&'static str]) -> JsonLines<u32> {
+        let chunks =
+            stream::iter(chunks.to_vec()).map(|chunk| Ok::<_, std::io::Error>(chunk.as_bytes()));
+        let res = http::Response::new(Body::stream(chunks));
+        crate::Response::from(res).json_lines()
+    }
+
+    async fn parse(chunks: &[&'static str]) -> Vec<Result<u32, String>> {
+        lines(chunks)
+            .map(|res| res.map_err(|err| err.to_string()))
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn json_lines_split_across_chunks() {
+        assert_eq!(
+            parse(&["1\n2", "3\n", "\n", "4", "5\r\n6"]).await,
+            [Ok(1), Ok(23), Ok(45), Ok(6)]
+        );
+        assert_eq!(parse(&["1\n", " \n"]).await, [Ok(1)]);
+        assert_eq!(parse(&[]).await, []);
+    }
+
+    #[tokio::test]
+    async fn json_lines_invalid_record() {
+        let records = parse(&["1\n\"two\"\n3\n"]).await;
+        assert_eq!(records.len(), 3);
+        assert!(records[1]
+            .as_ref()
+            .unwrap_err()
+            .starts_with("error decoding response body"));
+        assert_eq!(records[2], Ok(3));
+    }
+
+    async fn parse_max(chunks: &[&'static str], max: usize) -> Vec<Result<u32, String>> {
+        lines(chunks)
+            .max_line_length(max)
+            .map(|res| res.map_err(|err| err.source().unwrap().to_string()))
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn json_lines_too_long() {
+        let too_long = Err("line is longer than 4 bytes".to_owned());
+        // a body without newlines isn't buffered past the maximum
+        assert_eq!(
+            parse_max(&["12", "34", "56", "78"], 4).await,
+            [too_long.clone()]
+        );
+        assert_eq!(parse_max(&["1\n12345\n3\n"], 4).await, [Ok(1), too_long]);
+        assert_eq!(parse_max(&["1234\n"], 4).await, [Ok(1234)]);
+    }
+}
@@ -1,5 +1,14 @@
 pub use self::body::Body;
 pub use self::client::{Client, ClientBuilder};
+#[cfg(any(
+    feature = "gzip",
+    feature = "brotli",
+    feature = "zstd",
+    feature = "deflate"
+))]
+pub use self::encoder::Encoding;
+#[cfg(feature = "json")]
+pub use self::json_lines::JsonLines;
 pub use self::request::{Request, RequestBuilder};
 pub use self::response::Response;
 pub use self::upgrade::Upgraded;
@@ -10,9 +19,20 @@ pub(crate) use self::decoder::Decoder;
 pub mod body;
 pub mod client;
 pub mod decoder;
+#[cfg(any(
+    feature = "gzip",
+    feature = "brotli",
+    feature = "zstd",
+    feature = "deflate"
+))]
+mod encoder;
 pub mod h3_client;
+#[cfg(feature = "json")]
+mod json_lines;
 #[cfg(feature = "multipart")]
 pub mod multipart;
+pub(crate) mod progress;
 pub(crate) mod request;
 mod response;
+pub(crate) mod resume;
 mod upgrade;
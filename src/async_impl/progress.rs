@@ -0,0 +1,254 @@
+//! Reporting how much of a request or response body was transferred.
+
+use std::fmt;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use bytes::Buf;
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use pin_project_lite::pin_project;
+
+/// A callback given the number of bytes of a body transferred so far, and
+/// its total size if it is known.
+#[derive(Clone)]
+pub(crate) struct Progress(Arc<dyn Fn(u64, Option<u64>) + Send + Sync>);
+
+impl Progress {
+    pub(crate) fn new<F>(callback: F) -> Progress
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        Progress(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Progress").finish()
+    }
+}
+
+/// A callback given the number of bytes of a response body received so
+/// far, the size they were decoded to, and the total size to receive if it
+/// is known.
+#[derive(Clone)]
+pub(crate) struct DownloadProgress(Arc<dyn Fn(u64, u64, Option<u64>) + Send + Sync>);
+
+impl DownloadProgress {
+    pub(crate) fn new<F>(callback: F) -> DownloadProgress
+    where
+        F: Fn(u64, u64, Option<u64>) + Send + Sync + 'static,
+    {
+        DownloadProgress(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for DownloadProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("DownloadProgress").finish()
+    }
+}
+
+pin_project! {
+    /// A body that reports its progress as its frames are polled.
+    pub(super) struct ProgressBody<B> {
+        #[pin]
+        inner: B,
+        progress: Progress,
+        transferred: u64,
+        total: Option<u64>,
+    }
+}
+
+/// Reports the progress of `body`, out of `total` bytes.
+pub(super) fn track<B>(body: B, progress: Progress, total: Option<u64>) -> ProgressBody<B> {
+    ProgressBody {
+        inner: body,
+        progress,
+        transferred: 0,
+        total,
+    }
+}
+
+impl<B> HttpBody for ProgressBody<B>
+where
+    B: HttpBody,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx));
+        if let Some(Ok(ref frame)) = frame {
+            if let Some(data) = frame.data_ref() {
+                if data.has_remaining() {
+                    *this.transferred += data.remaining() as u64;
+                    (this.progress.0)(*this.transferred, *this.total);
+                }
+            }
+        }
+        Poll::Ready(frame)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+pin_project! {
+    /// A body that adds the size of its frames to a counter.
+    pub(super) struct Counted<B> {
+        #[pin]
+        inner: B,
+        count: Arc<AtomicU64>,
+    }
+}
+
+/// Adds the size of the frames of `body` to `count`.
+pub(super) fn count<B>(body: B, count: Arc<AtomicU64>) -> Counted<B> {
+    Counted { inner: body, count }
+}
+
+impl<B> HttpBody for Counted<B>
+where
+    B: HttpBody,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx));
+        if let Some(Ok(ref frame)) = frame {
+            if let Some(data) = frame.data_ref() {
+                this.count
+                    .fetch_add(data.remaining() as u64, Ordering::Relaxed);
+            }
+        }
+        Poll::Ready(frame)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+pin_project! {
+    /// A decoded response body that reports the progress of its download
+    /// as its frames are polled.
+    pub(super) struct DownloadBody<B> {
+        #[pin]
+        inner: B,
+        progress: DownloadProgress,
+        // the bytes received so far, counted before they were decoded
+        received: Arc<AtomicU64>,
+        // how many bytes were received at the last report
+        reported: u64,
+        decoded: u64,
+        total: Option<u64>,
+    }
+}
+
+/// Reports the progress of the decoded `body`, whose bytes are counted in
+/// `received` as they are received, out of `total` bytes.
+pub(super) fn track_download<B>(
+    body: B,
+    progress: DownloadProgress,
+    received: Arc<AtomicU64>,
+    total: Option<u64>,
+) -> DownloadBody<B> {
+    DownloadBody {
+        inner: body,
+        progress,
+        received,
+        reported: 0,
+        decoded: 0,
+        total,
+    }
+}
+
+impl<B> HttpBody for DownloadBody<B>
+where
+    B: HttpBody,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx));
+        let decoded = match frame {
+            Some(Ok(ref frame)) => frame.data_ref().map_or(0, |data| data.remaining() as u64),
+            _ => 0,
+        };
+        *this.decoded += decoded;
+        let received = this.received.load(Ordering::Relaxed);
+        // the end of the body may have been received without decoding to
+        // anything, like the trailer of a gzip stream
+        if decoded > 0 || (frame.is_none() && received != *this.reported) {
+            *this.reported = received;
+            (this.progress.0)(received, *this.decoded, *this.total);
+        }
+        Poll::Ready(frame)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::Infallible;
+    use std::sync::Mutex;
+
+    use bytes::Bytes;
+    use futures_util::StreamExt;
+    use http_body_util::{BodyExt, StreamBody};
+
+    #[tokio::test]
+    async fn reports_each_frame() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls2 = calls.clone();
+        let progress = Progress::new(move |sent, total| calls2.lock().unwrap().push((sent, total)));
+
+        let chunks = futures_util::stream::iter(["hello", "", " world"])
+            .map(|chunk| Ok::<_, Infallible>(Frame::data(Bytes::from(chunk))));
+        let body = track(StreamBody::new(chunks), progress, Some(11));
+        assert_eq!(body.collect().await.unwrap().to_bytes(), "hello world");
+
+        assert_eq!(*calls.lock().unwrap(), [(5, Some(11)), (11, Some(11))]);
+    }
+}
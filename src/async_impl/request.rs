@@ -1,6 +1,8 @@
 use std::convert::TryFrom;
 use std::fmt;
 use std::future::Future;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use serde::Serialize;
@@ -9,14 +11,38 @@ use serde_json;
 
 use super::body::Body;
 use super::client::{Client, Pending};
+#[cfg(any(
+    feature = "gzip",
+    feature = "brotli",
+    feature = "zstd",
+    feature = "deflate"
+))]
+use super::encoder::Encoding;
 #[cfg(feature = "multipart")]
 use super::multipart;
+use super::progress::{DownloadProgress, Progress};
 use super::response::Response;
-use crate::config::{RequestConfig, RequestTimeout};
+#[cfg(any(
+    feature = "gzip",
+    feature = "brotli",
+    feature = "zstd",
+    feature = "deflate"
+))]
+use crate::config::RequestCompression;
+#[cfg(feature = "digest-auth")]
+use crate::config::RequestDigestAuth;
+#[cfg(feature = "websocket")]
+use crate::config::RequestHttp1Only;
+#[cfg(unix)]
+use crate::config::RequestUnixSocket;
+use crate::config::{
+    RequestCacheMode, RequestConfig, RequestDownloadProgress, RequestMaxResponseBodySize,
+    RequestResumable, RequestRetry, RequestTimeout, RequestUploadProgress,
+};
 #[cfg(feature = "multipart")]
 use crate::header::CONTENT_LENGTH;
 use crate::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
-use crate::{Method, Url};
+use crate::{cache, retry, Method, Url};
 use http::{request::Parts, Extensions, Request as HttpRequest, Version};
 
 /// A request which can be executed with `Client::execute()`.
@@ -124,6 +150,106 @@ impl Request {
         RequestConfig::<RequestTimeout>::get_mut(&mut self.extensions)
     }
 
+    /// Get the retry policy.
+    #[inline]
+    pub fn retry(&self) -> Option<&retry::Policy> {
+        RequestConfig::<RequestRetry>::get(&self.extensions)
+    }
+
+    /// Get a mutable reference to the retry policy.
+    #[inline]
+    pub fn retry_mut(&mut self) -> &mut Option<retry::Policy> {
+        RequestConfig::<RequestRetry>::get_mut(&mut self.extensions)
+    }
+
+    /// Get the cache mode.
+    #[inline]
+    pub fn cache_mode(&self) -> Option<&cache::Mode> {
+        RequestConfig::<RequestCacheMode>::get(&self.extensions)
+    }
+
+    /// Get a mutable reference to the cache mode.
+    #[inline]
+    pub fn cache_mode_mut(&mut self) -> &mut Option<cache::Mode> {
+        RequestConfig::<RequestCacheMode>::get_mut(&mut self.extensions)
+    }
+
+    /// Get the maximum size of the response body.
+    #[inline]
+    pub fn max_response_body_size(&self) -> Option<u64> {
+        RequestConfig::<RequestMaxResponseBodySize>::get(&self.extensions).copied()
+    }
+
+    /// Get a mutable reference to the maximum size of the response body.
+    #[inline]
+    pub fn max_response_body_size_mut(&mut self) -> &mut Option<u64> {
+        RequestConfig::<RequestMaxResponseBodySize>::get_mut(&mut self.extensions)
+    }
+
+    /// Get whether the response body is resumed when it fails.
+    #[inline]
+    pub fn resumable(&self) -> bool {
+        RequestConfig::<RequestResumable>::get(&self.extensions)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Get a mutable reference to whether the response body is resumed when
+    /// it fails.
+    #[inline]
+    pub fn resumable_mut(&mut self) -> &mut Option<bool> {
+        RequestConfig::<RequestResumable>::get_mut(&mut self.extensions)
+    }
+
+    pub(crate) fn upload_progress_mut(&mut self) -> &mut Option<Progress> {
+        RequestConfig::<RequestUploadProgress>::get_mut(&mut self.extensions)
+    }
+
+    pub(crate) fn download_progress_mut(&mut self) -> &mut Option<DownloadProgress> {
+        RequestConfig::<RequestDownloadProgress>::get_mut(&mut self.extensions)
+    }
+
+    /// Get the encoding the request body is compressed with.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    #[inline]
+    pub fn compress(&self) -> Option<Encoding> {
+        RequestConfig::<RequestCompression>::get(&self.extensions).copied()
+    }
+
+    /// Get a mutable reference to the encoding the request body is
+    /// compressed with.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    #[inline]
+    pub fn compress_mut(&mut self) -> &mut Option<Encoding> {
+        RequestConfig::<RequestCompression>::get_mut(&mut self.extensions)
+    }
+
+    /// Get the Unix socket this request is sent over.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[inline]
+    pub fn unix_socket(&self) -> Option<&Path> {
+        RequestConfig::<RequestUnixSocket>::get(&self.extensions).map(PathBuf::as_path)
+    }
+
+    /// Get a mutable reference to the Unix socket this request is sent over.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[inline]
+    pub fn unix_socket_mut(&mut self) -> &mut Option<PathBuf> {
+        RequestConfig::<RequestUnixSocket>::get_mut(&mut self.extensions)
+    }
+
     /// Get the http version.
     #[inline]
     pub fn version(&self) -> Version {
@@ -275,6 +401,38 @@ impl RequestBuilder {
         self.header_sensitive(crate::header::AUTHORIZATION, header_value, true)
     }
 
+    /// Enable HTTP digest authentication.
+    ///
+    /// Unlike `basic_auth()`, nothing is sent up front. When the server
+    /// answers with `401 Unauthorized` and a `Digest` challenge, the request
+    /// is sent again answering it. See `ClientBuilder::digest_auth()`.
+    ///
+    /// ```rust
+    /// # use reqwest::Error;
+    ///
+    /// # async fn run() -> Result<(), Error> {
+    /// let client = reqwest::Client::new();
+    /// let resp = client.get("http://192.168.1.20/status")
+    ///     .digest_auth("admin", "good password")
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "digest-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "digest-auth")))]
+    pub fn digest_auth<U, P>(mut self, username: U, password: P) -> RequestBuilder
+    where
+        U: fmt::Display,
+        P: fmt::Display,
+    {
+        if let Ok(ref mut req) = self.request {
+            *RequestConfig::<RequestDigestAuth>::get_mut(req.extensions_mut()) =
+                Some(crate::auth::digest::Credentials::new(username, password));
+        }
+        self
+    }
+
     /// Set the request body.
     pub fn body<T: Into<Body>>(mut self, body: T) -> RequestBuilder {
         if let Ok(ref mut req) = self.request {
@@ -295,6 +453,215 @@ impl RequestBuilder {
         self
     }
 
+    /// Set a `retry::Policy` for this request.
+    ///
+    /// It affects only this request and overrides the policy configured
+    /// using `ClientBuilder::retry()`.
+    pub fn retry(mut self, policy: retry::Policy) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.retry_mut() = Some(policy);
+        }
+        self
+    }
+
+    /// Set how this request uses the client's cache.
+    ///
+    /// It affects only this request and overrides the mode configured
+    /// using `Cache::mode()`. It has no effect if the client has no cache.
+    pub fn cache_mode(mut self, mode: cache::Mode) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.cache_mode_mut() = Some(mode);
+        }
+        self
+    }
+
+    /// Set the maximum size of the response body, in bytes, after
+    /// decompression.
+    ///
+    /// It affects only this request and overrides the maximum configured
+    /// using `ClientBuilder::max_response_body_size()`.
+    pub fn max_response_body_size(mut self, max: u64) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.max_response_body_size_mut() = Some(max);
+        }
+        self
+    }
+
+    /// Enable or disable resuming the response body when it fails
+    /// mid-transfer.
+    ///
+    /// When enabled, a GET request is sent again with a `Range` header for
+    /// the rest of the body, and the body keeps yielding bytes as if it
+    /// never failed. The rest is only accepted if the resource is unchanged,
+    /// checked with an `If-Range` header of its strong `ETag` or
+    /// `Last-Modified` value. The body fails with the error instead if the
+    /// server doesn't send the rest, or the body can't be resumed after a
+    /// few attempts in a row, which are made after a short, growing delay.
+    ///
+    /// Responses without a validator, or with `Accept-Ranges: none`, are not
+    /// resumed. Any read timeout applies to each attempt.
+    ///
+    /// Default is `false`.
+    ///
+    /// ```rust
+    /// # use reqwest::Error;
+    /// #
+    /// # async fn run() -> Result<(), Error> {
+    /// let artifact = reqwest::Client::new()
+    ///     .get("https://example.com/artifact.tar")
+    ///     .resumable(true)
+    ///     .send()
+    ///     .await?
+    ///     .bytes()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resumable(mut self, enable: bool) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.resumable_mut() = Some(enable);
+        }
+        self
+    }
+
+    /// Call `callback` as the request body is sent, with the number of
+    /// bytes sent so far and the length of the body, if it is known.
+    ///
+    /// The bytes are counted as they are handed to the connection, after
+    /// the body is compressed, and include every part of a multipart form.
+    /// The count starts over if the body is sent again for a redirect.
+    ///
+    /// ```rust
+    /// # use reqwest::Error;
+    /// #
+    /// # async fn run() -> Result<(), Error> {
+    /// let res = reqwest::Client::new()
+    ///     .post("https://example.com/upload")
+    ///     .body(vec![0; 1 << 20])
+    ///     .on_upload_progress(|sent, total| {
+    ///         if let Some(total) = total {
+    ///             println!("uploaded {sent} of {total} bytes");
+    ///         }
+    ///     })
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_upload_progress<F>(mut self, callback: F) -> RequestBuilder
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        if let Ok(ref mut req) = self.request {
+            *req.upload_progress_mut() = Some(Progress::new(callback));
+        }
+        self
+    }
+
+    /// Call `callback` as the response body is read, with the number of
+    /// bytes received so far, the number of bytes of the body they were
+    /// decoded to, and the `Content-Length` of the response, if it has one.
+    ///
+    /// The bytes received are counted as they are read from the connection,
+    /// before the body is decompressed, so they add up to the
+    /// `Content-Length` even if the body is decompressed. They are counted
+    /// across the parts of a resumed body. The decoded bytes are those
+    /// yielded by the response body, which are as many unless it is
+    /// decompressed.
+    ///
+    /// ```rust
+    /// # use reqwest::Error;
+    /// #
+    /// # async fn run() -> Result<(), Error> {
+    /// let body = reqwest::Client::new()
+    ///     .get("https://example.com/archive")
+    ///     .on_download_progress(|received, decoded, total| {
+    ///         if let Some(total) = total {
+    ///             println!("received {received} of {total} bytes, {decoded} decoded");
+    ///         }
+    ///     })
+    ///     .send()
+    ///     .await?
+    ///     .bytes()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_download_progress<F>(mut self, callback: F) -> RequestBuilder
+    where
+        F: Fn(u64, u64, Option<u64>) + Send + Sync + 'static,
+    {
+        if let Ok(ref mut req) = self.request {
+            *req.download_progress_mut() = Some(DownloadProgress::new(callback));
+        }
+        self
+    }
+
+    /// Compress the body with `encoding`, and set the `Content-Encoding`
+    /// header.
+    ///
+    /// A body with a known length is compressed before it is sent and keeps
+    /// a `Content-Length`, while a streaming body is compressed as it is
+    /// sent, using chunked transfer encoding. The body is sent as is if the
+    /// `Content-Encoding` header is already set.
+    ///
+    /// It affects only this request and overrides the encoding configured
+    /// using `ClientBuilder::compress()`.
+    ///
+    /// ```rust
+    /// # use reqwest::Error;
+    /// #
+    /// # #[cfg(feature = "gzip")]
+    /// # async fn run() -> Result<(), Error> {
+    /// let client = reqwest::Client::new();
+    /// let res = client.post("http://httpbin.org/post")
+    ///     .body("{\"events\":[]}")
+    ///     .compress(reqwest::Encoding::Gzip)
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Optional
+    ///
+    /// This requires one of the optional `gzip`, `brotli`, `zstd` or
+    /// `deflate` features to be enabled.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(any(
+            feature = "gzip",
+            feature = "brotli",
+            feature = "zstd",
+            feature = "deflate"
+        )))
+    )]
+    pub fn compress(mut self, encoding: Encoding) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.compress_mut() = Some(encoding);
+        }
+        self
+    }
+
+    /// Send this request over the Unix socket at `path`.
+    ///
+    /// It affects only this request and overrides the socket configured
+    /// using `ClientBuilder::unix_socket()`.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn unix_socket<P: AsRef<Path>>(mut self, path: P) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.unix_socket_mut() = Some(path.as_ref().to_owned());
+        }
+        self
+    }
+
     /// Sends a multipart/form-data body.
     ///
     /// ```
@@ -384,6 +751,17 @@ impl RequestBuilder {
         self
     }
 
+    /// Send the request over HTTP/1.1, on a connection that doesn't offer
+    /// HTTP/2 with ALPN, as the upgrade of the WebSocket handshake needs.
+    #[cfg(feature = "websocket")]
+    pub(crate) fn http1_only(mut self) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            req.version = Version::HTTP_11;
+            *RequestConfig::<RequestHttp1Only>::get_mut(&mut req.extensions) = Some(true);
+        }
+        self
+    }
+
     /// Send a form body.
     ///
     /// Sets the body to the url encoded serialization of the passed value,
@@ -464,6 +842,37 @@ impl RequestBuilder {
         self
     }
 
+    /// Send a newline-delimited JSON body, with the values of a `Stream`.
+    ///
+    /// Each value is serialized on its own line as it is sent, and the
+    /// `Content-Type` is `application/x-ndjson` unless already set.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `json` feature enabled.
+    ///
+    /// # Errors
+    ///
+    /// Sending the request fails if a value can't be serialized.
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn json_lines<S>(mut self, items: S) -> RequestBuilder
+    where
+        S: futures_core::Stream + Send + 'static,
+        S::Item: Serialize,
+    {
+        if let Ok(ref mut req) = self.request {
+            if !req.headers().contains_key(CONTENT_TYPE) {
+                req.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("application/x-ndjson"),
+                );
+            }
+            *req.body_mut() = Some(super::json_lines::body(items));
+        }
+        self
+    }
+
     // This was a shell only meant to help with rendered documentation.
     // However, docs.rs can now show the docs for the wasm platforms, so this
     // is no longer needed.
@@ -15,7 +15,7 @@ use tokio::time::Sleep;
 use url::Url;
 
 use super::body::Body;
-use super::decoder::{Accepts, Decoder};
+use super::decoder::{Accepts, Decoder, Limits};
 use crate::async_impl::body::ResponseBody;
 #[cfg(feature = "cookies")]
 use crate::cookie;
@@ -40,12 +40,14 @@ impl Response {
         accepts: Accepts,
         total_timeout: Option<Pin<Box<Sleep>>>,
         read_timeout: Option<Duration>,
+        limits: Limits,
     ) -> Response {
         let (mut parts, body) = res.into_parts();
         let decoder = Decoder::detect(
             &mut parts.headers,
             super::body::response(body, total_timeout, read_timeout),
             accepts,
+            limits,
         );
         let res = hyper::Response::from_parts(parts, decoder);
 
@@ -276,6 +278,48 @@ impl Response {
         serde_json::from_slice(&full).map_err(crate::error::decode)
     }
 
+    /// Convert the response into a `Stream` of the values of a
+    /// newline-delimited JSON body, such as `application/x-ndjson`.
+    ///
+    /// Each line is deserialized as soon as it is received, so only the
+    /// line being received is buffered, up to
+    /// [`max_line_length`](crate::JsonLines::max_line_length). Blank lines
+    /// are skipped, and a line that fails to deserialize is an error of the
+    /// stream, which goes on with the next line.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate serde;
+    /// # use futures_util::StreamExt;
+    /// # use serde::Deserialize;
+    /// #
+    /// #[derive(Deserialize)]
+    /// struct Record {
+    ///     id: u64,
+    /// }
+    ///
+    /// # async fn run() -> Result<(), reqwest::Error> {
+    /// let mut records = reqwest::get("http://example.com/export")
+    ///     .await?
+    ///     .json_lines::<Record>();
+    ///
+    /// while let Some(record) = records.next().await {
+    ///     println!("{}", record?.id);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `json` feature enabled.
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn json_lines<T: DeserializeOwned>(self) -> super::JsonLines<T> {
+        super::json_lines::JsonLines::new(super::body::DataStream(self.res.into_body()))
+    }
+
     /// Get the full response body as `Bytes`.
     ///
     /// # Example
@@ -360,6 +404,38 @@ impl Response {
         super::body::DataStream(self.res.into_body())
     }
 
+    /// Convert the response into a `Stream` of the server-sent events of a
+    /// `text/event-stream` body.
+    ///
+    /// The response is read as is: see [`EventSource`](crate::sse::EventSource)
+    /// to check its status, and to reconnect when the connection is lost.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use futures_util::StreamExt;
+    ///
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut events = reqwest::get("https://stream.example/updates")
+    ///     .await?
+    ///     .event_stream();
+    ///
+    /// while let Some(event) = events.next().await {
+    ///     println!("Event: {:?}", event?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `sse` feature to be enabled.
+    #[cfg(feature = "sse")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "sse")))]
+    pub fn event_stream(self) -> crate::sse::EventStream {
+        crate::sse::EventStream::new(super::body::DataStream(self.res.into_body()))
+    }
+
     // util methods
 
     /// Turn a response into an error if the server returned an error.
@@ -433,6 +509,19 @@ impl Response {
     pub(crate) fn body_mut(&mut self) -> &mut Decoder {
         self.res.body_mut()
     }
+
+    // Used by the HAR recorder and download progress to see the decoded
+    // body as it is read.
+    pub(crate) fn wrap_body<B, F>(self, f: F) -> Response
+    where
+        F: FnOnce(Decoder) -> B,
+        B: hyper::body::Body<Data = Bytes, Error = crate::Error> + Send + Sync + 'static,
+    {
+        Response {
+            res: self.res.map(|body| body.wrap(f)),
+            url: self.url,
+        }
+    }
 }
 
 impl fmt::Debug for Response {
@@ -464,6 +553,7 @@ impl<T: Into<Body>> From<http::Response<T>> for Response {
             &mut parts.headers,
             ResponseBody::new(body.map_err(Into::into)),
             Accepts::none(),
+            Limits::default(),
         );
         let url = parts
             .extensions
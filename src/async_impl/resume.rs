@@ -0,0 +1,325 @@
+//! Resuming response bodies that fail mid-transfer, with range requests.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::header::{
+    HeaderMap, HeaderValue, ACCEPT_RANGES, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE,
+};
+use http::{Method, StatusCode};
+use hyper::body::{Body as HttpBody, Frame};
+use log::debug;
+use sync_wrapper::SyncWrapper;
+use tokio::time::Sleep;
+
+use super::body::{self, ResponseBody};
+use super::request::Request;
+use crate::error::{BoxError, RangeFailed};
+
+/// How many times in a row a body is resumed without receiving any more of
+/// it, before its error is returned.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// How long to wait before the first attempt to resume a body, doubled for
+/// each attempt after it.
+const BACKOFF: Duration = Duration::from_millis(100);
+
+pub(super) type RawResponseFuture =
+    Pin<Box<dyn Future<Output = Result<hyper::Response<ResponseBody>, BoxError>> + Send>>;
+
+/// Sends a single request, and returns the response with its body as
+/// received.
+pub(super) type Resend = Arc<dyn Fn(Request) -> RawResponseFuture + Send + Sync>;
+
+/// What is needed to send a request again for the rest of its response
+/// body.
+pub(super) struct Resume {
+    request: Request,
+    read_timeout: Option<Duration>,
+    resend: Resend,
+}
+
+impl Resume {
+    /// Prepares to resume the response body of `req`, if it is a GET
+    /// request for a whole resource.
+    pub(super) fn new(
+        req: &Request,
+        read_timeout: Option<Duration>,
+        resend: Resend,
+    ) -> Option<Resume> {
+        if req.method() != Method::GET || req.headers().contains_key(RANGE) {
+            return None;
+        }
+        let mut request = Request::new(Method::GET, req.url().clone());
+        *request.headers_mut() = req.headers().clone();
+        *request.version_mut() = req.version();
+        *request.extensions_mut() = req.extensions().clone();
+        Some(Resume {
+            request,
+            read_timeout,
+            resend,
+        })
+    }
+
+    /// Wraps the body of `res` so that it is resumed when it fails, if the
+    /// server allows it.
+    ///
+    /// The read timeout applies to each part of the body.
+    pub(super) fn wrap(self, res: hyper::Response<ResponseBody>) -> hyper::Response<ResponseBody> {
+        let validator = validator(res.headers());
+        let ranges = res
+            .headers()
+            .get(ACCEPT_RANGES)
+            .map_or(true, |ranges| ranges != "none");
+        let validator = match validator {
+            Some(validator) if res.status() == StatusCode::OK && ranges => validator,
+            _ => {
+                let read_timeout = self.read_timeout;
+                return res.map(|body| with_read_timeout(body, read_timeout));
+            }
+        };
+
+        res.map(|body| {
+            body::boxed(Resumable {
+                state: SyncWrapper::new(State::Reading(with_read_timeout(body, self.read_timeout))),
+                resume: self,
+                validator,
+                received: 0,
+                attempts: 0,
+            })
+        })
+    }
+}
+
+/// A response body that resumes from where it failed.
+struct Resumable {
+    state: SyncWrapper<State>,
+    resume: Resume,
+    /// The `ETag` or `Last-Modified` value of the first response.
+    validator: HeaderValue,
+    /// The size of the body received so far.
+    received: u64,
+    /// How many times the body was resumed since any of it was received.
+    attempts: u32,
+}
+
+enum State {
+    Reading(ResponseBody),
+    Waiting(Pin<Box<Sleep>>),
+    Resuming(RawResponseFuture),
+    Done,
+}
+
+impl Resumable {
+    /// Waits a little longer with each attempt before resending, so that a
+    /// struggling server isn't asked again right away.
+    fn wait(&mut self) -> State {
+        let delay = backoff(self.attempts);
+        self.attempts += 1;
+        State::Waiting(Box::pin(tokio::time::sleep(delay)))
+    }
+
+    /// Sends the request again, for the rest of the body.
+    fn resend(&self) -> RawResponseFuture {
+        let req = &self.resume.request;
+        let mut again = Request::new(Method::GET, req.url().clone());
+        *again.headers_mut() = req.headers().clone();
+        *again.version_mut() = req.version();
+        *again.extensions_mut() = req.extensions().clone();
+
+        let range = format!("bytes={}-", self.received);
+        let headers = again.headers_mut();
+        headers.insert(
+            RANGE,
+            HeaderValue::from_str(&range).expect("range is a valid header value"),
+        );
+        headers.insert(IF_RANGE, self.validator.clone());
+        (self.resume.resend)(again)
+    }
+
+    /// Checks that `res` has the rest of the body.
+    fn check(&self, res: &hyper::Response<ResponseBody>) -> Result<(), RangeFailed> {
+        check_range(
+            res.status(),
+            res.headers(),
+            self.received,
+            Some(&self.validator),
+        )
+    }
+}
+
+impl HttpBody for Resumable {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        loop {
+            let next = match this.state.get_mut() {
+                State::Reading(body) => match ready!(Pin::new(body).poll_frame(cx)) {
+                    Some(Ok(frame)) => {
+                        if let Some(data) = frame.data_ref() {
+                            if !data.is_empty() {
+                                this.received += data.len() as u64;
+                                this.attempts = 0;
+                            }
+                        }
+                        return Poll::Ready(Some(Ok(frame)));
+                    }
+                    Some(Err(err)) if this.attempts < MAX_ATTEMPTS => {
+                        debug!(
+                            "resuming '{}' from byte {}: {}",
+                            this.resume.request.url(),
+                            this.received,
+                            err
+                        );
+                        this.wait()
+                    }
+                    Some(Err(err)) => {
+                        *this.state.get_mut() = State::Done;
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                    None => {
+                        *this.state.get_mut() = State::Done;
+                        return Poll::Ready(None);
+                    }
+                },
+                State::Waiting(delay) => {
+                    ready!(delay.as_mut().poll(cx));
+                    State::Resuming(this.resend())
+                }
+                State::Resuming(in_flight) => match ready!(in_flight.as_mut().poll(cx)) {
+                    Ok(res) => match this.check(&res) {
+                        Ok(()) => State::Reading(with_read_timeout(
+                            res.into_body(),
+                            this.resume.read_timeout,
+                        )),
+                        Err(err) => {
+                            *this.state.get_mut() = State::Done;
+                            return Poll::Ready(Some(Err(crate::error::body(err).into())));
+                        }
+                    },
+                    Err(err) if this.attempts < MAX_ATTEMPTS => {
+                        debug!(
+                            "resuming '{}' from byte {} failed: {}",
+                            this.resume.request.url(),
+                            this.received,
+                            err
+                        );
+                        this.wait()
+                    }
+                    Err(err) => {
+                        *this.state.get_mut() = State::Done;
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                },
+                State::Done => return Poll::Ready(None),
+            };
+            *this.state.get_mut() = next;
+        }
+    }
+}
+
+/// The delay before the attempt to resume a body after `attempts` failed.
+fn backoff(attempts: u32) -> Duration {
+    BACKOFF * 2u32.saturating_pow(attempts)
+}
+
+fn with_read_timeout(body: ResponseBody, read_timeout: Option<Duration>) -> ResponseBody {
+    match read_timeout {
+        Some(timeout) => body::boxed(body::with_read_timeout(body, timeout)),
+        None => body,
+    }
+}
+
+/// Checks that a response to a range request starting at `start` has that
+/// range of the resource last seen with `validator`.
+pub(crate) fn check_range(
+    status: StatusCode,
+    headers: &HeaderMap,
+    start: u64,
+    validator: Option<&HeaderValue>,
+) -> Result<(), RangeFailed> {
+    match status {
+        StatusCode::PARTIAL_CONTENT => {
+            let sent = headers
+                .get(CONTENT_RANGE)
+                .and_then(|range| range.to_str().ok())
+                .and_then(range_start);
+            if sent == Some(start) {
+                Ok(())
+            } else {
+                Err(RangeFailed::RangeMismatch)
+            }
+        }
+        // a full response is sent instead if the validator doesn't match
+        StatusCode::OK if self::validator(headers).as_ref() == validator => {
+            Err(RangeFailed::RangeIgnored)
+        }
+        StatusCode::OK => Err(RangeFailed::Changed),
+        status => Err(RangeFailed::Status(status)),
+    }
+}
+
+/// The value to send in `If-Range`, to only get a range of the resource if
+/// it is unchanged.
+pub(crate) fn validator(headers: &HeaderMap) -> Option<HeaderValue> {
+    match headers.get(ETAG) {
+        // weak entity tags can't be used in `If-Range`
+        Some(etag) if !etag.as_bytes().starts_with(b"W/") => Some(etag.clone()),
+        _ => headers.get(LAST_MODIFIED).cloned(),
+    }
+}
+
+/// The first byte position of a `Content-Range` value.
+fn range_start(range: &str) -> Option<u64> {
+    let range = range.strip_prefix("bytes ")?;
+    let (start, _) = range.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_range_start() {
+        assert_eq!(range_start("bytes 100-199/200"), Some(100));
+        assert_eq!(range_start("bytes 0-0/*"), Some(0));
+        assert_eq!(range_start("bytes */200"), None);
+        assert_eq!(range_start("items 1-2/3"), None);
+    }
+
+    #[test]
+    fn backoff_doubles() {
+        assert_eq!(backoff(0), Duration::from_millis(100));
+        assert_eq!(backoff(1), Duration::from_millis(200));
+        assert_eq!(backoff(MAX_ATTEMPTS - 1), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn strong_validator() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(validator(&headers), None);
+
+        headers.insert(
+            LAST_MODIFIED,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        headers.insert(ETAG, "W/\"weak\"".parse().unwrap());
+        assert_eq!(
+            validator(&headers).unwrap(),
+            "Wed, 21 Oct 2015 07:28:00 GMT"
+        );
+
+        headers.insert(ETAG, "\"strong\"".parse().unwrap());
+        assert_eq!(validator(&headers).unwrap(), "\"strong\"");
+    }
+}
@@ -0,0 +1,645 @@
+//! HTTP Digest Access Authentication, as defined by RFC 7616.
+//!
+//! The client answers a `401 Unauthorized` carrying a `Digest` challenge
+//! by resending the request with an `Authorization` header. The challenge
+//! is then kept for the origin, so following requests to it are authorized
+//! up front, each with the next nonce count.
+
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+
+use http::header::WWW_AUTHENTICATE;
+use http::{HeaderMap, HeaderValue, Method};
+use log::{debug, trace};
+use md5::{Digest, Md5};
+use sha2::{Sha256, Sha512_256};
+use url::{Position, Url};
+
+/// The username and password used to answer digest challenges.
+#[derive(Clone)]
+pub(crate) struct Credentials {
+    username: String,
+    password: String,
+}
+
+impl Credentials {
+    pub(crate) fn new<U, P>(username: U, password: P) -> Credentials
+    where
+        U: fmt::Display,
+        P: fmt::Display,
+    {
+        Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The last challenge received from each origin.
+#[derive(Default)]
+pub(crate) struct Challenges {
+    by_origin: Mutex<HashMap<String, Challenge>>,
+}
+
+impl Challenges {
+    /// Remember `challenge` for the origin of `url`, and answer it.
+    ///
+    /// `body` is the request body, if it is known. It is only needed when
+    /// the challenge asks for `qop=auth-int`.
+    pub(crate) fn answer(
+        &self,
+        challenge: Challenge,
+        credentials: &Credentials,
+        method: &Method,
+        url: &Url,
+        body: Option<&[u8]>,
+    ) -> Option<HeaderValue> {
+        let origin = url.origin().ascii_serialization();
+        let mut by_origin = self.by_origin.lock().unwrap();
+        let challenge = match by_origin.entry(origin) {
+            hash_map::Entry::Occupied(mut entry) => {
+                entry.insert(challenge);
+                entry.into_mut()
+            }
+            hash_map::Entry::Vacant(entry) => entry.insert(challenge),
+        };
+        challenge.authorization(credentials, method, url, body)
+    }
+
+    /// Authorize a request to `url` with the challenge last received from
+    /// its origin, if any.
+    pub(crate) fn authorize(
+        &self,
+        credentials: &Credentials,
+        method: &Method,
+        url: &Url,
+        body: Option<&[u8]>,
+    ) -> Option<HeaderValue> {
+        let origin = url.origin().ascii_serialization();
+        let mut by_origin = self.by_origin.lock().unwrap();
+        by_origin
+            .get_mut(&origin)
+            .and_then(|challenge| challenge.authorization(credentials, method, url, body))
+    }
+}
+
+impl fmt::Debug for Challenges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Challenges")
+            .field("origins", &self.by_origin.lock().unwrap().len())
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Algorithm {
+    Md5,
+    Sha256,
+    Sha512_256,
+}
+
+impl Algorithm {
+    fn parse(name: &str) -> Option<(Algorithm, bool)> {
+        let (name, sess) = match name.len().checked_sub(5) {
+            Some(i) if name.is_char_boundary(i) && name[i..].eq_ignore_ascii_case("-sess") => {
+                (&name[..i], true)
+            }
+            _ => (name, false),
+        };
+        let algorithm = if name.eq_ignore_ascii_case("MD5") {
+            Algorithm::Md5
+        } else if name.eq_ignore_ascii_case("SHA-256") {
+            Algorithm::Sha256
+        } else if name.eq_ignore_ascii_case("SHA-512-256") {
+            Algorithm::Sha512_256
+        } else {
+            return None;
+        };
+        Some((algorithm, sess))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "MD5",
+            Algorithm::Sha256 => "SHA-256",
+            Algorithm::Sha512_256 => "SHA-512-256",
+        }
+    }
+
+    fn hash(self, data: &[u8]) -> String {
+        match self {
+            Algorithm::Md5 => hex(&Md5::digest(data)),
+            Algorithm::Sha256 => hex(&Sha256::digest(data)),
+            Algorithm::Sha512_256 => hex(&Sha512_256::digest(data)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Qop {
+    Auth,
+    AuthInt,
+}
+
+/// A `Digest` challenge from a `WWW-Authenticate` header.
+#[derive(Clone, Debug)]
+pub(crate) struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: Algorithm,
+    sess: bool,
+    qop: Option<Qop>,
+    userhash: bool,
+    stale: bool,
+    nonce_count: u32,
+}
+
+impl Challenge {
+    /// Find the first supported `Digest` challenge in the `WWW-Authenticate`
+    /// headers.
+    ///
+    /// Servers list the challenges they prefer first.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Challenge> {
+        headers
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(parse_challenges)
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("digest"))
+            .find_map(|(_, params)| Challenge::from_params(params))
+    }
+
+    fn from_params(params: Vec<(&str, String)>) -> Option<Challenge> {
+        let mut realm = None;
+        let mut nonce = None;
+        let mut opaque = None;
+        let mut algorithm = (Algorithm::Md5, false);
+        let mut qop = None;
+        let mut userhash = false;
+        let mut stale = false;
+
+        for (name, value) in params {
+            match name.to_ascii_lowercase().as_str() {
+                "realm" => realm = Some(value),
+                "nonce" => nonce = Some(value),
+                "opaque" => opaque = Some(value),
+                "algorithm" => match Algorithm::parse(&value) {
+                    Some(parsed) => algorithm = parsed,
+                    None => {
+                        trace!("unsupported digest algorithm {value:?}");
+                        return None;
+                    }
+                },
+                "qop" => {
+                    let offered = value.split(',').map(str::trim).collect::<Vec<_>>();
+                    qop = if offered.contains(&"auth") {
+                        Some(Qop::Auth)
+                    } else if offered.contains(&"auth-int") {
+                        Some(Qop::AuthInt)
+                    } else {
+                        trace!("unsupported digest qop {value:?}");
+                        return None;
+                    };
+                }
+                "userhash" => userhash = value.eq_ignore_ascii_case("true"),
+                "stale" => stale = value.eq_ignore_ascii_case("true"),
+                _ => {}
+            }
+        }
+
+        Some(Challenge {
+            realm: realm?,
+            nonce: nonce?,
+            opaque,
+            algorithm: algorithm.0,
+            sess: algorithm.1,
+            qop,
+            userhash,
+            stale,
+            nonce_count: 0,
+        })
+    }
+
+    /// Whether the server rejected the previous answer only because its
+    /// nonce had expired, so answering again may succeed.
+    pub(crate) fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Answer this challenge, using the next nonce count.
+    ///
+    /// Returns `None` if the challenge requires the body, and it isn't known.
+    fn authorization(
+        &mut self,
+        credentials: &Credentials,
+        method: &Method,
+        url: &Url,
+        body: Option<&[u8]>,
+    ) -> Option<HeaderValue> {
+        let body_hash = match (self.qop, body) {
+            (Some(Qop::AuthInt), Some(body)) => Some(self.algorithm.hash(body)),
+            (Some(Qop::AuthInt), None) => {
+                debug!("digest challenge requires the body, but it is not reusable");
+                return None;
+            }
+            _ => None,
+        };
+
+        let mut random = [0; 16];
+        if let Err(err) = getrandom::getrandom(&mut random) {
+            debug!("digest cnonce could not be generated: {err}");
+            return None;
+        }
+        let cnonce = random
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        self.nonce_count += 1;
+        let uri = &url[Position::BeforePath..Position::AfterQuery];
+
+        let value = self.respond(credentials, method, uri, &cnonce, body_hash.as_deref());
+        let mut value = HeaderValue::try_from(value).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+
+    fn respond(
+        &self,
+        credentials: &Credentials,
+        method: &Method,
+        uri: &str,
+        cnonce: &str,
+        body_hash: Option<&str>,
+    ) -> String {
+        let h = |data: String| self.algorithm.hash(data.as_bytes());
+        let nc = format!("{:08x}", self.nonce_count);
+
+        let mut ha1 = h(format!(
+            "{}:{}:{}",
+            credentials.username, self.realm, credentials.password
+        ));
+        if self.sess {
+            ha1 = h(format!("{ha1}:{}:{cnonce}", self.nonce));
+        }
+        let ha2 = match body_hash {
+            Some(body_hash) => h(format!("{method}:{uri}:{body_hash}")),
+            None => h(format!("{method}:{uri}")),
+        };
+        let response = match self.qop {
+            Some(qop) => h(format!(
+                "{ha1}:{}:{nc}:{cnonce}:{}:{ha2}",
+                self.nonce,
+                qop_name(qop)
+            )),
+            None => h(format!("{ha1}:{}:{ha2}", self.nonce)),
+        };
+
+        let username = if self.userhash {
+            h(format!("{}:{}", credentials.username, self.realm))
+        } else {
+            credentials.username.clone()
+        };
+
+        let mut value = String::from("Digest ");
+        let _ = write!(
+            value,
+            "username={}, realm={}, uri={}, algorithm={}{}, nonce={}",
+            quote(&username),
+            quote(&self.realm),
+            quote(uri),
+            self.algorithm.name(),
+            if self.sess { "-sess" } else { "" },
+            quote(&self.nonce),
+        );
+        if let Some(qop) = self.qop {
+            let _ = write!(
+                value,
+                ", nc={nc}, cnonce={}, qop={}",
+                quote(cnonce),
+                qop_name(qop)
+            );
+        }
+        let _ = write!(value, ", response={}", quote(&response));
+        if let Some(ref opaque) = self.opaque {
+            let _ = write!(value, ", opaque={}", quote(opaque));
+        }
+        if self.userhash {
+            value.push_str(", userhash=true");
+        }
+        value
+    }
+}
+
+fn qop_name(qop: Qop) -> &'static str {
+    match qop {
+        Qop::Auth => "auth",
+        Qop::AuthInt => "auth-int",
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(s, "{b:02x}");
+    }
+    s
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parse the challenges of a `WWW-Authenticate` header value into their
+/// schemes and parameters.
+///
+/// See https://www.rfc-editor.org/rfc/rfc9110.html#section-11.6.1
+fn parse_challenges(value: &str) -> Vec<(&str, Vec<(&str, String)>)> {
+    let mut challenges: Vec<(&str, Vec<(&str, String)>)> = Vec::new();
+    let mut rest = value;
+
+    loop {
+        rest = rest.trim_start_matches([',', ' ', '\t']);
+        if rest.is_empty() {
+            break;
+        }
+
+        let (name, after) = split_token(rest);
+        if name.is_empty() {
+            // not a token, skip to the next element
+            rest = after.find(',').map_or("", |i| &after[i..]);
+            continue;
+        }
+        let after = after.trim_start_matches([' ', '\t']);
+
+        match (after.strip_prefix('='), challenges.last_mut()) {
+            (Some(after), Some((_, params))) if !after.starts_with('=') => {
+                let after = after.trim_start_matches([' ', '\t']);
+                let (value, after) = if after.starts_with('"') {
+                    split_quoted(after)
+                } else {
+                    let (value, after) = split_token(after);
+                    (value.to_owned(), after)
+                };
+                params.push((name, value));
+                rest = after;
+            }
+            (Some(_), _) => {
+                // a token68, or a parameter without a scheme
+                rest = after.find(',').map_or("", |i| &after[i..]);
+            }
+            (None, _) => {
+                challenges.push((name, Vec::new()));
+                rest = after;
+            }
+        }
+    }
+
+    challenges
+}
+
+fn split_token(s: &str) -> (&str, &str) {
+    let end = s
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)))
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// Split a quoted string from the front of `s`, which starts with `"`.
+fn split_quoted(s: &str) -> (String, &str) {
+    let mut value = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return (value, &s[i + 1..]),
+            '\\' => {
+                if let Some((_, c)) = chars.next() {
+                    value.push(c);
+                }
+            }
+            c => value.push(c),
+        }
+    }
+    (value, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(value: &'static str) -> Challenge {
+        let mut headers = HeaderMap::new();
+        headers.insert(WWW_AUTHENTICATE, HeaderValue::from_static(value));
+        Challenge::from_headers(&headers).unwrap()
+    }
+
+    fn param<'a>(value: &'a str, name: &str) -> &'a str {
+        let start = value.find(&format!("{name}=")).unwrap() + name.len() + 1;
+        let value = &value[start..];
+        let end = value.find(", ").unwrap_or(value.len());
+        value[..end].trim_matches('"')
+    }
+
+    // The example from RFC 7616, section 3.9.1.
+    const RFC_CHALLENGE: &str = "Digest \
+        realm=\"http-auth@example.org\", \
+        qop=\"auth, auth-int\", \
+        algorithm=SHA-256, \
+        nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+        opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"";
+
+    #[test]
+    fn rfc_7616_sha_256() {
+        let challenge = challenge(RFC_CHALLENGE);
+        assert_eq!(challenge.algorithm, Algorithm::Sha256);
+        assert_eq!(challenge.qop, Some(Qop::Auth));
+
+        let mut challenge = challenge;
+        challenge.nonce_count = 1;
+        let credentials = Credentials::new("Mufasa", "Circle of Life");
+        let value = challenge.respond(
+            &credentials,
+            &Method::GET,
+            "/dir/index.html",
+            "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+            None,
+        );
+
+        assert_eq!(
+            param(&value, "response"),
+            "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1"
+        );
+        assert_eq!(param(&value, "nc"), "00000001");
+        assert_eq!(param(&value, "qop"), "auth");
+        assert_eq!(
+            param(&value, "opaque"),
+            "FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS"
+        );
+    }
+
+    #[test]
+    fn rfc_7616_md5() {
+        let mut challenge = challenge(
+            "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", \
+             algorithm=MD5, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+        );
+        challenge.nonce_count = 1;
+        let credentials = Credentials::new("Mufasa", "Circle of Life");
+        let value = challenge.respond(
+            &credentials,
+            &Method::GET,
+            "/dir/index.html",
+            "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+            None,
+        );
+
+        assert_eq!(
+            param(&value, "response"),
+            "8ca523f5e9506fed4657c9700eebdbec"
+        );
+    }
+
+    #[test]
+    fn rfc_2069_without_qop() {
+        let mut challenge = challenge(
+            "Digest realm=\"testrealm@host.com\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\"",
+        );
+        assert_eq!(challenge.qop, None);
+        challenge.nonce_count = 1;
+
+        let credentials = Credentials::new("Mufasa", "Circle Of Life");
+        let value = challenge.respond(&credentials, &Method::GET, "/dir/index.html", "", None);
+
+        assert_eq!(
+            param(&value, "response"),
+            "670fd8c2df070c60b045671b8b24ff02"
+        );
+        assert!(!value.contains("nc="));
+    }
+
+    #[test]
+    fn first_supported_challenge_is_used() {
+        let challenge = challenge(
+            "Basic realm=\"basic\", \
+             Digest realm=\"a\", nonce=\"1\", algorithm=SHA-1024, \
+             Digest realm=\"b\", nonce=\"2\", algorithm=SHA-512-256-sess, qop=auth-int, stale=TRUE",
+        );
+        assert_eq!(challenge.realm, "b");
+        assert_eq!(challenge.algorithm, Algorithm::Sha512_256);
+        assert!(challenge.sess);
+        assert_eq!(challenge.qop, Some(Qop::AuthInt));
+        assert!(challenge.is_stale());
+    }
+
+    #[test]
+    fn parse_quoted_and_token68() {
+        let challenges = parse_challenges(
+            "Negotiate a87421000492aa874209af8bc028==, \
+             Digest realm=\"say \\\"hi\\\", ok\", nonce=abc",
+        );
+        assert_eq!(challenges.len(), 2);
+        assert_eq!(challenges[0], ("Negotiate", vec![]));
+        assert_eq!(
+            challenges[1],
+            (
+                "Digest",
+                vec![
+                    ("realm", "say \"hi\", ok".to_owned()),
+                    ("nonce", "abc".to_owned())
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn userhash_and_quoting() {
+        let mut challenge = challenge(
+            "Digest realm=\"api@example.org\", qop=auth, algorithm=SHA-512-256, \
+             nonce=\"5TsQWLVdgBdmrQ0XsxbDODV+57QdFR34I9HAbC/RVvkK\", \
+             opaque=\"HRPCssKJSGjCrkzDg8OhwpzCiGPChXYjwrI2QmXDnsOS\", charset=UTF-8, userhash=true",
+        );
+        challenge.nonce_count = 1;
+        let credentials = Credentials::new("J\"o", "Secret");
+        let value = challenge.respond(&credentials, &Method::GET, "/doe.json", "x", None);
+
+        assert!(value.contains("userhash=true"));
+        assert!(!value.contains("J\\\"o"));
+        assert_eq!(
+            param(&value, "username"),
+            Algorithm::Sha512_256.hash(b"J\"o:api@example.org")
+        );
+    }
+
+    #[test]
+    fn nonce_count_increments() {
+        let challenges = Challenges::default();
+        let credentials = Credentials::new("user", "pass");
+        let url = Url::parse("http://example.org/a?b=c").unwrap();
+
+        let first = challenges
+            .answer(
+                challenge("Digest realm=\"r\", nonce=\"n\", qop=auth"),
+                &credentials,
+                &Method::GET,
+                &url,
+                None,
+            )
+            .unwrap();
+        let first = first.to_str().unwrap();
+        assert_eq!(param(first, "nc"), "00000001");
+        assert_eq!(param(first, "uri"), "/a?b=c");
+
+        let other = Url::parse("http://example.org/other").unwrap();
+        let second = challenges
+            .authorize(&credentials, &Method::GET, &other, None)
+            .unwrap();
+        assert_eq!(param(second.to_str().unwrap(), "nc"), "00000002");
+
+        let elsewhere = Url::parse("http://example.com/").unwrap();
+        assert!(challenges
+            .authorize(&credentials, &Method::GET, &elsewhere, None)
+            .is_none());
+    }
+
+    #[test]
+    fn auth_int_requires_body() {
+        let challenges = Challenges::default();
+        let credentials = Credentials::new("user", "pass");
+        let url = Url::parse("http://example.org/").unwrap();
+        let challenge = challenge("Digest realm=\"r\", nonce=\"n\", qop=\"auth-int\"");
+
+        assert!(challenges
+            .answer(challenge, &credentials, &Method::POST, &url, None)
+            .is_none());
+        assert!(challenges
+            .authorize(&credentials, &Method::POST, &url, Some(b"body"))
+            .is_some());
+    }
+
+    #[test]
+    fn debug_hides_password() {
+        let credentials = Credentials::new("user", "hunter2");
+        assert!(!format!("{credentials:?}").contains("hunter2"));
+    }
+}
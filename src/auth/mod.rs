@@ -0,0 +1,17 @@
+//! Authentication
+//!
+//! `RequestBuilder::basic_auth()` and `RequestBuilder::bearer_auth()` add
+//! a fixed `Authorization` header to a single request. For credentials that
+//! change over time, such as OAuth2 access tokens, a [`CredentialProvider`]
+//! can be passed to `ClientBuilder::credential_provider()` instead. The
+//! client then asks it for an `Authorization` value before sending each
+//! request, and asks it again after a `401 Unauthorized`.
+//!
+//! With the `digest-auth` feature, `RequestBuilder::digest_auth()` and
+//! `ClientBuilder::digest_auth()` answer HTTP Digest challenges.
+
+pub use self::provider::{Authorizing, CredentialProvider};
+
+#[cfg(feature = "digest-auth")]
+pub(crate) mod digest;
+mod provider;
@@ -0,0 +1,90 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use futures_util::future;
+use http::HeaderValue;
+use url::Url;
+
+use crate::error::BoxError;
+
+/// Alias for the `Future` type returned by a credential provider.
+pub type Authorizing = Pin<Box<dyn Future<Output = Result<Option<HeaderValue>, BoxError>> + Send>>;
+
+/// Trait for supplying the `Authorization` header of requests.
+///
+/// The client asks the provider for a value before sending each request,
+/// including every redirect and retry of it. A redirect to another origin
+/// never carries the previous value along; the provider is asked again
+/// with the new URL, and should return `None` for URLs that must not
+/// receive its credentials.
+///
+/// Requests that already have an `Authorization` header are sent as they
+/// are.
+///
+/// # Example
+///
+/// A provider that caches a token until the server rejects it:
+///
+/// ```rust
+/// # use std::sync::{Arc, Mutex};
+/// # use reqwest::auth::{Authorizing, CredentialProvider};
+/// # use reqwest::header::HeaderValue;
+/// # use reqwest::Url;
+/// #
+/// # async fn fetch_token() -> Result<String, reqwest::Error> {
+/// #     Ok(String::from("token"))
+/// # }
+/// #
+/// #[derive(Default)]
+/// struct OAuth2 {
+///     token: Arc<Mutex<Option<HeaderValue>>>,
+/// }
+///
+/// impl CredentialProvider for OAuth2 {
+///     fn authorization(&self, url: &Url) -> Authorizing {
+///         if url.host_str() != Some("api.example.com") {
+///             return Box::pin(async { Ok(None) });
+///         }
+///         if let Some(token) = self.token.lock().unwrap().clone() {
+///             return Box::pin(async { Ok(Some(token)) });
+///         }
+///         let cached = self.token.clone();
+///         Box::pin(async move {
+///             let token = HeaderValue::try_from(format!("Bearer {}", fetch_token().await?))?;
+///             *cached.lock().unwrap() = Some(token.clone());
+///             Ok(Some(token))
+///         })
+///     }
+///
+///     fn refresh(&self, url: &Url, _rejected: &HeaderValue) -> Authorizing {
+///         self.token.lock().unwrap().take();
+///         self.authorization(url)
+///     }
+/// }
+///
+/// let client = reqwest::Client::builder()
+///     .credential_provider(Arc::new(OAuth2::default()))
+///     .build();
+/// ```
+///
+/// A provider can also keep the expiry of a token, and fetch a new one
+/// before the old one is rejected.
+pub trait CredentialProvider: Send + Sync {
+    /// Get the `Authorization` value for a request to `url`.
+    ///
+    /// Returning `None` sends the request without one.
+    fn authorization(&self, url: &Url) -> Authorizing;
+
+    /// Get a new `Authorization` value for a request to `url`, after the
+    /// server answered `rejected` with `401 Unauthorized`.
+    ///
+    /// If a value is returned, the request is sent once more with it,
+    /// provided its body is reusable. Otherwise the `401` response is
+    /// returned.
+    ///
+    /// The default implementation returns `None`.
+    fn refresh(&self, url: &Url, rejected: &HeaderValue) -> Authorizing {
+        let _ = (url, rejected);
+        Box::pin(future::ready(Ok(None)))
+    }
+}
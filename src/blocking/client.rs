@@ -19,10 +19,13 @@ use tower::Service;
 use super::request::{Request, RequestBuilder};
 use super::response::Response;
 use super::wait;
+use crate::async_impl::client::BoxedClientService;
+use crate::auth::CredentialProvider;
 use crate::connect::sealed::{Conn, Unnameable};
 use crate::connect::BoxedConnectorService;
-use crate::dns::Resolve;
+use crate::dns::{IpFamily, Resolve};
 use crate::error::BoxError;
+use crate::event::EventListener;
 #[cfg(feature = "__tls")]
 use crate::tls;
 #[cfg(feature = "__rustls")]
@@ -31,7 +34,7 @@ use crate::tls::CertificateRevocationList;
 use crate::Certificate;
 #[cfg(any(feature = "native-tls", feature = "__rustls"))]
 use crate::Identity;
-use crate::{async_impl, header, redirect, IntoUrl, Method, Proxy};
+use crate::{async_impl, cache, header, redirect, retry, IntoUrl, Method, Proxy};
 
 /// A `Client` to make Requests with.
 ///
@@ -331,6 +334,29 @@ impl ClientBuilder {
         self.with_inner(|inner| inner.no_deflate())
     }
 
+    /// Compress request bodies with `encoding`, and set their
+    /// `Content-Encoding` header.
+    ///
+    /// See [`ClientBuilder::compress`](crate::ClientBuilder::compress) for details.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(any(
+            feature = "gzip",
+            feature = "brotli",
+            feature = "zstd",
+            feature = "deflate"
+        )))
+    )]
+    pub fn compress(self, encoding: crate::Encoding) -> ClientBuilder {
+        self.with_inner(move |inner| inner.compress(encoding))
+    }
+
     // Redirect options
 
     /// Set a `redirect::Policy` for this client.
@@ -347,6 +373,76 @@ impl ClientBuilder {
         self.with_inner(|inner| inner.referer(enable))
     }
 
+    // Retry options
+
+    /// Set a `retry::Policy` for this client.
+    ///
+    /// Default only retries requests that the server refused before
+    /// processing them, up to a maximum of 2 times.
+    pub fn retry(self, policy: retry::Policy) -> ClientBuilder {
+        self.with_inner(move |inner| inner.retry(policy))
+    }
+
+    // Auth options
+
+    /// Enable HTTP digest authentication for all requests of this client.
+    ///
+    /// When a server answers with `401 Unauthorized` and a `Digest`
+    /// challenge, the request is sent again with an `Authorization` header
+    /// answering it, as described in RFC 7616. The challenge is remembered,
+    /// so later requests to the same origin are authorized up front.
+    ///
+    /// Credentials are only used for the origin a request was made to,
+    /// never for an origin it was redirected to.
+    #[cfg(feature = "digest-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "digest-auth")))]
+    pub fn digest_auth<U, P>(self, username: U, password: P) -> ClientBuilder
+    where
+        U: fmt::Display,
+        P: fmt::Display,
+    {
+        self.with_inner(move |inner| inner.digest_auth(username, password))
+    }
+
+    /// Set the `CredentialProvider` that authorizes the requests of this
+    /// client.
+    ///
+    /// It is asked for an `Authorization` value before each request is
+    /// sent, including redirects and retries, and asked for a new one when
+    /// the server answers `401 Unauthorized`. See the [`auth`](crate::auth)
+    /// module for details.
+    ///
+    /// Default is no provider.
+    pub fn credential_provider<P: CredentialProvider + 'static>(
+        self,
+        provider: Arc<P>,
+    ) -> ClientBuilder {
+        self.with_inner(move |inner| inner.credential_provider(provider))
+    }
+
+    // Cache options
+
+    /// Enable an HTTP cache for this client.
+    ///
+    /// See the [`cache`](crate::cache) module for details.
+    ///
+    /// Default is no cache.
+    pub fn cache(self, cache: cache::Cache) -> ClientBuilder {
+        self.with_inner(move |inner| inner.cache(cache))
+    }
+
+    /// Record the exchanges of this client to a `Cassette`, or replay them
+    /// from it.
+    ///
+    /// See the [`replay`](crate::replay) module for details.
+    ///
+    /// Default is no cassette.
+    #[cfg(feature = "replay")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "replay")))]
+    pub fn replay(self, cassette: crate::replay::Cassette) -> ClientBuilder {
+        self.with_inner(move |inner| inner.replay(cassette))
+    }
+
     // Proxy options
 
     /// Add a `Proxy` to the list of proxies the `Client` will use.
@@ -399,6 +495,29 @@ impl ClientBuilder {
         }
     }
 
+    /// Set the maximum size of response bodies, in bytes, after
+    /// decompression.
+    ///
+    /// See [`ClientBuilder::max_response_body_size`](crate::ClientBuilder::max_response_body_size) for details.
+    pub fn max_response_body_size(self, max: u64) -> ClientBuilder {
+        self.with_inner(move |inner| inner.max_response_body_size(max))
+    }
+
+    /// Set the maximum size of decompressed response bodies, in bytes.
+    ///
+    /// See [`ClientBuilder::max_decompressed_size`](crate::ClientBuilder::max_decompressed_size) for details.
+    pub fn max_decompressed_size(self, max: u64) -> ClientBuilder {
+        self.with_inner(move |inner| inner.max_decompressed_size(max))
+    }
+
+    /// Set the maximum ratio of the decompressed size of response bodies to
+    /// their compressed size.
+    ///
+    /// See [`ClientBuilder::max_compression_ratio`](crate::ClientBuilder::max_compression_ratio) for details.
+    pub fn max_compression_ratio(self, ratio: u32) -> ClientBuilder {
+        self.with_inner(move |inner| inner.max_compression_ratio(ratio))
+    }
+
     /// Set whether connections should emit verbose logs.
     ///
     /// Enabling this option will emit [log][] messages at the `TRACE` level
@@ -409,6 +528,46 @@ impl ClientBuilder {
         self.with_inner(move |inner| inner.connection_verbose(verbose))
     }
 
+    /// Set an `EventListener` to be told about the network activity of
+    /// this client.
+    ///
+    /// It is called as hosts are resolved, connections are made and
+    /// acquired, and requests are sent and answered. See the
+    /// [`event`](crate::event) module for details.
+    ///
+    /// Default is no listener.
+    pub fn event_listener<L: EventListener + 'static>(self, listener: Arc<L>) -> ClientBuilder {
+        self.with_inner(move |inner| inner.event_listener(listener))
+    }
+
+    /// Set a `Propagator` to add the trace context to the headers of each
+    /// request sent by this client.
+    ///
+    /// It is given the span of every hop of a request, see the
+    /// [`trace`](crate::trace) module for details.
+    ///
+    /// Default is no propagator.
+    #[cfg(feature = "tracing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+    pub fn trace_propagator<P: crate::trace::Propagator + 'static>(
+        self,
+        propagator: Arc<P>,
+    ) -> ClientBuilder {
+        self.with_inner(move |inner| inner.trace_propagator(propagator))
+    }
+
+    /// Record every request this client sends, and its response, with a
+    /// `HarRecorder`.
+    ///
+    /// See the [`har`](crate::har) module for details.
+    ///
+    /// Default is no recorder.
+    #[cfg(feature = "har")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "har")))]
+    pub fn har_recorder(self, recorder: Arc<crate::har::HarRecorder>) -> ClientBuilder {
+        self.with_inner(move |inner| inner.har_recorder(recorder))
+    }
+
     // HTTP options
 
     /// Set an optional timeout for idle sockets being kept-alive.
@@ -527,6 +686,18 @@ impl ClientBuilder {
         self.with_inner(|inner| inner.http3_prior_knowledge())
     }
 
+    /// Enable upgrading to HTTP/3 with the `Alt-Svc` headers of responses.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `http3` feature to be
+    /// enabled.
+    #[cfg(feature = "http3")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http3")))]
+    pub fn http3_alt_svc(self, enabled: bool) -> ClientBuilder {
+        self.with_inner(|inner| inner.http3_alt_svc(enabled))
+    }
+
     // TCP options
 
     /// Set whether sockets have `TCP_NODELAY` enabled.
@@ -569,6 +740,15 @@ impl ClientBuilder {
         self.with_inner(move |inner| inner.interface(interface))
     }
 
+    /// Send requests over the Unix socket at `path` instead of TCP.
+    ///
+    /// See [`ClientBuilder::unix_socket`](crate::ClientBuilder::unix_socket) for details.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn unix_socket<P: AsRef<std::path::Path>>(self, path: P) -> ClientBuilder {
+        self.with_inner(move |inner| inner.unix_socket(path))
+    }
+
     /// Set that all sockets have `SO_KEEPALIVE` set with the supplied duration.
     ///
     /// If `None`, the option will not be set.
@@ -996,6 +1176,26 @@ impl ClientBuilder {
         self.with_inner(|inner| inner.dns_resolver(resolver))
     }
 
+    /// Set which IP families to connect with, and which to try first.
+    ///
+    /// Default is `IpFamily::Any`, which keeps the order of the resolver.
+    pub fn ip_family(self, ip_family: IpFamily) -> ClientBuilder {
+        self.with_inner(|inner| inner.ip_family(ip_family))
+    }
+
+    /// Set the delay before trying the other IP family when connecting, as
+    /// described by RFC 8305 ("Happy Eyeballs").
+    ///
+    /// If `None`, all addresses are tried one after the other.
+    ///
+    /// Default is 300 milliseconds.
+    pub fn happy_eyeballs_timeout<D>(self, timeout: D) -> ClientBuilder
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.with_inner(|inner| inner.happy_eyeballs_timeout(timeout))
+    }
+
     /// Adds a new Tower [`Layer`](https://docs.rs/tower/latest/tower/trait.Layer.html) to the
     /// base connector [`Service`](https://docs.rs/tower/latest/tower/trait.Service.html) which
     /// is responsible for connection establishment.
@@ -1025,6 +1225,63 @@ impl ClientBuilder {
         self.with_inner(|inner| inner.connector_layer(layer))
     }
 
+    /// Adds a new Tower [`Layer`](https://docs.rs/tower/latest/tower/trait.Layer.html) to the
+    /// [`Service`](https://docs.rs/tower/latest/tower/trait.Service.html) which sends each
+    /// request and receives its response.
+    ///
+    /// The layered service runs on the client's internal runtime, so it works
+    /// with the asynchronous [`Request`](crate::Request) and
+    /// [`Response`](crate::Response) types. It is called once per hop, including
+    /// redirects and retries.
+    ///
+    /// Each subsequent invocation of this function will wrap previous layers.
+    ///
+    /// Example usage:
+    /// ```
+    /// use reqwest::header::{HeaderValue, USER_AGENT};
+    ///
+    /// let client = reqwest::blocking::Client::builder()
+    ///     .layer(tower::util::MapRequestLayer::new(|mut req: reqwest::Request| {
+    ///         req.headers_mut()
+    ///             .insert(USER_AGENT, HeaderValue::from_static("my-agent"));
+    ///         req
+    ///     }))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn layer<L>(self, layer: L) -> ClientBuilder
+    where
+        L: Layer<BoxedClientService> + Clone + Send + Sync + 'static,
+        L::Service: Service<async_impl::Request, Response = async_impl::Response, Error = BoxError>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        <L::Service as Service<async_impl::Request>>::Future: Send + 'static,
+    {
+        self.with_inner(|inner| inner.layer(layer))
+    }
+
+    /// Sends requests with a Tower `Service` instead of over the network.
+    ///
+    /// The service is called from the client's internal runtime.
+    ///
+    /// See [`ClientBuilder::transport`](crate::ClientBuilder::transport) for details.
+    pub fn transport<S, B>(self, transport: S) -> ClientBuilder
+    where
+        S: Service<http::Request<async_impl::Body>, Response = http::Response<B>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Error: Into<BoxError>,
+        S::Future: Send + 'static,
+        B: http_body::Body<Data = bytes::Bytes> + Send + Sync + 'static,
+        B::Error: Into<BoxError>,
+    {
+        self.with_inner(|inner| inner.transport(transport))
+    }
+
     // private
 
     fn with_inner<F>(mut self, func: F) -> ClientBuilder
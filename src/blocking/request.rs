@@ -12,8 +12,10 @@ use super::body::{self, Body};
 #[cfg(feature = "multipart")]
 use super::multipart;
 use super::Client;
+#[cfg(feature = "digest-auth")]
+use crate::config::{RequestConfig, RequestDigestAuth};
 use crate::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
-use crate::{async_impl, Method, Url};
+use crate::{async_impl, cache, retry, Method, Url};
 
 /// A request which can be executed with `Client::execute()`.
 pub struct Request {
@@ -113,6 +115,96 @@ impl Request {
         self.inner.timeout_mut()
     }
 
+    /// Get the retry policy.
+    #[inline]
+    pub fn retry(&self) -> Option<&retry::Policy> {
+        self.inner.retry()
+    }
+
+    /// Get a mutable reference to the retry policy.
+    #[inline]
+    pub fn retry_mut(&mut self) -> &mut Option<retry::Policy> {
+        self.inner.retry_mut()
+    }
+
+    /// Get the cache mode.
+    #[inline]
+    pub fn cache_mode(&self) -> Option<&cache::Mode> {
+        self.inner.cache_mode()
+    }
+
+    /// Get a mutable reference to the cache mode.
+    #[inline]
+    pub fn cache_mode_mut(&mut self) -> &mut Option<cache::Mode> {
+        self.inner.cache_mode_mut()
+    }
+
+    /// Get the maximum size of the response body.
+    #[inline]
+    pub fn max_response_body_size(&self) -> Option<u64> {
+        self.inner.max_response_body_size()
+    }
+
+    /// Get a mutable reference to the maximum size of the response body.
+    #[inline]
+    pub fn max_response_body_size_mut(&mut self) -> &mut Option<u64> {
+        self.inner.max_response_body_size_mut()
+    }
+
+    /// Get whether the response body is resumed when it fails.
+    #[inline]
+    pub fn resumable(&self) -> bool {
+        self.inner.resumable()
+    }
+
+    /// Get a mutable reference to whether the response body is resumed when
+    /// it fails.
+    #[inline]
+    pub fn resumable_mut(&mut self) -> &mut Option<bool> {
+        self.inner.resumable_mut()
+    }
+
+    /// Get the encoding the request body is compressed with.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    #[inline]
+    pub fn compress(&self) -> Option<crate::Encoding> {
+        self.inner.compress()
+    }
+
+    /// Get a mutable reference to the encoding the request body is
+    /// compressed with.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    #[inline]
+    pub fn compress_mut(&mut self) -> &mut Option<crate::Encoding> {
+        self.inner.compress_mut()
+    }
+
+    /// Get the Unix socket this request is sent over.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[inline]
+    pub fn unix_socket(&self) -> Option<&std::path::Path> {
+        self.inner.unix_socket()
+    }
+
+    /// Get a mutable reference to the Unix socket this request is sent over.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    #[inline]
+    pub fn unix_socket_mut(&mut self) -> &mut Option<std::path::PathBuf> {
+        self.inner.unix_socket_mut()
+    }
+
     /// Attempts to clone the `Request`.
     ///
     /// None is returned if a body is which can not be cloned. This can be because the body is a
@@ -128,7 +220,10 @@ impl Request {
             None
         };
         let mut req = Request::new(self.method().clone(), self.url().clone());
+        *req.inner.extensions_mut() = self.inner.extensions().clone();
         *req.timeout_mut() = self.timeout().copied();
+        *req.retry_mut() = self.retry().cloned();
+        *req.cache_mode_mut() = self.cache_mode().copied();
         *req.headers_mut() = self.headers().clone();
         *req.version_mut() = self.version().clone();
         req.body = body;
@@ -302,6 +397,35 @@ impl RequestBuilder {
         self.header_sensitive(crate::header::AUTHORIZATION, &*header_value, true)
     }
 
+    /// Enable HTTP digest authentication.
+    ///
+    /// Unlike `basic_auth()`, nothing is sent up front. When the server
+    /// answers with `401 Unauthorized` and a `Digest` challenge, the request
+    /// is sent again answering it. See `ClientBuilder::digest_auth()`.
+    ///
+    /// ```rust
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = reqwest::blocking::Client::new();
+    /// let resp = client.get("http://192.168.1.20/status")
+    ///     .digest_auth("admin", "good password")
+    ///     .send()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "digest-auth")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "digest-auth")))]
+    pub fn digest_auth<U, P>(mut self, username: U, password: P) -> RequestBuilder
+    where
+        U: fmt::Display,
+        P: fmt::Display,
+    {
+        if let Ok(ref mut req) = self.request {
+            *RequestConfig::<RequestDigestAuth>::get_mut(req.inner.extensions_mut()) =
+                Some(crate::auth::digest::Credentials::new(username, password));
+        }
+        self
+    }
+
     /// Set the request body.
     ///
     /// # Examples
@@ -364,6 +488,129 @@ impl RequestBuilder {
         self
     }
 
+    /// Set a `retry::Policy` for this request.
+    ///
+    /// It affects only this request and overrides the policy configured
+    /// using `ClientBuilder::retry()`.
+    pub fn retry(mut self, policy: retry::Policy) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.retry_mut() = Some(policy);
+        }
+        self
+    }
+
+    /// Set how this request uses the client's cache.
+    ///
+    /// It affects only this request and overrides the mode configured
+    /// using `Cache::mode()`. It has no effect if the client has no cache.
+    pub fn cache_mode(mut self, mode: cache::Mode) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.cache_mode_mut() = Some(mode);
+        }
+        self
+    }
+
+    /// Set the maximum size of the response body, in bytes, after
+    /// decompression.
+    ///
+    /// It affects only this request and overrides the maximum configured
+    /// using `ClientBuilder::max_response_body_size()`.
+    pub fn max_response_body_size(mut self, max: u64) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.max_response_body_size_mut() = Some(max);
+        }
+        self
+    }
+
+    /// Enable or disable resuming the response body when it fails
+    /// mid-transfer.
+    ///
+    /// See [`RequestBuilder::resumable`](crate::RequestBuilder::resumable)
+    /// for details.
+    ///
+    /// Default is `false`.
+    pub fn resumable(mut self, enable: bool) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.resumable_mut() = Some(enable);
+        }
+        self
+    }
+
+    /// Call `callback` as the request body is sent, with the number of
+    /// bytes sent so far and the length of the body, if it is known.
+    ///
+    /// See [`RequestBuilder::on_upload_progress`](crate::RequestBuilder::on_upload_progress)
+    /// for details.
+    pub fn on_upload_progress<F>(mut self, callback: F) -> RequestBuilder
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        if let Ok(ref mut req) = self.request {
+            *req.inner.upload_progress_mut() =
+                Some(crate::async_impl::progress::Progress::new(callback));
+        }
+        self
+    }
+
+    /// Call `callback` as the response body is read, with the number of
+    /// bytes received so far, the number of bytes of the body they were
+    /// decoded to, and the `Content-Length` of the response, if it has one.
+    ///
+    /// See [`RequestBuilder::on_download_progress`](crate::RequestBuilder::on_download_progress)
+    /// for details.
+    pub fn on_download_progress<F>(mut self, callback: F) -> RequestBuilder
+    where
+        F: Fn(u64, u64, Option<u64>) + Send + Sync + 'static,
+    {
+        if let Ok(ref mut req) = self.request {
+            *req.inner.download_progress_mut() =
+                Some(crate::async_impl::progress::DownloadProgress::new(callback));
+        }
+        self
+    }
+
+    /// Compress the body with `encoding`, and set the `Content-Encoding`
+    /// header.
+    ///
+    /// It affects only this request and overrides the encoding configured
+    /// using `ClientBuilder::compress()`. See
+    /// [`RequestBuilder::compress`](crate::RequestBuilder::compress) for
+    /// details.
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(any(
+            feature = "gzip",
+            feature = "brotli",
+            feature = "zstd",
+            feature = "deflate"
+        )))
+    )]
+    pub fn compress(mut self, encoding: crate::Encoding) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.compress_mut() = Some(encoding);
+        }
+        self
+    }
+
+    /// Send this request over the Unix socket at `path`.
+    ///
+    /// It affects only this request and overrides the socket configured
+    /// using `ClientBuilder::unix_socket()`.
+    #[cfg(unix)]
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn unix_socket<P: AsRef<std::path::Path>>(mut self, path: P) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.unix_socket_mut() = Some(path.as_ref().to_owned());
+        }
+        self
+    }
+
     /// Modify the query string of the URL.
     ///
     /// Modifies the URL of this request, adding the parameters provided.
@@ -0,0 +1,112 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+use super::{CacheStore, Entry};
+
+/// A `CacheStore` that keeps each entry in a file in a directory.
+///
+/// Entries survive the process, so they can be shared by every run of a
+/// command line tool. The directory is created when the first entry is
+/// stored. Files are written to a temporary name and then renamed, so
+/// concurrent processes never read a partially written entry.
+///
+/// The file system is accessed directly from the calling task, so this
+/// store is best suited for a local disk.
+#[derive(Debug, Clone)]
+pub struct DiskStore {
+    dir: PathBuf,
+}
+
+impl DiskStore {
+    /// Creates a store that keeps its entries in `dir`.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> DiskStore {
+        DiskStore { dir: dir.into() }
+    }
+
+    /// Get the directory entries are kept in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.entry", fnv1a(key.as_bytes())))
+    }
+
+    fn read(&self, key: &str) -> io::Result<Option<Entry>> {
+        let buf = match fs::read(self.path(key)) {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        // The key is stored in front of the entry, in case of a hash collision.
+        let rest = match buf.strip_prefix(key.as_bytes()) {
+            Some(rest) => rest,
+            None => return Ok(None),
+        };
+        let rest = match rest.strip_prefix(b"\n") {
+            Some(rest) => rest,
+            None => return Ok(None),
+        };
+
+        Entry::decode(rest)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn write(&self, key: &str, entry: &Entry) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let mut buf = Vec::with_capacity(key.len() + 1);
+        buf.extend_from_slice(key.as_bytes());
+        buf.push(b'\n');
+        buf.extend_from_slice(&entry.encode());
+
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{:016x}.tmp", crate::util::fast_random()));
+        fs::write(&tmp, buf)?;
+        fs::rename(&tmp, &path).map_err(|err| {
+            let _ = fs::remove_file(&tmp);
+            err
+        })
+    }
+}
+
+impl CacheStore for DiskStore {
+    fn get(&self, key: &str) -> Option<Entry> {
+        match self.read(key) {
+            Ok(entry) => entry,
+            Err(err) => {
+                debug!("failed to read cache entry for '{key}': {err}");
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, entry: Entry) {
+        if let Err(err) = self.write(key, &entry) {
+            debug!("failed to write cache entry for '{key}': {err}");
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        match fs::remove_file(self.path(key)) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => debug!("failed to remove cache entry for '{key}': {err}"),
+        }
+    }
+}
+
+// FNV-1a, since file names must stay the same across processes and builds.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use super::{CacheStore, Entry};
+
+/// A `CacheStore` that keeps entries in memory.
+///
+/// This is the store used by `Cache::memory()`. Entries are lost when the
+/// store is dropped.
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl MemoryStore {
+    /// Creates an empty `MemoryStore`.
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    /// Removes all entries from the store.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn get(&self, key: &str) -> Option<Entry> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, entry: Entry) {
+        self.entries.lock().unwrap().insert(key.to_owned(), entry);
+    }
+
+    fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+impl fmt::Debug for MemoryStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryStore")
+            .field("entries", &self.entries.lock().unwrap().len())
+            .finish()
+    }
+}
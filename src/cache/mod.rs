@@ -0,0 +1,140 @@
+//! HTTP Caching
+//!
+//! A `Client` can keep responses in a private cache, following the rules of
+//! [RFC 9111]. Caching is opt-in, and is enabled by passing a [`Cache`] to
+//! `ClientBuilder::cache()`.
+//!
+//! When enabled, the cache will:
+//!
+//! - serve fresh responses to `GET` requests without contacting the server,
+//!   based on `Cache-Control`, `Expires`, `Age` and `Last-Modified`,
+//! - only reuse a response if the request headers named by its `Vary`
+//!   header are the same,
+//! - revalidate stale responses with `If-None-Match` or
+//!   `If-Modified-Since`, and reuse the stored body on a `304 Not Modified`,
+//! - invalidate the stored response for a URL after an unsafe request, such
+//!   as a `POST`, to it succeeds.
+//!
+//! How a single request uses the cache can be changed with a [`Mode`], either
+//! for every request with [`Cache::mode`], or per request with
+//! `RequestBuilder::cache_mode()`.
+//!
+//! Responses are stored in a [`CacheStore`]. Two are provided: a
+//! [`MemoryStore`], and a [`DiskStore`] that persists responses across
+//! processes.
+//!
+//! Stored responses have already been decompressed, and are at most
+//! [`Cache::max_entry_size`] bytes long.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use std::sync::Arc;
+//! # use reqwest::cache::{Cache, DiskStore};
+//! #
+//! # fn run() -> Result<(), reqwest::Error> {
+//! let store = DiskStore::new(std::env::temp_dir().join("my-cli-cache"));
+//!
+//! let client = reqwest::Client::builder()
+//!     .cache(Cache::new(Arc::new(store)))
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [RFC 9111]: https://www.rfc-editor.org/rfc/rfc9111.html
+
+use std::fmt;
+use std::sync::Arc;
+
+pub use self::disk::DiskStore;
+pub use self::memory::MemoryStore;
+pub use self::store::{CacheStore, Entry};
+pub(crate) use self::service::CacheService;
+
+mod disk;
+mod memory;
+mod policy;
+mod service;
+mod store;
+
+/// The default value of `Cache::max_entry_size`, 10 MiB.
+const DEFAULT_MAX_ENTRY_SIZE: usize = 10 * 1024 * 1024;
+
+/// The configuration of a `Client`'s HTTP cache.
+#[derive(Clone)]
+pub struct Cache {
+    store: Arc<dyn CacheStore>,
+    mode: Mode,
+    max_entry_size: usize,
+}
+
+/// How a request uses the cache.
+///
+/// These mirror the cache modes of the [Fetch standard].
+///
+/// [Fetch standard]: https://fetch.spec.whatwg.org/#concept-request-cache-mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Mode {
+    /// Use fresh stored responses, revalidate stale ones, and store new
+    /// responses.
+    #[default]
+    Default,
+    /// Neither use nor update the cache.
+    NoStore,
+    /// Always fetch from the server, but store the new response.
+    Reload,
+    /// Always revalidate a stored response with the server before using it.
+    NoCache,
+    /// Use any stored response, even if it is stale, and only fetch from the
+    /// server when there is none.
+    ForceCache,
+    /// Use any stored response, even if it is stale, and never contact the
+    /// server. Without a stored response, a `504 Gateway Timeout` response is
+    /// returned.
+    OnlyIfCached,
+}
+
+impl Cache {
+    /// Creates a cache that stores responses in `store`.
+    pub fn new<S: CacheStore + 'static>(store: Arc<S>) -> Cache {
+        Cache {
+            store,
+            mode: Mode::Default,
+            max_entry_size: DEFAULT_MAX_ENTRY_SIZE,
+        }
+    }
+
+    /// Creates a cache that stores responses in a new `MemoryStore`.
+    pub fn memory() -> Cache {
+        Cache::new(Arc::new(MemoryStore::new()))
+    }
+
+    /// Sets the `Mode` used by requests that don't set their own.
+    ///
+    /// Default is `Mode::Default`.
+    pub fn mode(mut self, mode: Mode) -> Cache {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the largest response body, in bytes, that will be stored.
+    ///
+    /// Larger responses are still returned, but are not stored.
+    ///
+    /// Default is 10 MiB.
+    pub fn max_entry_size(mut self, bytes: usize) -> Cache {
+        self.max_entry_size = bytes;
+        self
+    }
+}
+
+impl fmt::Debug for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("mode", &self.mode)
+            .field("max_entry_size", &self.max_entry_size)
+            .finish()
+    }
+}
@@ -0,0 +1,425 @@
+//! The rules of RFC 9111 deciding what may be stored, and for how long a
+//! stored response may be used.
+
+use std::time::{Duration, SystemTime};
+
+use http::header::{HeaderName, AGE, CACHE_CONTROL, DATE, EXPIRES, LAST_MODIFIED, PRAGMA, VARY};
+use http::{HeaderMap, StatusCode};
+
+use super::Entry;
+
+/// The directives of the `Cache-Control` header(s) a cache acts on.
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct CacheControl {
+    pub(super) no_store: bool,
+    pub(super) no_cache: bool,
+    pub(super) max_age: Option<Duration>,
+    pub(super) max_stale: Option<Duration>,
+    pub(super) min_fresh: Option<Duration>,
+    pub(super) only_if_cached: bool,
+    pub(super) must_revalidate: bool,
+    pub(super) public: bool,
+    pub(super) private: bool,
+}
+
+impl CacheControl {
+    pub(super) fn parse(headers: &HeaderMap) -> CacheControl {
+        let mut cc = CacheControl::default();
+        let mut any = false;
+
+        for value in headers.get_all(CACHE_CONTROL) {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            for directive in value.split(',') {
+                any = true;
+                let (name, arg) = match directive.split_once('=') {
+                    Some((name, arg)) => (name.trim(), Some(arg.trim().trim_matches('"'))),
+                    None => (directive.trim(), None),
+                };
+                let seconds = || {
+                    arg.and_then(|arg| arg.parse().ok())
+                        .map(Duration::from_secs)
+                };
+
+                match &*name.to_ascii_lowercase() {
+                    "no-store" => cc.no_store = true,
+                    // `no-cache="field"` only limits what may be reused, but
+                    // revalidating is always allowed.
+                    "no-cache" => cc.no_cache = true,
+                    "max-age" => cc.max_age = seconds().or(Some(Duration::ZERO)),
+                    // without a value, any staleness is accepted
+                    "max-stale" => cc.max_stale = Some(seconds().unwrap_or(Duration::MAX)),
+                    "min-fresh" => cc.min_fresh = seconds(),
+                    "only-if-cached" => cc.only_if_cached = true,
+                    "must-revalidate" | "proxy-revalidate" => cc.must_revalidate = true,
+                    "public" => cc.public = true,
+                    "private" => cc.private = true,
+                    _ => {}
+                }
+            }
+        }
+
+        // Pragma is only considered when there is no Cache-Control.
+        if !any {
+            cc.no_cache = headers
+                .get_all(PRAGMA)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .any(|v| v.trim().eq_ignore_ascii_case("no-cache"));
+        }
+
+        cc
+    }
+}
+
+/// Whether a response to a `GET` request may be stored.
+pub(super) fn is_storable(request: &CacheControl, status: StatusCode, headers: &HeaderMap) -> bool {
+    let response = CacheControl::parse(headers);
+
+    if request.no_store || response.no_store {
+        return false;
+    }
+
+    // Partial content would need to be combined with other ranges.
+    if status.is_informational() || status == StatusCode::PARTIAL_CONTENT {
+        return false;
+    }
+
+    // `Vary: *` can never match a later request.
+    if vary_names(headers).any(|name| name.is_none()) {
+        return false;
+    }
+
+    response.public
+        || response.private
+        || response.max_age.is_some()
+        || headers.contains_key(EXPIRES)
+        || is_heuristically_cacheable(status)
+}
+
+/// The names of the request headers listed by `Vary`, with `None` for `*`.
+pub(super) fn vary_names(headers: &HeaderMap) -> impl Iterator<Item = Option<HeaderName>> + '_ {
+    headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| {
+            if name == "*" {
+                Some(None)
+            } else {
+                HeaderName::from_bytes(name.as_bytes()).ok().map(Some)
+            }
+        })
+}
+
+// https://www.rfc-editor.org/rfc/rfc9110.html#section-15.1-2
+fn is_heuristically_cacheable(status: StatusCode) -> bool {
+    matches!(
+        status.as_u16(),
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+impl Entry {
+    /// Whether the request headers named by `Vary` are the same as those of
+    /// the request that got this entry.
+    pub(super) fn matches(&self, request: &HeaderMap) -> bool {
+        vary_names(&self.headers).all(|name| match name {
+            Some(name) => request.get_all(&name).iter().eq(self.vary.get_all(&name)),
+            None => false,
+        })
+    }
+
+    /// How long this entry is fresh for, after it was generated by the
+    /// server.
+    ///
+    /// See https://www.rfc-editor.org/rfc/rfc9111.html#section-4.2.1
+    pub(super) fn freshness_lifetime(&self) -> Duration {
+        let cc = CacheControl::parse(&self.headers);
+        if let Some(max_age) = cc.max_age {
+            return max_age;
+        }
+
+        let date = self.date();
+        if self.headers.contains_key(EXPIRES) {
+            // An invalid Expires, such as "0", means already expired.
+            return http_date(&self.headers, EXPIRES)
+                .and_then(|expires| expires.duration_since(date).ok())
+                .unwrap_or_default();
+        }
+
+        // A heuristic of 10% of the time since the last modification.
+        if is_heuristically_cacheable(self.status) {
+            if let Some(last_modified) = http_date(&self.headers, LAST_MODIFIED) {
+                return date.duration_since(last_modified).unwrap_or_default() / 10;
+            }
+        }
+
+        Duration::ZERO
+    }
+
+    /// How old this entry is at `now`.
+    ///
+    /// See https://www.rfc-editor.org/rfc/rfc9111.html#section-4.2.3
+    pub(super) fn current_age(&self, now: SystemTime) -> Duration {
+        let age_value = self
+            .headers
+            .get(AGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| delta_seconds(v.trim()))
+            .unwrap_or_default();
+
+        let apparent_age = self
+            .response_time
+            .duration_since(self.date())
+            .unwrap_or_default();
+        let response_delay = self
+            .response_time
+            .duration_since(self.request_time)
+            .unwrap_or_default();
+        let corrected_initial_age = apparent_age.max(age_value.saturating_add(response_delay));
+        let resident_time = now.duration_since(self.response_time).unwrap_or_default();
+
+        corrected_initial_age.saturating_add(resident_time)
+    }
+
+    /// Whether this entry can be used at `now` without revalidating it,
+    /// considering the directives of the request.
+    pub(super) fn is_fresh(&self, request: &CacheControl, now: SystemTime) -> bool {
+        let response = CacheControl::parse(&self.headers);
+        if response.no_cache || request.no_cache {
+            return false;
+        }
+
+        let lifetime = self.freshness_lifetime();
+        let age = self.current_age(now);
+
+        if let Some(max_age) = request.max_age {
+            if age > max_age {
+                return false;
+            }
+        }
+
+        if let Some(min_fresh) = request.min_fresh {
+            return lifetime.saturating_sub(age) >= min_fresh;
+        }
+
+        if age < lifetime {
+            return true;
+        }
+
+        match request.max_stale {
+            Some(max_stale) if !response.must_revalidate => age - lifetime <= max_stale,
+            _ => false,
+        }
+    }
+
+    fn date(&self) -> SystemTime {
+        http_date(&self.headers, DATE).unwrap_or(self.response_time)
+    }
+}
+
+/// Parses a delta-seconds value, clamping it to 2^31 seconds.
+///
+/// See https://www.rfc-editor.org/rfc/rfc9111.html#section-1.2.2
+fn delta_seconds(value: &str) -> Option<Duration> {
+    const MAX: u64 = 1 << 31;
+
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let seconds = value.parse::<u64>().map_or(MAX, |seconds| seconds.min(MAX));
+    Some(Duration::from_secs(seconds))
+}
+
+fn http_date(headers: &HeaderMap, name: HeaderName) -> Option<SystemTime> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for &(name, value) in pairs {
+            headers.append(name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn entry(pairs: &[(&'static str, &'static str)], response_time: SystemTime) -> Entry {
+        Entry {
+            status: StatusCode::OK,
+            headers: headers(pairs),
+            vary: HeaderMap::new(),
+            body: Bytes::new(),
+            request_time: response_time,
+            response_time,
+        }
+    }
+
+    #[test]
+    fn parse_cache_control() {
+        let cc = CacheControl::parse(&headers(&[
+            ("cache-control", "Max-Age=60, no-cache"),
+            ("cache-control", "max-stale, min-fresh=\"5\""),
+        ]));
+
+        assert_eq!(
+            cc,
+            CacheControl {
+                no_cache: true,
+                max_age: Some(Duration::from_secs(60)),
+                max_stale: Some(Duration::MAX),
+                min_fresh: Some(Duration::from_secs(5)),
+                ..CacheControl::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_pragma_only_without_cache_control() {
+        assert!(CacheControl::parse(&headers(&[("pragma", "no-cache")])).no_cache);
+        assert!(
+            !CacheControl::parse(&headers(&[
+                ("pragma", "no-cache"),
+                ("cache-control", "public")
+            ]))
+            .no_cache
+        );
+    }
+
+    #[test]
+    fn storable() {
+        let req = CacheControl::default();
+        let ok = StatusCode::OK;
+
+        assert!(is_storable(&req, ok, &headers(&[])));
+        assert!(is_storable(&req, StatusCode::NOT_FOUND, &headers(&[])));
+        assert!(!is_storable(&req, StatusCode::CREATED, &headers(&[])));
+        assert!(is_storable(
+            &req,
+            StatusCode::CREATED,
+            &headers(&[("cache-control", "max-age=5")])
+        ));
+        assert!(!is_storable(
+            &req,
+            ok,
+            &headers(&[("cache-control", "no-store")])
+        ));
+        assert!(!is_storable(&req, ok, &headers(&[("vary", "accept, *")])));
+        assert!(!is_storable(
+            &req,
+            StatusCode::PARTIAL_CONTENT,
+            &headers(&[("cache-control", "max-age=5")])
+        ));
+
+        let no_store = CacheControl {
+            no_store: true,
+            ..CacheControl::default()
+        };
+        assert!(!is_storable(&no_store, ok, &headers(&[])));
+    }
+
+    #[test]
+    fn freshness_from_max_age_and_age() {
+        let now = SystemTime::now();
+        let e = entry(&[("cache-control", "max-age=60"), ("age", "50")], now);
+        let req = CacheControl::default();
+
+        assert_eq!(e.freshness_lifetime(), Duration::from_secs(60));
+        assert!(e.is_fresh(&req, now));
+        assert!(!e.is_fresh(&req, now + Duration::from_secs(11)));
+    }
+
+    #[test]
+    fn huge_age_is_clamped() {
+        let now = SystemTime::now();
+        let req = CacheControl::default();
+        for age in ["18446744073709551615", "99999999999999999999999"] {
+            let e = entry(&[("cache-control", "max-age=60"), ("age", age)], now);
+            assert_eq!(e.current_age(now), Duration::from_secs(1 << 31));
+            assert!(!e.is_fresh(&req, now));
+        }
+    }
+
+    #[test]
+    fn freshness_from_expires() {
+        let e = entry(
+            &[
+                ("date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                ("expires", "Sun, 06 Nov 1994 08:50:37 GMT"),
+            ],
+            SystemTime::now(),
+        );
+        assert_eq!(e.freshness_lifetime(), Duration::from_secs(60));
+
+        let e = entry(&[("expires", "0")], SystemTime::now());
+        assert_eq!(e.freshness_lifetime(), Duration::ZERO);
+    }
+
+    #[test]
+    fn freshness_heuristic() {
+        let e = entry(
+            &[
+                ("date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                ("last-modified", "Sun, 06 Nov 1994 08:33:57 GMT"),
+            ],
+            SystemTime::now(),
+        );
+        assert_eq!(e.freshness_lifetime(), Duration::from_secs(94));
+    }
+
+    #[test]
+    fn request_directives_limit_freshness() {
+        let now = SystemTime::now();
+        let e = entry(&[("cache-control", "max-age=60")], now);
+        let later = now + Duration::from_secs(30);
+
+        let max_age = CacheControl {
+            max_age: Some(Duration::from_secs(10)),
+            ..CacheControl::default()
+        };
+        assert!(!e.is_fresh(&max_age, later));
+
+        let min_fresh = CacheControl {
+            min_fresh: Some(Duration::from_secs(40)),
+            ..CacheControl::default()
+        };
+        assert!(!e.is_fresh(&min_fresh, later));
+
+        let max_stale = CacheControl {
+            max_stale: Some(Duration::from_secs(60)),
+            ..CacheControl::default()
+        };
+        assert!(e.is_fresh(&max_stale, now + Duration::from_secs(100)));
+
+        let e = entry(&[("cache-control", "max-age=60, must-revalidate")], now);
+        assert!(!e.is_fresh(&max_stale, now + Duration::from_secs(100)));
+    }
+
+    #[test]
+    fn vary_matching() {
+        let mut e = entry(&[("vary", "Accept, accept-language")], SystemTime::now());
+        e.vary = headers(&[("accept", "text/html")]);
+
+        assert!(e.matches(&headers(&[("accept", "text/html")])));
+        assert!(!e.matches(&headers(&[("accept", "application/json")])));
+        assert!(!e.matches(&headers(&[
+            ("accept", "text/html"),
+            ("accept-language", "en")
+        ])));
+    }
+}
@@ -0,0 +1,330 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::SystemTime;
+
+use bytes::{Bytes, BytesMut};
+use http::header::{
+    HeaderName, HeaderValue, AGE, CONNECTION, CONTENT_ENCODING, CONTENT_LENGTH, ETAG,
+    IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, IF_UNMODIFIED_SINCE, LAST_MODIFIED,
+    RANGE, TRANSFER_ENCODING,
+};
+use http::{HeaderMap, Method, StatusCode};
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use log::{debug, trace};
+use tower::{Service, ServiceExt};
+use url::Url;
+
+use super::policy::{self, CacheControl};
+use super::{Cache, CacheStore, Entry, Mode};
+use crate::async_impl::client::BoxedClientService;
+use crate::config::{RequestCacheMode, RequestConfig};
+use crate::error::BoxError;
+use crate::response::ResponseUrl;
+use crate::{Body, Request, Response};
+
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, BoxError>> + Send>>;
+
+/// The service answering requests from a `Cache`, in front of the service
+/// that sends them to the server.
+#[derive(Clone)]
+pub(crate) struct CacheService {
+    cache: Cache,
+    inner: BoxedClientService,
+}
+
+impl CacheService {
+    pub(crate) fn new(cache: Cache, inner: BoxedClientService) -> CacheService {
+        CacheService { cache, inner }
+    }
+}
+
+impl Service<Request> for CacheService {
+    type Response = Response;
+    type Error = BoxError;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // take the service that was polled ready, leaving a clone behind
+        let clone = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(self.cache.clone().send(inner, req))
+    }
+}
+
+impl Cache {
+    async fn send(self, inner: BoxedClientService, mut req: Request) -> Result<Response, BoxError> {
+        let request_cc = CacheControl::parse(req.headers());
+        let mode = match RequestConfig::<RequestCacheMode>::get(req.extensions()) {
+            Some(mode) => *mode,
+            None => self.mode,
+        };
+        let mode = match mode {
+            Mode::Default if request_cc.only_if_cached => Mode::OnlyIfCached,
+            Mode::Default if request_cc.no_cache => Mode::NoCache,
+            mode => mode,
+        };
+
+        if mode == Mode::NoStore || request_cc.no_store {
+            return inner.oneshot(req).await;
+        }
+
+        if *req.method() != Method::GET {
+            return self.send_uncached(inner, req).await;
+        }
+
+        // Requests that are already conditional, or ask for a range, are
+        // passed through untouched.
+        if [IF_MATCH, IF_NONE_MATCH, IF_MODIFIED_SINCE, IF_UNMODIFIED_SINCE, IF_RANGE, RANGE]
+            .iter()
+            .any(|name| req.headers().contains_key(name))
+        {
+            return inner.oneshot(req).await;
+        }
+
+        let url = req.url().clone();
+        let key = cache_key(&url);
+
+        let stored = match mode {
+            Mode::Reload => None,
+            _ => self
+                .store
+                .get(&key)
+                .filter(|entry| entry.matches(req.headers())),
+        };
+
+        if let Some(ref entry) = stored {
+            let now = SystemTime::now();
+            let use_stored = match mode {
+                Mode::ForceCache | Mode::OnlyIfCached => true,
+                Mode::Default => entry.is_fresh(&request_cc, now),
+                _ => false,
+            };
+            if use_stored {
+                trace!("using cached response for '{url}'");
+                return Ok(entry.to_response(url, now));
+            }
+        } else if mode == Mode::OnlyIfCached {
+            debug!("no cached response for '{url}'");
+            return Ok(gateway_timeout(url));
+        }
+
+        if let Some(ref entry) = stored {
+            if let Some(etag) = entry.headers.get(ETAG) {
+                req.headers_mut().insert(IF_NONE_MATCH, etag.clone());
+            }
+            if let Some(last_modified) = entry.headers.get(LAST_MODIFIED) {
+                req.headers_mut()
+                    .insert(IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+
+        let request_headers = req.headers().clone();
+        let request_time = SystemTime::now();
+        let res = inner.oneshot(req).await?;
+        let response_time = SystemTime::now();
+
+        if let Some(mut entry) = stored {
+            if res.status() == StatusCode::NOT_MODIFIED {
+                debug!("cached response for '{url}' was not modified");
+                entry.update(res.headers(), request_time, response_time);
+                self.store.put(&key, entry.clone());
+                return Ok(entry.to_response(url, response_time));
+            }
+        }
+
+        if !policy::is_storable(&request_cc, res.status(), res.headers()) {
+            return Ok(res);
+        }
+
+        let mut vary = HeaderMap::new();
+        for name in policy::vary_names(res.headers()).flatten() {
+            for value in request_headers.get_all(&name) {
+                vary.append(name.clone(), value.clone());
+            }
+        }
+
+        let mut headers = res.headers().clone();
+        remove_hop_by_hop(&mut headers);
+
+        let entry = Entry {
+            status: res.status(),
+            headers,
+            vary,
+            body: Bytes::new(),
+            request_time,
+            response_time,
+        };
+
+        Ok(self.store_body(res, key, entry))
+    }
+
+    /// Send a request that can't be answered from the cache, invalidating
+    /// the stored response if the request may have changed it.
+    async fn send_uncached(
+        &self,
+        inner: BoxedClientService,
+        req: Request,
+    ) -> Result<Response, BoxError> {
+        let is_safe = matches!(
+            *req.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        );
+        let key = cache_key(req.url());
+
+        let res = inner.oneshot(req).await?;
+
+        if !is_safe && (res.status().is_success() || res.status().is_redirection()) {
+            trace!("invalidating cached response for '{key}'");
+            self.store.remove(&key);
+        }
+
+        Ok(res)
+    }
+
+    /// Wrap the body of `res` so that the complete body is stored with
+    /// `entry` once it has been read.
+    fn store_body(&self, res: Response, key: String, entry: Entry) -> Response {
+        let url = res.url().clone();
+        let (mut parts, body) = http::Response::<Body>::from(res).into_parts();
+        parts.extensions.insert(ResponseUrl(url));
+
+        let body = StoreBody {
+            inner: body,
+            buf: Some(BytesMut::new()),
+            max_size: self.max_entry_size,
+            pending: Some((self.store.clone(), key, entry)),
+        };
+
+        Response::from(http::Response::from_parts(parts, Body::wrap(body)))
+    }
+}
+
+impl Entry {
+    fn to_response(&self, url: Url, now: SystemTime) -> Response {
+        let mut res = http::Response::new(Body::from(self.body.clone()));
+        *res.status_mut() = self.status;
+        *res.headers_mut() = self.headers.clone();
+        res.headers_mut()
+            .insert(AGE, HeaderValue::from(self.current_age(now).as_secs()));
+        res.extensions_mut().insert(ResponseUrl(url));
+        Response::from(res)
+    }
+
+    /// Update this entry with the headers of a `304 Not Modified` response.
+    ///
+    /// See https://www.rfc-editor.org/rfc/rfc9111.html#section-3.2
+    fn update(&mut self, headers: &HeaderMap, request_time: SystemTime, response_time: SystemTime) {
+        let mut headers = headers.clone();
+        remove_hop_by_hop(&mut headers);
+        // the stored body is already decoded
+        headers.remove(CONTENT_ENCODING);
+        headers.remove(CONTENT_LENGTH);
+
+        let mut name = None;
+        for (next, value) in headers {
+            if next.is_some() {
+                name = next;
+                self.headers.remove(name.as_ref().unwrap());
+            }
+            self.headers.append(name.clone().unwrap(), value);
+        }
+
+        self.request_time = request_time;
+        self.response_time = response_time;
+    }
+}
+
+fn cache_key(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url.into()
+}
+
+fn remove_hop_by_hop(headers: &mut HeaderMap) {
+    let listed = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect::<Vec<_>>();
+    for name in listed {
+        headers.remove(name);
+    }
+
+    for name in [
+        "connection",
+        "keep-alive",
+        "proxy-connection",
+        "te",
+        "trailer",
+        "upgrade",
+    ] {
+        headers.remove(name);
+    }
+    headers.remove(TRANSFER_ENCODING);
+}
+
+fn gateway_timeout(url: Url) -> Response {
+    let mut res = http::Response::new(Body::empty());
+    *res.status_mut() = StatusCode::GATEWAY_TIMEOUT;
+    res.extensions_mut().insert(ResponseUrl(url));
+    Response::from(res)
+}
+
+/// A response body that stores the response once all of it has been read.
+struct StoreBody {
+    inner: Body,
+    buf: Option<BytesMut>,
+    max_size: usize,
+    pending: Option<(Arc<dyn CacheStore>, String, Entry)>,
+}
+
+impl HttpBody for StoreBody {
+    type Data = Bytes;
+    type Error = crate::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+            Some(Ok(frame)) => {
+                if let (Some(buf), Some(data)) = (this.buf.as_mut(), frame.data_ref()) {
+                    if buf.len() + data.len() > this.max_size {
+                        debug!("response is too large to be cached");
+                        this.buf = None;
+                    } else {
+                        buf.extend_from_slice(data);
+                    }
+                }
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Some(Err(err)) => {
+                this.buf = None;
+                Poll::Ready(Some(Err(err)))
+            }
+            None => {
+                if let (Some(buf), Some((store, key, mut entry))) =
+                    (this.buf.take(), this.pending.take())
+                {
+                    trace!("storing cached response for '{key}'");
+                    entry.body = buf.freeze();
+                    store.put(&key, entry);
+                }
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+use std::str;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::header::{HeaderName, HeaderValue};
+use http::{HeaderMap, StatusCode};
+
+/// Storage for the responses of an HTTP cache.
+///
+/// Entries are looked up by a key derived from the request URL. A store
+/// holds at most one `Entry` per key; storing an entry replaces any entry
+/// already stored for that key.
+///
+/// Methods are called from within the `Client`'s requests, so they should
+/// not block for long.
+pub trait CacheStore: Send + Sync {
+    /// Get the entry stored for `key`, if any.
+    fn get(&self, key: &str) -> Option<Entry>;
+    /// Store `entry` for `key`, replacing any previous entry.
+    fn put(&self, key: &str, entry: Entry);
+    /// Remove the entry stored for `key`, if any.
+    fn remove(&self, key: &str);
+}
+
+/// A response stored in a `CacheStore`.
+///
+/// Besides the response itself, an entry records when it was requested and
+/// received, and the request headers it varies on, which are needed to
+/// decide if it can be reused.
+///
+/// An entry can be turned into bytes with [`Entry::encode`], and back with
+/// [`Entry::decode`], for stores that persist entries outside of memory.
+#[derive(Clone)]
+pub struct Entry {
+    pub(super) status: StatusCode,
+    pub(super) headers: HeaderMap,
+    pub(super) vary: HeaderMap,
+    pub(super) body: Bytes,
+    pub(super) request_time: SystemTime,
+    pub(super) response_time: SystemTime,
+}
+
+const MAGIC: &str = "reqwest-cache-entry 1";
+
+impl Entry {
+    /// Get the status code of the stored response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the headers of the stored response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the body of the stored response.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Get the time at which the stored response was received.
+    pub fn response_time(&self) -> SystemTime {
+        self.response_time
+    }
+
+    /// Encode this entry into bytes that can be decoded with `Entry::decode`.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.body.len() + 512);
+        buf.extend_from_slice(MAGIC.as_bytes());
+        buf.push(b'\n');
+        buf.extend_from_slice(self.status.as_str().as_bytes());
+        buf.push(b'\n');
+        encode_time(&mut buf, self.request_time);
+        encode_time(&mut buf, self.response_time);
+        encode_headers(&mut buf, &self.headers);
+        encode_headers(&mut buf, &self.vary);
+        buf.extend_from_slice(&self.body);
+        buf
+    }
+
+    /// Decode an entry from bytes returned by `Entry::encode`.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the bytes are not a valid encoded entry.
+    pub fn decode(buf: &[u8]) -> crate::Result<Entry> {
+        let mut rest = buf;
+
+        if next_line(&mut rest)? != MAGIC.as_bytes() {
+            return Err(invalid("unknown format"));
+        }
+        let status = StatusCode::from_bytes(next_line(&mut rest)?).map_err(|_| invalid("status"))?;
+        let request_time = decode_time(next_line(&mut rest)?)?;
+        let response_time = decode_time(next_line(&mut rest)?)?;
+        let headers = decode_headers(&mut rest)?;
+        let vary = decode_headers(&mut rest)?;
+
+        Ok(Entry {
+            status,
+            headers,
+            vary,
+            body: Bytes::copy_from_slice(rest),
+            request_time,
+            response_time,
+        })
+    }
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Entry")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .field("response_time", &self.response_time)
+            .finish()
+    }
+}
+
+fn next_line<'a>(rest: &mut &'a [u8]) -> crate::Result<&'a [u8]> {
+    let buf: &'a [u8] = rest;
+    let end = buf
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or_else(|| invalid("unexpected end"))?;
+    *rest = &buf[end + 1..];
+    Ok(&buf[..end])
+}
+
+fn encode_time(buf: &mut Vec<u8>, time: SystemTime) {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let line = format!("{}.{:09}\n", since.as_secs(), since.subsec_nanos());
+    buf.extend_from_slice(line.as_bytes());
+}
+
+fn decode_time(line: &[u8]) -> crate::Result<SystemTime> {
+    let line = str::from_utf8(line).map_err(|_| invalid("time"))?;
+    let (secs, nanos) = line.split_once('.').ok_or_else(|| invalid("time"))?;
+    let secs = secs.parse().map_err(|_| invalid("time"))?;
+    let nanos = nanos.parse().map_err(|_| invalid("time"))?;
+    Ok(UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+// Header values can't contain newlines, so each header is written on its
+// own line, with an empty line after the last one.
+fn encode_headers(buf: &mut Vec<u8>, headers: &HeaderMap) {
+    for (name, value) in headers {
+        buf.extend_from_slice(name.as_str().as_bytes());
+        buf.extend_from_slice(b": ");
+        buf.extend_from_slice(value.as_bytes());
+        buf.push(b'\n');
+    }
+    buf.push(b'\n');
+}
+
+fn decode_headers(rest: &mut &[u8]) -> crate::Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    loop {
+        let line = next_line(rest)?;
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        let colon = line
+            .iter()
+            .position(|&b| b == b':')
+            .ok_or_else(|| invalid("header"))?;
+        let name = HeaderName::from_bytes(&line[..colon]).map_err(|_| invalid("header"))?;
+        let value = line[colon + 1..].strip_prefix(b" ").unwrap_or(&line[colon + 1..]);
+        let value = HeaderValue::from_bytes(value).map_err(|_| invalid("header"))?;
+        headers.append(name, value);
+    }
+}
+
+fn invalid(what: &'static str) -> crate::Error {
+    crate::error::decode(format!("invalid cache entry: {what}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_encode_round_trip() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cache-control", HeaderValue::from_static("max-age=60"));
+        headers.append("set-thing", HeaderValue::from_static("a"));
+        headers.append("set-thing", HeaderValue::from_static("b: c"));
+        let mut vary = HeaderMap::new();
+        vary.insert("accept", HeaderValue::from_static("text/html"));
+
+        let entry = Entry {
+            status: StatusCode::NOT_FOUND,
+            headers,
+            vary,
+            body: Bytes::from_static(b"line one\n\nline two"),
+            request_time: UNIX_EPOCH + Duration::new(1_700_000_000, 5),
+            response_time: UNIX_EPOCH + Duration::new(1_700_000_001, 123_456_789),
+        };
+
+        let decoded = Entry::decode(&entry.encode()).unwrap();
+
+        assert_eq!(decoded.status, entry.status);
+        assert_eq!(decoded.headers, entry.headers);
+        assert_eq!(decoded.vary, entry.vary);
+        assert_eq!(decoded.body, entry.body);
+        assert_eq!(decoded.request_time, entry.request_time);
+        assert_eq!(decoded.response_time, entry.response_time);
+    }
+
+    #[test]
+    fn entry_decode_rejects_garbage() {
+        assert!(Entry::decode(b"").is_err());
+        assert!(Entry::decode(b"not an entry\n200\n").is_err());
+        assert!(Entry::decode(b"reqwest-cache-entry 1\n200\n").is_err());
+    }
+}
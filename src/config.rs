@@ -108,3 +108,93 @@ pub(crate) struct RequestTimeout;
 impl RequestConfigValue for RequestTimeout {
     type Value = Duration;
 }
+
+#[derive(Clone, Copy)]
+pub(crate) struct RequestRetry;
+
+impl RequestConfigValue for RequestRetry {
+    type Value = crate::retry::Policy;
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct RequestCacheMode;
+
+impl RequestConfigValue for RequestCacheMode {
+    type Value = crate::cache::Mode;
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct RequestMaxResponseBodySize;
+
+impl RequestConfigValue for RequestMaxResponseBodySize {
+    type Value = u64;
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct RequestResumable;
+
+impl RequestConfigValue for RequestResumable {
+    type Value = bool;
+}
+
+/// Whether a request must be sent over a connection that only speaks
+/// HTTP/1.1, as an upgrade to another protocol is.
+#[cfg(feature = "websocket")]
+#[derive(Clone, Copy)]
+pub(crate) struct RequestHttp1Only;
+
+#[cfg(feature = "websocket")]
+impl RequestConfigValue for RequestHttp1Only {
+    type Value = bool;
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct RequestUploadProgress;
+
+impl RequestConfigValue for RequestUploadProgress {
+    type Value = crate::async_impl::progress::Progress;
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct RequestDownloadProgress;
+
+impl RequestConfigValue for RequestDownloadProgress {
+    type Value = crate::async_impl::progress::DownloadProgress;
+}
+
+#[cfg(any(
+    feature = "gzip",
+    feature = "brotli",
+    feature = "zstd",
+    feature = "deflate"
+))]
+#[derive(Clone, Copy)]
+pub(crate) struct RequestCompression;
+
+#[cfg(any(
+    feature = "gzip",
+    feature = "brotli",
+    feature = "zstd",
+    feature = "deflate"
+))]
+impl RequestConfigValue for RequestCompression {
+    type Value = crate::Encoding;
+}
+
+#[cfg(unix)]
+#[derive(Clone, Copy)]
+pub(crate) struct RequestUnixSocket;
+
+#[cfg(unix)]
+impl RequestConfigValue for RequestUnixSocket {
+    type Value = std::path::PathBuf;
+}
+
+#[cfg(feature = "digest-auth")]
+#[derive(Clone, Copy)]
+pub(crate) struct RequestDigestAuth;
+
+#[cfg(feature = "digest-auth")]
+impl RequestConfigValue for RequestDigestAuth {
+    type Value = crate::auth::digest::Credentials;
+}
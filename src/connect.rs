@@ -5,7 +5,7 @@ use http::uri::Scheme;
 use http::Uri;
 use hyper::rt::{Read, ReadBufCursor, Write};
 use hyper_util::client::legacy::connect::{Connected, Connection};
-#[cfg(any(feature = "socks", feature = "__tls"))]
+#[cfg(any(unix, feature = "socks", feature = "__tls"))]
 use hyper_util::rt::TokioIo;
 #[cfg(feature = "default-tls")]
 use native_tls_crate::{TlsConnector, TlsConnectorBuilder};
@@ -17,10 +17,13 @@ use tower_service::Service;
 use std::future::Future;
 use std::io::{self, IoSlice};
 use std::net::IpAddr;
+#[cfg(unix)]
+use std::path::Path;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "default-tls")]
 use self::native_tls_conn::NativeTlsConn;
@@ -28,6 +31,7 @@ use self::native_tls_conn::NativeTlsConn;
 use self::rustls_tls_conn::RustlsTlsConn;
 use crate::dns::DynResolver;
 use crate::error::{cast_to_internal_error, BoxError};
+use crate::event::{EventListener, ResponseTimings};
 use crate::proxy::{Intercepted, Matcher as ProxyMatcher};
 use sealed::{Conn, Unnameable};
 
@@ -67,11 +71,13 @@ pub(crate) type BoxedConnectorService = BoxCloneSyncService<Unnameable, Conn, Bo
 pub(crate) type BoxedConnectorLayer =
     BoxCloneSyncServiceLayer<BoxedConnectorService, Unnameable, Conn, BoxError>;
 
+#[derive(Clone)]
 pub(crate) struct ConnectorBuilder {
     inner: Inner,
     proxies: Arc<Vec<ProxyMatcher>>,
     verbose: verbose::Wrapper,
     timeout: Option<Duration>,
+    event_listener: Option<Arc<dyn EventListener>>,
     #[cfg(feature = "__tls")]
     nodelay: bool,
     #[cfg(feature = "__tls")]
@@ -80,6 +86,11 @@ pub(crate) struct ConnectorBuilder {
     user_agent: Option<HeaderValue>,
     #[cfg(feature = "socks")]
     resolver: Option<DynResolver>,
+    #[cfg(unix)]
+    unix_socket: Option<Arc<Path>>,
+    /// The TLS connector used by `set_http1_only`.
+    #[cfg(all(feature = "websocket", feature = "native-tls-alpn"))]
+    tls_http1: Option<TlsConnector>,
 }
 
 impl ConnectorBuilder {
@@ -90,6 +101,7 @@ where {
             inner: self.inner,
             proxies: self.proxies,
             verbose: self.verbose,
+            event_listener: self.event_listener,
             #[cfg(feature = "__tls")]
             nodelay: self.nodelay,
             #[cfg(feature = "__tls")]
@@ -99,6 +111,8 @@ where {
             simple_timeout: None,
             #[cfg(feature = "socks")]
             resolver: self.resolver.unwrap_or_else(DynResolver::gai),
+            #[cfg(unix)]
+            unix_socket: self.unix_socket,
         };
 
         if layers.is_empty() {
@@ -194,8 +208,13 @@ where {
             proxies,
             verbose: verbose::OFF,
             timeout: None,
+            event_listener: None,
             #[cfg(feature = "socks")]
             resolver: None,
+            #[cfg(unix)]
+            unix_socket: None,
+            #[cfg(all(feature = "websocket", feature = "native-tls-alpn"))]
+            tls_http1: None,
         }
     }
 
@@ -225,10 +244,10 @@ where {
     where
         T: Into<Option<IpAddr>>,
     {
-        let tls = tls.build().map_err(crate::error::builder)?;
-        Ok(Self::from_built_default_tls(
+        let built = tls.build().map_err(crate::error::builder)?;
+        let builder = Self::from_built_default_tls(
             http,
-            tls,
+            built,
             proxies,
             user_agent,
             local_addr,
@@ -247,7 +266,16 @@ where {
             interface,
             nodelay,
             tls_info,
-        ))
+        );
+        // upgrades need a connection on which HTTP/2 wasn't negotiated
+        #[cfg(all(feature = "websocket", feature = "native-tls-alpn"))]
+        let builder = {
+            let (mut tls, mut builder) = (tls, builder);
+            tls.request_alpns(&["http/1.1"]);
+            builder.tls_http1 = Some(tls.build().map_err(crate::error::builder)?);
+            builder
+        };
+        Ok(builder)
     }
 
     #[cfg(feature = "default-tls")]
@@ -303,8 +331,13 @@ where {
             tls_info,
             user_agent,
             timeout: None,
+            event_listener: None,
             #[cfg(feature = "socks")]
             resolver: None,
+            #[cfg(unix)]
+            unix_socket: None,
+            #[cfg(all(feature = "websocket", feature = "native-tls-alpn"))]
+            tls_http1: None,
         }
     }
 
@@ -374,8 +407,36 @@ where {
             tls_info,
             user_agent,
             timeout: None,
+            event_listener: None,
             #[cfg(feature = "socks")]
             resolver: None,
+            #[cfg(unix)]
+            unix_socket: None,
+            #[cfg(all(feature = "websocket", feature = "native-tls-alpn"))]
+            tls_http1: None,
+        }
+    }
+
+    /// Only offer HTTP/1.1 with ALPN, so that servers can't choose HTTP/2.
+    #[cfg(feature = "websocket")]
+    pub(crate) fn set_http1_only(&mut self) {
+        match &mut self.inner {
+            #[cfg(feature = "default-tls")]
+            Inner::DefaultTls(_http, _tls) => {
+                // without ALPN, HTTP/1.1 is the only choice anyway
+                #[cfg(feature = "native-tls-alpn")]
+                if let Some(tls) = self.tls_http1.clone() {
+                    *_tls = tls;
+                }
+            }
+            #[cfg(feature = "__rustls")]
+            Inner::RustlsTls { tls, .. } => {
+                let mut config = (**tls).clone();
+                config.alpn_protocols = vec![b"http/1.1".to_vec()];
+                *tls = Arc::new(config);
+            }
+            #[cfg(not(feature = "__tls"))]
+            Inner::Http(_) => (),
         }
     }
 
@@ -387,6 +448,10 @@ where {
         self.verbose.0 = enabled;
     }
 
+    pub(crate) fn set_event_listener(&mut self, listener: Option<Arc<dyn EventListener>>) {
+        self.event_listener = listener;
+    }
+
     pub(crate) fn set_keepalive(&mut self, dur: Option<Duration>) {
         match &mut self.inner {
             #[cfg(feature = "default-tls")]
@@ -424,6 +489,11 @@ where {
     pub(crate) fn set_socks_resolver(&mut self, resolver: DynResolver) {
         self.resolver = Some(resolver);
     }
+
+    #[cfg(unix)]
+    pub(crate) fn set_unix_socket(&mut self, path: Option<Arc<Path>>) {
+        self.unix_socket = path;
+    }
 }
 
 #[allow(missing_debug_implementations)]
@@ -437,6 +507,7 @@ pub(crate) struct ConnectorService {
     /// This lets us avoid an extra `Box::pin` indirection layer
     /// since `tokio::time::Timeout` is `Unpin`
     simple_timeout: Option<Duration>,
+    event_listener: Option<Arc<dyn EventListener>>,
     #[cfg(feature = "__tls")]
     nodelay: bool,
     #[cfg(feature = "__tls")]
@@ -445,6 +516,9 @@ pub(crate) struct ConnectorService {
     user_agent: Option<HeaderValue>,
     #[cfg(feature = "socks")]
     resolver: DynResolver,
+    /// Connect to this socket instead of the host of the URI.
+    #[cfg(unix)]
+    unix_socket: Option<Arc<Path>>,
 }
 
 #[derive(Clone)]
@@ -477,16 +551,25 @@ impl ConnectorService {
             Inner::DefaultTls(_http, tls) => {
                 if dst.scheme() == Some(&Scheme::HTTPS) {
                     let host = dst.host().ok_or("no host in url")?.to_string();
-                    let conn = socks::connect(proxy, dst, dns, &self.resolver).await?;
+                    let conn = self.socks_tunnel(proxy, &dst, dns).await?;
                     let conn = TokioIo::new(conn);
                     let conn = TokioIo::new(conn);
                     let tls_connector = tokio_native_tls::TlsConnector::from(tls.clone());
+                    if let Some(ref listener) = self.event_listener {
+                        listener.tls_handshake_start(&dst);
+                    }
+                    let started = Instant::now();
                     let io = tls_connector.connect(&host, conn).await?;
+                    ConnectTimings::handshaken(started);
+                    if let Some(ref listener) = self.event_listener {
+                        listener.tls_handshake_end(&dst);
+                    }
                     let io = TokioIo::new(io);
                     return Ok(Conn {
                         inner: self.verbose.wrap(NativeTlsConn { inner: io }),
                         is_proxy: false,
                         tls_info: self.tls_info,
+                        timings: ConnectTimings::default(),
                     });
                 }
             }
@@ -498,20 +581,29 @@ impl ConnectorService {
 
                     let tls = tls.clone();
                     let host = dst.host().ok_or("no host in url")?.to_string();
-                    let conn = socks::connect(proxy, dst, dns, &self.resolver).await?;
+                    let conn = self.socks_tunnel(proxy, &dst, dns).await?;
                     let conn = TokioIo::new(conn);
                     let conn = TokioIo::new(conn);
                     let server_name =
                         rustls_pki_types::ServerName::try_from(host.as_str().to_owned())
                             .map_err(|_| "Invalid Server Name")?;
+                    if let Some(ref listener) = self.event_listener {
+                        listener.tls_handshake_start(&dst);
+                    }
+                    let started = Instant::now();
                     let io = RustlsConnector::from(tls)
                         .connect(server_name, conn)
                         .await?;
+                    ConnectTimings::handshaken(started);
+                    if let Some(ref listener) = self.event_listener {
+                        listener.tls_handshake_end(&dst);
+                    }
                     let io = TokioIo::new(io);
                     return Ok(Conn {
                         inner: self.verbose.wrap(RustlsTlsConn { inner: io }),
                         is_proxy: false,
                         tls_info: false,
+                        timings: ConnectTimings::default(),
                     });
                 }
             }
@@ -519,24 +611,46 @@ impl ConnectorService {
             Inner::Http(_) => (),
         }
 
-        socks::connect(proxy, dst, dns, &self.resolver)
-            .await
-            .map(|tcp| Conn {
-                inner: self.verbose.wrap(TokioIo::new(tcp)),
-                is_proxy: false,
-                tls_info: false,
-            })
+        self.socks_tunnel(proxy, &dst, dns).await.map(|tcp| Conn {
+            inner: self.verbose.wrap(TokioIo::new(tcp)),
+            is_proxy: false,
+            tls_info: false,
+            timings: ConnectTimings::default(),
+        })
+    }
+
+    #[cfg(feature = "socks")]
+    async fn socks_tunnel(
+        &self,
+        proxy: Intercepted,
+        dst: &Uri,
+        dns: socks::DnsResolve,
+    ) -> Result<tokio::net::TcpStream, BoxError> {
+        let proxy_dst = proxy.uri().clone();
+        let started = Instant::now();
+        let tcp = socks::connect(proxy, dst.clone(), dns, &self.resolver).await?;
+        ConnectTimings::record(|timings| {
+            let dns = timings.dns.unwrap_or_default();
+            timings.proxy_tunnel = Some(started.elapsed().saturating_sub(dns));
+        });
+        if let Some(ref listener) = self.event_listener {
+            listener.proxy_tunnel(dst, &proxy_dst);
+        }
+        Ok(tcp)
     }
 
     async fn connect_with_maybe_proxy(self, dst: Uri, is_proxy: bool) -> Result<Conn, BoxError> {
         match self.inner {
             #[cfg(not(feature = "__tls"))]
             Inner::Http(mut http) => {
+                let started = Instant::now();
                 let io = http.call(dst).await?;
+                ConnectTimings::connected(started);
                 Ok(Conn {
                     inner: self.verbose.wrap(io),
                     is_proxy,
                     tls_info: false,
+                    timings: ConnectTimings::default(),
                 })
             }
             #[cfg(feature = "default-tls")]
@@ -550,11 +664,20 @@ impl ConnectorService {
                     http.set_nodelay(true);
                 }
 
+                let http = HandshakeStart {
+                    http,
+                    event_listener: self.event_listener.clone(),
+                };
                 let tls_connector = tokio_native_tls::TlsConnector::from(tls.clone());
                 let mut http = hyper_tls::HttpsConnector::from((http, tls_connector));
-                let io = http.call(dst).await?;
+                let started = Instant::now();
+                let io = http.call(dst.clone()).await?;
 
                 if let hyper_tls::MaybeHttpsStream::Https(stream) = io {
+                    ConnectTimings::secured(started);
+                    if let Some(ref listener) = self.event_listener {
+                        listener.tls_handshake_end(&dst);
+                    }
                     if !self.nodelay {
                         stream
                             .inner()
@@ -569,12 +692,14 @@ impl ConnectorService {
                         inner: self.verbose.wrap(NativeTlsConn { inner: stream }),
                         is_proxy,
                         tls_info: self.tls_info,
+                        timings: ConnectTimings::default(),
                     })
                 } else {
                     Ok(Conn {
                         inner: self.verbose.wrap(io),
                         is_proxy,
                         tls_info: false,
+                        timings: ConnectTimings::default(),
                     })
                 }
             }
@@ -589,10 +714,19 @@ impl ConnectorService {
                     http.set_nodelay(true);
                 }
 
+                let http = HandshakeStart {
+                    http,
+                    event_listener: self.event_listener.clone(),
+                };
                 let mut http = hyper_rustls::HttpsConnector::from((http, tls.clone()));
-                let io = http.call(dst).await?;
+                let started = Instant::now();
+                let io = http.call(dst.clone()).await?;
 
                 if let hyper_rustls::MaybeHttpsStream::Https(stream) = io {
+                    ConnectTimings::secured(started);
+                    if let Some(ref listener) = self.event_listener {
+                        listener.tls_handshake_end(&dst);
+                    }
                     if !self.nodelay {
                         let (io, _) = stream.inner().get_ref();
                         io.inner().inner().set_nodelay(false)?;
@@ -601,18 +735,89 @@ impl ConnectorService {
                         inner: self.verbose.wrap(RustlsTlsConn { inner: stream }),
                         is_proxy,
                         tls_info: self.tls_info,
+                        timings: ConnectTimings::default(),
                     })
                 } else {
                     Ok(Conn {
                         inner: self.verbose.wrap(io),
                         is_proxy,
                         tls_info: false,
+                        timings: ConnectTimings::default(),
                     })
                 }
             }
         }
     }
 
+    #[cfg(unix)]
+    async fn connect_unix(self, dst: Uri, path: Arc<Path>) -> Result<Conn, BoxError> {
+        let started = Instant::now();
+        let io = tokio::net::UnixStream::connect(&*path).await?;
+        ConnectTimings::connected(started);
+
+        if dst.scheme_str() == Some("https") {
+            return self.secure_unix(dst, io).await;
+        }
+
+        Ok(Conn {
+            inner: self.verbose.wrap(TokioIo::new(io)),
+            is_proxy: false,
+            tls_info: false,
+            timings: ConnectTimings::default(),
+        })
+    }
+
+    /// Run a TLS handshake with the host of `dst` over the Unix socket `io`.
+    #[cfg(all(unix, feature = "__tls"))]
+    async fn secure_unix(self, dst: Uri, io: tokio::net::UnixStream) -> Result<Conn, BoxError> {
+        let host = dst.host().ok_or("no host in url")?;
+        if let Some(ref listener) = self.event_listener {
+            listener.tls_handshake_start(&dst);
+        }
+        let started = Instant::now();
+        let inner = match self.inner {
+            #[cfg(feature = "default-tls")]
+            Inner::DefaultTls(_, ref tls) => {
+                let tls_connector = tokio_native_tls::TlsConnector::from(tls.clone());
+                let io = tls_connector.connect(host, io).await?;
+                self.verbose.wrap(NativeTlsConn {
+                    inner: TokioIo::new(io),
+                })
+            }
+            #[cfg(feature = "__rustls")]
+            Inner::RustlsTls { ref tls, .. } => {
+                use rustls_pki_types::ServerName;
+                use std::convert::TryFrom;
+                use tokio_rustls::TlsConnector as RustlsConnector;
+
+                let server_name =
+                    ServerName::try_from(host.to_owned()).map_err(|_| "Invalid Server Name")?;
+                let io = RustlsConnector::from(tls.clone())
+                    .connect(server_name, io)
+                    .await?;
+                self.verbose.wrap(RustlsTlsConn {
+                    inner: TokioIo::new(io),
+                })
+            }
+        };
+        ConnectTimings::handshaken(started);
+        if let Some(ref listener) = self.event_listener {
+            listener.tls_handshake_end(&dst);
+        }
+
+        Ok(Conn {
+            inner,
+            is_proxy: false,
+            tls_info: self.tls_info,
+            timings: ConnectTimings::default(),
+        })
+    }
+
+    #[cfg(all(unix, not(feature = "__tls")))]
+    async fn secure_unix(self, _dst: Uri, _io: tokio::net::UnixStream) -> Result<Conn, BoxError> {
+        Err("HTTPS over a Unix socket requires a TLS backend".into())
+    }
+
     async fn connect_via_proxy(self, dst: Uri, proxy: Intercepted) -> Result<Conn, BoxError> {
         log::debug!("proxy({proxy:?}) intercepts '{dst:?}'");
 
@@ -634,11 +839,22 @@ impl ConnectorService {
                 if dst.scheme() == Some(&Scheme::HTTPS) {
                     let host = dst.host().to_owned();
                     let port = dst.port().map(|p| p.as_u16()).unwrap_or(443);
-                    let http = http.clone();
+                    let http = HandshakeStart {
+                        http: http.clone(),
+                        event_listener: self.event_listener.clone(),
+                    };
                     let tls_connector = tokio_native_tls::TlsConnector::from(tls.clone());
                     let mut http = hyper_tls::HttpsConnector::from((http, tls_connector));
-                    let conn = http.call(proxy_dst).await?;
+                    let started = Instant::now();
+                    let conn = http.call(proxy_dst.clone()).await?;
+                    if let hyper_tls::MaybeHttpsStream::Https(_) = conn {
+                        ConnectTimings::secured(started);
+                        if let Some(ref listener) = self.event_listener {
+                            listener.tls_handshake_end(&proxy_dst);
+                        }
+                    }
                     log::trace!("tunneling HTTPS over proxy");
+                    let started = Instant::now();
                     let tunneled = tunnel(
                         conn,
                         host.ok_or("no host in url")?.to_string(),
@@ -647,16 +863,27 @@ impl ConnectorService {
                         auth,
                     )
                     .await?;
+                    ConnectTimings::tunneled(started);
+                    if let Some(ref listener) = self.event_listener {
+                        listener.proxy_tunnel(&dst, &proxy_dst);
+                        listener.tls_handshake_start(&dst);
+                    }
+                    let started = Instant::now();
                     let tls_connector = tokio_native_tls::TlsConnector::from(tls.clone());
                     let io = tls_connector
                         .connect(host.ok_or("no host in url")?, TokioIo::new(tunneled))
                         .await?;
+                    ConnectTimings::handshaken(started);
+                    if let Some(ref listener) = self.event_listener {
+                        listener.tls_handshake_end(&dst);
+                    }
                     return Ok(Conn {
                         inner: self.verbose.wrap(NativeTlsConn {
                             inner: TokioIo::new(io),
                         }),
                         is_proxy: false,
                         tls_info: false,
+                        timings: ConnectTimings::default(),
                     });
                 }
             }
@@ -673,18 +900,39 @@ impl ConnectorService {
 
                     let host = dst.host().ok_or("no host in url")?.to_string();
                     let port = dst.port().map(|r| r.as_u16()).unwrap_or(443);
-                    let http = http.clone();
+                    let http = HandshakeStart {
+                        http: http.clone(),
+                        event_listener: self.event_listener.clone(),
+                    };
                     let mut http = hyper_rustls::HttpsConnector::from((http, tls_proxy.clone()));
                     let tls = tls.clone();
-                    let conn = http.call(proxy_dst).await?;
+                    let started = Instant::now();
+                    let conn = http.call(proxy_dst.clone()).await?;
+                    if let hyper_rustls::MaybeHttpsStream::Https(_) = conn {
+                        ConnectTimings::secured(started);
+                        if let Some(ref listener) = self.event_listener {
+                            listener.tls_handshake_end(&proxy_dst);
+                        }
+                    }
                     log::trace!("tunneling HTTPS over proxy");
                     let maybe_server_name = ServerName::try_from(host.as_str().to_owned())
                         .map_err(|_| "Invalid Server Name");
+                    let started = Instant::now();
                     let tunneled = tunnel(conn, host, port, self.user_agent.clone(), auth).await?;
+                    ConnectTimings::tunneled(started);
                     let server_name = maybe_server_name?;
+                    if let Some(ref listener) = self.event_listener {
+                        listener.proxy_tunnel(&dst, &proxy_dst);
+                        listener.tls_handshake_start(&dst);
+                    }
+                    let started = Instant::now();
                     let io = RustlsConnector::from(tls)
                         .connect(server_name, TokioIo::new(tunneled))
                         .await?;
+                    ConnectTimings::handshaken(started);
+                    if let Some(ref listener) = self.event_listener {
+                        listener.tls_handshake_end(&dst);
+                    }
 
                     return Ok(Conn {
                         inner: self.verbose.wrap(RustlsTlsConn {
@@ -692,6 +940,7 @@ impl ConnectorService {
                         }),
                         is_proxy: false,
                         tls_info: false,
+                        timings: ConnectTimings::default(),
                     });
                 }
             }
@@ -718,18 +967,16 @@ where
     }
 }
 
-impl Service<Uri> for ConnectorService {
-    type Response = Conn;
-    type Error = BoxError;
-    type Future = Connecting;
-
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
-    }
+impl ConnectorService {
+    fn connect(&self, dst: Uri, timeout: Option<Duration>) -> Connecting {
+        #[cfg(unix)]
+        if let Some(ref path) = self.unix_socket {
+            return Box::pin(with_timeout(
+                self.clone().connect_unix(dst, path.clone()),
+                timeout,
+            ));
+        }
 
-    fn call(&mut self, dst: Uri) -> Self::Future {
-        log::debug!("starting new connection: {dst:?}");
-        let timeout = self.simple_timeout;
         for prox in self.proxies.iter() {
             if let Some(intercepted) = prox.intercept(&dst) {
                 return Box::pin(with_timeout(
@@ -746,6 +993,177 @@ impl Service<Uri> for ConnectorService {
     }
 }
 
+impl Service<Uri> for ConnectorService {
+    type Response = Conn;
+    type Error = BoxError;
+    type Future = Connecting;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        log::debug!("starting new connection: {dst:?}");
+        let timeout = self.simple_timeout;
+        let listener = match self.event_listener {
+            Some(ref listener) => listener.clone(),
+            None => return ConnectTimings::measure(self.connect(dst, timeout)),
+        };
+
+        listener.connect_start(&dst);
+        let connecting = ConnectTimings::measure(self.connect(dst.clone(), timeout));
+        Box::pin(async move {
+            let res = connecting.await;
+            match res {
+                Ok(_) => listener.connect_end(&dst),
+                Err(ref err) => listener.connect_failed(&dst, &**err),
+            }
+            res
+        })
+    }
+}
+
+/// The TCP connector given to a TLS connector, telling the listener when
+/// the handshake that follows starts.
+#[cfg(feature = "__tls")]
+#[derive(Clone)]
+struct HandshakeStart {
+    http: HttpConnector,
+    event_listener: Option<Arc<dyn EventListener>>,
+}
+
+#[cfg(feature = "__tls")]
+impl Service<Uri> for HandshakeStart {
+    type Response = <HttpConnector as Service<Uri>>::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let started = Instant::now();
+        let connecting = self.http.call(dst.clone());
+        let listener = self.event_listener.clone();
+        Box::pin(async move {
+            let io = connecting.await?;
+            ConnectTimings::connected(started);
+            if let Some(listener) = listener {
+                if dst.scheme() == Some(&Scheme::HTTPS) {
+                    listener.tls_handshake_start(&dst);
+                }
+            }
+            Ok(io)
+        })
+    }
+}
+
+tokio::task_local! {
+    /// The timings of the connection being made by the current task.
+    static TIMINGS: Arc<Mutex<ConnectTimings>>;
+}
+
+/// How long each step of making a connection took.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ConnectTimings {
+    dns: Option<Duration>,
+    connect: Option<Duration>,
+    tls_handshake: Option<Duration>,
+    proxy_tunnel: Option<Duration>,
+}
+
+impl ConnectTimings {
+    /// Make the connection of `connecting`, recording its timings into the
+    /// `Conn`.
+    fn measure(connecting: Connecting) -> Connecting {
+        let timings = Arc::new(Mutex::new(ConnectTimings::default()));
+        let connecting = TIMINGS.scope(timings.clone(), connecting);
+        Box::pin(async move {
+            let mut conn = connecting.await?;
+            conn.timings = *timings.lock().unwrap();
+            Ok(conn)
+        })
+    }
+
+    /// Record a step of the connection being made by the current task, if
+    /// any.
+    fn record(step: impl FnOnce(&mut ConnectTimings)) {
+        let _ = TIMINGS.try_with(|timings| step(&mut timings.lock().unwrap()));
+    }
+
+    /// Record that name resolution started at `started` just finished.
+    pub(crate) fn resolved(started: Instant) {
+        Self::record(|timings| timings.dns = Some(started.elapsed()));
+    }
+
+    /// Record that a TCP connection was just established, `started` being
+    /// when resolving its host started.
+    fn connected(started: Instant) {
+        Self::record(|timings| {
+            let dns = timings.dns.unwrap_or_default();
+            timings.connect = Some(started.elapsed().saturating_sub(dns));
+        });
+    }
+
+    /// Record that a TLS connection was just established, `started` being
+    /// when resolving its host started.
+    #[cfg(feature = "__tls")]
+    fn secured(started: Instant) {
+        Self::record(|timings| {
+            let tcp = timings.dns.unwrap_or_default() + timings.connect.unwrap_or_default();
+            timings.tls_handshake = Some(started.elapsed().saturating_sub(tcp));
+        });
+    }
+
+    /// Record that a TLS handshake started at `started` just finished.
+    #[cfg(feature = "__tls")]
+    fn handshaken(started: Instant) {
+        Self::record(|timings| timings.tls_handshake = Some(started.elapsed()));
+    }
+
+    /// Record that a tunnel through a proxy was just opened, `started`
+    /// being when opening it started.
+    #[cfg(feature = "__tls")]
+    fn tunneled(started: Instant) {
+        Self::record(|timings| timings.proxy_tunnel = Some(started.elapsed()));
+    }
+}
+
+/// What a connection knows about itself, attached to it by
+/// `Conn::connected()`, which hyper calls once per connection.
+#[derive(Clone)]
+pub(crate) struct ConnectionInfo {
+    uses: Arc<AtomicUsize>,
+    timings: ConnectTimings,
+}
+
+impl ConnectionInfo {
+    /// Count a request that acquired the `connected` connection, returning
+    /// the timings known so far.
+    ///
+    /// The steps of making the connection are only counted for the first
+    /// request sent on it.
+    pub(crate) fn acquire(connected: &Connected) -> Option<ResponseTimings> {
+        let mut extensions = http::Extensions::new();
+        connected.get_extras(&mut extensions);
+        let info = extensions.get::<ConnectionInfo>()?;
+        if info.uses.fetch_add(1, Ordering::Relaxed) > 0 {
+            return Some(ResponseTimings {
+                reused: true,
+                ..ResponseTimings::default()
+            });
+        }
+        Some(ResponseTimings {
+            dns: info.timings.dns,
+            connect: info.timings.connect,
+            tls_handshake: info.timings.tls_handshake,
+            proxy_tunnel: info.timings.proxy_tunnel,
+            ..ResponseTimings::default()
+        })
+    }
+}
+
 #[cfg(feature = "__tls")]
 trait TlsInfoFactory {
     fn tls_info(&self) -> Option<crate::tls::TlsInfo>;
@@ -758,6 +1176,13 @@ impl TlsInfoFactory for tokio::net::TcpStream {
     }
 }
 
+#[cfg(all(unix, feature = "__tls"))]
+impl TlsInfoFactory for tokio::net::UnixStream {
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+        None
+    }
+}
+
 #[cfg(feature = "__tls")]
 impl<T: TlsInfoFactory> TlsInfoFactory for TokioIo<T> {
     fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
@@ -795,6 +1220,19 @@ impl TlsInfoFactory
     }
 }
 
+#[cfg(all(unix, feature = "default-tls"))]
+impl TlsInfoFactory for tokio_native_tls::TlsStream<tokio::net::UnixStream> {
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+        let peer_certificate = self
+            .get_ref()
+            .peer_certificate()
+            .ok()
+            .flatten()
+            .and_then(|c| c.to_der().ok());
+        Some(crate::tls::TlsInfo { peer_certificate })
+    }
+}
+
 #[cfg(feature = "default-tls")]
 impl TlsInfoFactory for hyper_tls::MaybeHttpsStream<TokioIo<tokio::net::TcpStream>> {
     fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
@@ -835,6 +1273,19 @@ impl TlsInfoFactory
     }
 }
 
+#[cfg(all(unix, feature = "__rustls"))]
+impl TlsInfoFactory for tokio_rustls::client::TlsStream<tokio::net::UnixStream> {
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+        let peer_certificate = self
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .map(|c| c.to_vec());
+        Some(crate::tls::TlsInfo { peer_certificate })
+    }
+}
+
 #[cfg(feature = "__rustls")]
 impl TlsInfoFactory for hyper_rustls::MaybeHttpsStream<TokioIo<tokio::net::TcpStream>> {
     fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
@@ -881,12 +1332,20 @@ pub(crate) mod sealed {
             pub(super) is_proxy: bool,
             // Only needed for __tls, but #[cfg()] on fields breaks pin_project!
             pub(super) tls_info: bool,
+            pub(super) timings: ConnectTimings,
         }
     }
 
     impl Connection for Conn {
         fn connected(&self) -> Connected {
-            let connected = self.inner.connected().proxy(self.is_proxy);
+            let connected = self
+                .inner
+                .connected()
+                .proxy(self.is_proxy)
+                .extra(ConnectionInfo {
+                    uses: Arc::default(),
+                    timings: self.timings,
+                });
             #[cfg(feature = "__tls")]
             if self.tls_info {
                 if let Some(tls_info) = self.inner.tls_info() {
@@ -1090,6 +1549,20 @@ mod native_tls_conn {
         }
     }
 
+    #[cfg(unix)]
+    impl Connection for NativeTlsConn<tokio::net::UnixStream> {
+        fn connected(&self) -> Connected {
+            let connected = self.inner.inner().get_ref().get_ref().get_ref().connected();
+            #[cfg(feature = "native-tls-alpn")]
+            match self.inner.inner().get_ref().negotiated_alpn().ok() {
+                Some(Some(alpn_protocol)) if alpn_protocol == b"h2" => connected.negotiated_h2(),
+                _ => connected,
+            }
+            #[cfg(not(feature = "native-tls-alpn"))]
+            connected
+        }
+    }
+
     impl<T: AsyncRead + AsyncWrite + Unpin> Read for NativeTlsConn<T> {
         fn poll_read(
             self: Pin<&mut Self>,
@@ -1205,6 +1678,17 @@ mod rustls_tls_conn {
         }
     }
 
+    #[cfg(unix)]
+    impl Connection for RustlsTlsConn<tokio::net::UnixStream> {
+        fn connected(&self) -> Connected {
+            if self.inner.inner().get_ref().1.alpn_protocol() == Some(b"h2") {
+                self.inner.inner().get_ref().0.connected().negotiated_h2()
+            } else {
+                self.inner.inner().get_ref().0.connected()
+            }
+        }
+    }
+
     impl<T: AsyncRead + AsyncWrite + Unpin> Read for RustlsTlsConn<T> {
         fn poll_read(
             self: Pin<&mut Self>,
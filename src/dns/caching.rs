@@ -0,0 +1,335 @@
+//! A resolver caching the addresses of names for a while.
+
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::OnceCell;
+
+#[cfg(feature = "hickory-dns")]
+use super::hickory::HickoryDnsResolver;
+use super::{gai::GaiResolver, Addrs, Name, Resolve, Resolving};
+use crate::error::BoxError;
+
+/// A DNS resolver caching the results of another one.
+///
+/// Resolved addresses are kept for a configurable time to live, or for the
+/// TTL of the DNS records when wrapping the hickory-dns resolver. Lookups of
+/// names that don't exist are kept for a shorter time, while other failures,
+/// such as timeouts, aren't cached. Concurrent lookups of the same name share
+/// a single lookup.
+///
+/// Negative caching requires the wrapped resolver to report a name that
+/// doesn't exist with an [`io::Error`](std::io::Error) of kind
+/// [`NotFound`](std::io::ErrorKind::NotFound). The errors of the system
+/// resolver and of hickory-dns for such names are recognized from their
+/// error codes, and any other error isn't cached.
+///
+/// Clones share the same cache, so one can be kept to invalidate entries
+/// after giving another to a client:
+///
+/// ```
+/// # #[cfg(all(feature = "__rustls", not(feature = "__rustls-ring")))]
+/// # let _ = rustls::crypto::ring::default_provider().install_default();
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use reqwest::dns::CachingResolver;
+///
+/// let resolver = CachingResolver::default().ttl(Duration::from_secs(300));
+/// let client = reqwest::Client::builder()
+///     .dns_resolver(Arc::new(resolver.clone()))
+///     .build()
+///     .unwrap();
+///
+/// // after a failover, look the name up again
+/// resolver.invalidate("db.internal");
+/// ```
+#[derive(Clone)]
+pub struct CachingResolver {
+    source: Source,
+    ttl: Duration,
+    negative_ttl: Duration,
+    entries: Arc<Mutex<HashMap<String, Arc<OnceCell<Lookup>>>>>,
+}
+
+/// The resolver whose results are cached.
+#[derive(Clone)]
+enum Source {
+    Resolve(Arc<dyn Resolve>),
+    #[cfg(feature = "hickory-dns")]
+    Hickory(HickoryDnsResolver),
+}
+
+/// The cached result of looking up a name.
+struct Lookup {
+    result: Result<Arc<[SocketAddr]>, Arc<dyn StdError + Send + Sync>>,
+    /// When the result expires, unless it never does.
+    expires: Option<Instant>,
+}
+
+impl Lookup {
+    fn is_valid(&self, now: Instant) -> bool {
+        self.expires.map_or(true, |expires| expires > now)
+    }
+}
+
+/// A lookup error, returned again while it is cached.
+#[derive(Debug)]
+struct CachedError(Arc<dyn StdError + Send + Sync>);
+
+impl CachingResolver {
+    /// Cache the results of `resolver`.
+    pub fn new<R: Resolve + 'static>(resolver: R) -> CachingResolver {
+        CachingResolver::with_source(Source::Resolve(Arc::new(resolver)))
+    }
+
+    /// Cache the results of the hickory-dns resolver, for as long as the
+    /// TTL of the DNS records.
+    ///
+    /// # Optional
+    ///
+    /// This requires the optional `hickory-dns` feature to be enabled.
+    #[cfg(feature = "hickory-dns")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "hickory-dns")))]
+    pub fn hickory() -> CachingResolver {
+        CachingResolver::with_source(Source::Hickory(HickoryDnsResolver::default()))
+    }
+
+    fn with_source(source: Source) -> CachingResolver {
+        CachingResolver {
+            source,
+            ttl: Duration::from_secs(60),
+            negative_ttl: Duration::from_secs(5),
+            entries: Arc::default(),
+        }
+    }
+
+    /// Set how long the addresses of a name are cached.
+    ///
+    /// It is not used with the hickory-dns resolver, which gives the TTL of
+    /// each record.
+    ///
+    /// Default is 60 seconds.
+    pub fn ttl(mut self, ttl: Duration) -> CachingResolver {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set how long the lookup of a name that doesn't exist is cached.
+    ///
+    /// Default is 5 seconds.
+    pub fn negative_ttl(mut self, ttl: Duration) -> CachingResolver {
+        self.negative_ttl = ttl;
+        self
+    }
+
+    /// Forget the cached result for `name`, so that it is looked up again.
+    pub fn invalidate(&self, name: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(&name.to_ascii_lowercase());
+    }
+
+    /// Forget all cached results.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Get the entry for `name`, replacing it if it expired.
+    fn entry(&self, name: &str) -> Arc<OnceCell<Lookup>> {
+        let now = Instant::now();
+        let key = name.to_ascii_lowercase();
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get(&key) {
+            // an entry without a lookup yet is still being looked up
+            if entry.get().map_or(true, |lookup| lookup.is_valid(now)) {
+                return entry.clone();
+            }
+        }
+        entries.retain(|_, entry| entry.get().map_or(true, |lookup| lookup.is_valid(now)));
+        let entry = Arc::new(OnceCell::new());
+        entries.insert(key, entry.clone());
+        entry
+    }
+}
+
+impl Default for CachingResolver {
+    /// Cache the results of the system resolver, `getaddrinfo`.
+    fn default() -> CachingResolver {
+        CachingResolver::new(GaiResolver::new())
+    }
+}
+
+impl Source {
+    /// Look up `name`, returning its addresses, and how long they are valid
+    /// for if the resolver knows.
+    async fn lookup(&self, name: Name) -> Result<(Vec<SocketAddr>, Option<Instant>), BoxError> {
+        match self {
+            Source::Resolve(resolver) => Ok((resolver.resolve(name).await?.collect(), None)),
+            #[cfg(feature = "hickory-dns")]
+            Source::Hickory(resolver) => {
+                let (addrs, valid_until) = resolver.lookup(name).await?;
+                Ok((addrs, Some(valid_until)))
+            }
+        }
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let entry = self.entry(name.as_str());
+        let source = self.source.clone();
+        let ttl = self.ttl;
+        let negative_ttl = self.negative_ttl;
+        Box::pin(async move {
+            // the cell stays empty after an uncached failure, so the next
+            // lookup tries again
+            let lookup = entry
+                .get_or_try_init(|| async move {
+                    match source.lookup(name).await {
+                        Ok((addrs, valid_until)) => Ok(Lookup {
+                            result: Ok(addrs.into()),
+                            expires: valid_until.or_else(|| Instant::now().checked_add(ttl)),
+                        }),
+                        Err(err) if is_not_found(&*err) => Ok(Lookup {
+                            result: Err(Arc::from(err)),
+                            expires: Instant::now().checked_add(negative_ttl),
+                        }),
+                        Err(err) => Err(err),
+                    }
+                })
+                .await?;
+            match lookup.result {
+                Ok(ref addrs) => {
+                    let addrs = addrs.clone();
+                    Ok(Box::new((0..addrs.len()).map(move |i| addrs[i])) as Addrs)
+                }
+                Err(ref err) => Err(Box::new(CachedError(err.clone())) as BoxError),
+            }
+        })
+    }
+}
+
+/// Whether `err` says that a name doesn't exist, rather than that it
+/// couldn't be looked up.
+fn is_not_found(err: &(dyn StdError + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        #[cfg(feature = "hickory-dns")]
+        if let Some(err) = err.downcast_ref::<hickory_resolver::error::ResolveError>() {
+            use hickory_resolver::error::ResolveErrorKind;
+            use hickory_resolver::proto::op::ResponseCode;
+
+            return match err.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+                    matches!(
+                        *response_code,
+                        ResponseCode::NXDomain | ResponseCode::NoError
+                    )
+                }
+                _ => false,
+            };
+        }
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            return err.kind() == io::ErrorKind::NotFound || is_gai_not_found(err);
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Whether `err` is a `getaddrinfo` error for a name without addresses.
+#[cfg(windows)]
+fn is_gai_not_found(err: &io::Error) -> bool {
+    const WSAHOST_NOT_FOUND: i32 = 11001;
+    const WSANO_DATA: i32 = 11004;
+
+    matches!(err.raw_os_error(), Some(WSAHOST_NOT_FOUND | WSANO_DATA))
+}
+
+/// Whether `err` is a `getaddrinfo` error for a name without addresses.
+///
+/// The standard library only keeps the `gai_strerror` message of the error
+/// code, so it is compared with the messages of `EAI_NONAME` and
+/// `EAI_NODATA`, in the same locale.
+#[cfg(unix)]
+fn is_gai_not_found(err: &io::Error) -> bool {
+    use std::ffi::CStr;
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    const CODES: &[libc::c_int] = &[libc::EAI_NONAME, libc::EAI_NODATA];
+    #[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+    const CODES: &[libc::c_int] = &[libc::EAI_NONAME];
+
+    // `EAI_SYSTEM` errors are reported with their OS error instead
+    if err.raw_os_error().is_some() {
+        return false;
+    }
+    let message = err.to_string();
+    CODES.iter().any(|&code| {
+        // SAFETY: gai_strerror returns a static, nul-terminated string
+        let expected = unsafe { CStr::from_ptr(libc::gai_strerror(code)) };
+        message.ends_with(&*expected.to_string_lossy())
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_gai_not_found(_err: &io::Error) -> bool {
+    false
+}
+
+impl fmt::Debug for CachingResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachingResolver")
+            .field("ttl", &self.ttl)
+            .field("negative_ttl", &self.negative_ttl)
+            .field("entries", &self.entries.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl fmt::Display for CachedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl StdError for CachedError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.0.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_found_errors() {
+        #[cfg(unix)]
+        {
+            // how the standard library reports getaddrinfo errors
+            let gai = |code: libc::c_int| {
+                let message = unsafe { std::ffi::CStr::from_ptr(libc::gai_strerror(code)) };
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "failed to lookup address information: {}",
+                        message.to_string_lossy()
+                    ),
+                )
+            };
+            assert!(is_not_found(&gai(libc::EAI_NONAME)));
+            assert!(!is_not_found(&gai(libc::EAI_AGAIN)));
+        }
+
+        let custom: BoxError = Box::new(io::Error::from(io::ErrorKind::NotFound));
+        assert!(is_not_found(&*custom));
+        assert!(!is_not_found(&io::Error::from(io::ErrorKind::TimedOut)));
+    }
+}
@@ -0,0 +1,321 @@
+//! DNS resolution over HTTPS, as specified in [RFC 8484].
+//!
+//! [RFC 8484]: https://www.rfc-editor.org/rfc/rfc8484
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use http::header::{ACCEPT, CONTENT_TYPE};
+use log::debug;
+use url::Url;
+
+use super::{Addrs, Name, Resolve, Resolving};
+use crate::async_impl::Client;
+use crate::error::BoxError;
+use crate::IntoUrl;
+
+const DNS_MESSAGE: &str = "application/dns-message";
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const RCODE_NXDOMAIN: u8 = 3;
+
+/// A DNS resolver sending queries to a DNS-over-HTTPS server.
+///
+/// The `A` and `AAAA` records of a name are queried concurrently, as
+/// `application/dns-message` requests to the endpoint of the server. A
+/// name that doesn't exist, or has no addresses, fails with an
+/// [`io::Error`] of kind [`NotFound`](io::ErrorKind::NotFound), so that a
+/// [`CachingResolver`](super::CachingResolver) caches it as missing.
+///
+/// The host of the endpoint is resolved with the system resolver, unless
+/// bootstrap addresses are given for it:
+///
+/// ```
+/// # #[cfg(all(feature = "__rustls", not(feature = "__rustls-ring")))]
+/// # let _ = rustls::crypto::ring::default_provider().install_default();
+/// use std::sync::Arc;
+/// use reqwest::dns::DohResolver;
+///
+/// let resolver = DohResolver::builder("https://dns.example/dns-query")
+///     .bootstrap(&["192.0.2.53".parse().unwrap()])
+///     .build()
+///     .unwrap();
+/// let client = reqwest::Client::builder()
+///     .dns_resolver(Arc::new(resolver))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct DohResolver {
+    endpoint: Url,
+    post: bool,
+    client: Client,
+}
+
+/// A builder to configure a [`DohResolver`].
+#[must_use]
+pub struct DohResolverBuilder {
+    endpoint: crate::Result<Url>,
+    bootstrap: Vec<IpAddr>,
+    post: bool,
+}
+
+impl DohResolver {
+    /// Resolve names with the DNS-over-HTTPS server at `endpoint`, such as
+    /// `https://dns.example/dns-query`.
+    ///
+    /// This is the same as `DohResolver::builder(endpoint).build()`.
+    pub fn new<U: IntoUrl>(endpoint: U) -> crate::Result<DohResolver> {
+        DohResolver::builder(endpoint).build()
+    }
+
+    /// Create a `DohResolverBuilder`, to configure a resolver using the
+    /// server at `endpoint`.
+    pub fn builder<U: IntoUrl>(endpoint: U) -> DohResolverBuilder {
+        DohResolverBuilder {
+            endpoint: endpoint.into_url(),
+            bootstrap: Vec::new(),
+            post: false,
+        }
+    }
+
+    /// Query the records of `rtype` for `name`, returning their addresses.
+    async fn query(&self, name: &str, rtype: u16) -> Result<Vec<IpAddr>, BoxError> {
+        let message = encode_query(name, rtype)?;
+        let req = if self.post {
+            self.client
+                .post(self.endpoint.clone())
+                .header(CONTENT_TYPE, DNS_MESSAGE)
+                .body(message)
+        } else {
+            let mut url = self.endpoint.clone();
+            url.query_pairs_mut()
+                .append_pair("dns", &BASE64_URL_SAFE_NO_PAD.encode(message));
+            self.client.get(url)
+        };
+        let res = req
+            .header(ACCEPT, DNS_MESSAGE)
+            .send()
+            .await?
+            .error_for_status()?;
+        let body = res.bytes().await?;
+        decode_response(&body, rtype)
+            .map_err(|err| io::Error::new(err.kind(), format!("{err} for {name}")).into())
+    }
+}
+
+impl DohResolverBuilder {
+    /// Set the addresses of the host of the endpoint, so that it is not
+    /// looked up with the system resolver.
+    ///
+    /// It is not needed when the host of the endpoint is an IP address.
+    pub fn bootstrap(mut self, addrs: &[IpAddr]) -> DohResolverBuilder {
+        self.bootstrap = addrs.to_vec();
+        self
+    }
+
+    /// Send queries in the body of `POST` requests, instead of in the URL
+    /// of `GET` requests.
+    ///
+    /// `GET` requests are more likely to be cached by HTTP caches.
+    ///
+    /// Default is `false`.
+    pub fn post(mut self, enable: bool) -> DohResolverBuilder {
+        self.post = enable;
+        self
+    }
+
+    /// Build the resolver, and the client it sends queries with.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the endpoint is not a valid URL, or if the
+    /// client cannot be built.
+    pub fn build(self) -> crate::Result<DohResolver> {
+        let endpoint = self.endpoint?;
+        let mut builder = Client::builder();
+        if let (false, Some(host)) = (self.bootstrap.is_empty(), endpoint.host_str()) {
+            let addrs = self
+                .bootstrap
+                .iter()
+                .map(|ip| SocketAddr::new(*ip, 0))
+                .collect::<Vec<_>>();
+            builder = builder.resolve_to_addrs(host, &addrs);
+        }
+        Ok(DohResolver {
+            endpoint,
+            post: self.post,
+            client: builder.build()?,
+        })
+    }
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let name = name.as_str();
+            let (v4, v6) = futures_util::future::join(
+                resolver.query(name, TYPE_A),
+                resolver.query(name, TYPE_AAAA),
+            )
+            .await;
+            let addrs = match (v4, v6) {
+                (Ok(mut v4), Ok(v6)) => {
+                    v4.extend(v6);
+                    v4
+                }
+                (Ok(addrs), Err(err)) | (Err(err), Ok(addrs)) => {
+                    debug!("DNS query over HTTPS failed for one address family: {err}");
+                    addrs
+                }
+                (Err(err), Err(_)) => return Err(err),
+            };
+            if addrs.is_empty() {
+                let message = format!("no addresses found for {name}");
+                return Err(io::Error::new(io::ErrorKind::NotFound, message).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+impl fmt::Debug for DohResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DohResolver")
+            .field("endpoint", &self.endpoint.as_str())
+            .field("post", &self.post)
+            .finish()
+    }
+}
+
+impl fmt::Debug for DohResolverBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DohResolverBuilder")
+            .field("endpoint", &self.endpoint)
+            .field("bootstrap", &self.bootstrap)
+            .field("post", &self.post)
+            .finish()
+    }
+}
+
+/// Encode a recursive query of the records of `rtype` for `name`.
+///
+/// The ID is 0, so that `GET` requests for the same query can be cached.
+fn encode_query(name: &str, rtype: u16) -> Result<Vec<u8>, BoxError> {
+    let mut message = vec![
+        0, 0, // ID
+        1, 0, // flags: recursion desired
+        0, 1, // one question
+        0, 0, 0, 0, 0, 0, // no answer, authority or additional records
+    ];
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("invalid DNS name: {name}").into());
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&rtype.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(message)
+}
+
+/// Decode a response, returning the addresses of its records of `rtype`.
+///
+/// Records of other types, such as the `CNAME` records leading to the
+/// addresses, are skipped.
+///
+/// A `NXDOMAIN` response fails with an error of kind `NotFound`.
+fn decode_response(message: &[u8], rtype: u16) -> io::Result<Vec<IpAddr>> {
+    let mut reader = Reader { message, pos: 0 };
+    let header = reader.take(12)?;
+    if header[2] & 0x80 == 0 {
+        return Err(invalid_response("DNS message is not a response"));
+    }
+    match header[3] & 0x0f {
+        0 => (),
+        RCODE_NXDOMAIN => {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no such domain"));
+        }
+        _ => return Err(invalid_response("DNS server failed to answer")),
+    }
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    let answers = u16::from_be_bytes([header[6], header[7]]);
+
+    for _ in 0..questions {
+        reader.skip_name()?;
+        reader.take(4)?;
+    }
+
+    let mut addrs = Vec::new();
+    for _ in 0..answers {
+        reader.skip_name()?;
+        let record = reader.take(10)?;
+        let record_type = u16::from_be_bytes([record[0], record[1]]);
+        let class = u16::from_be_bytes([record[2], record[3]]);
+        let len = u16::from_be_bytes([record[8], record[9]]);
+        let data = reader.take(len.into())?;
+        if record_type != rtype || class != CLASS_IN {
+            continue;
+        }
+        let addr = match (rtype, data.len()) {
+            (TYPE_A, 4) => IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+            (TYPE_AAAA, 16) => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(data);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => return Err(invalid_response("invalid address in DNS response")),
+        };
+        addrs.push(addr);
+    }
+    Ok(addrs)
+}
+
+/// Reads the sections of a DNS message.
+struct Reader<'a> {
+    message: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .message
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid_response("truncated DNS response"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Skip a name, which ends with either an empty label or a pointer to
+    /// the rest of the name elsewhere in the message.
+    fn skip_name(&mut self) -> io::Result<()> {
+        loop {
+            let len = self.take(1)?[0];
+            match len {
+                0 => return Ok(()),
+                len if len & 0xc0 == 0xc0 => {
+                    self.take(1)?;
+                    return Ok(());
+                }
+                len => {
+                    self.take(len.into())?;
+                }
+            }
+        }
+    }
+}
+
+fn invalid_response(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
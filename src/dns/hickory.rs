@@ -9,8 +9,10 @@ use once_cell::sync::OnceCell;
 use std::fmt;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Instant;
 
 use super::{Addrs, Name, Resolve, Resolving};
+use crate::error::BoxError;
 
 /// Wrapper around an `AsyncResolver`, which implements the `Resolve` trait.
 #[derive(Debug, Default, Clone)]
@@ -28,6 +30,19 @@ struct SocketAddrs {
 #[derive(Debug)]
 struct HickoryDnsSystemConfError(ResolveError);
 
+impl HickoryDnsResolver {
+    /// Look up the addresses of `name`, and until when they are valid.
+    pub(crate) async fn lookup(&self, name: Name) -> Result<(Vec<SocketAddr>, Instant), BoxError> {
+        let resolver = self.state.get_or_try_init(new_resolver)?;
+        let lookup = resolver.lookup_ip(name.as_str()).await?;
+        let valid_until = lookup.valid_until();
+        let addrs = SocketAddrs {
+            iter: lookup.into_iter(),
+        };
+        Ok((addrs.collect(), valid_until))
+    }
+}
+
 impl Resolve for HickoryDnsResolver {
     fn resolve(&self, name: Name) -> Resolving {
         let resolver = self.clone();
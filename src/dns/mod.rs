@@ -1,8 +1,12 @@
 //! DNS resolution
 
-pub use resolve::{Addrs, Name, Resolve, Resolving};
+pub use caching::CachingResolver;
+pub use doh::{DohResolver, DohResolverBuilder};
+pub use resolve::{Addrs, IpFamily, Name, Resolve, Resolving};
 pub(crate) use resolve::{DnsResolverWithOverrides, DynResolver};
 
+pub(crate) mod caching;
+pub(crate) mod doh;
 pub(crate) mod gai;
 #[cfg(feature = "hickory-dns")]
 pub(crate) mod hickory;
@@ -8,8 +8,11 @@ use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Instant;
 
+use crate::connect::ConnectTimings;
 use crate::error::BoxError;
+use crate::event::EventListener;
 
 /// Alias for an `Iterator` trait object over `SocketAddr`.
 pub type Addrs = Box<dyn Iterator<Item = SocketAddr> + Send>;
@@ -54,14 +57,101 @@ impl FromStr for Name {
     }
 }
 
+/// The IP families a client connects with, and which it tries first.
+///
+/// Only resolved addresses are affected: hosts given as IP addresses in
+/// URLs are always connected to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IpFamily {
+    /// Use the addresses in the order the resolver returns them.
+    #[default]
+    Any,
+    /// Only connect to IPv4 addresses.
+    V4Only,
+    /// Only connect to IPv6 addresses.
+    V6Only,
+    /// Try IPv4 addresses first, falling back to IPv6.
+    PreferV4,
+    /// Try IPv6 addresses first, falling back to IPv4.
+    PreferV6,
+}
+
+impl IpFamily {
+    /// Filter and order the resolved `addrs` of `name`.
+    fn select(self, name: &HyperName, addrs: Addrs) -> Result<Addrs, BoxError> {
+        let mut addrs = match self {
+            IpFamily::Any => return Ok(addrs),
+            IpFamily::V4Only => addrs.filter(SocketAddr::is_ipv4).collect::<Vec<_>>(),
+            IpFamily::V6Only => addrs.filter(SocketAddr::is_ipv6).collect::<Vec<_>>(),
+            IpFamily::PreferV4 | IpFamily::PreferV6 => addrs.collect::<Vec<_>>(),
+        };
+        match self {
+            IpFamily::PreferV4 => addrs.sort_by_key(SocketAddr::is_ipv6),
+            IpFamily::PreferV6 => addrs.sort_by_key(SocketAddr::is_ipv4),
+            IpFamily::V4Only if addrs.is_empty() => {
+                return Err(format!("no IPv4 address found for {}", name.as_str()).into())
+            }
+            IpFamily::V6Only if addrs.is_empty() => {
+                return Err(format!("no IPv6 address found for {}", name.as_str()).into())
+            }
+            _ => (),
+        }
+        Ok(Box::new(addrs.into_iter()))
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct DynResolver {
     resolver: Arc<dyn Resolve>,
+    event_listener: Option<Arc<dyn EventListener>>,
+    ip_family: IpFamily,
 }
 
 impl DynResolver {
     pub(crate) fn new(resolver: Arc<dyn Resolve>) -> Self {
-        Self { resolver }
+        Self {
+            resolver,
+            event_listener: None,
+            ip_family: IpFamily::Any,
+        }
+    }
+
+    pub(crate) fn with_event_listener(mut self, listener: Option<Arc<dyn EventListener>>) -> Self {
+        self.event_listener = listener;
+        self
+    }
+
+    pub(crate) fn with_ip_family(mut self, ip_family: IpFamily) -> Self {
+        self.ip_family = ip_family;
+        self
+    }
+
+    fn resolve(&self, name: HyperName) -> Resolving {
+        let started = Instant::now();
+        let listener = match self.event_listener {
+            Some(ref listener) => {
+                listener.dns_start(&Name(name.clone()));
+                Some((listener.clone(), name.clone()))
+            }
+            None => None,
+        };
+
+        let ip_family = self.ip_family;
+        let resolving = self.resolver.resolve(Name(name.clone()));
+        Box::pin(async move {
+            let addrs = resolving.await?;
+            ConnectTimings::resolved(started);
+            let addrs = ip_family.select(&name, addrs)?;
+            match listener {
+                Some((listener, name)) => {
+                    let addrs = addrs.collect::<Vec<_>>();
+                    listener.dns_end(&Name(name), &addrs);
+                    Ok(Box::new(addrs.into_iter()) as Addrs)
+                }
+                None => Ok(addrs),
+            }
+        })
     }
 
     #[cfg(feature = "socks")]
@@ -89,7 +179,8 @@ impl DynResolver {
 
         let explicit_port = target.port().is_some();
 
-        let addrs = self.resolver.resolve(host.parse()?).await?;
+        let name: Name = host.parse()?;
+        let addrs = self.resolve(name.0).await?;
 
         Ok(addrs.map(move |mut addr| {
             if explicit_port || addr.port() == 0 {
@@ -110,7 +201,7 @@ impl Service<HyperName> for DynResolver {
     }
 
     fn call(&mut self, name: HyperName) -> Self::Future {
-        self.resolver.resolve(Name(name))
+        self.resolve(name)
     }
 }
 
@@ -0,0 +1,295 @@
+//! Downloading a resource to a file, in parallel segments.
+//!
+//! A [`Download`] asks the server for the size of the resource, and then
+//! fetches ranges of it over several connections of the pool at once.
+//! Each range is written at its offset of the file. The file is only put in
+//! place once all of it was received, so a failed download never leaves a
+//! partial file behind.
+//!
+//! Servers that don't support range requests get a single request instead.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), reqwest::Error> {
+//! let client = reqwest::Client::new();
+//! let size = client
+//!     .download("https://artifacts.example/release.tar.gz")
+//!     .segments(8)
+//!     .to_file("release.tar.gz")
+//!     .await?;
+//! println!("downloaded {size} bytes");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ffi::OsString;
+use std::fmt;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use futures_util::future;
+use http::header::{HeaderValue, ACCEPT_ENCODING, CONTENT_RANGE, IF_RANGE, RANGE};
+use http::StatusCode;
+use log::debug;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use crate::async_impl::resume::{check_range, validator};
+use crate::error::{self, SizeMismatch};
+use crate::{Client, Response, Url};
+
+/// The number of segments fetched at once, unless set with
+/// [`Download::segments`].
+const DEFAULT_SEGMENTS: usize = 4;
+
+/// A download of a resource to a file.
+///
+/// Created with [`Client::download`](crate::Client::download).
+#[must_use = "a download does nothing until written with `to_file`"]
+pub struct Download {
+    client: Client,
+    url: crate::Result<Url>,
+    segments: usize,
+}
+
+impl Download {
+    pub(crate) fn new(client: Client, url: crate::Result<Url>) -> Download {
+        Download {
+            client,
+            url,
+            segments: DEFAULT_SEGMENTS,
+        }
+    }
+
+    /// Sets how many ranges of the resource are fetched at once.
+    ///
+    /// Default is 4. A value of 0 is treated as 1.
+    pub fn segments(mut self, segments: usize) -> Download {
+        self.segments = segments.max(1);
+        self
+    }
+
+    /// Downloads the resource to the file at `path`, and returns its size.
+    ///
+    /// The resource is written to a `.part` file next to `path` first,
+    /// which is renamed to `path` once the whole resource was received. An
+    /// existing file at `path` is replaced.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if a request fails or gets an error status, if the
+    /// resource changes while it is downloaded, if fewer or more bytes are
+    /// received than the server announced, or if the file can't be written.
+    pub async fn to_file<P: AsRef<Path>>(self, path: P) -> crate::Result<u64> {
+        let path = path.as_ref();
+        let url = match self.url {
+            Ok(ref url) => url.clone(),
+            Err(err) => return Err(err),
+        };
+        let part = part_path(path);
+
+        let mut file = File::create(&part).await.map_err(error::body)?;
+        let written = match self.fetch(&url, &mut file, &part).await {
+            Ok(written) => written,
+            Err(err) => {
+                drop(file);
+                let _ = fs::remove_file(&part).await;
+                return Err(err.with_url(url));
+            }
+        };
+
+        let renamed = async {
+            file.sync_all().await?;
+            drop(file);
+            fs::rename(&part, path).await
+        };
+        if let Err(err) = renamed.await {
+            let _ = fs::remove_file(&part).await;
+            return Err(error::body(err).with_url(url));
+        }
+        Ok(written)
+    }
+
+    /// Fetches the resource into `file`, in segments if the server allows
+    /// it.
+    async fn fetch(&self, url: &Url, file: &mut File, part: &Path) -> crate::Result<u64> {
+        // a single byte tells if ranges are supported, and the full size
+        let probe = self.get(url, Some((0, 0)), None).send().await?;
+        if probe.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            // the resource is empty
+            let res = self.get(url, None, None).send().await?;
+            return write_body(res.error_for_status()?, file, None).await;
+        }
+        let probe = probe.error_for_status()?;
+        if probe.status() != StatusCode::PARTIAL_CONTENT {
+            debug!("'{url}' doesn't support range requests, downloading it at once");
+            let expected = probe.content_length();
+            return write_body(probe, file, expected).await;
+        }
+
+        let total = match probe
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|range| range.to_str().ok())
+            .and_then(complete_length)
+        {
+            Some(total) => total,
+            None => {
+                debug!("'{url}' didn't send its size, downloading it at once");
+                let res = self.get(url, None, None).send().await?;
+                let expected = res.content_length();
+                return write_body(res.error_for_status()?, file, expected).await;
+            }
+        };
+        let validator = validator(probe.headers());
+        drop(probe);
+
+        file.set_len(total).await.map_err(error::body)?;
+        future::try_join_all(
+            segments(total, self.segments)
+                .map(|(start, end)| self.segment(url, part, start, end, validator.as_ref())),
+        )
+        .await?;
+        Ok(total)
+    }
+
+    /// Fetches the bytes `start..=end` of the resource, and writes them at
+    /// the same offset of the file at `part`.
+    async fn segment(
+        &self,
+        url: &Url,
+        part: &Path,
+        start: u64,
+        end: u64,
+        validator: Option<&HeaderValue>,
+    ) -> crate::Result<()> {
+        let res = self.get(url, Some((start, end)), validator).send().await?;
+        check_range(res.status(), res.headers(), start, validator).map_err(error::body)?;
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(part)
+            .await
+            .map_err(error::body)?;
+        file.seek(SeekFrom::Start(start))
+            .await
+            .map_err(error::body)?;
+        write_body(res, &mut file, Some(end - start + 1)).await?;
+        Ok(())
+    }
+
+    fn get(
+        &self,
+        url: &Url,
+        range: Option<(u64, u64)>,
+        validator: Option<&HeaderValue>,
+    ) -> crate::RequestBuilder {
+        // ranges apply to the encoded resource, so it must not be decoded
+        let mut req = self
+            .client
+            .get(url.clone())
+            .header(ACCEPT_ENCODING, "identity");
+        if let Some((start, end)) = range {
+            req = req.header(RANGE, format!("bytes={start}-{end}"));
+        }
+        if let Some(validator) = validator {
+            req = req.header(IF_RANGE, validator.clone());
+        }
+        req
+    }
+}
+
+impl fmt::Debug for Download {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Download")
+            .field("url", &self.url)
+            .field("segments", &self.segments)
+            .finish()
+    }
+}
+
+/// Writes the body of `res` to `file`, checking that it has the `expected`
+/// size.
+async fn write_body<W>(mut res: Response, mut file: W, expected: Option<u64>) -> crate::Result<u64>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    let mut received = 0;
+    while let Some(chunk) = res.chunk().await? {
+        received += chunk.len() as u64;
+        if expected.map_or(false, |expected| received > expected) {
+            break;
+        }
+        file.write_all(&chunk).await.map_err(error::body)?;
+    }
+    file.flush().await.map_err(error::body)?;
+
+    match expected {
+        Some(expected) if received != expected => {
+            Err(error::body(SizeMismatch { expected, received }))
+        }
+        _ => Ok(received),
+    }
+}
+
+/// The inclusive byte ranges of `count` segments of `total` bytes.
+fn segments(total: u64, count: usize) -> impl Iterator<Item = (u64, u64)> {
+    let count = count as u64;
+    // rounded up, without overflowing for huge totals
+    let size = (total / count + u64::from(total % count != 0)).max(1);
+    (0..total)
+        .step_by(usize::try_from(size).unwrap_or(usize::MAX))
+        .map(move |start| (start, start.saturating_add(size).min(total) - 1))
+}
+
+/// The complete length of a `Content-Range` value, if it is known.
+fn complete_length(range: &str) -> Option<u64> {
+    let (_, length) = range.strip_prefix("bytes ")?.split_once('/')?;
+    length.trim().parse().ok()
+}
+
+/// The file the resource is written to until it is complete.
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = path
+        .file_name()
+        .map_or_else(OsString::new, |name| name.to_os_string());
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_into_segments() {
+        let ranges: Vec<_> = segments(10, 3).collect();
+        assert_eq!(ranges, [(0, 3), (4, 7), (8, 9)]);
+
+        let ranges: Vec<_> = segments(2, 4).collect();
+        assert_eq!(ranges, [(0, 0), (1, 1)]);
+
+        let ranges: Vec<_> = segments(5, 1).collect();
+        assert_eq!(ranges, [(0, 4)]);
+
+        let ranges: Vec<_> = segments(u64::MAX, 2).collect();
+        assert_eq!(
+            ranges,
+            [(0, u64::MAX / 2), (u64::MAX / 2 + 1, u64::MAX - 1)]
+        );
+    }
+
+    #[test]
+    fn content_range_complete_length() {
+        assert_eq!(complete_length("bytes 0-0/1234"), Some(1234));
+        assert_eq!(complete_length("bytes 0-0/*"), None);
+        assert_eq!(complete_length("items 0-0/3"), None);
+    }
+
+    #[test]
+    fn part_file_next_to_target() {
+        assert_eq!(
+            part_path(Path::new("out/release.tar.gz")),
+            Path::new("out/release.tar.gz.part")
+        );
+    }
+}
@@ -149,6 +149,18 @@ impl Error {
         matches!(self.inner.kind, Kind::Decode)
     }
 
+    /// Returns true if the error is from a response body larger than the
+    /// maximum set with `max_response_body_size`.
+    pub fn is_body_too_large(&self) -> bool {
+        self.find_source::<BodyTooLarge>().is_some()
+    }
+
+    /// Returns true if the error is from a response body exceeding the
+    /// maximum decompressed size or compression ratio.
+    pub fn is_decompression_limit(&self) -> bool {
+        self.find_source::<DecompressionLimit>().is_some()
+    }
+
     /// Returns the status code, if the error was generated from a response.
     pub fn status(&self) -> Option<StatusCode> {
         match self.inner.kind {
@@ -159,6 +171,19 @@ impl Error {
 
     // private
 
+    fn find_source<E: StdError + 'static>(&self) -> Option<&E> {
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if let Some(err) = err.downcast_ref::<E>() {
+                return Some(err);
+            }
+            source = err.source();
+        }
+
+        None
+    }
+
     #[allow(unused)]
     pub(crate) fn into_io(self) -> io::Error {
         io::Error::new(io::ErrorKind::Other, self)
@@ -347,6 +372,146 @@ impl fmt::Display for BadScheme {
 
 impl StdError for BadScheme {}
 
+#[derive(Debug)]
+pub(crate) struct BodyTooLarge(pub(crate) u64);
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "response body is larger than the limit of {} bytes",
+            self.0
+        )
+    }
+}
+
+impl StdError for BodyTooLarge {}
+
+#[derive(Debug)]
+pub(crate) enum DecompressionLimit {
+    Size(u64),
+    Ratio(u32),
+}
+
+impl fmt::Display for DecompressionLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecompressionLimit::Size(limit) => write!(
+                f,
+                "decompressed response body is larger than the limit of {limit} bytes"
+            ),
+            DecompressionLimit::Ratio(ratio) => write!(
+                f,
+                "response body decompresses to more than {ratio} times its size"
+            ),
+        }
+    }
+}
+
+impl StdError for DecompressionLimit {}
+
+#[derive(Debug)]
+pub(crate) enum RangeFailed {
+    Changed,
+    RangeIgnored,
+    RangeMismatch,
+    Status(StatusCode),
+}
+
+impl fmt::Display for RangeFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RangeFailed::Changed => f.write_str("resource changed between range requests"),
+            RangeFailed::RangeIgnored => f.write_str("server ignored the range request"),
+            RangeFailed::RangeMismatch => {
+                f.write_str("server sent another range than was requested")
+            }
+            RangeFailed::Status(status) => write!(f, "range request failed with status {status}"),
+        }
+    }
+}
+
+impl StdError for RangeFailed {}
+
+#[cfg(feature = "download")]
+#[derive(Debug)]
+pub(crate) struct SizeMismatch {
+    pub(crate) expected: u64,
+    pub(crate) received: u64,
+}
+
+#[cfg(feature = "download")]
+impl fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "received {} bytes, but expected {}",
+            self.received, self.expected
+        )
+    }
+}
+
+#[cfg(feature = "download")]
+impl StdError for SizeMismatch {}
+
+#[cfg(any(
+    feature = "gzip",
+    feature = "zstd",
+    feature = "brotli",
+    feature = "deflate"
+))]
+#[derive(Debug)]
+pub(crate) struct UnsupportedCoding(pub(crate) String);
+
+#[cfg(any(
+    feature = "gzip",
+    feature = "zstd",
+    feature = "brotli",
+    feature = "deflate"
+))]
+impl fmt::Display for UnsupportedCoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unsupported content coding `{}`", self.0)
+    }
+}
+
+#[cfg(any(
+    feature = "gzip",
+    feature = "zstd",
+    feature = "brotli",
+    feature = "deflate"
+))]
+impl StdError for UnsupportedCoding {}
+
+#[cfg(any(
+    feature = "gzip",
+    feature = "zstd",
+    feature = "brotli",
+    feature = "deflate"
+))]
+#[derive(Debug)]
+pub(crate) struct TooManyCodings(pub(crate) usize);
+
+#[cfg(any(
+    feature = "gzip",
+    feature = "zstd",
+    feature = "brotli",
+    feature = "deflate"
+))]
+impl fmt::Display for TooManyCodings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "too many content codings ({})", self.0)
+    }
+}
+
+#[cfg(any(
+    feature = "gzip",
+    feature = "zstd",
+    feature = "brotli",
+    feature = "deflate"
+))]
+impl StdError for TooManyCodings {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -404,4 +569,19 @@ mod tests {
         let nested = super::request(io);
         assert!(nested.is_timeout());
     }
+
+    #[test]
+    fn is_body_too_large() {
+        let err = super::body(super::BodyTooLarge(10));
+        assert!(err.is_body_too_large());
+        assert!(!err.is_decompression_limit());
+
+        let io = io::Error::new(io::ErrorKind::Other, err);
+        let nested = super::decode(io);
+        assert!(nested.is_body_too_large());
+
+        let err = super::decode(super::DecompressionLimit::Ratio(100));
+        assert!(err.is_decompression_limit());
+        assert!(!err.is_body_too_large());
+    }
 }
@@ -0,0 +1,342 @@
+//! Connection lifecycle events and timings
+//!
+//! An [`EventListener`] set with `ClientBuilder::event_listener()` is told
+//! about each step a request goes through on the network: resolving the
+//! host, connecting to it, the TLS handshake, acquiring a pooled
+//! connection, sending the request, and receiving the response.
+//!
+//! For a new connection, the events arrive in this order:
+//!
+//! - `connect_start`
+//! - `dns_start` and `dns_end`, unless the host is an IP address
+//! - `proxy_tunnel`, when tunneling through a proxy
+//! - `tls_handshake_start` and `tls_handshake_end`, for `https`
+//! - `connect_end`, or `connect_failed`
+//!
+//! followed, for every request sent on it, by:
+//!
+//! - `connection_acquired`
+//! - `request_headers_sent`
+//! - `response_headers_received`
+//! - `connection_released`, once the response body is read or dropped
+//!
+//! A request that is redirected or retried goes through these steps for
+//! every hop. Responses from the cache don't touch the network, and don't
+//! cause any of them.
+//!
+//! Responses from the network also carry the [`ResponseTimings`] of these
+//! steps in their extensions, whether or not a listener is set.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use std::sync::Arc;
+//! # use reqwest::event::EventListener;
+//! # use reqwest::StatusCode;
+//! # use reqwest::Url;
+//! struct LogStatus;
+//!
+//! impl EventListener for LogStatus {
+//!     fn response_headers_received(&self, url: &Url, status: StatusCode) {
+//!         println!("{url}: {status}");
+//!     }
+//! }
+//!
+//! let client = reqwest::Client::builder()
+//!     .event_listener(Arc::new(LogStatus))
+//!     .build();
+//! ```
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures_util::future::{self, Either};
+use http::{StatusCode, Uri};
+use http_body::{Body, Frame, SizeHint};
+use hyper_util::client::legacy::connect::CaptureConnection;
+use pin_project_lite::pin_project;
+use url::Url;
+
+use crate::connect::ConnectionInfo;
+use crate::dns::Name;
+
+/// Trait for observing the network activity of a `Client`.
+///
+/// Every method has an empty default implementation, so only the events
+/// of interest need to be implemented.
+///
+/// The methods are called from within the futures driving requests and
+/// connections, so they should return quickly.
+pub trait EventListener: Send + Sync {
+    /// Called before `name` is resolved.
+    fn dns_start(&self, name: &Name) {
+        let _ = name;
+    }
+
+    /// Called after `name` was resolved to `addrs`.
+    ///
+    /// A failed resolution is reported by `connect_failed()`.
+    fn dns_end(&self, name: &Name, addrs: &[SocketAddr]) {
+        let _ = (name, addrs);
+    }
+
+    /// Called before a new connection to `dst` is made.
+    fn connect_start(&self, dst: &Uri) {
+        let _ = dst;
+    }
+
+    /// Called after a tunnel to `dst` was opened through `proxy`, either
+    /// with an HTTP `CONNECT` request or a SOCKS handshake.
+    fn proxy_tunnel(&self, dst: &Uri, proxy: &Uri) {
+        let _ = (dst, proxy);
+    }
+
+    /// Called before the TLS handshake with `dst`.
+    ///
+    /// When an `https` proxy is used, this is also called for the
+    /// handshake with the proxy.
+    fn tls_handshake_start(&self, dst: &Uri) {
+        let _ = dst;
+    }
+
+    /// Called after the TLS handshake with `dst`.
+    fn tls_handshake_end(&self, dst: &Uri) {
+        let _ = dst;
+    }
+
+    /// Called once the connection to `dst` is ready to send requests.
+    fn connect_end(&self, dst: &Uri) {
+        let _ = dst;
+    }
+
+    /// Called when the connection to `dst` could not be made, including
+    /// when resolving its host failed or the connect timeout elapsed.
+    fn connect_failed(&self, dst: &Uri, error: &(dyn StdError + 'static)) {
+        let _ = (dst, error);
+    }
+
+    /// Called when a request to `url` got a connection, either a new one
+    /// or one `reused` from the pool.
+    fn connection_acquired(&self, url: &Url, reused: bool) {
+        let _ = (url, reused);
+    }
+
+    /// Called when the request to `url` was handed to its connection, which
+    /// writes the request headers right away.
+    fn request_headers_sent(&self, url: &Url) {
+        let _ = url;
+    }
+
+    /// Called when the response headers for a request to `url` arrived.
+    fn response_headers_received(&self, url: &Url, status: StatusCode) {
+        let _ = (url, status);
+    }
+
+    /// Called when the response body from `url` was read to its end, or
+    /// dropped, so the request no longer holds its connection.
+    fn connection_released(&self, url: &Url) {
+        let _ = url;
+    }
+
+    /// Called when a request failed with `error`, after any retries.
+    fn call_failed(&self, error: &crate::Error) {
+        let _ = error;
+    }
+}
+
+/// How long the steps of getting a response took.
+///
+/// Every response received from the network carries its timings in its
+/// extensions:
+///
+/// ```rust
+/// # async fn run() -> Result<(), reqwest::Error> {
+/// use reqwest::event::ResponseTimings;
+///
+/// let res = reqwest::get("https://hyper.rs").await?;
+/// if let Some(timings) = res.extensions().get::<ResponseTimings>() {
+///     println!("first byte after {:?}", timings.time_to_first_byte());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The steps of making a connection are only counted for the first request
+/// sent on it. When a request was redirected or retried, all timings but
+/// `total()` are about the last hop.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ResponseTimings {
+    pub(crate) dns: Option<Duration>,
+    pub(crate) connect: Option<Duration>,
+    pub(crate) tls_handshake: Option<Duration>,
+    pub(crate) proxy_tunnel: Option<Duration>,
+    pub(crate) time_to_first_byte: Duration,
+    pub(crate) total: Duration,
+    pub(crate) reused: bool,
+}
+
+impl ResponseTimings {
+    /// Get the time spent resolving the host.
+    ///
+    /// `None` if the connection was reused, or the host is an IP address.
+    pub fn dns(&self) -> Option<Duration> {
+        self.dns
+    }
+
+    /// Get the time spent establishing the TCP connection.
+    ///
+    /// `None` if the connection was reused, or went through a SOCKS proxy.
+    pub fn connect(&self) -> Option<Duration> {
+        self.connect
+    }
+
+    /// Get the time spent on the TLS handshake.
+    ///
+    /// `None` if the connection was reused, or isn't secured by TLS.
+    pub fn tls_handshake(&self) -> Option<Duration> {
+        self.tls_handshake
+    }
+
+    /// Get the time spent opening a tunnel through a proxy, with an HTTP
+    /// `CONNECT` request, or by connecting to a SOCKS proxy.
+    ///
+    /// `None` if the connection was reused, or not tunneled.
+    pub fn proxy_tunnel(&self) -> Option<Duration> {
+        self.proxy_tunnel
+    }
+
+    /// Get the time from the request being handed to its connection until
+    /// the response headers arrived.
+    pub fn time_to_first_byte(&self) -> Duration {
+        self.time_to_first_byte
+    }
+
+    /// Get the time from the request being sent until the response headers
+    /// arrived, including any redirects and retries.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Returns whether the request was sent on a connection from the pool.
+    pub fn connection_reused(&self) -> bool {
+        self.reused
+    }
+}
+
+impl ResponseTimings {
+    /// The timings of a response that didn't go through the connection pool,
+    /// of which only the time since `started` is known.
+    pub(crate) fn since(started: Instant) -> ResponseTimings {
+        let elapsed = started.elapsed();
+        ResponseTimings {
+            time_to_first_byte: elapsed,
+            total: elapsed,
+            ..ResponseTimings::default()
+        }
+    }
+}
+
+/// Drive the request future `in_flight`, telling `listener` when it got its
+/// connection, and return the timings of that connection along with the
+/// time to first byte.
+pub(crate) async fn acquire<F: Future>(
+    in_flight: F,
+    mut captured: CaptureConnection,
+    listener: Option<&dyn EventListener>,
+    url: &Url,
+) -> (F::Output, ResponseTimings) {
+    let started = Instant::now();
+    futures_util::pin_mut!(in_flight);
+    let done = {
+        let connected = captured.wait_for_connection_metadata();
+        futures_util::pin_mut!(connected);
+        match future::select(in_flight.as_mut(), connected).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        }
+    };
+
+    let acquired = Instant::now();
+    let timings = captured
+        .connection_metadata()
+        .as_ref()
+        .and_then(ConnectionInfo::acquire);
+    if let (Some(listener), Some(timings)) = (listener, &timings) {
+        listener.connection_acquired(url, timings.reused);
+        listener.request_headers_sent(url);
+    }
+
+    let output = match done {
+        Some(output) => output,
+        None => in_flight.await,
+    };
+    let mut timings = timings.unwrap_or_default();
+    timings.time_to_first_byte = acquired.elapsed();
+    timings.total = started.elapsed();
+    (output, timings)
+}
+
+pin_project! {
+    /// A response body that tells the listener when it is done with its
+    /// connection.
+    pub(crate) struct Release<B> {
+        #[pin]
+        body: B,
+        listener: Option<(Arc<dyn EventListener>, Url)>,
+    }
+
+    impl<B> PinnedDrop for Release<B> {
+        fn drop(this: Pin<&mut Self>) {
+            release(this.project().listener);
+        }
+    }
+}
+
+impl<B> Release<B> {
+    pub(crate) fn new(body: B, listener: Arc<dyn EventListener>, url: Url) -> Self {
+        Release {
+            body,
+            listener: Some((listener, url)),
+        }
+    }
+}
+
+fn release(listener: &mut Option<(Arc<dyn EventListener>, Url)>) {
+    if let Some((listener, url)) = listener.take() {
+        listener.connection_released(&url);
+    }
+}
+
+impl<B> Body for Release<B>
+where
+    B: Body<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = ready!(this.body.as_mut().poll_frame(cx));
+        if !matches!(frame, Some(Ok(_))) || this.body.is_end_stream() {
+            release(this.listener);
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
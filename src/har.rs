@@ -0,0 +1,613 @@
+//! HTTP Archive recording
+//!
+//! A [`HarRecorder`] set with `ClientBuilder::har_recorder()` keeps every
+//! request the client sends, and the response it got, as an entry of an
+//! [HAR 1.2][har] document. The document can be opened by the network panel
+//! of most browsers, and by other HAR viewers.
+//!
+//! Each hop of a request is an entry of its own, so a redirect is recorded
+//! as a response with its `redirectURL`, followed by the request to it.
+//! Responses served from the cache don't touch the network, and aren't
+//! recorded.
+//!
+//! Entries record:
+//!
+//! - the method, URL, query string, headers and cookies of the request,
+//! - its body, if it was given as bytes rather than a stream,
+//! - the status, headers and cookies of the response,
+//! - its body as it is read, after decompression,
+//! - the timings of the connection and of the exchange, as in
+//!   [`ResponseTimings`](crate::event::ResponseTimings).
+//!
+//! Bodies are kept up to [`HarRecorder::max_body_size`] bytes. Values of
+//! headers marked as sensitive, such as those set by
+//! `RequestBuilder::basic_auth()`, are recorded as `REDACTED`.
+//!
+//! Entries are kept in memory until the recorder is cleared, and only the
+//! last [`HarRecorder::max_entries`] of them are kept, so that a long-lived
+//! client doesn't grow without bound. Write them out and
+//! [`clear`](HarRecorder::clear) the recorder regularly to keep them all.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use std::sync::Arc;
+//! # use reqwest::har::HarRecorder;
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let recorder = Arc::new(HarRecorder::new());
+//! let client = reqwest::Client::builder()
+//!     .har_recorder(recorder.clone())
+//!     .build()?;
+//!
+//! client.get("https://hyper.rs").send().await?.text().await?;
+//!
+//! recorder.write_to_file("hyper.har")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [har]: http://www.softwareishard.com/blog/har-12-spec/
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, BufWriter, Write};
+use std::net::IpAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE};
+use http::{HeaderMap, Version};
+use http_body::{Body, Frame, SizeHint};
+use pin_project_lite::pin_project;
+use serde_json::{json, Map, Value};
+
+use crate::error::BoxError;
+use crate::event::ResponseTimings;
+use crate::{Request, Response};
+
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+/// Records the requests of a `Client` as an HTTP Archive.
+///
+/// The recorder is shared with the client it is set on, and can be read
+/// at any time, including while requests are in flight.
+pub struct HarRecorder {
+    entries: Mutex<VecDeque<Arc<Mutex<Entry>>>>,
+    max_body_size: usize,
+    max_entries: usize,
+}
+
+impl HarRecorder {
+    /// Create an empty recorder.
+    pub fn new() -> HarRecorder {
+        HarRecorder {
+            entries: Mutex::new(VecDeque::new()),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    /// Set how many bytes of each request and response body are kept.
+    ///
+    /// Longer bodies are cut off, and the entry says so in its `comment`.
+    /// The recorded `size` is still that of the whole body.
+    ///
+    /// Default is 1 MiB.
+    pub fn max_body_size(mut self, bytes: usize) -> HarRecorder {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// Set how many entries are kept.
+    ///
+    /// Once there are as many, the oldest entry is dropped for each new one.
+    ///
+    /// Default is 1000.
+    pub fn max_entries(mut self, entries: usize) -> HarRecorder {
+        self.max_entries = entries;
+        self
+    }
+
+    /// Returns the number of entries recorded so far.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Returns whether no entries have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget all entries recorded so far.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Get the HAR document of the entries recorded so far.
+    pub fn to_value(&self) -> Value {
+        let entries = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.lock().unwrap().to_value())
+            .collect::<Vec<_>>();
+        json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": "reqwest",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "pages": [],
+                "entries": entries,
+            }
+        })
+    }
+
+    /// Write the HAR document of the entries recorded so far to `writer`.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, &self.to_value())?;
+        Ok(())
+    }
+
+    /// Write the HAR document of the entries recorded so far to the file
+    /// at `path`, replacing it if it exists.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_to(&mut file)?;
+        file.flush()
+    }
+
+    /// Add an entry for `req`, which is about to be sent.
+    pub(crate) fn start(&self, req: &Request) -> Arc<Mutex<Entry>> {
+        let entry = Arc::new(Mutex::new(Entry {
+            started: SystemTime::now(),
+            sent: Instant::now(),
+            request: request(req, self.max_body_size),
+            response: None,
+            content: Content {
+                limit: self.max_body_size,
+                ..Content::default()
+            },
+            timings: None,
+            received: None,
+            receive: None,
+            server_ip: None,
+            error: None,
+        }));
+        let mut entries = self.entries.lock().unwrap();
+        while !entries.is_empty() && entries.len() >= self.max_entries {
+            entries.pop_front();
+        }
+        if self.max_entries > 0 {
+            entries.push_back(entry.clone());
+        }
+        entry
+    }
+}
+
+impl Default for HarRecorder {
+    fn default() -> HarRecorder {
+        HarRecorder::new()
+    }
+}
+
+impl fmt::Debug for HarRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HarRecorder")
+            .field("entries", &self.len())
+            .field("max_body_size", &self.max_body_size)
+            .field("max_entries", &self.max_entries)
+            .finish()
+    }
+}
+
+/// Drive the hop `in_flight`, recording its response, or error, to `entry`.
+pub(crate) async fn record<F>(entry: Arc<Mutex<Entry>>, in_flight: F) -> Result<Response, BoxError>
+where
+    F: Future<Output = Result<Response, BoxError>>,
+{
+    match in_flight.await {
+        Ok(res) => {
+            entry.lock().unwrap().response(&res);
+            Ok(res.wrap_body(move |body| RecordBody { body, entry }))
+        }
+        Err(err) => {
+            entry.lock().unwrap().error = Some(err.to_string());
+            Err(err)
+        }
+    }
+}
+
+/// A single request and response.
+pub(crate) struct Entry {
+    started: SystemTime,
+    sent: Instant,
+    request: Value,
+    response: Option<Value>,
+    content: Content,
+    timings: Option<ResponseTimings>,
+    received: Option<Instant>,
+    receive: Option<Duration>,
+    server_ip: Option<IpAddr>,
+    error: Option<String>,
+}
+
+/// The response body read so far.
+#[derive(Default)]
+struct Content {
+    bytes: Vec<u8>,
+    size: usize,
+    limit: usize,
+}
+
+impl Entry {
+    fn response(&mut self, res: &Response) {
+        let redirect_url = match res.headers().get(LOCATION) {
+            Some(location) if res.status().is_redirection() => location
+                .to_str()
+                .ok()
+                .and_then(|location| res.url().join(location).ok())
+                .map(String::from)
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        self.response = Some(json!({
+            "status": res.status().as_u16(),
+            "statusText": res.status().canonical_reason().unwrap_or(""),
+            "httpVersion": http_version(res.version()),
+            "cookies": response_cookies(res.headers()),
+            "headers": headers(res.headers()),
+            "redirectURL": redirect_url,
+            "headersSize": -1,
+            "bodySize": content_length(res.headers()),
+        }));
+        self.received = Some(Instant::now());
+        self.timings = res.extensions().get::<ResponseTimings>().copied();
+        self.server_ip = res.remote_addr().map(|addr| addr.ip());
+    }
+
+    /// The response body was read to its end.
+    fn finish(&mut self) {
+        if let (Some(received), None) = (self.received, self.receive) {
+            self.receive = Some(received.elapsed());
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        let timings = self.timings.unwrap_or_default();
+        let wait = timings.time_to_first_byte();
+        let receive = self.receive.unwrap_or_default();
+        let time = match self.response {
+            Some(_) => timings.total() + receive,
+            None => self.sent.elapsed(),
+        };
+
+        let mut entry = Map::new();
+        entry.insert("startedDateTime".into(), date_time(self.started).into());
+        entry.insert("time".into(), millis(time).into());
+        entry.insert("request".into(), self.request.clone());
+        entry.insert("response".into(), self.response_value());
+        entry.insert("cache".into(), json!({}));
+        entry.insert(
+            "timings".into(),
+            json!({
+                "blocked": -1,
+                "dns": optional_millis(timings.dns()),
+                "connect": optional_millis(
+                    timings.connect().map(|c| c + timings.tls_handshake().unwrap_or_default())
+                ),
+                "ssl": optional_millis(timings.tls_handshake()),
+                "send": 0,
+                "wait": millis(wait),
+                "receive": millis(receive),
+            }),
+        );
+        if let Some(ip) = self.server_ip {
+            entry.insert("serverIPAddress".into(), ip.to_string().into());
+        }
+        if let Some(ref error) = self.error {
+            entry.insert("_error".into(), error.clone().into());
+        }
+        Value::Object(entry)
+    }
+
+    fn response_value(&self) -> Value {
+        let mut response = match self.response {
+            Some(ref response) => response.clone(),
+            // browsers record failed requests with a status of 0
+            None => json!({
+                "status": 0,
+                "statusText": "",
+                "httpVersion": "",
+                "cookies": [],
+                "headers": [],
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": -1,
+            }),
+        };
+
+        let mime_type = response["headers"]
+            .as_array()
+            .and_then(|headers| {
+                headers
+                    .iter()
+                    .find(|h| h["name"].as_str() == Some(CONTENT_TYPE.as_str()))
+            })
+            .and_then(|h| h["value"].as_str())
+            .unwrap_or("")
+            .to_owned();
+        let mut content = body(&self.content.bytes, self.content.size);
+        content.insert("mimeType".into(), mime_type.into());
+        response["content"] = Value::Object(content);
+        if response["bodySize"] == -1 && self.receive.is_some() {
+            response["bodySize"] = self.content.size.into();
+        }
+        response
+    }
+}
+
+pin_project! {
+    /// A response body that records what is read from it.
+    struct RecordBody<B> {
+        #[pin]
+        body: B,
+        entry: Arc<Mutex<Entry>>,
+    }
+}
+
+impl<B> Body for RecordBody<B>
+where
+    B: Body<Data = Bytes, Error = crate::Error>,
+{
+    type Data = Bytes;
+    type Error = crate::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = ready!(this.body.as_mut().poll_frame(cx));
+        let mut entry = this.entry.lock().unwrap();
+        match frame {
+            Some(Ok(ref frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    let content = &mut entry.content;
+                    let keep = content.limit.saturating_sub(content.bytes.len());
+                    content
+                        .bytes
+                        .extend_from_slice(&data[..keep.min(data.len())]);
+                    content.size += data.len();
+                }
+                if this.body.is_end_stream() {
+                    entry.finish();
+                }
+            }
+            Some(Err(ref err)) => entry.error = Some(err.to_string()),
+            None => entry.finish(),
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+fn request(req: &Request, limit: usize) -> Value {
+    let query_string = req
+        .url()
+        .query_pairs()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect::<Vec<_>>();
+    let cookies = req
+        .headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect::<Vec<_>>();
+
+    let mut request = json!({
+        "method": req.method().as_str(),
+        "url": req.url().as_str(),
+        "httpVersion": http_version(req.version()),
+        "cookies": cookies,
+        "headers": headers(req.headers()),
+        "queryString": query_string,
+        "headersSize": -1,
+        "bodySize": 0,
+    });
+
+    if let Some(body) = req.body() {
+        let mime_type = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+        let mut post_data = match body.as_bytes() {
+            Some(bytes) => {
+                request["bodySize"] = bytes.len().into();
+                body_text(&bytes[..limit.min(bytes.len())], bytes.len())
+            }
+            None => {
+                request["bodySize"] = content_length(req.headers());
+                let mut post_data = Map::new();
+                post_data.insert("comment".into(), "streaming body not recorded".into());
+                post_data
+            }
+        };
+        post_data.insert("mimeType".into(), mime_type.into());
+        post_data.insert("params".into(), json!([]));
+        if let (Some(text), "application/x-www-form-urlencoded") =
+            (post_data.get("text").and_then(Value::as_str), mime_type)
+        {
+            let params = url::form_urlencoded::parse(text.as_bytes())
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect::<Vec<_>>();
+            post_data.insert("params".into(), params.into());
+        }
+        request["postData"] = Value::Object(post_data);
+    }
+
+    request
+}
+
+/// The `text` of a body of `size` bytes, of which `bytes` were kept, along
+/// with its `size`.
+fn body(bytes: &[u8], size: usize) -> Map<String, Value> {
+    let mut content = body_text(bytes, size);
+    content.insert("size".into(), size.into());
+    content
+}
+
+fn body_text(bytes: &[u8], size: usize) -> Map<String, Value> {
+    let mut content = Map::new();
+    match std::str::from_utf8(bytes) {
+        Ok(text) => {
+            content.insert("text".into(), text.into());
+        }
+        Err(_) => {
+            content.insert("text".into(), BASE64_STANDARD.encode(bytes).into());
+            content.insert("encoding".into(), "base64".into());
+        }
+    }
+    if bytes.len() < size {
+        content.insert(
+            "comment".into(),
+            format!("truncated to {} of {size} bytes", bytes.len()).into(),
+        );
+    }
+    content
+}
+
+fn headers(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if value.is_sensitive() {
+                "REDACTED".into()
+            } else {
+                String::from_utf8_lossy(value.as_bytes())
+            };
+            json!({ "name": name.as_str(), "value": value })
+        })
+        .collect()
+}
+
+fn response_cookies(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| {
+            let mut parts = value.split(';');
+            let (name, value) = parts.next()?.trim().split_once('=')?;
+            let mut cookie = json!({ "name": name, "value": value });
+            for attr in parts {
+                let (key, value) = match attr.trim().split_once('=') {
+                    Some((key, value)) => (key, Some(value)),
+                    None => (attr.trim(), None),
+                };
+                let field = match key.to_ascii_lowercase().as_str() {
+                    "path" => "path",
+                    "domain" => "domain",
+                    "expires" => "expires",
+                    "httponly" => "httpOnly",
+                    "secure" => "secure",
+                    _ => continue,
+                };
+                cookie[field] = match value {
+                    Some(value) => value.into(),
+                    None => true.into(),
+                };
+            }
+            Some(cookie)
+        })
+        .collect()
+}
+
+fn content_length(headers: &HeaderMap) -> Value {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map_or(Value::from(-1), Value::from)
+}
+
+fn http_version(version: Version) -> &'static str {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9",
+        Version::HTTP_10 => "HTTP/1.0",
+        Version::HTTP_11 => "HTTP/1.1",
+        Version::HTTP_2 => "HTTP/2",
+        Version::HTTP_3 => "HTTP/3",
+        _ => "",
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn optional_millis(duration: Option<Duration>) -> Value {
+    duration.map_or(Value::from(-1), |d| millis(d).into())
+}
+
+/// Format `time` in ISO 8601, such as `2009-07-24T19:20:30.450Z`.
+fn date_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // civil_from_days, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_time_is_iso_8601() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_248_463_230_450);
+        assert_eq!(date_time(time), "2009-07-24T19:20:30.450Z");
+        assert_eq!(date_time(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let leap = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!(date_time(leap), "2000-02-29T00:00:00.000Z");
+    }
+}
@@ -128,6 +128,19 @@
 //! maximum redirect chain of 10 hops. To customize this behavior, a
 //! [`redirect::Policy`][redirect] can be used with a `ClientBuilder`.
 //!
+//! ## Retries
+//!
+//! By default, a `Client` only retries requests the server refused before
+//! processing them. To also retry connection errors or overloaded responses,
+//! with backoff, a [`retry::Policy`][retry] can be used with a `ClientBuilder`
+//! or a `RequestBuilder`.
+//!
+//! ## Caching
+//!
+//! Responses are not cached by default. A `Client` can keep a private HTTP
+//! cache, revalidating stale responses as needed, by passing a
+//! [`cache::Cache`][cache] to a `ClientBuilder`.
+//!
 //! ## Cookies
 //!
 //! The automatic storing and sending of session cookies can be enabled with
@@ -200,6 +213,13 @@
 //! - **blocking**: Provides the [blocking][] client API.
 //! - **charset** *(enabled by default)*: Improved support for decoding text.
 //! - **cookies**: Provides cookie session support.
+//! - **digest-auth**: Provides HTTP Digest authentication.
+//! - **tracing**: Provides `tracing` spans for requests.
+//! - **har**: Provides recording of requests as an HTTP Archive.
+//! - **replay**: Provides recording and replaying of exchanges for tests.
+//! - **websocket**: Provides WebSocket connections.
+//! - **sse**: Provides parsing of server-sent events.
+//! - **download**: Provides parallel segmented downloads to a file.
 //! - **gzip**: Provides response body gzip decompression.
 //! - **brotli**: Provides response body brotli decompression.
 //! - **zstd**: Provides response body zstd decompression.
@@ -242,6 +262,8 @@
 //! [builder]: ./struct.RequestBuilder.html
 //! [serde]: http://serde.rs
 //! [redirect]: crate::redirect
+//! [retry]: crate::retry
+//! [cache]: crate::cache
 //! [Proxy]: ./struct.Proxy.html
 //! [cargo-features]: https://doc.rust-lang.org/stable/cargo/reference/manifest.html#the-features-section
 //! [sponsor]: https://seanmonstar.com/sponsor
@@ -357,22 +379,47 @@ if_hyper! {
     #[cfg(feature = "__tls")]
     // Re-exports, to be removed in a future release
     pub use tls::{Certificate, Identity};
+    #[cfg(any(
+        feature = "gzip",
+        feature = "brotli",
+        feature = "zstd",
+        feature = "deflate"
+    ))]
+    pub use self::async_impl::Encoding;
+    #[cfg(feature = "json")]
+    pub use self::async_impl::JsonLines;
     #[cfg(feature = "multipart")]
     pub use self::async_impl::multipart;
 
 
     mod async_impl;
+    pub mod auth;
     #[cfg(feature = "blocking")]
     pub mod blocking;
+    pub mod cache;
     mod connect;
     #[cfg(feature = "cookies")]
     pub mod cookie;
     pub mod dns;
+    #[cfg(feature = "download")]
+    pub mod download;
+    pub mod event;
+    #[cfg(feature = "har")]
+    pub mod har;
     mod proxy;
     pub mod redirect;
+    #[cfg(feature = "replay")]
+    pub mod replay;
+    pub mod retry;
+    #[cfg(feature = "sse")]
+    pub mod sse;
     #[cfg(feature = "__tls")]
     pub mod tls;
+    #[cfg(feature = "tracing")]
+    pub mod trace;
     mod util;
+    #[cfg(feature = "websocket")]
+    pub mod websocket;
 }
 
 if_wasm! {
@@ -0,0 +1,611 @@
+//! Record and replay of HTTP exchanges
+//!
+//! A [`Cassette`] set with `ClientBuilder::replay()` lets tests run against
+//! recorded responses instead of the network:
+//!
+//! - In record mode, every request is sent as usual, and the exchange is
+//!   added to the cassette once the response body has been read to its end.
+//!   The body still streams, as it is copied while it is read. Recorded
+//!   exchanges are kept in memory, and written to the cassette file by
+//!   [`Cassette::save`], or once the cassette and every `Client` using it
+//!   are dropped.
+//! - In replay mode, every request is answered by the first recorded
+//!   exchange that matches it and wasn't replayed yet. Nothing is sent, and
+//!   a request without a match fails.
+//!
+//! Requests match when their method and URL are the same, along with their
+//! body and any headers named by [`Cassette::match_header`].
+//!
+//! Cassettes are JSON files, and can be edited by hand. Response bodies are
+//! stored already decompressed. Header values that might be secret, such as
+//! `Authorization`, are recorded as `REDACTED`; more headers can be named
+//! with [`Cassette::redact_header`].
+//!
+//! Each hop of a request is an exchange of its own, so a redirect is
+//! recorded and replayed as the redirect response, followed by the request
+//! to its location.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use reqwest::replay::Cassette;
+//! # async fn run() -> Result<(), reqwest::Error> {
+//! let path = "tests/cassettes/rust-lang.json";
+//! let cassette = if std::env::var_os("RECORD").is_some() {
+//!     Cassette::record(path)
+//! } else {
+//!     Cassette::replay(path)?
+//! };
+//!
+//! let client = reqwest::Client::builder().replay(cassette).build()?;
+//! let body = client.get("https://www.rust-lang.org").send().await?.text().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use bytes::{Bytes, BytesMut};
+use futures_util::future;
+use http::header::{HeaderName, HeaderValue, AUTHORIZATION, PROXY_AUTHORIZATION};
+use http::{HeaderMap, Method, StatusCode, Version};
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use log::{debug, trace, warn};
+use serde_json::{json, Map, Value};
+use tower::{Service, ServiceExt};
+use url::Url;
+
+use crate::async_impl::client::BoxedClientService;
+use crate::error::BoxError;
+use crate::response::ResponseUrl;
+use crate::{Body, Request, Response};
+
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, BoxError>> + Send>>;
+
+/// A file of recorded HTTP exchanges, that a `Client` either records to or
+/// replays from.
+#[derive(Clone)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    match_body: bool,
+    match_headers: Vec<HeaderName>,
+    redact_headers: Vec<HeaderName>,
+    state: Arc<Mutex<State>>,
+}
+
+/// Whether a `Cassette` is being recorded or replayed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Mode {
+    /// Send requests to the server, and record the exchanges.
+    Record,
+    /// Answer requests from the recorded exchanges, without any network.
+    Replay,
+}
+
+struct State {
+    exchanges: Vec<Exchange>,
+    replayed: Vec<bool>,
+    /// The file to write exchanges recorded since the last save to.
+    unsaved: Option<PathBuf>,
+}
+
+/// A recorded request and its response.
+struct Exchange {
+    method: Method,
+    url: Url,
+    request_headers: HeaderMap,
+    request_body: Option<Bytes>,
+    status: StatusCode,
+    version: Version,
+    response_headers: HeaderMap,
+    response_body: Bytes,
+}
+
+impl Cassette {
+    /// Creates a cassette that records to the file at `path`.
+    ///
+    /// The file, and any missing parent directories, are created when the
+    /// cassette is saved. An existing file is replaced.
+    pub fn record<P: AsRef<Path>>(path: P) -> Cassette {
+        Cassette::new(path.as_ref(), Mode::Record, Vec::new())
+    }
+
+    /// Creates a cassette that replays the exchanges recorded in the file at
+    /// `path`.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the file can't be read, or isn't a cassette.
+    pub fn replay<P: AsRef<Path>>(path: P) -> crate::Result<Cassette> {
+        let path = path.as_ref();
+        let exchanges = fs::read(path)
+            .map_err(BoxError::from)
+            .and_then(|json| parse(&json))
+            .map_err(|err| crate::error::builder(InvalidCassette(path.to_owned(), err)))?;
+        Ok(Cassette::new(path, Mode::Replay, exchanges))
+    }
+
+    fn new(path: &Path, mode: Mode, exchanges: Vec<Exchange>) -> Cassette {
+        Cassette {
+            path: path.to_owned(),
+            mode,
+            match_body: true,
+            match_headers: Vec::new(),
+            redact_headers: vec![AUTHORIZATION, PROXY_AUTHORIZATION],
+            state: Arc::new(Mutex::new(State {
+                replayed: vec![false; exchanges.len()],
+                exchanges,
+                unsaved: None,
+            })),
+        }
+    }
+
+    /// Sets whether a request only matches an exchange with the same body.
+    ///
+    /// Requests with a streaming body match regardless of it.
+    ///
+    /// Default is `true`.
+    pub fn match_body(mut self, enable: bool) -> Cassette {
+        self.match_body = enable;
+        self
+    }
+
+    /// Adds a header that a request only matches an exchange with the same
+    /// values of.
+    ///
+    /// Redacted headers are recorded as `REDACTED`, and shouldn't be
+    /// matched on.
+    pub fn match_header(mut self, name: HeaderName) -> Cassette {
+        self.match_headers.push(name);
+        self
+    }
+
+    /// Adds a header whose values are recorded as `REDACTED`, in both
+    /// requests and responses.
+    ///
+    /// `Authorization`, `Proxy-Authorization` and values marked as
+    /// sensitive are always redacted.
+    pub fn redact_header(mut self, name: HeaderName) -> Cassette {
+        self.redact_headers.push(name);
+        self
+    }
+
+    /// Returns whether this cassette records or replays exchanges.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the number of exchanges recorded in this cassette.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().exchanges.len()
+    }
+
+    /// Returns whether no exchanges are recorded in this cassette.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of recorded exchanges that weren't replayed yet.
+    pub fn remaining(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.replayed.iter().filter(|replayed| !**replayed).count()
+    }
+
+    /// Writes the recorded exchanges to the cassette file.
+    ///
+    /// A recording cassette is also saved once it and every `Client` using
+    /// it are dropped, but any error is then only logged. Saving a replayed
+    /// cassette does nothing.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the file or its parent directories can't be
+    /// written.
+    pub fn save(&self) -> io::Result<()> {
+        if self.mode != Mode::Record {
+            return Ok(());
+        }
+        let json = {
+            let mut state = self.state.lock().unwrap();
+            state.unsaved = None;
+            to_json(&state.exchanges)?
+        };
+        write(&self.path, &json)
+    }
+
+    /// Send `req` with `inner`, and record the exchange once the response
+    /// body has been read to its end.
+    async fn record_exchange(
+        self,
+        inner: BoxedClientService,
+        req: Request,
+    ) -> Result<Response, BoxError> {
+        let method = req.method().clone();
+        let url = req.url().clone();
+        let request_headers = self.redact(req.headers());
+        let request_body = req
+            .body()
+            .and_then(Body::as_bytes)
+            .map(Bytes::copy_from_slice);
+
+        let res = inner.oneshot(req).await?;
+        let res_url = res.url().clone();
+        let (mut parts, body) = http::Response::<Body>::from(res).into_parts();
+
+        let exchange = Exchange {
+            method,
+            url,
+            request_headers,
+            request_body,
+            status: parts.status,
+            version: parts.version,
+            response_headers: self.redact(&parts.headers),
+            response_body: Bytes::new(),
+        };
+        let body = Recording {
+            inner: body,
+            received: BytesMut::new(),
+            exchange: Some(exchange),
+            cassette: self,
+        };
+
+        parts.extensions.insert(ResponseUrl(res_url));
+        Ok(Response::from(http::Response::from_parts(
+            parts,
+            Body::wrap(body),
+        )))
+    }
+
+    /// Answer `req` with the first matching exchange not replayed yet.
+    fn replay_exchange(&self, req: &Request) -> Result<Response, BoxError> {
+        let mut state = self.state.lock().unwrap();
+        let State {
+            ref exchanges,
+            ref mut replayed,
+            ..
+        } = *state;
+
+        let index = exchanges
+            .iter()
+            .zip(replayed.iter())
+            .position(|(exchange, replayed)| !replayed && self.matches(exchange, req));
+        let index = match index {
+            Some(index) => index,
+            None => {
+                debug!("no recorded exchange for {} '{}'", req.method(), req.url());
+                return Err(Unmatched(req.method().clone(), req.url().clone()).into());
+            }
+        };
+        replayed[index] = true;
+
+        trace!("replaying {} '{}'", req.method(), req.url());
+        let exchange = &exchanges[index];
+        let mut res = http::Response::new(Body::from(exchange.response_body.clone()));
+        *res.status_mut() = exchange.status;
+        *res.version_mut() = exchange.version;
+        *res.headers_mut() = exchange.response_headers.clone();
+        res.extensions_mut().insert(ResponseUrl(req.url().clone()));
+        Ok(Response::from(res))
+    }
+
+    fn matches(&self, exchange: &Exchange, req: &Request) -> bool {
+        if exchange.method != *req.method() || exchange.url != *req.url() {
+            return false;
+        }
+
+        if self.match_body {
+            let body = match req.body() {
+                Some(body) => body.as_bytes(),
+                None => Some(&[][..]),
+            };
+            let recorded = exchange.request_body.as_deref().unwrap_or_default();
+            if body.map_or(false, |body| body != recorded) {
+                return false;
+            }
+        }
+
+        self.match_headers.iter().all(|name| {
+            exchange
+                .request_headers
+                .get_all(name)
+                .iter()
+                .eq(req.headers().get_all(name).iter())
+        })
+    }
+
+    fn redact(&self, headers: &HeaderMap) -> HeaderMap {
+        let mut redacted = headers.clone();
+        for (name, value) in redacted.iter_mut() {
+            if value.is_sensitive() || self.redact_headers.contains(name) {
+                *value = HeaderValue::from_static("REDACTED");
+            }
+        }
+        redacted
+    }
+
+    /// Add `exchange` to the cassette, to be written to its file when it is
+    /// saved.
+    fn push(&self, exchange: Exchange) {
+        let mut state = self.state.lock().unwrap();
+        state.exchanges.push(exchange);
+        state.replayed.push(true);
+        state.unsaved = Some(self.path.clone());
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.unsaved {
+            let saved = to_json(&self.exchanges).and_then(|json| write(path, &json));
+            if let Err(err) = saved {
+                warn!("failed to save cassette {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+/// A response body that records its exchange once it has been read to its
+/// end, copying its chunks as they are read.
+struct Recording {
+    inner: Body,
+    received: BytesMut,
+    /// The exchange to record, until it is.
+    exchange: Option<Exchange>,
+    cassette: Cassette,
+}
+
+impl Recording {
+    fn record(&mut self) {
+        if let Some(mut exchange) = self.exchange.take() {
+            trace!("recording {} '{}'", exchange.method, exchange.url);
+            exchange.response_body = std::mem::take(&mut self.received).freeze();
+            self.cassette.push(exchange);
+        }
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        // a body known to be complete, such as the empty body of a
+        // redirect, is recorded even if it wasn't read to its end
+        let complete = self.inner.size_hint().exact() == Some(self.received.len() as u64);
+        if complete {
+            self.record();
+        }
+    }
+}
+
+impl HttpBody for Recording {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    this.received.extend_from_slice(data);
+                }
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Some(Err(err)) => {
+                // an incomplete response isn't recorded
+                this.exchange = None;
+                Poll::Ready(Some(Err(err.into())))
+            }
+            None => {
+                this.record();
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl fmt::Debug for Cassette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cassette")
+            .field("path", &self.path)
+            .field("mode", &self.mode)
+            .field("match_body", &self.match_body)
+            .field("match_headers", &self.match_headers)
+            .field("redact_headers", &self.redact_headers)
+            .finish()
+    }
+}
+
+impl Exchange {
+    fn to_json(&self) -> Value {
+        let mut request = Map::new();
+        request.insert("method".into(), self.method.as_str().into());
+        request.insert("url".into(), self.url.as_str().into());
+        request.insert("headers".into(), headers_to_json(&self.request_headers));
+        if let Some(ref body) = self.request_body {
+            body_to_json(&mut request, body);
+        }
+
+        let mut response = Map::new();
+        response.insert("status".into(), self.status.as_u16().into());
+        response.insert("version".into(), format!("{:?}", self.version).into());
+        response.insert("headers".into(), headers_to_json(&self.response_headers));
+        body_to_json(&mut response, &self.response_body);
+
+        json!({ "request": request, "response": response })
+    }
+
+    fn from_json(exchange: &Value) -> Result<Exchange, BoxError> {
+        let request = &exchange["request"];
+        let response = &exchange["response"];
+        Ok(Exchange {
+            method: str_field(request, "method")?.parse()?,
+            url: str_field(request, "url")?.parse()?,
+            request_headers: headers_from_json(&request["headers"])?,
+            request_body: body_from_json(request)?,
+            status: StatusCode::from_u16(
+                response["status"]
+                    .as_u64()
+                    .and_then(|status| u16::try_from(status).ok())
+                    .ok_or("missing \"status\"")?,
+            )?,
+            version: match response["version"].as_str() {
+                Some("HTTP/0.9") => Version::HTTP_09,
+                Some("HTTP/1.0") => Version::HTTP_10,
+                Some("HTTP/2.0") => Version::HTTP_2,
+                Some("HTTP/3.0") => Version::HTTP_3,
+                _ => Version::HTTP_11,
+            },
+            response_headers: headers_from_json(&response["headers"])?,
+            response_body: body_from_json(response)?.unwrap_or_default(),
+        })
+    }
+}
+
+fn to_json(exchanges: &[Exchange]) -> io::Result<Vec<u8>> {
+    let exchanges = exchanges.iter().map(Exchange::to_json).collect::<Vec<_>>();
+    Ok(serde_json::to_vec_pretty(
+        &json!({ "exchanges": exchanges }),
+    )?)
+}
+
+fn write(path: &Path, json: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, json)
+}
+
+fn parse(json: &[u8]) -> Result<Vec<Exchange>, BoxError> {
+    let cassette: Value = serde_json::from_slice(json)?;
+    cassette["exchanges"]
+        .as_array()
+        .ok_or("missing \"exchanges\"")?
+        .iter()
+        .map(Exchange::from_json)
+        .collect()
+}
+
+fn str_field<'a>(value: &'a Value, name: &str) -> Result<&'a str, BoxError> {
+    value[name]
+        .as_str()
+        .ok_or_else(|| format!("missing \"{name}\"").into())
+}
+
+fn headers_to_json(headers: &HeaderMap) -> Value {
+    headers
+        .iter()
+        .map(|(name, value)| json!([name.as_str(), String::from_utf8_lossy(value.as_bytes())]))
+        .collect()
+}
+
+fn headers_from_json(headers: &Value) -> Result<HeaderMap, BoxError> {
+    let mut map = HeaderMap::new();
+    for header in headers.as_array().ok_or("missing \"headers\"")? {
+        let name = header[0].as_str().ok_or("invalid header")?;
+        let value = header[1].as_str().ok_or("invalid header")?;
+        map.append(HeaderName::from_bytes(name.as_bytes())?, value.parse()?);
+    }
+    Ok(map)
+}
+
+/// Store `body` as `"body"` if it is text, or as `"body_base64"`.
+fn body_to_json(map: &mut Map<String, Value>, body: &[u8]) {
+    match std::str::from_utf8(body) {
+        Ok(text) => map.insert("body".into(), text.into()),
+        Err(_) => map.insert("body_base64".into(), BASE64_STANDARD.encode(body).into()),
+    };
+}
+
+fn body_from_json(value: &Value) -> Result<Option<Bytes>, BoxError> {
+    if let Some(text) = value["body"].as_str() {
+        return Ok(Some(Bytes::copy_from_slice(text.as_bytes())));
+    }
+    match value["body_base64"].as_str() {
+        Some(encoded) => Ok(Some(BASE64_STANDARD.decode(encoded)?.into())),
+        None => Ok(None),
+    }
+}
+
+/// The service answering requests from a `Cassette`, in front of the
+/// service that sends them to the server.
+#[derive(Clone)]
+pub(crate) struct ReplayService {
+    cassette: Cassette,
+    inner: BoxedClientService,
+}
+
+impl ReplayService {
+    pub(crate) fn new(cassette: Cassette, inner: BoxedClientService) -> ReplayService {
+        ReplayService { cassette, inner }
+    }
+}
+
+impl Service<Request> for ReplayService {
+    type Response = Response;
+    type Error = BoxError;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.cassette.mode {
+            Mode::Record => self.inner.poll_ready(cx),
+            Mode::Replay => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        match self.cassette.mode {
+            Mode::Record => {
+                // take the service that was polled ready, leaving a clone behind
+                let clone = self.inner.clone();
+                let inner = std::mem::replace(&mut self.inner, clone);
+                Box::pin(self.cassette.clone().record_exchange(inner, req))
+            }
+            Mode::Replay => Box::pin(future::ready(self.cassette.replay_exchange(&req))),
+        }
+    }
+}
+
+/// The error of a request that no recorded exchange matches.
+#[derive(Debug)]
+struct Unmatched(Method, Url);
+
+impl fmt::Display for Unmatched {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no recorded exchange matches {} {}", self.0, self.1)
+    }
+}
+
+impl StdError for Unmatched {}
+
+/// The error of a cassette file that couldn't be loaded.
+#[derive(Debug)]
+struct InvalidCassette(PathBuf, BoxError);
+
+impl fmt::Display for InvalidCassette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid cassette {}: {}", self.0.display(), self.1)
+    }
+}
+
+impl StdError for InvalidCassette {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&*self.1)
+    }
+}
@@ -0,0 +1,571 @@
+//! Retry Handling
+//!
+//! By default, a `Client` will only retry requests that the server
+//! explicitly refused before processing them (such as an HTTP/2 `GOAWAY` or
+//! `REFUSED_STREAM`), at most twice. To retry more kinds of failures, a
+//! `retry::Policy` can be used with a `ClientBuilder`, or with a single
+//! `RequestBuilder`.
+//!
+//! A request can only be retried if its body is reusable, meaning it was
+//! not created from a stream.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use reqwest::{retry, StatusCode};
+//! #
+//! # fn run() -> Result<(), reqwest::Error> {
+//! let policy = retry::Policy::limited(3)
+//!     .statuses([StatusCode::TOO_MANY_REQUESTS, StatusCode::SERVICE_UNAVAILABLE])
+//!     .backoff(Duration::from_millis(50), Duration::from_secs(5));
+//!
+//! let client = reqwest::Client::builder()
+//!     .retry(policy)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use http::header::RETRY_AFTER;
+use http::{HeaderMap, Method, StatusCode};
+use tower::retry::budget::{Budget as _, TpsBudget};
+
+/// A type that controls if and how failed requests are retried.
+///
+/// The default value only retries requests that were refused by the server
+/// before being processed, at most twice, and without waiting in between.
+///
+/// - `limited` can be used to also retry connection errors and overloaded
+///   responses, with exponential backoff.
+/// - `none` can be used to disable all retry behavior.
+#[derive(Clone)]
+pub struct Policy {
+    inner: Arc<PolicyInner>,
+}
+
+/// A budget limiting how many retries a `Policy` may perform, relative to
+/// the number of original requests.
+///
+/// A budget is shared by every request using the same `Policy`, so that a
+/// struggling server is not overwhelmed by a storm of retries.
+#[derive(Clone)]
+pub struct Budget {
+    inner: Arc<TpsBudget>,
+}
+
+#[derive(Clone)]
+struct PolicyInner {
+    max_retries: usize,
+    statuses: Vec<StatusCode>,
+    connect_errors: bool,
+    non_idempotent: bool,
+    backoff: Backoff,
+    retry_after: bool,
+    budget: Option<Budget>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Backoff {
+    base: Duration,
+    max: Duration,
+    jitter: bool,
+}
+
+impl Policy {
+    /// Create a `Policy` that retries a request up to `max` times.
+    ///
+    /// Besides requests refused by the server, this retries connection
+    /// errors, and `429 Too Many Requests` and `503 Service Unavailable`
+    /// responses to idempotent requests. Between attempts it waits with an
+    /// exponential, jittered backoff starting at 100 milliseconds and capped
+    /// at 10 seconds, or as long as the response's `Retry-After` header asks.
+    pub fn limited(max: usize) -> Self {
+        Self {
+            inner: Arc::new(PolicyInner {
+                max_retries: max,
+                statuses: vec![
+                    StatusCode::TOO_MANY_REQUESTS,
+                    StatusCode::SERVICE_UNAVAILABLE,
+                ],
+                connect_errors: true,
+                non_idempotent: false,
+                backoff: Backoff {
+                    base: Duration::from_millis(100),
+                    max: Duration::from_secs(10),
+                    jitter: true,
+                },
+                retry_after: true,
+                budget: None,
+            }),
+        }
+    }
+
+    /// Create a `Policy` that never retries a request.
+    pub fn none() -> Self {
+        Self {
+            inner: Arc::new(PolicyInner {
+                max_retries: 0,
+                ..PolicyInner::default()
+            }),
+        }
+    }
+
+    /// Set the maximum number of retries for a single request.
+    pub fn max_retries(mut self, max: usize) -> Self {
+        self.inner_mut().max_retries = max;
+        self
+    }
+
+    /// Set the response status codes that should be retried.
+    ///
+    /// This replaces any previously configured status codes. Passing an
+    /// empty list disables retrying based on the response status.
+    pub fn statuses<I>(mut self, statuses: I) -> Self
+    where
+        I: IntoIterator<Item = StatusCode>,
+    {
+        self.inner_mut().statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Set whether errors establishing a connection should be retried.
+    ///
+    /// Since nothing was sent to the server, these are retried for any
+    /// request method.
+    pub fn connect_errors(mut self, enable: bool) -> Self {
+        self.inner_mut().connect_errors = enable;
+        self
+    }
+
+    /// Set whether requests with a non-idempotent method, such as `POST`
+    /// or `PATCH`, may be retried after a retryable status code.
+    ///
+    /// Default is `false`, as the server may have already acted on the
+    /// request.
+    pub fn non_idempotent(mut self, enable: bool) -> Self {
+        self.inner_mut().non_idempotent = enable;
+        self
+    }
+
+    /// Set the exponential backoff between attempts.
+    ///
+    /// The first retry waits for `base`, and each subsequent retry waits
+    /// twice as long as the previous one, up to `max`.
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        let backoff = &mut self.inner_mut().backoff;
+        backoff.base = base;
+        backoff.max = max;
+        self
+    }
+
+    /// Set whether the backoff delay is randomized.
+    ///
+    /// With jitter enabled, each delay is randomly picked between half and
+    /// all of the computed backoff, which spreads out retries from many
+    /// clients failing at the same time.
+    pub fn jitter(mut self, enable: bool) -> Self {
+        self.inner_mut().backoff.jitter = enable;
+        self
+    }
+
+    /// Set whether the `Retry-After` response header is honored.
+    ///
+    /// If the server asks to wait longer than the maximum backoff delay, the
+    /// response is returned instead of retrying.
+    pub fn retry_after(mut self, enable: bool) -> Self {
+        self.inner_mut().retry_after = enable;
+        self
+    }
+
+    /// Limit retries with a shared `Budget`.
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.inner_mut().budget = Some(budget);
+        self
+    }
+
+    fn inner_mut(&mut self) -> &mut PolicyInner {
+        Arc::make_mut(&mut self.inner)
+    }
+
+    pub(crate) fn is_default(&self) -> bool {
+        let inner = &*self.inner;
+        inner.max_retries == 2
+            && inner.statuses.is_empty()
+            && !inner.connect_errors
+            && inner.backoff.base.is_zero()
+            && inner.budget.is_none()
+    }
+
+    /// Record that a new request, which isn't itself a retry, is starting.
+    pub(crate) fn deposit(&self) {
+        if let Some(ref budget) = self.inner.budget {
+            budget.inner.deposit();
+        }
+    }
+
+    /// Decide whether a failed attempt should be retried, and after how long.
+    ///
+    /// `retries` is the number of retries already performed for this request.
+    pub(crate) fn check(
+        &self,
+        method: &Method,
+        retries: usize,
+        failure: Failure<'_>,
+    ) -> Option<Duration> {
+        let inner = &*self.inner;
+
+        if retries >= inner.max_retries {
+            log::trace!("retry count too high");
+            return None;
+        }
+
+        let delay = match failure {
+            Failure::Refused => inner.backoff.delay(retries),
+            Failure::Connect if inner.connect_errors => inner.backoff.delay(retries),
+            Failure::Connect => return None,
+            Failure::Status(status, headers) => {
+                if !inner.statuses.contains(&status) {
+                    return None;
+                }
+                if !inner.non_idempotent && !is_idempotent(method) {
+                    log::debug!("status {status} is retryable, but {method} is not idempotent");
+                    return None;
+                }
+                match retry_after(headers).filter(|_| inner.retry_after) {
+                    Some(delay) if delay > inner.backoff.max => {
+                        log::debug!("Retry-After of {delay:?} exceeds maximum backoff");
+                        return None;
+                    }
+                    Some(delay) => delay,
+                    None => inner.backoff.delay(retries),
+                }
+            }
+        };
+
+        if let Some(ref budget) = inner.budget {
+            if !budget.inner.withdraw() {
+                log::debug!("retry budget exhausted");
+                return None;
+            }
+        }
+
+        Some(delay)
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        // Keep `is_default` in sync
+        Policy {
+            inner: Arc::new(PolicyInner::default()),
+        }
+    }
+}
+
+impl Default for PolicyInner {
+    fn default() -> PolicyInner {
+        PolicyInner {
+            max_retries: 2,
+            statuses: Vec::new(),
+            connect_errors: false,
+            non_idempotent: false,
+            backoff: Backoff {
+                base: Duration::ZERO,
+                max: Duration::ZERO,
+                jitter: false,
+            },
+            retry_after: false,
+            budget: None,
+        }
+    }
+}
+
+impl fmt::Debug for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = &*self.inner;
+        f.debug_struct("Policy")
+            .field("max_retries", &inner.max_retries)
+            .field("statuses", &inner.statuses)
+            .field("connect_errors", &inner.connect_errors)
+            .field("non_idempotent", &inner.non_idempotent)
+            .field("backoff", &inner.backoff)
+            .field("retry_after", &inner.retry_after)
+            .field("budget", &inner.budget.is_some())
+            .finish()
+    }
+}
+
+impl Budget {
+    /// Create a new `Budget`.
+    ///
+    /// - `ttl` is how long a deposit from an original request counts
+    ///   towards the budget, between 1 and 60 seconds.
+    /// - `min_per_sec` is the number of retries always allowed per second,
+    ///   regardless of how many requests were made.
+    /// - `retry_percent` is the ratio of retries allowed on top of the
+    ///   original requests, between 0 and 1000. For example, `0.1` allows
+    ///   one retry for every 10 requests.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ttl` is less than 1 second or more than 60 seconds, or if
+    /// `retry_percent` is less than 0 or more than 1000, or if `min_per_sec`
+    /// is `i32::MAX` or more.
+    pub fn new(ttl: Duration, min_per_sec: u32, retry_percent: f32) -> Self {
+        Budget {
+            inner: Arc::new(TpsBudget::new(ttl, min_per_sec, retry_percent)),
+        }
+    }
+}
+
+impl Default for Budget {
+    /// A budget allowing 10 retries per second, plus 20% of the requests
+    /// made in the last 10 seconds.
+    fn default() -> Budget {
+        Budget::new(Duration::from_secs(10), 10, 0.2)
+    }
+}
+
+impl fmt::Debug for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Budget").field(&self.inner).finish()
+    }
+}
+
+impl Backoff {
+    fn delay(&self, retries: usize) -> Duration {
+        if self.base.is_zero() {
+            return Duration::ZERO;
+        }
+
+        let factor = 1u32.checked_shl(retries as u32).unwrap_or(u32::MAX);
+        let delay = self.base.saturating_mul(factor).min(self.max);
+
+        if self.jitter {
+            let half = delay / 2;
+            let nanos = half.as_nanos() as u64;
+            let spread = if nanos == 0 {
+                0
+            } else {
+                crate::util::fast_random() % nanos
+            };
+            half + Duration::from_nanos(spread)
+        } else {
+            delay
+        }
+    }
+}
+
+// pub(crate)
+
+/// The reason an attempt failed, as seen by a `Policy`.
+#[derive(Debug)]
+pub(crate) enum Failure<'a> {
+    /// The server refused the request before processing it.
+    #[cfg_attr(not(any(feature = "http2", feature = "http3")), allow(dead_code))]
+    Refused,
+    /// A connection to the server couldn't be established.
+    Connect,
+    /// The server responded with a status code.
+    Status(StatusCode, &'a HeaderMap),
+}
+
+impl Failure<'_> {
+    /// Classify an error returned while sending a request.
+    pub(crate) fn from_error(err: &(dyn StdError + 'static)) -> Option<Failure<'static>> {
+        // pop the legacy::Error
+        let legacy = err.downcast_ref::<hyper_util::client::legacy::Error>();
+        if legacy.map_or(false, |err| err.is_connect()) {
+            return Some(Failure::Connect);
+        }
+
+        let err = err.source()?;
+
+        #[cfg(feature = "http3")]
+        if let Some(cause) = err.source() {
+            if let Some(err) = cause.downcast_ref::<h3::error::ConnectionError>() {
+                log::debug!("determining if HTTP/3 error {err} can be retried");
+                // TODO: Does h3 provide an API for checking the error?
+                if err.to_string().as_str() == "timeout" {
+                    return Some(Failure::Refused);
+                }
+                return None;
+            }
+        }
+
+        #[cfg(feature = "http2")]
+        if let Some(cause) = err.source() {
+            if let Some(err) = cause.downcast_ref::<h2::Error>() {
+                // They sent us a graceful shutdown, try with a new connection!
+                if err.is_go_away()
+                    && err.is_remote()
+                    && err.reason() == Some(h2::Reason::NO_ERROR)
+                {
+                    return Some(Failure::Refused);
+                }
+
+                // REFUSED_STREAM was sent from the server, which is safe to retry.
+                // https://www.rfc-editor.org/rfc/rfc9113.html#section-8.7-3.2
+                if err.is_reset()
+                    && err.is_remote()
+                    && err.reason() == Some(h2::Reason::REFUSED_STREAM)
+                {
+                    return Some(Failure::Refused);
+                }
+            }
+        }
+
+        #[cfg(not(any(feature = "http2", feature = "http3")))]
+        let _ = err;
+
+        None
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+    )
+}
+
+/// Parse a `Retry-After` header, either as delay-seconds or an HTTP-date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[test]
+fn test_retry_policy_default_only_retries_refused() {
+    let policy = Policy::default();
+    let headers = HeaderMap::new();
+
+    assert_eq!(
+        policy.check(&Method::POST, 0, Failure::Refused),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(policy.check(&Method::POST, 2, Failure::Refused), None);
+    assert_eq!(policy.check(&Method::GET, 0, Failure::Connect), None);
+    assert_eq!(
+        policy.check(
+            &Method::GET,
+            0,
+            Failure::Status(StatusCode::SERVICE_UNAVAILABLE, &headers)
+        ),
+        None
+    );
+    assert!(policy.is_default());
+}
+
+#[test]
+fn test_retry_policy_none() {
+    let policy = Policy::none();
+    assert_eq!(policy.check(&Method::GET, 0, Failure::Refused), None);
+}
+
+#[test]
+fn test_retry_policy_status_idempotency() {
+    let policy = Policy::limited(2).jitter(false);
+    let headers = HeaderMap::new();
+    let failure = || Failure::Status(StatusCode::TOO_MANY_REQUESTS, &headers);
+
+    assert_eq!(
+        policy.check(&Method::GET, 0, failure()),
+        Some(Duration::from_millis(100))
+    );
+    assert_eq!(policy.check(&Method::POST, 0, failure()), None);
+
+    let policy = policy.non_idempotent(true);
+    assert_eq!(
+        policy.check(&Method::POST, 1, failure()),
+        Some(Duration::from_millis(200))
+    );
+    assert_eq!(
+        policy.check(
+            &Method::GET,
+            0,
+            Failure::Status(StatusCode::INTERNAL_SERVER_ERROR, &headers)
+        ),
+        None
+    );
+}
+
+#[test]
+fn test_retry_policy_backoff() {
+    let policy = Policy::limited(10)
+        .backoff(Duration::from_millis(10), Duration::from_millis(50))
+        .jitter(false);
+
+    let delays = (0..5)
+        .map(|n| policy.check(&Method::GET, n, Failure::Connect).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        delays,
+        [10, 20, 40, 50, 50].map(Duration::from_millis).to_vec()
+    );
+
+    let policy = policy.jitter(true);
+    for _ in 0..20 {
+        let delay = policy.check(&Method::GET, 2, Failure::Connect).unwrap();
+        assert!(delay >= Duration::from_millis(20) && delay <= Duration::from_millis(40));
+    }
+}
+
+#[test]
+fn test_retry_policy_retry_after() {
+    use http::HeaderValue;
+
+    let policy = Policy::limited(2);
+    let mut headers = HeaderMap::new();
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("3"));
+
+    assert_eq!(
+        policy.check(
+            &Method::GET,
+            0,
+            Failure::Status(StatusCode::SERVICE_UNAVAILABLE, &headers)
+        ),
+        Some(Duration::from_secs(3))
+    );
+
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("3600"));
+    assert_eq!(
+        policy.check(
+            &Method::GET,
+            0,
+            Failure::Status(StatusCode::SERVICE_UNAVAILABLE, &headers)
+        ),
+        None
+    );
+
+    headers.insert(
+        RETRY_AFTER,
+        HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+    );
+    assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+}
+
+#[test]
+fn test_retry_policy_budget() {
+    let policy = Policy::limited(5)
+        .backoff(Duration::ZERO, Duration::ZERO)
+        .budget(Budget::new(Duration::from_secs(1), 0, 0.0));
+
+    policy.deposit();
+    assert_eq!(policy.check(&Method::GET, 0, Failure::Connect), None);
+}
@@ -0,0 +1,473 @@
+//! Server-sent events, as specified by the [WHATWG].
+//!
+//! The events of a `text/event-stream` response are parsed by
+//! [`Response::event_stream`], which gives a `Stream` of [`Event`]s.
+//!
+//! An [`EventSource`] also reconnects when the connection is lost, sending
+//! the `Last-Event-ID` of the last event received, after the `retry` delay
+//! chosen by the server.
+//!
+//! ```no_run
+//! use futures_util::StreamExt;
+//! use reqwest::sse::EventSource;
+//!
+//! # async fn run() -> Result<(), reqwest::Error> {
+//! let client = reqwest::Client::new();
+//! let mut events = EventSource::new(client.get("https://stream.example/updates"));
+//!
+//! while let Some(event) = events.next().await {
+//!     match event {
+//!         Ok(event) => println!("{}: {}", event.event, event.data),
+//!         // the connection is retried, unless the error ends the stream
+//!         Err(err) => eprintln!("{err}"),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [WHATWG]: https://html.spec.whatwg.org/multipage/server-sent-events.html
+//! [`Response::event_stream`]: crate::Response::event_stream
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures_core::Stream;
+use futures_util::ready;
+use http::header::{HeaderValue, ACCEPT, CACHE_CONTROL, CONTENT_TYPE};
+use http::StatusCode;
+use tokio::time::Sleep;
+
+use crate::async_impl::body::DataStream;
+use crate::async_impl::decoder::Decoder;
+use crate::async_impl::{RequestBuilder, Response};
+use crate::error;
+
+/// The delay before reconnecting, until the server sets one.
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// An event sent by the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// The type of the event, `message` unless the server set one.
+    pub event: String,
+    /// The data of the event, with its lines joined by `\n`.
+    pub data: String,
+    /// The last event ID set by the server, if any.
+    ///
+    /// It is kept for the following events, until the server sets another.
+    pub id: Option<String>,
+    /// The reconnection delay set along with this event, if any.
+    pub retry: Option<Duration>,
+}
+
+/// A `Stream` of the events of a response body.
+///
+/// Created by [`Response::event_stream`](crate::Response::event_stream).
+pub struct EventStream {
+    body: DataStream<Decoder>,
+    parser: Parser,
+}
+
+/// A `Stream` of server-sent events that reconnects when the connection is
+/// lost.
+///
+/// Errors reading the events are returned by the stream, and followed by a
+/// reconnection. The stream ends after a response that isn't a successful
+/// `text/event-stream` one: with an error, or without one for
+/// `204 No Content`, which is how servers ask clients to stop.
+#[must_use = "streams do nothing unless polled"]
+pub struct EventSource {
+    /// The request to send again, unless it can't be cloned.
+    request: Option<RequestBuilder>,
+    last_event_id: Option<String>,
+    retry: Duration,
+    state: State,
+}
+
+enum State {
+    Connecting(Pin<Box<dyn Future<Output = crate::Result<Response>> + Send>>),
+    Open(EventStream),
+    Waiting(Pin<Box<Sleep>>),
+    Closed,
+}
+
+/// Parses the lines of an event stream into events.
+#[derive(Default)]
+struct Parser {
+    buf: BytesMut,
+    /// Whether the start of the stream was checked for a BOM.
+    started: bool,
+    /// Whether a `\n` would end the same line as the last `\r`.
+    after_cr: bool,
+    event: String,
+    data: String,
+    last_event_id: String,
+    /// The reconnection delay last set by the server.
+    retry: Option<Duration>,
+    /// Whether the reconnection delay was set by the current event.
+    event_retry: bool,
+}
+
+// ===== impl EventStream =====
+
+impl EventStream {
+    pub(crate) fn new(body: DataStream<Decoder>) -> EventStream {
+        EventStream {
+            body,
+            parser: Parser::default(),
+        }
+    }
+
+    /// The last event ID set by the server, if any.
+    pub fn last_event_id(&self) -> Option<&str> {
+        Some(&*self.parser.last_event_id).filter(|id| !id.is_empty())
+    }
+}
+
+impl Stream for EventStream {
+    type Item = crate::Result<Event>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(event) = self.parser.next_event() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            // an incomplete event at the end is discarded
+            match ready!(Pin::new(&mut self.body).poll_next(cx)) {
+                Some(Ok(chunk)) => self.parser.feed(&chunk),
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventStream")
+            .field("last_event_id", &self.last_event_id())
+            .finish()
+    }
+}
+
+// ===== impl EventSource =====
+
+impl EventSource {
+    /// Create an `EventSource` sending `request`, and sending it again to
+    /// reconnect.
+    ///
+    /// The request must have a body that can be cloned, if any, for the
+    /// stream to reconnect. Otherwise the stream ends with an error when
+    /// it would reconnect.
+    pub fn new(request: RequestBuilder) -> EventSource {
+        // an invalid request is sent anyway, to return its error
+        let (first, request) = match request.try_clone() {
+            Some(first) => (first, Some(request)),
+            None => (request, None),
+        };
+        let mut source = EventSource {
+            request,
+            last_event_id: None,
+            retry: DEFAULT_RETRY,
+            state: State::Closed,
+        };
+        source.connect(first);
+        source
+    }
+
+    /// The last event ID set by the server, which is sent when
+    /// reconnecting.
+    pub fn last_event_id(&self) -> Option<&str> {
+        match self.state {
+            State::Open(ref events) => events.last_event_id(),
+            _ => self.last_event_id.as_deref(),
+        }
+    }
+
+    fn connect(&mut self, request: RequestBuilder) {
+        let mut request = request
+            .header(ACCEPT, HeaderValue::from_static("text/event-stream"))
+            .header(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        if let Some(ref id) = self.last_event_id {
+            request = request.header("last-event-id", id);
+        }
+        self.state = State::Connecting(Box::pin(request.send()));
+    }
+
+    fn wait(&mut self) {
+        if let State::Open(ref events) = self.state {
+            self.last_event_id = events.last_event_id().map(str::to_owned);
+        }
+        self.state = State::Waiting(Box::pin(tokio::time::sleep(self.retry)));
+    }
+}
+
+/// Check that `res` is an event stream to read.
+fn check_response(res: &Response) -> crate::Result<()> {
+    if res.status() != StatusCode::OK {
+        return res.error_for_status_ref().and_then(|res| {
+            let msg = format!("unexpected status {}", res.status());
+            Err(error::decode(msg).with_url(res.url().clone()))
+        });
+    }
+    let is_event_stream = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .map_or(false, |mime| mime.essence_str() == "text/event-stream");
+    if !is_event_stream {
+        let msg = "response is not a text/event-stream";
+        return Err(error::decode(msg).with_url(res.url().clone()));
+    }
+    Ok(())
+}
+
+impl Stream for EventSource {
+    type Item = crate::Result<Event>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.state {
+                State::Connecting(ref mut pending) => match ready!(pending.as_mut().poll(cx)) {
+                    Ok(res) if res.status() == StatusCode::NO_CONTENT => {
+                        self.state = State::Closed;
+                        return Poll::Ready(None);
+                    }
+                    Ok(res) => {
+                        if let Err(err) = check_response(&res) {
+                            self.state = State::Closed;
+                            return Poll::Ready(Some(Err(err)));
+                        }
+                        let mut events = res.event_stream();
+                        // the ID is kept until the server sets another
+                        if let Some(ref id) = self.last_event_id {
+                            events.parser.last_event_id = id.clone();
+                        }
+                        self.state = State::Open(events);
+                    }
+                    Err(err) => {
+                        self.wait();
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                },
+                State::Open(ref mut events) => {
+                    let next = ready!(Pin::new(&mut *events).poll_next(cx));
+                    if let Some(retry) = events.parser.retry {
+                        self.retry = retry;
+                    }
+                    match next {
+                        Some(Ok(event)) => return Poll::Ready(Some(Ok(event))),
+                        Some(Err(err)) => {
+                            self.wait();
+                            return Poll::Ready(Some(Err(err)));
+                        }
+                        None => self.wait(),
+                    }
+                }
+                State::Waiting(ref mut delay) => {
+                    ready!(delay.as_mut().poll(cx));
+                    match self.request.as_ref().and_then(RequestBuilder::try_clone) {
+                        Some(request) => self.connect(request),
+                        None => {
+                            self.state = State::Closed;
+                            let msg = "request can not be cloned to reconnect";
+                            return Poll::Ready(Some(Err(error::builder(msg))));
+                        }
+                    }
+                }
+                State::Closed => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for EventSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventSource")
+            .field("request", &self.request)
+            .field("last_event_id", &self.last_event_id())
+            .field("retry", &self.retry)
+            .finish()
+    }
+}
+
+// ===== impl Parser =====
+
+impl Parser {
+    fn feed(&mut self, chunk: &Bytes) {
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Parse the buffered lines until an event is complete.
+    fn next_event(&mut self) -> Option<Event> {
+        if !self.started {
+            if self.buf.len() < BOM.len() && BOM.starts_with(&self.buf) {
+                return None;
+            }
+            if self.buf.starts_with(BOM) {
+                self.buf.advance(BOM.len());
+            }
+            self.started = true;
+        }
+        loop {
+            if self.after_cr && !self.buf.is_empty() {
+                if self.buf[0] == b'\n' {
+                    self.buf.advance(1);
+                }
+                self.after_cr = false;
+            }
+            let end = self.buf.iter().position(|&b| b == b'\r' || b == b'\n')?;
+            let line = self.buf.split_to(end + 1);
+            self.after_cr = line[end] == b'\r';
+            if let Some(event) = self.line(&String::from_utf8_lossy(&line[..end])) {
+                return Some(event);
+            }
+        }
+    }
+
+    /// Process a line, returning the event it completes, if any.
+    fn line(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        let (field, value) = match line.split_once(':') {
+            Some(("", _)) => return None,
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = value.to_owned(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = value.to_owned(),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(millis) = value.parse() {
+                    self.retry = Some(Duration::from_millis(millis));
+                    self.event_retry = true;
+                }
+            }
+            _ => (),
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = std::mem::take(&mut self.event);
+        let retry = std::mem::take(&mut self.event_retry);
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut data = std::mem::take(&mut self.data);
+        data.pop();
+        Some(Event {
+            event: if event.is_empty() {
+                "message".to_owned()
+            } else {
+                event
+            },
+            data,
+            id: Some(self.last_event_id.clone()).filter(|id| !id.is_empty()),
+            retry: self.retry.filter(|_| retry),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(chunks: &[&str]) -> Vec<Event> {
+        let mut parser = Parser::default();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            parser.feed(&Bytes::copy_from_slice(chunk.as_bytes()));
+            while let Some(event) = parser.next_event() {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn message(data: &str, id: Option<&str>) -> Event {
+        Event {
+            event: "message".to_owned(),
+            data: data.to_owned(),
+            id: id.map(str::to_owned),
+            retry: None,
+        }
+    }
+
+    #[test]
+    fn parse_spec_examples() {
+        let events = parse(&["data: YHOO\ndata: +2\ndata: 10\n\n"]);
+        assert_eq!(events, [message("YHOO\n+2\n10", None)]);
+
+        let events = parse(&[
+            ": test stream\n\ndata: first event\nid: 1\n\n",
+            "data:second event\nid\n\ndata:  third event\n\n",
+        ]);
+        assert_eq!(
+            events,
+            [
+                message("first event", Some("1")),
+                message("second event", None),
+                message(" third event", None),
+            ]
+        );
+
+        let events = parse(&["data\n\ndata\ndata\n\ndata:\n"]);
+        assert_eq!(events, [message("", None), message("\n", None)]);
+    }
+
+    #[test]
+    fn parse_fields() {
+        let events = parse(&["event: add\r\nid: 7\r\nretry: 2500\r\ndata: x\r\n\r\n"]);
+        assert_eq!(
+            events,
+            [Event {
+                event: "add".to_owned(),
+                data: "x".to_owned(),
+                id: Some("7".to_owned()),
+                retry: Some(Duration::from_millis(2500)),
+            }]
+        );
+
+        // the type is reset by each event, and the ID isn't
+        let events = parse(&["id: 1\nevent: a\n\nretry: 1s\ndata: y\n\n"]);
+        assert_eq!(events, [message("y", Some("1"))]);
+        let events = parse(&["id: a\0b\nunknown: z\ndata: z\n\n"]);
+        assert_eq!(events, [message("z", None)]);
+    }
+
+    #[test]
+    fn parse_split_lines() {
+        let events = parse(&[
+            "\u{feff}da",
+            "ta: one\r",
+            "\ndata: t",
+            "wo\r\r",
+            "data: é\n",
+            "\n",
+        ]);
+        assert_eq!(events, [message("one\ntwo", None), message("é", None)]);
+
+        let mut parser = Parser::default();
+        parser.feed(&Bytes::from_static(b"\xef\xbb"));
+        assert_eq!(parser.next_event(), None);
+        parser.feed(&Bytes::from_static(b"\xbfdata: bom\n\n"));
+        assert_eq!(parser.next_event(), Some(message("bom", None)));
+
+        // an incomplete event is not dispatched
+        assert_eq!(parse(&["data: partial\n"]), []);
+    }
+}
@@ -0,0 +1,207 @@
+//! Tracing spans
+//!
+//! With the `tracing` feature, every request sent by a `Client` gets a span
+//! for its whole exchange, and a child span for every hop of it, meaning the
+//! first attempt, each redirect, each retry, and each answered
+//! authentication challenge.
+//!
+//! The spans are at the `INFO` level, and record the attributes of the
+//! [OpenTelemetry HTTP client conventions][semconv]:
+//!
+//! - `http.request.method`
+//! - `url.full`, with any user name and password replaced by `REDACTED`
+//! - `server.address` and `server.port`
+//! - `http.request.resend_count`, on the hops after the first
+//! - `http.response.status_code`
+//! - `network.protocol.version`
+//! - `error.type`, for a failed request or an error status
+//!
+//! along with `otel.name`, `otel.kind` and `otel.status_code`, as used by
+//! `tracing-opentelemetry`.
+//!
+//! A [`Propagator`] set with `ClientBuilder::trace_propagator()` can add the
+//! trace context of each hop to its headers, such as a W3C `traceparent`.
+//!
+//! [semconv]: https://opentelemetry.io/docs/specs/semconv/http/http-spans/
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io;
+
+use http::{HeaderMap, Method, StatusCode, Version};
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+use url::Url;
+
+use crate::error::{BoxError, TimedOut};
+
+/// Trait for adding the trace context of a request to its headers.
+///
+/// # Example
+///
+/// With `tracing-opentelemetry`, the OpenTelemetry context of the span can
+/// be injected by the global propagator. A propagator without other
+/// dependencies could pass along the span ID:
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # use reqwest::header::{HeaderMap, HeaderValue};
+/// # use reqwest::trace::Propagator;
+/// struct SpanId;
+///
+/// impl Propagator for SpanId {
+///     fn inject(&self, span: &tracing::Span, headers: &mut HeaderMap) {
+///         if let Some(id) = span.id() {
+///             headers.insert("x-span-id", HeaderValue::from(id.into_u64()));
+///         }
+///     }
+/// }
+///
+/// let client = reqwest::Client::builder()
+///     .trace_propagator(Arc::new(SpanId))
+///     .build();
+/// ```
+pub trait Propagator: Send + Sync {
+    /// Add the context of `span`, the span of a single hop, to the
+    /// `headers` it is about to be sent with.
+    fn inject(&self, span: &Span, headers: &mut HeaderMap);
+}
+
+/// The span of a request, covering all of its hops.
+pub(crate) fn request_span(method: &Method, url: &Url) -> Span {
+    tracing::info_span!(
+        "HTTP request",
+        otel.name = %method,
+        otel.status_code = Empty,
+        http.request.method = %method,
+        url.full = %redacted(url),
+        server.address = url.host_str(),
+        server.port = url.port_or_known_default(),
+        http.response.status_code = Empty,
+        network.protocol.version = Empty,
+        "error.type" = Empty,
+    )
+}
+
+/// The span of a single hop of the request of the `parent` span.
+pub(crate) fn hop_span(parent: &Span, method: &Method, url: &Url, resend_count: u32) -> Span {
+    let span = tracing::info_span!(
+        parent: parent,
+        "HTTP hop",
+        otel.name = %method,
+        otel.kind = "client",
+        otel.status_code = Empty,
+        http.request.method = %method,
+        url.full = %redacted(url),
+        server.address = url.host_str(),
+        server.port = url.port_or_known_default(),
+        http.request.resend_count = Empty,
+        http.response.status_code = Empty,
+        network.protocol.version = Empty,
+        "error.type" = Empty,
+    );
+    if resend_count > 0 {
+        span.record("http.request.resend_count", resend_count);
+    }
+    span
+}
+
+/// Drive the hop `in_flight` within its `span`, recording its outcome.
+pub(crate) fn instrument<F>(
+    span: Span,
+    in_flight: F,
+) -> impl Future<Output = Result<crate::Response, BoxError>>
+where
+    F: Future<Output = Result<crate::Response, BoxError>>,
+{
+    let recorded = span.clone();
+    async move {
+        let res = in_flight.await;
+        match res {
+            Ok(ref res) => record_response(&recorded, res.status(), res.version()),
+            Err(ref err) => record_error(&recorded, &**err),
+        }
+        res
+    }
+    .instrument(span)
+}
+
+/// Record the response of a request or hop to its span.
+pub(crate) fn record_response(span: &Span, status: StatusCode, version: Version) {
+    span.record("http.response.status_code", status.as_u16());
+    if let Some(version) = protocol_version(version) {
+        span.record("network.protocol.version", version);
+    }
+    if status.is_client_error() || status.is_server_error() {
+        span.record("error.type", status.as_str());
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+/// Record the error a request or hop failed with to its span.
+pub(crate) fn record_error(span: &Span, err: &(dyn StdError + 'static)) {
+    span.record("error.type", error_type(err));
+    span.record("otel.status_code", "ERROR");
+}
+
+/// A low-cardinality name for the kind of `err`, named after the
+/// `reqwest::Error` predicate that matches it.
+fn error_type(err: &(dyn StdError + 'static)) -> &'static str {
+    if let Some(err) = err.downcast_ref::<crate::Error>() {
+        if err.is_builder() {
+            return "builder";
+        }
+        if err.is_redirect() {
+            return "redirect";
+        }
+        if err.is_body() {
+            return "body";
+        }
+        if err.is_decode() {
+            return "decode";
+        }
+    }
+
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if err.is::<TimedOut>() {
+            return "timeout";
+        }
+        if let Some(io) = err.downcast_ref::<io::Error>() {
+            if io.kind() == io::ErrorKind::TimedOut {
+                return "timeout";
+            }
+        }
+        if let Some(hyper_err) = err.downcast_ref::<hyper_util::client::legacy::Error>() {
+            if hyper_err.is_connect() {
+                return "connect";
+            }
+        }
+        source = err.source();
+    }
+
+    "_OTHER"
+}
+
+fn protocol_version(version: Version) -> Option<&'static str> {
+    match version {
+        Version::HTTP_09 => Some("0.9"),
+        Version::HTTP_10 => Some("1.0"),
+        Version::HTTP_11 => Some("1.1"),
+        Version::HTTP_2 => Some("2"),
+        Version::HTTP_3 => Some("3"),
+        _ => None,
+    }
+}
+
+/// The URL to record, without credentials.
+fn redacted(url: &Url) -> Url {
+    let mut url = url.clone();
+    if !url.username().is_empty() {
+        let _ = url.set_username("REDACTED");
+    }
+    if url.password().is_some() {
+        let _ = url.set_password(Some("REDACTED"));
+    }
+    url
+}
@@ -0,0 +1,409 @@
+//! The framing of WebSocket messages.
+
+use std::io;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::deflate::{Deflate, DeflateConfig};
+use super::{CloseFrame, Message};
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+const FIN: u8 = 0x80;
+const RSV1: u8 = 0x40;
+const MASK: u8 = 0x80;
+
+/// Decodes the frames received from a server into messages, and encodes
+/// messages into masked frames.
+pub(super) struct Codec {
+    max_message_size: usize,
+    deflate: Option<Deflate>,
+    /// The data message whose frames are being received.
+    partial: Option<Partial>,
+}
+
+struct Partial {
+    opcode: u8,
+    compressed: bool,
+    data: BytesMut,
+}
+
+struct Header {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    len: usize,
+    payload_len: usize,
+}
+
+impl Codec {
+    pub(super) fn new(max_message_size: usize, deflate: Option<DeflateConfig>) -> Codec {
+        Codec {
+            max_message_size,
+            deflate: deflate.map(Deflate::new),
+            partial: None,
+        }
+    }
+
+    /// Handle a received frame, returning its message if it is complete.
+    fn frame(&mut self, header: Header, payload: BytesMut) -> io::Result<Option<Message>> {
+        if header.opcode & 0x8 != 0 {
+            if !header.fin || header.rsv1 || payload.len() > 125 {
+                return Err(protocol_error("invalid control frame"));
+            }
+            let message = match header.opcode {
+                OP_CLOSE => Message::Close(close_frame(payload)?),
+                OP_PING => Message::Ping(payload.freeze()),
+                OP_PONG => Message::Pong(payload.freeze()),
+                _ => return Err(protocol_error("unknown opcode")),
+            };
+            return Ok(Some(message));
+        }
+
+        let partial = match header.opcode {
+            OP_TEXT | OP_BINARY if self.partial.is_none() => {
+                if header.rsv1 && self.deflate.is_none() {
+                    return Err(protocol_error("unexpected compressed frame"));
+                }
+                Partial {
+                    opcode: header.opcode,
+                    compressed: header.rsv1,
+                    data: payload,
+                }
+            }
+            OP_CONTINUATION if !header.rsv1 => {
+                let mut partial = self
+                    .partial
+                    .take()
+                    .ok_or_else(|| protocol_error("unexpected continuation frame"))?;
+                partial.data.extend_from_slice(&payload);
+                partial
+            }
+            OP_TEXT | OP_BINARY => return Err(protocol_error("expected a continuation frame")),
+            _ => return Err(protocol_error("invalid data frame")),
+        };
+        if !header.fin {
+            self.partial = Some(partial);
+            return Ok(None);
+        }
+
+        let data = match self.deflate {
+            Some(ref mut deflate) if partial.compressed => {
+                deflate.decompress(&partial.data, self.max_message_size)?
+            }
+            _ => partial.data.freeze(),
+        };
+        let message = if partial.opcode == OP_TEXT {
+            let text = String::from_utf8(data.into())
+                .map_err(|_| protocol_error("text message is not UTF-8"))?;
+            Message::Text(text)
+        } else {
+            Message::Binary(data)
+        };
+        Ok(Some(message))
+    }
+}
+
+impl Decoder for Codec {
+    type Item = Message;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Message>> {
+        loop {
+            let header = match parse_header(src)? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+            if header.opcode & 0x8 != 0 && header.payload_len > 125 {
+                return Err(protocol_error("invalid control frame"));
+            }
+            let buffered = self
+                .partial
+                .as_ref()
+                .map_or(0, |partial| partial.data.len());
+            if header.opcode & 0x8 == 0
+                && buffered.saturating_add(header.payload_len) > self.max_message_size
+            {
+                return Err(protocol_error("message is too large"));
+            }
+            let frame_len = header
+                .len
+                .checked_add(header.payload_len)
+                .ok_or_else(|| protocol_error("frame is too large"))?;
+            if src.len() < frame_len {
+                src.reserve(frame_len - src.len());
+                return Ok(None);
+            }
+
+            src.advance(header.len);
+            let payload = src.split_to(header.payload_len);
+            if let Some(message) = self.frame(header, payload)? {
+                return Ok(Some(message));
+            }
+        }
+    }
+}
+
+impl Encoder<Message> for Codec {
+    type Error = io::Error;
+
+    fn encode(&mut self, message: Message, dst: &mut BytesMut) -> io::Result<()> {
+        let (opcode, payload) = match message {
+            Message::Text(text) => (OP_TEXT, Bytes::from(text)),
+            Message::Binary(data) => (OP_BINARY, data),
+            Message::Ping(data) => (OP_PING, data),
+            Message::Pong(data) => (OP_PONG, data),
+            Message::Close(None) => (OP_CLOSE, Bytes::new()),
+            Message::Close(Some(frame)) => {
+                let mut payload = BytesMut::with_capacity(2 + frame.reason.len());
+                payload.put_u16(frame.code);
+                payload.put_slice(frame.reason.as_bytes());
+                (OP_CLOSE, payload.freeze())
+            }
+        };
+        if opcode & 0x8 != 0 && payload.len() > 125 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "control message payload is longer than 125 bytes",
+            ));
+        }
+        let mask = super::random::<4>()?;
+        let (rsv1, payload) = match self.deflate {
+            Some(ref mut deflate) if opcode & 0x8 == 0 => (RSV1, deflate.compress(&payload)?),
+            _ => (0, payload),
+        };
+
+        dst.reserve(14 + payload.len());
+        dst.put_u8(FIN | rsv1 | opcode);
+        match payload.len() {
+            len if len < 126 => dst.put_u8(MASK | len as u8),
+            len if len <= u16::MAX.into() => {
+                dst.put_u8(MASK | 126);
+                dst.put_u16(len as u16);
+            }
+            len => {
+                dst.put_u8(MASK | 127);
+                dst.put_u64(len as u64);
+            }
+        }
+        dst.put_slice(&mask);
+        dst.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        Ok(())
+    }
+}
+
+/// Parse the header of the next frame in `src`, if it is all there.
+fn parse_header(src: &[u8]) -> io::Result<Option<Header>> {
+    if src.len() < 2 {
+        return Ok(None);
+    }
+    if src[0] & 0x30 != 0 {
+        return Err(protocol_error("reserved bits are set"));
+    }
+    if src[1] & MASK != 0 {
+        return Err(protocol_error("frames from the server must not be masked"));
+    }
+    let (len, payload_len) = match src[1] & 0x7f {
+        126 if src.len() < 4 => return Ok(None),
+        126 => (4, u16::from_be_bytes([src[2], src[3]]).into()),
+        127 if src.len() < 10 => return Ok(None),
+        127 => {
+            let len = u64::from_be_bytes(src[2..10].try_into().unwrap());
+            let len = usize::try_from(len).map_err(|_| protocol_error("message is too large"))?;
+            (10, len)
+        }
+        len => (2, len.into()),
+    };
+    Ok(Some(Header {
+        fin: src[0] & FIN != 0,
+        rsv1: src[0] & RSV1 != 0,
+        opcode: src[0] & 0x0f,
+        len,
+        payload_len,
+    }))
+}
+
+fn close_frame(mut payload: BytesMut) -> io::Result<Option<CloseFrame>> {
+    match payload.len() {
+        0 => Ok(None),
+        1 => Err(protocol_error("invalid close frame")),
+        _ => {
+            let code = payload.get_u16();
+            let reason = String::from_utf8(payload.to_vec())
+                .map_err(|_| protocol_error("close reason is not UTF-8"))?;
+            Ok(Some(CloseFrame { code, reason }))
+        }
+    }
+}
+
+fn protocol_error(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(codec: &mut Codec, bytes: &[u8]) -> Vec<Message> {
+        let mut src = BytesMut::from(bytes);
+        let mut messages = Vec::new();
+        while let Some(message) = codec.decode(&mut src).unwrap() {
+            messages.push(message);
+        }
+        assert!(src.is_empty());
+        messages
+    }
+
+    #[test]
+    fn decode_rfc_examples() {
+        let mut codec = Codec::new(1024, None);
+        let messages = decode_all(
+            &mut codec,
+            &[
+                // a single-frame text message
+                0x81, 0x05, b'H', b'e', b'l', b'l', b'o',
+                // a fragmented text message, with a ping in between
+                0x01, 0x03, b'H', b'e', b'l', 0x89, 0x05, b'H', b'e', b'l', b'l', b'o', 0x80, 0x02,
+                b'l', b'o',
+            ],
+        );
+        assert_eq!(
+            messages,
+            [
+                Message::Text("Hello".into()),
+                Message::Ping(Bytes::from_static(b"Hello")),
+                Message::Text("Hello".into()),
+            ]
+        );
+
+        let mut frame = vec![0x82, 0x7e, 0x01, 0x00];
+        frame.extend_from_slice(&[7; 256]);
+        assert_eq!(
+            decode_all(&mut codec, &frame),
+            [Message::Binary(vec![7; 256].into())]
+        );
+    }
+
+    #[test]
+    fn decode_partial_frames() {
+        let mut codec = Codec::new(1024, None);
+        let mut src = BytesMut::from(&[0x82, 0x7e, 0x01][..]);
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        src.extend_from_slice(&[0x00, 1, 2]);
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        src.extend_from_slice(&[3; 254]);
+        let message = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(
+            message,
+            Message::Binary(Bytes::from([&[1, 2][..], &[3; 254]].concat()))
+        );
+    }
+
+    #[test]
+    fn decode_compressed_with_context_takeover() {
+        let mut codec = Codec::new(1024, Some(DeflateConfig::default()));
+        let messages = decode_all(
+            &mut codec,
+            &[
+                0xc1, 0x07, 0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00,
+                // the same message, referring to the first one
+                0xc1, 0x05, 0xf2, 0x00, 0x11, 0x00, 0x00,
+            ],
+        );
+        assert_eq!(
+            messages,
+            [Message::Text("Hello".into()), Message::Text("Hello".into())]
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        let error = |deflate: Option<DeflateConfig>, bytes: &[u8]| {
+            let mut codec = Codec::new(32, deflate);
+            codec
+                .decode(&mut BytesMut::from(bytes))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(None, &[0x81, 0x85, 0, 0, 0, 0]),
+            "frames from the server must not be masked"
+        );
+        assert_eq!(
+            error(None, &[0xc1, 0x01, 0x00]),
+            "unexpected compressed frame"
+        );
+        assert_eq!(
+            error(None, &[0x80, 0x01, 0x00]),
+            "unexpected continuation frame"
+        );
+        assert_eq!(error(None, &[0x09, 0x00]), "invalid control frame");
+        assert_eq!(
+            error(
+                None,
+                &[0x89, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+            ),
+            "invalid control frame"
+        );
+        assert_eq!(error(None, &[0x82, 0x21]), "message is too large");
+        assert_eq!(
+            error(None, &[0x81, 0x01, 0xff]),
+            "text message is not UTF-8"
+        );
+
+        // 1 KiB of zeros compresses to less than the limit
+        let mut bomb = Deflate::new(DeflateConfig::default());
+        let payload = bomb.compress(&[0; 1024]).unwrap();
+        assert!(payload.len() <= 32);
+        let mut frame = vec![0xc2, payload.len() as u8];
+        frame.extend_from_slice(&payload);
+        assert_eq!(
+            error(Some(DeflateConfig::default()), &frame),
+            "decompressed message is too large"
+        );
+    }
+
+    #[test]
+    fn encode_masked_frames() {
+        let unmask = |frame: &[u8]| {
+            let (header, rest) = frame.split_at(2);
+            let (mask, payload) = rest.split_at(4);
+            let payload = payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m);
+            (header.to_vec(), payload.collect::<Vec<u8>>())
+        };
+
+        let mut codec = Codec::new(1024, None);
+        let mut dst = BytesMut::new();
+        codec
+            .encode(Message::Text("Hello".into()), &mut dst)
+            .unwrap();
+        assert_eq!(unmask(&dst), (vec![0x81, 0x85], b"Hello".to_vec()));
+
+        dst.clear();
+        let close = CloseFrame {
+            code: 1000,
+            reason: "bye".into(),
+        };
+        codec.encode(Message::Close(Some(close)), &mut dst).unwrap();
+        assert_eq!(unmask(&dst), (vec![0x88, 0x85], b"\x03\xe8bye".to_vec()));
+
+        let mut codec = Codec::new(1024, Some(DeflateConfig::default()));
+        dst.clear();
+        codec
+            .encode(Message::Text("Hello".into()), &mut dst)
+            .unwrap();
+        assert_eq!(
+            unmask(&dst),
+            (
+                vec![0xc1, 0x87],
+                vec![0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00]
+            )
+        );
+    }
+}
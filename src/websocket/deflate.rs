@@ -0,0 +1,129 @@
+//! The `permessage-deflate` extension, as specified in [RFC 7692].
+//!
+//! [RFC 7692]: https://www.rfc-editor.org/rfc/rfc7692
+
+use std::io;
+
+use bytes::Bytes;
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+use crate::error::BoxError;
+
+/// The end of a sync flush, which is removed from compressed messages.
+const TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// The parameters of `permessage-deflate` accepted by the server.
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct DeflateConfig {
+    client_no_context_takeover: bool,
+    server_no_context_takeover: bool,
+}
+
+/// The compression contexts of a connection.
+pub(super) struct Deflate {
+    config: DeflateConfig,
+    compress: Compress,
+    decompress: Decompress,
+}
+
+impl DeflateConfig {
+    /// Parse the parameters of the extension in the handshake response.
+    ///
+    /// The window of the server can be smaller than that of the client,
+    /// but `client_max_window_bits` isn't offered, so it can't be accepted.
+    pub(super) fn negotiated<'a, I>(params: I) -> Result<DeflateConfig, BoxError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let mut config = DeflateConfig::default();
+        for param in params {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (param, None),
+            };
+            match (name, value) {
+                ("client_no_context_takeover", None) => config.client_no_context_takeover = true,
+                ("server_no_context_takeover", None) => config.server_no_context_takeover = true,
+                ("server_max_window_bits", Some(bits)) if matches!(bits.parse(), Ok(8..=15u8)) => {}
+                _ => return Err(format!("unsupported permessage-deflate parameter {param}").into()),
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Deflate {
+    pub(super) fn new(config: DeflateConfig) -> Deflate {
+        Deflate {
+            config,
+            compress: Compress::new(Compression::default(), false),
+            decompress: Decompress::new(false),
+        }
+    }
+
+    /// Compress the payload of a message to send.
+    pub(super) fn compress(&mut self, data: &[u8]) -> io::Result<Bytes> {
+        let start = self.compress.total_in();
+        let mut out = Vec::with_capacity(data.len() / 2 + 64);
+        loop {
+            let consumed = (self.compress.total_in() - start) as usize;
+            self.compress
+                .compress_vec(&data[consumed..], &mut out, FlushCompress::Sync)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            let consumed = (self.compress.total_in() - start) as usize;
+            // the flush is done when there is room left in the output
+            if consumed == data.len() && out.len() < out.capacity() {
+                break;
+            }
+            out.reserve(out.capacity());
+        }
+        if out.ends_with(&TAIL) {
+            out.truncate(out.len() - TAIL.len());
+        }
+        if self.config.client_no_context_takeover {
+            self.compress.reset();
+        }
+        Ok(out.into())
+    }
+
+    /// Decompress the payload of a received message, up to `max` bytes.
+    pub(super) fn decompress(&mut self, data: &[u8], max: usize) -> io::Result<Bytes> {
+        let mut input = Vec::with_capacity(data.len() + TAIL.len());
+        input.extend_from_slice(data);
+        input.extend_from_slice(&TAIL);
+
+        let start = self.decompress.total_in();
+        let limit = max.saturating_add(1);
+        let mut out = Vec::with_capacity((data.len() * 2).max(64).min(limit));
+        loop {
+            let consumed = (self.decompress.total_in() - start) as usize;
+            let produced = out.len();
+            let status = self
+                .decompress
+                .decompress_vec(&input[consumed..], &mut out, FlushDecompress::Sync)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            if out.len() > max {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "decompressed message is too large",
+                ));
+            }
+            if status == Status::StreamEnd {
+                // a final block ends the context
+                self.decompress.reset(false);
+                break;
+            }
+            let stalled =
+                self.decompress.total_in() - start == consumed as u64 && out.len() == produced;
+            let consumed = (self.decompress.total_in() - start) as usize;
+            if consumed == input.len() && out.len() < out.capacity() || stalled {
+                break;
+            }
+            out.reserve(out.capacity().min(limit - out.len()).max(1));
+        }
+        if self.config.server_no_context_takeover {
+            self.decompress.reset(false);
+        }
+        Ok(out.into())
+    }
+}
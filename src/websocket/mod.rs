@@ -0,0 +1,520 @@
+//! WebSocket connections, as specified in [RFC 6455].
+//!
+//! A connection is opened with [`Client::websocket`], which sends the
+//! opening handshake like other requests of the client, with its proxies,
+//! TLS configuration, cookies and default headers. The handshake is sent
+//! over a connection of its own that only offers HTTP/1.1 in the TLS
+//! handshake, so `wss://` servers that also speak HTTP/2 accept it, while
+//! servers that only speak HTTP/2 can't be connected to.
+//!
+//! A [`WebSocket`] is a `Stream` of the messages received and a `Sink` of
+//! the messages to send. Pings are answered, and a close from the server is
+//! echoed, as the stream is polled.
+//!
+//! ```no_run
+//! use futures_util::{SinkExt, StreamExt};
+//! use reqwest::websocket::Message;
+//!
+//! # async fn run() -> Result<(), reqwest::Error> {
+//! let mut ws = reqwest::Client::new()
+//!     .websocket("wss://chat.example/")
+//!     .protocols(["chat.v2"])
+//!     .send()
+//!     .await?;
+//!
+//! ws.send(Message::Text("hello".into())).await?;
+//! while let Some(message) = ws.next().await {
+//!     match message? {
+//!         Message::Text(text) => println!("{text}"),
+//!         Message::Close(_) => break,
+//!         _ => (),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [RFC 6455]: https://www.rfc-editor.org/rfc/rfc6455
+//! [`Client::websocket`]: crate::Client::websocket
+
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_util::{ready, Sink, SinkExt, StreamExt};
+use http::header::{
+    HeaderName, HeaderValue, CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_EXTENSIONS,
+    SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL, SEC_WEBSOCKET_VERSION, UPGRADE,
+};
+use http::StatusCode;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use tokio_util::codec::Framed;
+use url::Url;
+
+use self::codec::Codec;
+use self::deflate::DeflateConfig;
+use crate::async_impl::{RequestBuilder, Response, Upgraded};
+use crate::error::{self, BoxError};
+use crate::header::HeaderMap;
+
+mod codec;
+mod deflate;
+
+/// The GUID appended to the key of a handshake to compute the accept key.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// A builder to open a WebSocket connection.
+///
+/// It is created with [`Client::websocket`](crate::Client::websocket).
+#[must_use = "WebSocketRequestBuilder does nothing until you 'send' it"]
+pub struct WebSocketRequestBuilder {
+    inner: RequestBuilder,
+    protocols: Vec<String>,
+    permessage_deflate: bool,
+    max_message_size: usize,
+}
+
+/// An open WebSocket connection.
+///
+/// Received messages are read as a `Stream`, and messages are sent as a
+/// `Sink`.
+pub struct WebSocket {
+    inner: Framed<Upgraded, Codec>,
+    protocol: Option<String>,
+    /// The pong or close to send in reply to a received message.
+    reply: Option<Message>,
+    /// Whether a close was sent, after which no other message can be.
+    closed: bool,
+}
+
+/// A WebSocket message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// A text message.
+    Text(String),
+    /// A binary message.
+    Binary(Bytes),
+    /// A ping, with up to 125 bytes of data.
+    Ping(Bytes),
+    /// A pong, with up to 125 bytes of data.
+    Pong(Bytes),
+    /// A close, with an optional code and reason.
+    Close(Option<CloseFrame>),
+}
+
+/// The code and reason of a close message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CloseFrame {
+    /// The status code, such as 1000 for a normal closure.
+    pub code: u16,
+    /// Why the connection is closed.
+    pub reason: String,
+}
+
+/// Convert a WebSocket URL to the HTTP URL of its opening handshake.
+pub(crate) fn http_url(mut url: Url) -> crate::Result<Url> {
+    let scheme = match url.scheme() {
+        "ws" | "http" => "http",
+        "wss" | "https" => "https",
+        _ => return Err(error::url_bad_scheme(url)),
+    };
+    url.set_scheme(scheme)
+        .expect("ws and http schemes are interchangeable");
+    Ok(url)
+}
+
+impl WebSocketRequestBuilder {
+    pub(crate) fn new(inner: RequestBuilder) -> WebSocketRequestBuilder {
+        WebSocketRequestBuilder {
+            inner,
+            protocols: Vec::new(),
+            permessage_deflate: true,
+            max_message_size: 64 << 20,
+        }
+    }
+
+    /// Add a header to the opening handshake.
+    pub fn header<K, V>(mut self, key: K, value: V) -> WebSocketRequestBuilder
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        self.inner = self.inner.header(key, value);
+        self
+    }
+
+    /// Add a set of headers to the opening handshake.
+    pub fn headers(mut self, headers: HeaderMap) -> WebSocketRequestBuilder {
+        self.inner = self.inner.headers(headers);
+        self
+    }
+
+    /// Enable HTTP basic authentication of the opening handshake.
+    pub fn basic_auth<U, P>(mut self, username: U, password: Option<P>) -> WebSocketRequestBuilder
+    where
+        U: fmt::Display,
+        P: fmt::Display,
+    {
+        self.inner = self.inner.basic_auth(username, password);
+        self
+    }
+
+    /// Enable HTTP bearer authentication of the opening handshake.
+    pub fn bearer_auth<T>(mut self, token: T) -> WebSocketRequestBuilder
+    where
+        T: fmt::Display,
+    {
+        self.inner = self.inner.bearer_auth(token);
+        self
+    }
+
+    /// Modify the query string of the URL.
+    ///
+    /// See [`RequestBuilder::query`](crate::RequestBuilder::query).
+    pub fn query<T: Serialize + ?Sized>(mut self, query: &T) -> WebSocketRequestBuilder {
+        self.inner = self.inner.query(query);
+        self
+    }
+
+    /// Set the subprotocols to offer, in order of preference.
+    ///
+    /// The one chosen by the server is given by [`WebSocket::protocol`].
+    pub fn protocols<I, S>(mut self, protocols: I) -> WebSocketRequestBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set whether to offer the `permessage-deflate` extension, which
+    /// compresses messages when the server accepts it.
+    ///
+    /// Default is `true`.
+    pub fn permessage_deflate(mut self, enable: bool) -> WebSocketRequestBuilder {
+        self.permessage_deflate = enable;
+        self
+    }
+
+    /// Set the maximum size of a received message, after decompression.
+    ///
+    /// Larger messages are an error of the stream of messages.
+    ///
+    /// Default is 64 MiB.
+    pub fn max_message_size(mut self, max: usize) -> WebSocketRequestBuilder {
+        self.max_message_size = max;
+        self
+    }
+
+    /// Send the opening handshake, and return the connection once the
+    /// server accepts it.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the handshake could not be sent, or if the
+    /// server did not accept it with a valid response.
+    pub async fn send(self) -> crate::Result<WebSocket> {
+        let key = BASE64_STANDARD.encode(random::<16>().map_err(error::request)?);
+        let mut req = self
+            .inner
+            .http1_only()
+            .header(CONNECTION, "upgrade")
+            .header(UPGRADE, "websocket")
+            .header(SEC_WEBSOCKET_VERSION, "13")
+            .header(SEC_WEBSOCKET_KEY, &key);
+        if !self.protocols.is_empty() {
+            req = req.header(SEC_WEBSOCKET_PROTOCOL, self.protocols.join(", "));
+        }
+        if self.permessage_deflate {
+            req = req.header(SEC_WEBSOCKET_EXTENSIONS, "permessage-deflate");
+        }
+
+        let res = req.send().await?;
+        let (protocol, deflate) =
+            check_handshake(&res, &key, &self.protocols, self.permessage_deflate)
+                .map_err(|err| error::upgrade(err).with_url(res.url().clone()))?;
+        let upgraded = res.upgrade().await?;
+
+        Ok(WebSocket {
+            inner: Framed::new(upgraded, Codec::new(self.max_message_size, deflate)),
+            protocol,
+            reply: None,
+            closed: false,
+        })
+    }
+}
+
+impl fmt::Debug for WebSocketRequestBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocketRequestBuilder")
+            .field("inner", &self.inner)
+            .field("protocols", &self.protocols)
+            .field("permessage_deflate", &self.permessage_deflate)
+            .field("max_message_size", &self.max_message_size)
+            .finish()
+    }
+}
+
+/// Random bytes from the entropy source of the system, as RFC 6455 requires
+/// for the key of the handshake and the masks of frames.
+fn random<const N: usize>() -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Check that the response accepts the opening handshake with `key`,
+/// returning the chosen subprotocol and the `permessage-deflate` config.
+fn check_handshake(
+    res: &Response,
+    key: &str,
+    protocols: &[String],
+    permessage_deflate: bool,
+) -> Result<(Option<String>, Option<DeflateConfig>), BoxError> {
+    if res.status() != StatusCode::SWITCHING_PROTOCOLS {
+        return Err(format!(
+            "server responded with {} instead of switching protocols",
+            res.status()
+        )
+        .into());
+    }
+    let headers = res.headers();
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if !header(UPGRADE).map_or(false, |value| value.eq_ignore_ascii_case("websocket")) {
+        return Err("missing `Upgrade: websocket` header".into());
+    }
+    let has_upgrade = |value: &str| {
+        value
+            .split(',')
+            .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+    };
+    if !header(CONNECTION).map_or(false, has_upgrade) {
+        return Err("missing `Connection: upgrade` header".into());
+    }
+    if header(SEC_WEBSOCKET_ACCEPT) != Some(&*accept_key(key)) {
+        return Err("invalid `Sec-WebSocket-Accept` header".into());
+    }
+
+    let protocol = match header(SEC_WEBSOCKET_PROTOCOL) {
+        Some(protocol) if protocols.iter().any(|offered| offered == protocol) => {
+            Some(protocol.to_owned())
+        }
+        Some(protocol) => {
+            return Err(format!("server chose unoffered subprotocol {protocol}").into())
+        }
+        None => None,
+    };
+
+    let mut deflate = None;
+    for value in headers.get_all(SEC_WEBSOCKET_EXTENSIONS) {
+        let value = value.to_str()?;
+        for extension in value.split(',') {
+            let mut params = extension.split(';').map(str::trim);
+            let name = params.next().unwrap_or_default();
+            if name != "permessage-deflate" || !permessage_deflate || deflate.is_some() {
+                return Err(format!("server chose unoffered extension {name}").into());
+            }
+            deflate = Some(DeflateConfig::negotiated(params)?);
+        }
+    }
+
+    Ok((protocol, deflate))
+}
+
+/// The value of `Sec-WebSocket-Accept` for `key`.
+fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    BASE64_STANDARD.encode(sha1.finalize())
+}
+
+impl WebSocket {
+    /// The subprotocol chosen by the server, if any.
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Close the connection, waiting for the server to close it too.
+    ///
+    /// Messages received meanwhile are discarded.
+    pub async fn close(mut self, frame: Option<CloseFrame>) -> crate::Result<()> {
+        if !self.closed {
+            self.send(Message::Close(frame)).await?;
+        }
+        while let Some(message) = self.next().await {
+            message?;
+        }
+        SinkExt::<Message>::close(&mut self).await
+    }
+
+    /// Send the pending reply to a received message, if any.
+    fn poll_reply(&mut self, cx: &mut Context<'_>) -> Poll<crate::Result<()>> {
+        if let Some(reply) = self.reply.take() {
+            let mut inner = Pin::new(&mut self.inner);
+            match inner.as_mut().poll_ready(cx) {
+                Poll::Ready(Ok(())) => {
+                    if let Message::Close(_) = reply {
+                        self.closed = true;
+                    }
+                    inner.start_send(reply).map_err(error::body)?;
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(error::body(err))),
+                Poll::Pending => {
+                    self.reply = Some(reply);
+                    return Poll::Pending;
+                }
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Stream for WebSocket {
+    type Item = crate::Result<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // a reply that could not be sent before is sent first
+        if self.reply.is_some() {
+            if let Poll::Ready(Err(err)) = self.poll_reply(cx) {
+                return Poll::Ready(Some(Err(err)));
+            }
+            let _ = Pin::new(&mut self.inner).poll_flush(cx);
+        }
+
+        let message = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+            Some(Ok(message)) => message,
+            Some(Err(err)) => return Poll::Ready(Some(Err(error::body(err)))),
+            None => return Poll::Ready(None),
+        };
+        match message {
+            Message::Ping(ref data) if !self.closed => {
+                self.reply = Some(Message::Pong(data.clone()));
+            }
+            Message::Close(ref frame) if !self.closed => {
+                // echo the status code, as is usual
+                self.reply = Some(Message::Close(frame.as_ref().map(|frame| CloseFrame {
+                    code: frame.code,
+                    reason: String::new(),
+                })));
+            }
+            _ => (),
+        }
+        // errors sending the reply are returned by the next poll
+        if let Poll::Ready(Ok(())) = self.poll_reply(cx) {
+            let _ = Pin::new(&mut self.inner).poll_flush(cx);
+        }
+        Poll::Ready(Some(Ok(message)))
+    }
+}
+
+impl Sink<Message> for WebSocket {
+    type Error = crate::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_reply(cx))?;
+        Pin::new(&mut self.inner)
+            .poll_ready(cx)
+            .map_err(error::body)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, message: Message) -> Result<(), Self::Error> {
+        if self.closed {
+            return Err(error::body("the WebSocket connection is closed"));
+        }
+        if let Message::Close(_) = message {
+            self.closed = true;
+        }
+        Pin::new(&mut self.inner)
+            .start_send(message)
+            .map_err(error::body)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_reply(cx))?;
+        Pin::new(&mut self.inner)
+            .poll_flush(cx)
+            .map_err(error::body)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_reply(cx))?;
+        Pin::new(&mut self.inner)
+            .poll_close(cx)
+            .map_err(error::body)
+    }
+}
+
+impl fmt::Debug for WebSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocket")
+            .field("protocol", &self.protocol)
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Message {
+        Message::Text(text)
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Message {
+        Message::Text(text.to_owned())
+    }
+}
+
+impl From<Bytes> for Message {
+    fn from(data: Bytes) -> Message {
+        Message::Binary(data)
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(data: Vec<u8>) -> Message {
+        Message::Binary(data.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_key_of_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn http_urls() {
+        let http = |url: &str| http_url(url.parse().unwrap()).map(|url| url.to_string());
+        assert_eq!(
+            http("ws://example.com/chat").unwrap(),
+            "http://example.com/chat"
+        );
+        assert_eq!(
+            http("wss://example.com:8443/").unwrap(),
+            "https://example.com:8443/"
+        );
+        assert_eq!(
+            http("https://example.com/").unwrap(),
+            "https://example.com/"
+        );
+        assert!(http("ftp://example.com/").is_err());
+    }
+}
@@ -0,0 +1,202 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use reqwest::auth::{Authorizing, CredentialProvider};
+use reqwest::header::HeaderValue;
+use reqwest::{Body, StatusCode, Url};
+use support::server;
+
+/// Hands out `Bearer 1`, `Bearer 2`, ... for the given origin, moving on to
+/// the next token on every refresh.
+struct Tokens {
+    origin: String,
+    current: Mutex<usize>,
+    authorizations: AtomicUsize,
+    refreshes: AtomicUsize,
+    refreshed: bool,
+}
+
+impl Tokens {
+    fn new(addr: std::net::SocketAddr, refreshed: bool) -> Arc<Tokens> {
+        Arc::new(Tokens {
+            origin: format!("http://{addr}"),
+            current: Mutex::new(1),
+            authorizations: AtomicUsize::new(0),
+            refreshes: AtomicUsize::new(0),
+            refreshed,
+        })
+    }
+
+    fn token(&self, url: &Url) -> Option<HeaderValue> {
+        if url.origin().ascii_serialization() != self.origin {
+            return None;
+        }
+        let current = *self.current.lock().unwrap();
+        Some(HeaderValue::try_from(format!("Bearer {current}")).unwrap())
+    }
+}
+
+impl CredentialProvider for Tokens {
+    fn authorization(&self, url: &Url) -> Authorizing {
+        self.authorizations.fetch_add(1, Ordering::SeqCst);
+        let token = self.token(url);
+        Box::pin(async move { Ok(token) })
+    }
+
+    fn refresh(&self, url: &Url, rejected: &HeaderValue) -> Authorizing {
+        self.refreshes.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(Some(rejected), self.token(url).as_ref());
+        if !self.refreshed {
+            return Box::pin(async { Ok(None) });
+        }
+        *self.current.lock().unwrap() += 1;
+        let token = self.token(url);
+        Box::pin(async move { Ok(token) })
+    }
+}
+
+/// A server that only accepts `Bearer 2`, counting its requests.
+fn server() -> (server::Server, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |req| {
+        hits2.fetch_add(1, Ordering::SeqCst);
+        async move {
+            let status = match req.headers().get("authorization") {
+                Some(value) if value == "Bearer 2" => StatusCode::OK,
+                _ => StatusCode::UNAUTHORIZED,
+            };
+            http::Response::builder()
+                .status(status)
+                .body(Body::default())
+                .unwrap()
+        }
+    });
+    (server, hits)
+}
+
+#[tokio::test]
+async fn rejected_credentials_are_refreshed() {
+    let _ = env_logger::try_init();
+
+    let (server, hits) = server();
+    let tokens = Tokens::new(server.addr(), true);
+    let client = reqwest::Client::builder()
+        .credential_provider(tokens.clone())
+        .build()
+        .unwrap();
+    let url = format!("http://{}/", server.addr());
+
+    let res = client.post(&url).body("hello").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // the refreshed token is used from now on
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+    assert_eq!(tokens.authorizations.load(Ordering::SeqCst), 2);
+    assert_eq!(tokens.refreshes.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn unauthorized_is_returned_without_refresh() {
+    let (server, hits) = server();
+    let tokens = Tokens::new(server.addr(), false);
+    let client = reqwest::Client::builder()
+        .credential_provider(tokens.clone())
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+    assert_eq!(tokens.refreshes.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn explicit_authorization_is_kept() {
+    let (server, hits) = server();
+    let tokens = Tokens::new(server.addr(), true);
+    let client = reqwest::Client::builder()
+        .credential_provider(tokens.clone())
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://{}/", server.addr()))
+        .bearer_auth("2")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+    assert_eq!(tokens.authorizations.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn provider_is_asked_again_after_cross_origin_redirect() {
+    let other = server::http(move |req| async move {
+        assert_eq!(req.headers().get("authorization"), None);
+        http::Response::default()
+    });
+
+    let location = format!("http://{}/", other.addr());
+    let server = server::http(move |req| {
+        let location = location.clone();
+        async move {
+            assert_eq!(req.headers()["authorization"], "Bearer 1");
+            http::Response::builder()
+                .status(StatusCode::FOUND)
+                .header("location", location)
+                .body(Body::default())
+                .unwrap()
+        }
+    });
+
+    let tokens = Tokens::new(server.addr(), false);
+    let client = reqwest::Client::builder()
+        .credential_provider(tokens.clone())
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.url().as_str(), format!("http://{}/", other.addr()));
+    assert_eq!(tokens.authorizations.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn provider_error_is_returned() {
+    struct Failing;
+
+    impl CredentialProvider for Failing {
+        fn authorization(&self, _url: &Url) -> Authorizing {
+            Box::pin(async { Err("token endpoint unavailable".into()) })
+        }
+    }
+
+    let (server, hits) = server();
+    let client = reqwest::Client::builder()
+        .credential_provider(Arc::new(Failing))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/", server.addr());
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_request());
+    assert_eq!(err.url().map(|u| u.as_str()), Some(url.as_str()));
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
@@ -355,3 +355,57 @@ async fn test_chunked_fragmented_response_with_extra_bytes() {
     assert!(err.is_decode());
     assert!(start.elapsed() >= DELAY_BETWEEN_RESPONSE_PARTS - DELAY_MARGIN);
 }
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn brotli_stacked_on_gzip() {
+    use std::io::Write;
+
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(b"hello world").unwrap();
+    let body = brotli_compress(&gzip.finish().unwrap());
+
+    let server = server::http(move |_req| {
+        let body = body.clone();
+        async move {
+            http::Response::builder()
+                .header("content-encoding", "gzip, br")
+                .body(body.into())
+                .unwrap()
+        }
+    });
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/stacked", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert!(res.headers().get("content-encoding").is_none());
+    assert_eq!(res.text().await.unwrap(), "hello world");
+}
+
+#[tokio::test]
+async fn brotli_request_body() {
+    let server = server::http(move |req| async move {
+        use http_body_util::BodyExt;
+
+        assert_eq!(req.headers()["content-encoding"], "br");
+        assert!(req.headers().contains_key("content-length"));
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        let mut decoded = Vec::new();
+        brotli_crate::Decompressor::new(&body[..], 4096)
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, b"hello world");
+        http::Response::default()
+    });
+
+    let res = reqwest::Client::new()
+        .post(format!("http://{}/upload", server.addr()))
+        .body("hello world")
+        .compress(reqwest::Encoding::Brotli)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
@@ -0,0 +1,282 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use reqwest::cache::{Cache, DiskStore, Mode};
+use reqwest::{Body, StatusCode};
+use support::server;
+
+/// A server counting its requests, answering each with the given headers
+/// and a body of "hello".
+fn counting(
+    headers: &'static [(&'static str, &'static str)],
+) -> (server::Server, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |_req| {
+        hits2.fetch_add(1, Ordering::SeqCst);
+        async move {
+            let mut res = http::Response::builder();
+            for (name, value) in headers {
+                res = res.header(*name, *value);
+            }
+            res.body(Body::from("hello")).unwrap()
+        }
+    });
+    (server, hits)
+}
+
+fn cached_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .cache(Cache::memory())
+        .no_proxy()
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn fresh_response_is_reused() {
+    let _ = env_logger::try_init();
+
+    let (server, hits) = counting(&[("cache-control", "max-age=60")]);
+    let client = cached_client();
+    let url = format!("http://{}/fresh", server.addr());
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "hello");
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.url().as_str(), url);
+    assert_eq!(res.headers()["age"], "0");
+    assert_eq!(res.text().await.unwrap(), "hello");
+
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn unread_body_is_not_stored() {
+    let (server, hits) = counting(&[("cache-control", "max-age=60")]);
+    let client = cached_client();
+    let url = format!("http://{}/unread", server.addr());
+
+    drop(client.get(&url).send().await.unwrap());
+    client.get(&url).send().await.unwrap();
+
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn stale_response_is_revalidated() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |req| {
+        hits2.fetch_add(1, Ordering::SeqCst);
+        async move {
+            if req.headers().get("if-none-match").map(|v| v.as_bytes()) == Some(b"\"v1\"") {
+                http::Response::builder()
+                    .status(StatusCode::NOT_MODIFIED)
+                    .header("etag", "\"v1\"")
+                    .header("x-revalidated", "yes")
+                    .body(Body::default())
+                    .unwrap()
+            } else {
+                http::Response::builder()
+                    .header("cache-control", "no-cache")
+                    .header("etag", "\"v1\"")
+                    .body(Body::from("hello"))
+                    .unwrap()
+            }
+        }
+    });
+
+    let client = cached_client();
+    let url = format!("http://{}/etag", server.addr());
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "hello");
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["x-revalidated"], "yes");
+    assert_eq!(res.text().await.unwrap(), "hello");
+
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn revalidates_with_last_modified() {
+    let server = server::http(move |req| async move {
+        if let Some(since) = req.headers().get("if-modified-since") {
+            assert_eq!(since, "Sun, 06 Nov 1994 08:49:37 GMT");
+            http::Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::default())
+                .unwrap()
+        } else {
+            http::Response::builder()
+                .header("cache-control", "max-age=0")
+                .header("last-modified", "Sun, 06 Nov 1994 08:49:37 GMT")
+                .body(Body::from("hello"))
+                .unwrap()
+        }
+    });
+
+    let client = cached_client();
+    let url = format!("http://{}/last-modified", server.addr());
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "hello");
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.text().await.unwrap(), "hello");
+}
+
+#[tokio::test]
+async fn vary_must_match() {
+    let (server, hits) = counting(&[("cache-control", "max-age=60"), ("vary", "accept")]);
+    let client = cached_client();
+    let url = format!("http://{}/vary", server.addr());
+
+    for accept in ["text/html", "text/html", "application/json"] {
+        let res = client
+            .get(&url)
+            .header("accept", accept)
+            .send()
+            .await
+            .unwrap();
+        res.text().await.unwrap();
+    }
+
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn no_store_response_is_not_stored() {
+    let (server, hits) = counting(&[("cache-control", "no-store, max-age=60")]);
+    let client = cached_client();
+    let url = format!("http://{}/no-store", server.addr());
+
+    for _ in 0..2 {
+        let res = client.get(&url).send().await.unwrap();
+        res.text().await.unwrap();
+    }
+
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn request_modes() {
+    let (server, hits) = counting(&[("cache-control", "max-age=60")]);
+    let client = cached_client();
+    let url = format!("http://{}/modes", server.addr());
+
+    let res = client
+        .get(&url)
+        .cache_mode(Mode::OnlyIfCached)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+    let res = client
+        .get(&url)
+        .cache_mode(Mode::NoStore)
+        .send()
+        .await
+        .unwrap();
+    res.text().await.unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    let res = client.get(&url).send().await.unwrap();
+    res.text().await.unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    let res = client
+        .get(&url)
+        .cache_mode(Mode::OnlyIfCached)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "hello");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    let res = client
+        .get(&url)
+        .cache_mode(Mode::Reload)
+        .send()
+        .await
+        .unwrap();
+    res.text().await.unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+    let res = client
+        .get(&url)
+        .header("cache-control", "no-cache")
+        .send()
+        .await
+        .unwrap();
+    res.text().await.unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn unsafe_request_invalidates() {
+    let (server, hits) = counting(&[("cache-control", "max-age=60")]);
+    let client = cached_client();
+    let url = format!("http://{}/invalidate", server.addr());
+
+    let res = client.get(&url).send().await.unwrap();
+    res.text().await.unwrap();
+    client.post(&url).send().await.unwrap();
+    let res = client.get(&url).send().await.unwrap();
+    res.text().await.unwrap();
+
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn too_large_response_is_not_stored() {
+    let (server, hits) = counting(&[("cache-control", "max-age=60")]);
+    let client = reqwest::Client::builder()
+        .cache(Cache::memory().max_entry_size(4))
+        .no_proxy()
+        .build()
+        .unwrap();
+    let url = format!("http://{}/large", server.addr());
+
+    for _ in 0..2 {
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "hello");
+    }
+
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn disk_store_is_shared_between_clients() {
+    let (server, hits) = counting(&[("cache-control", "max-age=60")]);
+    let dir = std::env::temp_dir().join(format!(
+        "reqwest-cache-test-{}-{}",
+        std::process::id(),
+        server.addr().port()
+    ));
+    let url = format!("http://{}/disk", server.addr());
+
+    for _ in 0..2 {
+        let client = reqwest::Client::builder()
+            .cache(Cache::new(Arc::new(DiskStore::new(&dir))))
+            .no_proxy()
+            .build()
+            .unwrap();
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "hello");
+    }
+
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+    let _ = std::fs::remove_dir_all(dir);
+}
@@ -158,6 +158,154 @@ async fn response_json() {
     assert_eq!("Hello", text);
 }
 
+#[tokio::test]
+#[cfg(feature = "json")]
+async fn response_json_lines() {
+    use futures_util::StreamExt;
+
+    let server = server::http(move |_req| async {
+        http::Response::new("{\"id\":1}\n\n{\"id\":2}\r\n{\"id\":3}".into())
+    });
+
+    let res = Client::new()
+        .get(&format!("http://{}/ndjson", server.addr()))
+        .send()
+        .await
+        .expect("Failed to get");
+    let records = res
+        .json_lines::<HashMap<String, u32>>()
+        .map(|record| record.expect("Failed to get json")["id"])
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(records, [1, 2, 3]);
+}
+
+#[tokio::test]
+#[cfg(feature = "json")]
+async fn json_lines_body() {
+    use http_body_util::BodyExt;
+
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers()[CONTENT_TYPE], "application/x-ndjson");
+        assert_eq!(req.headers()[TRANSFER_ENCODING], "chunked");
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "{\"id\":1}\n{\"id\":2}\n");
+        http::Response::default()
+    });
+
+    let records = (1..=2).map(|id| HashMap::from([("id", id)]));
+    let res = Client::new()
+        .post(&format!("http://{}/ndjson", server.addr()))
+        .json_lines(futures_util::stream::iter(records))
+        .send()
+        .await
+        .expect("Failed to post");
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn max_response_body_size() {
+    use http_body_util::StreamBody;
+    use hyper::body::Frame;
+
+    let server = server::http(move |req| async move {
+        if req.uri() == "/chunked" {
+            let chunks = futures_util::stream::iter((0..4).map(|_| {
+                Ok::<_, std::convert::Infallible>(Frame::data(bytes::Bytes::from(vec![b'x'; 1000])))
+            }));
+            http::Response::new(reqwest::Body::wrap(StreamBody::new(chunks)))
+        } else {
+            http::Response::new(vec![b'x'; 4000].into())
+        }
+    });
+
+    let client = Client::builder()
+        .max_response_body_size(3000)
+        .build()
+        .unwrap();
+
+    // a larger Content-Length fails before reading the body
+    let res = client
+        .get(&format!("http://{}/sized", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.content_length(), Some(4000));
+    let err = res.bytes().await.unwrap_err();
+    assert!(err.is_body());
+    assert!(err.is_body_too_large());
+
+    let mut res = client
+        .get(&format!("http://{}/chunked", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    let mut read = 0;
+    let err = loop {
+        match res.chunk().await {
+            Ok(Some(chunk)) => read += chunk.len(),
+            Ok(None) => panic!("body was read past the limit"),
+            Err(err) => break err,
+        }
+    };
+    assert_eq!(read, 3000);
+    assert!(err.is_body_too_large());
+    assert!(res.chunk().await.unwrap().is_none());
+
+    // the limit can be raised for a request
+    let body = client
+        .get(&format!("http://{}/chunked", server.addr()))
+        .max_response_body_size(4000)
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(body.len(), 4000);
+}
+
+#[tokio::test]
+async fn upload_and_download_progress() {
+    use std::sync::{Arc, Mutex};
+
+    let server = server::http(move |req| async move {
+        use http_body_util::BodyExt;
+
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body.len(), 5000);
+        http::Response::new(vec![b'x'; 3000].into())
+    });
+
+    let uploaded = Arc::new(Mutex::new(Vec::new()));
+    let downloaded = Arc::new(Mutex::new(Vec::new()));
+    let (up, down) = (uploaded.clone(), downloaded.clone());
+
+    let body = Client::new()
+        .post(&format!("http://{}/progress", server.addr()))
+        .body(vec![b'y'; 5000])
+        .on_upload_progress(move |sent, total| up.lock().unwrap().push((sent, total)))
+        .on_download_progress(move |received, decoded, total| {
+            down.lock().unwrap().push((received, decoded, total))
+        })
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(body.len(), 3000);
+
+    let uploaded = uploaded.lock().unwrap();
+    assert_eq!(uploaded.last(), Some(&(5000, Some(5000))));
+    let downloaded = downloaded.lock().unwrap();
+    assert!(downloaded.windows(2).all(|w| w[0].0 < w[1].0));
+    assert!(downloaded
+        .iter()
+        .all(|&(received, decoded, _)| received == decoded));
+    assert_eq!(downloaded.last(), Some(&(3000, 3000, Some(3000))));
+}
+
 #[tokio::test]
 async fn body_pipe_response() {
     use http_body_util::BodyExt;
@@ -263,6 +411,44 @@ async fn overridden_dns_resolution_with_gai_multiple() {
     assert_eq!("Hello", text);
 }
 
+#[tokio::test]
+async fn ip_family_filters_resolved_addrs() {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let overridden_domain = "rust-lang.org";
+    let url = format!(
+        "http://{overridden_domain}:{}/ip_family",
+        server.addr().port()
+    );
+    let v6 = std::net::SocketAddr::new(
+        std::net::IpAddr::V6(std::net::Ipv6Addr::LOCALHOST),
+        server.addr().port(),
+    );
+
+    // the server runs on IPv4 localhost only
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .resolve_to_addrs(overridden_domain, &[v6, server.addr()])
+        .ip_family(reqwest::dns::IpFamily::V4Only)
+        .happy_eyeballs_timeout(None)
+        .build()
+        .expect("client builder");
+    let res = client.get(&url).send().await.expect("request");
+    assert_eq!(res.text().await.expect("Failed to get text"), "Hello");
+
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .resolve(overridden_domain, server.addr())
+        .ip_family(reqwest::dns::IpFamily::V6Only)
+        .retry(reqwest::retry::Policy::none())
+        .build()
+        .expect("client builder");
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_connect());
+    assert!(format!("{err:?}").contains("no IPv6 address found for rust-lang.org"));
+}
+
 #[cfg(feature = "hickory-dns")]
 #[tokio::test]
 async fn overridden_dns_resolution_with_hickory_dns() {
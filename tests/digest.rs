@@ -0,0 +1,278 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use http_body_util::BodyExt;
+use md5::{Digest, Md5};
+use reqwest::{Body, StatusCode};
+use sha2::Sha256;
+use support::server;
+
+const USER: &str = "Mufasa";
+const PASS: &str = "Circle of Life";
+const REALM: &str = "http-auth@example.org";
+
+fn md5(data: String) -> String {
+    hex(&Md5::digest(data.as_bytes()))
+}
+
+fn sha256(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn params(authorization: &str) -> HashMap<String, String> {
+    authorization
+        .strip_prefix("Digest ")
+        .expect("digest authorization")
+        .split(", ")
+        .map(|param| {
+            let (name, value) = param.split_once('=').unwrap();
+            (name.to_owned(), value.trim_matches('"').to_owned())
+        })
+        .collect()
+}
+
+fn unauthorized(challenge: &str) -> http::Response<Body> {
+    http::Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header("www-authenticate", challenge)
+        .body(Body::default())
+        .unwrap()
+}
+
+/// Check a `qop=auth` MD5 answer, returning its nonce count.
+fn check_md5(req: &http::request::Parts, nonce: &str) -> Option<String> {
+    let authorization = req.headers.get("authorization")?.to_str().unwrap();
+    let p = params(authorization);
+    assert_eq!(p["username"], USER);
+    assert_eq!(p["realm"], REALM);
+    assert_eq!(p["uri"], req.uri.path_and_query().unwrap().as_str());
+    assert_eq!(p["algorithm"], "MD5");
+    assert_eq!(p["qop"], "auth");
+    assert_eq!(p["opaque"], "opaque-data");
+
+    let ha1 = md5(format!("{USER}:{REALM}:{PASS}"));
+    let ha2 = md5(format!("{}:{}", req.method, p["uri"]));
+    let expected = md5(format!(
+        "{ha1}:{nonce}:{}:{}:auth:{ha2}",
+        p["nc"], p["cnonce"]
+    ));
+    if p["nonce"] == nonce && p["response"] == expected {
+        Some(p["nc"].clone())
+    } else {
+        None
+    }
+}
+
+fn md5_server(hits: Arc<AtomicUsize>) -> server::Server {
+    server::http(move |req| {
+        hits.fetch_add(1, Ordering::SeqCst);
+        async move {
+            let (parts, _) = req.into_parts();
+            match check_md5(&parts, "nonce-1") {
+                Some(nc) => http::Response::builder()
+                    .header("x-nc", nc)
+                    .body(Body::from("welcome"))
+                    .unwrap(),
+                None => unauthorized(&format!(
+                    "Basic realm=\"{REALM}\", Digest realm=\"{REALM}\", qop=\"auth,auth-int\", \
+                     algorithm=MD5, nonce=\"nonce-1\", opaque=\"opaque-data\""
+                )),
+            }
+        }
+    })
+}
+
+#[tokio::test]
+async fn answers_digest_challenge() {
+    let _ = env_logger::try_init();
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = md5_server(hits.clone());
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/dir/index.html?a=b", server.addr()))
+        .digest_auth(USER, PASS)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["x-nc"], "00000001");
+    assert_eq!(res.text().await.unwrap(), "welcome");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn later_requests_reuse_challenge() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = md5_server(hits.clone());
+    let client = reqwest::Client::builder()
+        .digest_auth(USER, PASS)
+        .build()
+        .unwrap();
+
+    for (path, nc) in [("/a", "00000001"), ("/b", "00000002"), ("/a", "00000003")] {
+        let res = client
+            .get(format!("http://{}{path}", server.addr()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["x-nc"], nc);
+    }
+
+    // only the first request was challenged
+    assert_eq!(hits.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn request_without_credentials_is_not_answered() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = md5_server(hits.clone());
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn wrong_password_returns_unauthorized() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server = md5_server(hits.clone());
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/", server.addr()))
+        .digest_auth(USER, "wrong")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn body_is_resent_with_auth_int() {
+    let server = server::http(move |req| async move {
+        let (parts, body) = req.into_parts();
+        let body = body.collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello");
+
+        let authorization = match parts.headers.get("authorization") {
+            Some(value) => value.to_str().unwrap(),
+            None => {
+                return unauthorized(&format!(
+                    "Digest realm=\"{REALM}\", qop=\"auth-int\", algorithm=SHA-256-sess, \
+                     nonce=\"nonce-2\""
+                ))
+            }
+        };
+        let p = params(authorization);
+        assert_eq!(p["algorithm"], "SHA-256-sess");
+        assert_eq!(p["qop"], "auth-int");
+
+        let ha1 = sha256(format!("{USER}:{REALM}:{PASS}").as_bytes());
+        let ha1 = sha256(format!("{ha1}:nonce-2:{}", p["cnonce"]).as_bytes());
+        let ha2 = sha256(format!("POST:{}:{}", p["uri"], sha256(&body)).as_bytes());
+        let expected = sha256(
+            format!(
+                "{ha1}:nonce-2:{}:{}:auth-int:{ha2}",
+                p["nc"], p["cnonce"]
+            )
+            .as_bytes(),
+        );
+        assert_eq!(p["response"], expected);
+
+        http::Response::default()
+    });
+
+    let res = reqwest::Client::new()
+        .post(format!("http://{}/upload", server.addr()))
+        .digest_auth(USER, PASS)
+        .body("hello")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn stale_nonce_is_answered_again() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |req| {
+        let hit = hits2.fetch_add(1, Ordering::SeqCst);
+        async move {
+            let (parts, _) = req.into_parts();
+            let challenge = |nonce: &str, stale: bool| {
+                unauthorized(&format!(
+                    "Digest realm=\"{REALM}\", qop=auth, nonce=\"{nonce}\", \
+                     opaque=\"opaque-data\", stale={stale}"
+                ))
+            };
+            match hit {
+                0 => challenge("old", false),
+                1 => {
+                    assert!(check_md5(&parts, "old").is_some());
+                    challenge("new", true)
+                }
+                _ => match check_md5(&parts, "new") {
+                    Some(_) => http::Response::default(),
+                    None => challenge("new", false),
+                },
+            }
+        }
+    });
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/", server.addr()))
+        .digest_auth(USER, PASS)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn credentials_are_not_answered_after_cross_origin_redirect() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let other = md5_server(hits.clone());
+
+    let location = format!("http://{}/", other.addr());
+    let server = server::http(move |_req| {
+        let location = location.clone();
+        async move {
+            http::Response::builder()
+                .status(StatusCode::FOUND)
+                .header("location", location)
+                .body(Body::default())
+                .unwrap()
+        }
+    });
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/", server.addr()))
+        .digest_auth(USER, PASS)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,144 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::dns::{Addrs, CachingResolver, Name, Resolve, Resolving};
+use support::server;
+
+/// Resolves `ok.test` to `127.0.0.1` after a little while, times out for
+/// `flaky.test`, and doesn't find other names, counting its lookups.
+#[derive(Clone, Default)]
+struct Counting {
+    lookups: Arc<AtomicUsize>,
+}
+
+impl Counting {
+    fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::SeqCst)
+    }
+}
+
+impl Resolve for Counting {
+    fn resolve(&self, name: Name) -> Resolving {
+        self.lookups.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            if name.as_str() == "ok.test" {
+                let addrs: Addrs = Box::new(std::iter::once(SocketAddr::from(([127, 0, 0, 1], 0))));
+                Ok(addrs)
+            } else if name.as_str() == "flaky.test" {
+                Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "lookup timed out").into())
+            } else {
+                let message = format!("no such host: {}", name.as_str());
+                Err(std::io::Error::new(std::io::ErrorKind::NotFound, message).into())
+            }
+        })
+    }
+}
+
+async fn resolve(resolver: &CachingResolver, name: &str) -> Result<Vec<SocketAddr>, String> {
+    match resolver.resolve(name.parse().unwrap()).await {
+        Ok(addrs) => Ok(addrs.collect()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn results_are_cached_for_ttl() {
+    let counting = Counting::default();
+    let resolver = CachingResolver::new(counting.clone()).ttl(Duration::from_millis(200));
+
+    let expected = vec![SocketAddr::from(([127, 0, 0, 1], 0))];
+    assert_eq!(resolve(&resolver, "ok.test").await.unwrap(), expected);
+    assert_eq!(resolve(&resolver, "OK.test").await.unwrap(), expected);
+    assert_eq!(counting.lookups(), 1);
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(resolve(&resolver, "ok.test").await.unwrap(), expected);
+    assert_eq!(counting.lookups(), 2);
+
+    resolver.invalidate("ok.test");
+    resolve(&resolver, "ok.test").await.unwrap();
+    assert_eq!(counting.lookups(), 3);
+
+    resolver.clear();
+    resolve(&resolver, "ok.test").await.unwrap();
+    assert_eq!(counting.lookups(), 4);
+}
+
+#[tokio::test]
+async fn missing_names_are_cached_for_negative_ttl() {
+    let counting = Counting::default();
+    let resolver = CachingResolver::new(counting.clone()).negative_ttl(Duration::from_millis(100));
+
+    for _ in 0..2 {
+        let err = resolve(&resolver, "missing.test").await.unwrap_err();
+        assert_eq!(err, "no such host: missing.test");
+    }
+    assert_eq!(counting.lookups(), 1);
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    resolve(&resolver, "missing.test").await.unwrap_err();
+    assert_eq!(counting.lookups(), 2);
+}
+
+#[tokio::test]
+async fn transient_failures_are_not_cached() {
+    let counting = Counting::default();
+    let resolver = CachingResolver::new(counting.clone());
+
+    for _ in 0..2 {
+        let err = resolve(&resolver, "flaky.test").await.unwrap_err();
+        assert_eq!(err, "lookup timed out");
+    }
+    assert_eq!(counting.lookups(), 2);
+}
+
+#[tokio::test]
+async fn unbounded_ttls_never_expire() {
+    let counting = Counting::default();
+    let resolver = CachingResolver::new(counting.clone())
+        .ttl(Duration::MAX)
+        .negative_ttl(Duration::MAX);
+
+    for _ in 0..2 {
+        resolve(&resolver, "ok.test").await.unwrap();
+        resolve(&resolver, "missing.test").await.unwrap_err();
+    }
+    assert_eq!(counting.lookups(), 2);
+}
+
+#[tokio::test]
+async fn concurrent_lookups_are_collapsed() {
+    let counting = Counting::default();
+    let resolver = CachingResolver::new(counting.clone());
+
+    let lookups = (0..10).map(|_| resolve(&resolver, "ok.test"));
+    let results = futures_util::future::join_all(lookups).await;
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(counting.lookups(), 1);
+}
+
+#[tokio::test]
+async fn client_uses_caching_resolver() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    let counting = Counting::default();
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .pool_max_idle_per_host(0)
+        .dns_resolver(Arc::new(CachingResolver::new(counting.clone())))
+        .build()
+        .unwrap();
+
+    let url = format!("http://ok.test:{}/", server.addr().port());
+    for _ in 0..3 {
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "Hello");
+    }
+    assert_eq!(counting.lookups(), 1);
+}
@@ -0,0 +1,169 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use http_body_util::BodyExt;
+use reqwest::dns::{CachingResolver, DohResolver, Resolve};
+use support::server;
+
+/// Answer a DNS query for `*.test` with `127.0.0.1` or `::1`, through a
+/// `CNAME` record, and with `NXDOMAIN` for other names.
+fn answer(query: &[u8]) -> Vec<u8> {
+    let question = &query[12..];
+    let name_len = question.iter().position(|&b| b == 0).unwrap() + 1;
+    let rtype = u16::from_be_bytes([question[name_len], question[name_len + 1]]);
+    let name = &question[..name_len];
+    let known = name.ends_with(b"\x04test\x00");
+
+    let mut message = vec![0, 0, 0x81, if known { 0x80 } else { 0x83 }, 0, 1, 0, 0];
+    message.extend_from_slice(&[0, 0, 0, 0]);
+    message.extend_from_slice(&question[..name_len + 4]);
+    if !known {
+        return message;
+    }
+    message[7] = 2;
+
+    // CNAME to `target.test`
+    message.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 13]);
+    message.extend_from_slice(b"\x06target\x04test\x00");
+    let target = (12 + name_len + 4 + 12) as u8;
+    let rdata: &[u8] = if rtype == 1 {
+        &[127, 0, 0, 1]
+    } else {
+        &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]
+    };
+    message.extend_from_slice(&[0xc0, target, 0, rtype as u8, 0, 1, 0, 0, 0, 60]);
+    message.extend_from_slice(&[0, rdata.len() as u8]);
+    message.extend_from_slice(rdata);
+    message
+}
+
+/// Serve DNS queries at `/dns-query`, recording their methods, and
+/// "Hello" otherwise.
+fn doh_server(methods: Arc<Mutex<Vec<http::Method>>>) -> server::Server {
+    server::http(move |req| {
+        let methods = methods.clone();
+        async move {
+            if req.uri().path() != "/dns-query" {
+                return http::Response::new("Hello".into());
+            }
+            assert_eq!(req.headers()["accept"], "application/dns-message");
+            methods.lock().unwrap().push(req.method().clone());
+            let query = if req.method() == http::Method::POST {
+                assert_eq!(req.headers()["content-type"], "application/dns-message");
+                req.into_body().collect().await.unwrap().to_bytes().to_vec()
+            } else {
+                let dns = req.uri().query().unwrap().strip_prefix("dns=").unwrap();
+                BASE64_URL_SAFE_NO_PAD.decode(dns).unwrap()
+            };
+            http::Response::builder()
+                .header("content-type", "application/dns-message")
+                .body(answer(&query).into())
+                .unwrap()
+        }
+    })
+}
+
+async fn resolve<R: Resolve>(resolver: &R, name: &str) -> Result<Vec<IpAddr>, String> {
+    match resolver.resolve(name.parse().unwrap()).await {
+        Ok(addrs) => Ok(addrs.map(|addr| addr.ip()).collect()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn doh_get_and_post() {
+    let methods = Arc::default();
+    let server = doh_server(Arc::clone(&methods));
+    let endpoint = format!("http://{}/dns-query", server.addr());
+
+    let expected = vec![
+        IpAddr::from([127, 0, 0, 1]),
+        IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1]),
+    ];
+    let resolver = DohResolver::new(&endpoint).unwrap();
+    assert_eq!(resolve(&resolver, "app.test").await.unwrap(), expected);
+    assert_eq!(*methods.lock().unwrap(), [http::Method::GET; 2]);
+
+    methods.lock().unwrap().clear();
+    let resolver = DohResolver::builder(&endpoint).post(true).build().unwrap();
+    assert_eq!(resolve(&resolver, "app.test.").await.unwrap(), expected);
+    assert_eq!(*methods.lock().unwrap(), [http::Method::POST; 2]);
+}
+
+#[tokio::test]
+async fn doh_nxdomain() {
+    let server = doh_server(Arc::default());
+    let resolver = DohResolver::new(format!("http://{}/dns-query", server.addr())).unwrap();
+
+    let err = resolver
+        .resolve("app.example".parse().unwrap())
+        .await
+        .err()
+        .unwrap();
+    let err = err.downcast_ref::<std::io::Error>().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    assert_eq!(err.to_string(), "no such domain for app.example");
+}
+
+#[tokio::test]
+async fn doh_nxdomain_is_cached() {
+    let methods = Arc::default();
+    let server = doh_server(Arc::clone(&methods));
+    let resolver = CachingResolver::new(
+        DohResolver::new(format!("http://{}/dns-query", server.addr())).unwrap(),
+    );
+
+    for _ in 0..2 {
+        let err = resolve(&resolver, "app.example").await.unwrap_err();
+        assert_eq!(err, "no such domain for app.example");
+    }
+    // only the queries of the first lookup were sent
+    assert_eq!(methods.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn doh_server_error() {
+    let server = server::http(move |_req| async {
+        http::Response::builder()
+            .status(http::StatusCode::BAD_REQUEST)
+            .body(reqwest::Body::default())
+            .unwrap()
+    });
+    let resolver = DohResolver::new(format!("http://{}/dns-query", server.addr())).unwrap();
+
+    let err = resolve(&resolver, "app.test").await.unwrap_err();
+    assert!(err.contains("400 Bad Request"), "{err}");
+}
+
+#[tokio::test]
+async fn client_with_bootstrapped_doh_resolver() {
+    let server = doh_server(Arc::default());
+    let port = server.addr().port();
+
+    // `doh.test` can only be resolved with the bootstrap address
+    let resolver = DohResolver::builder(format!("http://doh.test:{port}/dns-query"))
+        .bootstrap(&[IpAddr::from([127, 0, 0, 1])])
+        .build()
+        .unwrap();
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .dns_resolver(Arc::new(resolver))
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://app.test:{port}/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        res.remote_addr(),
+        Some(SocketAddr::from(([127, 0, 0, 1], port)))
+    );
+    assert_eq!(res.text().await.unwrap(), "Hello");
+}
@@ -0,0 +1,173 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::error::Error as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use http::header::{ACCEPT_ENCODING, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+use reqwest::{Body, StatusCode};
+use support::server;
+
+const SIZE: usize = 10_000;
+
+fn content() -> Vec<u8> {
+    (0..SIZE).map(|i| (i % 251) as u8).collect()
+}
+
+fn target(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("reqwest-download-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+fn part(path: &std::path::Path) -> PathBuf {
+    let mut name = path.file_name().unwrap().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// The requested range of `req`, if any.
+fn range(req: &http::Request<hyper::body::Incoming>) -> Option<(usize, usize)> {
+    let range = req.headers().get(RANGE)?.to_str().unwrap();
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    Some((start.parse().unwrap(), end.parse().unwrap()))
+}
+
+/// A server for a resource that supports range requests, whose answers
+/// are changed by `partial`, and which counts its requests.
+fn ranged_server<F>(partial: F) -> (server::Server, Arc<AtomicUsize>)
+where
+    F: Fn(usize, usize, Vec<u8>) -> http::Response<Body> + Clone + Send + Sync + 'static,
+{
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |req| {
+        hits2.fetch_add(1, Ordering::SeqCst);
+        let partial = partial.clone();
+        async move {
+            assert_eq!(req.headers()[ACCEPT_ENCODING], "identity");
+            match range(&req) {
+                Some((start, end)) => {
+                    if start > 0 {
+                        assert_eq!(req.headers()[IF_RANGE], "\"v1\"");
+                    }
+                    partial(start, end, content()[start..=end].to_vec())
+                }
+                None => http::Response::builder()
+                    .header(ETAG, "\"v1\"")
+                    .body(content().into())
+                    .unwrap(),
+            }
+        }
+    });
+    (server, hits)
+}
+
+fn partial_content(start: usize, end: usize, data: Vec<u8>) -> http::Response<Body> {
+    http::Response::builder()
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(ETAG, "\"v1\"")
+        .header(CONTENT_RANGE, format!("bytes {start}-{end}/{SIZE}"))
+        .body(data.into())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn download_in_segments() {
+    let _ = env_logger::try_init();
+
+    let (server, hits) = ranged_server(partial_content);
+    let path = target("segments.bin");
+
+    let size = reqwest::Client::new()
+        .download(format!("http://{}/artifact", server.addr()))
+        .segments(4)
+        .to_file(&path)
+        .await
+        .unwrap();
+
+    assert_eq!(size, SIZE as u64);
+    assert_eq!(std::fs::read(&path).unwrap(), content());
+    assert!(!part(&path).exists());
+    // the probe, and a request for each segment
+    assert_eq!(hits.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn download_without_range_support() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |_req| {
+        hits2.fetch_add(1, Ordering::SeqCst);
+        async move { http::Response::new(content().into()) }
+    });
+    let path = target("whole.bin");
+
+    let size = reqwest::Client::new()
+        .download(format!("http://{}/artifact", server.addr()))
+        .segments(4)
+        .to_file(&path)
+        .await
+        .unwrap();
+
+    assert_eq!(size, SIZE as u64);
+    assert_eq!(std::fs::read(&path).unwrap(), content());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn download_of_changed_resource_fails() {
+    let (server, _hits) = ranged_server(|start, end, data| {
+        if start == 0 {
+            return partial_content(start, end, data);
+        }
+        // If-Range didn't match, so the whole new resource is sent
+        http::Response::builder()
+            .header(ETAG, "\"v2\"")
+            .body(content().into())
+            .unwrap()
+    });
+    let path = target("changed.bin");
+
+    let err = reqwest::Client::new()
+        .download(format!("http://{}/artifact", server.addr()))
+        .to_file(&path)
+        .await
+        .unwrap_err();
+
+    assert!(err.is_body());
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "resource changed between range requests"
+    );
+    assert!(!path.exists());
+    assert!(!part(&path).exists());
+}
+
+#[tokio::test]
+async fn download_of_short_segment_fails() {
+    let (server, _hits) = ranged_server(|start, end, mut data| {
+        if start > 0 {
+            data.truncate(data.len() - 1);
+        }
+        partial_content(start, end, data)
+    });
+    let path = target("short.bin");
+
+    let err = reqwest::Client::new()
+        .download(format!("http://{}/artifact", server.addr()))
+        .segments(2)
+        .to_file(&path)
+        .await
+        .unwrap_err();
+
+    assert!(err.is_body());
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "received 4999 bytes, but expected 5000"
+    );
+    assert!(!path.exists());
+    assert!(!part(&path).exists());
+}
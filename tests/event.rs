@@ -0,0 +1,277 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::error::Error as StdError;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use http::Uri;
+use reqwest::dns::Name;
+use reqwest::event::{EventListener, ResponseTimings};
+use reqwest::{StatusCode, Url};
+use support::server;
+
+/// Records every event as a line of text.
+#[derive(Default)]
+struct Recorder {
+    events: Mutex<Vec<String>>,
+}
+
+impl Recorder {
+    fn push(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+impl EventListener for Recorder {
+    fn dns_start(&self, name: &Name) {
+        self.push(format!("dns_start {}", name.as_str()));
+    }
+
+    fn dns_end(&self, name: &Name, addrs: &[SocketAddr]) {
+        self.push(format!("dns_end {} {addrs:?}", name.as_str()));
+    }
+
+    fn connect_start(&self, dst: &Uri) {
+        self.push(format!("connect_start {dst}"));
+    }
+
+    fn connect_end(&self, dst: &Uri) {
+        self.push(format!("connect_end {dst}"));
+    }
+
+    fn connect_failed(&self, dst: &Uri, _error: &(dyn StdError + 'static)) {
+        self.push(format!("connect_failed {dst}"));
+    }
+
+    fn connection_acquired(&self, url: &Url, reused: bool) {
+        self.push(format!("connection_acquired {url} reused={reused}"));
+    }
+
+    fn request_headers_sent(&self, url: &Url) {
+        self.push(format!("request_headers_sent {url}"));
+    }
+
+    fn response_headers_received(&self, url: &Url, status: StatusCode) {
+        self.push(format!("response_headers_received {url} {status}"));
+    }
+
+    fn connection_released(&self, url: &Url) {
+        self.push(format!("connection_released {url}"));
+    }
+
+    fn call_failed(&self, error: &reqwest::Error) {
+        self.push(format!("call_failed {:?}", error.url().map(Url::as_str)));
+    }
+}
+
+#[tokio::test]
+async fn new_and_reused_connection() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |_req| async { http::Response::new("hello".into()) });
+    let recorder = Arc::new(Recorder::default());
+    let client = reqwest::Client::builder()
+        .resolve("events.test", server.addr())
+        .event_listener(recorder.clone())
+        .build()
+        .unwrap();
+
+    let port = server.addr().port();
+    let url = format!("http://events.test:{port}/");
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "hello");
+
+    assert_eq!(
+        recorder.take(),
+        [
+            format!("connect_start {url}"),
+            "dns_start events.test".to_owned(),
+            format!("dns_end events.test [{}]", server.addr()),
+            format!("connect_end {url}"),
+            format!("connection_acquired {url} reused=false"),
+            format!("request_headers_sent {url}"),
+            format!("response_headers_received {url} 200 OK"),
+            format!("connection_released {url}"),
+        ]
+    );
+
+    let res = client.get(&url).send().await.unwrap();
+    drop(res);
+
+    assert_eq!(
+        recorder.take(),
+        [
+            format!("connection_acquired {url} reused=true"),
+            format!("request_headers_sent {url}"),
+            format!("response_headers_received {url} 200 OK"),
+            format!("connection_released {url}"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn dns_end_reports_ip_family_order() {
+    let server = server::http(move |_req| async { http::Response::default() });
+    let v6 = SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], server.addr().port()));
+    let recorder = Arc::new(Recorder::default());
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .resolve_to_addrs("events.test", &[server.addr(), v6])
+        .ip_family(reqwest::dns::IpFamily::PreferV6)
+        .event_listener(recorder.clone())
+        .build()
+        .unwrap();
+
+    // IPv6 is tried first, the server only listens on IPv4
+    let url = format!("http://events.test:{}/", server.addr().port());
+    client.get(&url).send().await.unwrap();
+
+    let events = recorder.take();
+    assert!(events.contains(&format!("dns_end events.test [{v6}, {}]", server.addr())));
+}
+
+#[tokio::test]
+async fn redirect_is_reported_per_hop() {
+    let server = server::http(move |req| async move {
+        if req.uri().path() == "/start" {
+            http::Response::builder()
+                .status(StatusCode::FOUND)
+                .header("location", "/end")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            http::Response::default()
+        }
+    });
+    let recorder = Arc::new(Recorder::default());
+    let client = reqwest::Client::builder()
+        .event_listener(recorder.clone())
+        .build()
+        .unwrap();
+
+    let start = format!("http://{}/start", server.addr());
+    let end = format!("http://{}/end", server.addr());
+    let res = client.get(&start).send().await.unwrap();
+    assert_eq!(res.url().as_str(), end);
+
+    let events = recorder.take();
+    assert!(!events.iter().any(|event| event.starts_with("dns_")));
+    assert!(events.contains(&format!("response_headers_received {start} 302 Found")));
+    assert!(events.contains(&format!("connection_released {start}")));
+    assert!(events.contains(&format!("response_headers_received {end} 200 OK")));
+}
+
+#[tokio::test]
+async fn failed_connect_and_call() {
+    let addr = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    };
+    let recorder = Arc::new(Recorder::default());
+    let client = reqwest::Client::builder()
+        .event_listener(recorder.clone())
+        .build()
+        .unwrap();
+
+    let url = format!("http://{addr}/");
+    let err = client.get(&url).send().await.unwrap_err();
+    assert!(err.is_connect());
+
+    assert_eq!(
+        recorder.take(),
+        [
+            format!("connect_start {url}"),
+            format!("connect_failed {url}"),
+            format!("call_failed Some({url:?})"),
+        ]
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_client() {
+    let server = server::http(move |_req| async { http::Response::default() });
+    let recorder = Arc::new(Recorder::default());
+    let client = reqwest::blocking::Client::builder()
+        .event_listener(recorder.clone())
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/", server.addr());
+    let res = client.get(&url).send().unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let events = recorder.take();
+    assert_eq!(events.first(), Some(&format!("connect_start {url}")));
+    assert!(events.contains(&format!("connection_acquired {url} reused=false")));
+}
+
+#[tokio::test]
+async fn response_timings() {
+    let server = server::http(move |req| async move {
+        if req.uri().path() == "/slow" {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        http::Response::default()
+    });
+    let client = reqwest::Client::builder()
+        .resolve("timings.test", server.addr())
+        .build()
+        .unwrap();
+    let port = server.addr().port();
+
+    let res = client
+        .get(format!("http://timings.test:{port}/slow"))
+        .send()
+        .await
+        .unwrap();
+    let timings = *res.extensions().get::<ResponseTimings>().unwrap();
+    assert!(!timings.connection_reused());
+    assert!(timings.dns().is_some());
+    assert!(timings.connect().is_some());
+    assert_eq!(timings.tls_handshake(), None);
+    assert_eq!(timings.proxy_tunnel(), None);
+    assert!(timings.time_to_first_byte() >= Duration::from_millis(50));
+    assert!(timings.total() >= timings.time_to_first_byte());
+    drop(res);
+
+    let res = client
+        .get(format!("http://timings.test:{port}/"))
+        .send()
+        .await
+        .unwrap();
+    let timings = *res.extensions().get::<ResponseTimings>().unwrap();
+    assert!(timings.connection_reused());
+    assert_eq!(timings.dns(), None);
+    assert_eq!(timings.connect(), None);
+}
+
+#[tokio::test]
+async fn response_timings_total_includes_redirects() {
+    let server = server::http(move |req| async move {
+        if req.uri().path() == "/start" {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            http::Response::builder()
+                .status(StatusCode::FOUND)
+                .header("location", "/end")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            http::Response::default()
+        }
+    });
+
+    let res = reqwest::get(format!("http://{}/start", server.addr()))
+        .await
+        .unwrap();
+    let timings = res.extensions().get::<ResponseTimings>().unwrap();
+    assert!(timings.connection_reused());
+    assert!(timings.total() >= Duration::from_millis(50));
+    assert!(timings.total() > timings.time_to_first_byte());
+}
@@ -358,3 +358,311 @@ async fn test_chunked_fragmented_response_with_extra_bytes() {
     assert!(err.is_decode());
     assert!(start.elapsed() >= DELAY_BETWEEN_RESPONSE_PARTS - DELAY_MARGIN);
 }
+
+#[tokio::test]
+async fn gzip_max_decompressed_size() {
+    let content = "x".repeat(10_000);
+    let gzipped = gzip_compress(content.as_bytes());
+    let server = server::http(move |_req| {
+        let gzipped = gzipped.clone();
+        async move {
+            http::Response::builder()
+                .header("content-encoding", "gzip")
+                .body(gzipped.into())
+                .unwrap()
+        }
+    });
+    let url = format!("http://{}/gzip", server.addr());
+
+    let client = reqwest::Client::builder()
+        .max_decompressed_size(1000)
+        .build()
+        .unwrap();
+    let err = client
+        .get(&url)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap_err();
+    assert!(err.is_decompression_limit());
+    assert!(!err.is_body_too_large());
+
+    let client = reqwest::Client::builder()
+        .max_decompressed_size(10_000)
+        .build()
+        .unwrap();
+    let body = client.get(&url).send().await.unwrap().text().await.unwrap();
+    assert_eq!(body, content);
+}
+
+#[tokio::test]
+async fn gzip_max_compression_ratio() {
+    // 8 MiB of zeros compress about a thousand times
+    let gzipped = gzip_compress(&vec![0; 8 * 1024 * 1024]);
+    let server = server::http(move |_req| {
+        let gzipped = gzipped.clone();
+        async move {
+            http::Response::builder()
+                .header("content-encoding", "gzip")
+                .body(gzipped.into())
+                .unwrap()
+        }
+    });
+    let url = format!("http://{}/gzip", server.addr());
+
+    let client = reqwest::Client::builder()
+        .max_compression_ratio(100)
+        .build()
+        .unwrap();
+    let err = client
+        .get(&url)
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap_err();
+    assert!(err.is_decompression_limit());
+
+    let client = reqwest::Client::builder()
+        .max_compression_ratio(10_000)
+        .build()
+        .unwrap();
+    let body = client
+        .get(&url)
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(body.len(), 8 * 1024 * 1024);
+}
+
+#[tokio::test]
+async fn gzip_max_response_body_size_is_decompressed_size() {
+    let gzipped = gzip_compress(&[b'x'; 10_000]);
+    let server = server::http(move |_req| {
+        let gzipped = gzipped.clone();
+        async move {
+            http::Response::builder()
+                .header("content-encoding", "gzip")
+                .body(gzipped.into())
+                .unwrap()
+        }
+    });
+
+    let client = reqwest::Client::builder()
+        .max_response_body_size(5000)
+        .build()
+        .unwrap();
+    let err = client
+        .get(format!("http://{}/gzip", server.addr()))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap_err();
+    assert!(err.is_body_too_large());
+}
+
+async fn gunzip_request(req: http::Request<hyper::body::Incoming>) -> String {
+    use http_body_util::BodyExt;
+    use std::io::Read;
+
+    assert_eq!(req.headers()["content-encoding"], "gzip");
+    let body = req.into_body().collect().await.unwrap().to_bytes();
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&body[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    decoded
+}
+
+#[tokio::test]
+async fn gzip_request_body() {
+    let content = "{\"event\":\"upload\"}".repeat(1000);
+    let expected = content.clone();
+    let server = server::http(move |req| {
+        let expected = expected.clone();
+        async move {
+            let len: usize = req.headers()["content-length"]
+                .to_str()
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!(len < expected.len());
+            assert_eq!(gunzip_request(req).await, expected);
+            http::Response::default()
+        }
+    });
+
+    let res = reqwest::Client::new()
+        .post(format!("http://{}/upload", server.addr()))
+        .body(content)
+        .compress(reqwest::Encoding::Gzip)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn gzip_streaming_request_body() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers()["transfer-encoding"], "chunked");
+        assert!(!req.headers().contains_key("content-length"));
+        assert_eq!(gunzip_request(req).await, "hello world");
+        http::Response::default()
+    });
+
+    let chunks = futures_util::stream::iter(["hello", " ", "world"].map(Ok::<_, std::io::Error>));
+    let res = reqwest::Client::builder()
+        .compress(reqwest::Encoding::Gzip)
+        .build()
+        .unwrap()
+        .post(format!("http://{}/upload", server.addr()))
+        .body(reqwest::Body::wrap_stream(chunks))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn gzip_request_body_already_encoded() {
+    let server = server::http(move |req| async move {
+        assert_eq!(gunzip_request(req).await, "hello world");
+        http::Response::default()
+    });
+
+    let res = reqwest::Client::builder()
+        .compress(reqwest::Encoding::Gzip)
+        .build()
+        .unwrap()
+        .post(format!("http://{}/upload", server.addr()))
+        .header("content-encoding", "gzip")
+        .body(gzip_compress(b"hello world"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}
+
+async fn encoded_response(
+    content_encoding: &'static [&'static str],
+    body: Vec<u8>,
+) -> reqwest::Response {
+    let server = server::http(move |_req| {
+        let body = body.clone();
+        async move {
+            let mut res = http::Response::builder();
+            for value in content_encoding {
+                res = res.header("content-encoding", *value);
+            }
+            res.body(body.into()).unwrap()
+        }
+    });
+
+    reqwest::Client::new()
+        .get(format!("http://{}/encoded", server.addr()))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn gzip_stacked_content_encoding() {
+    let body = gzip_compress(&gzip_compress(b"hello world"));
+    let res = encoded_response(&["gzip, identity, GZIP"], body).await;
+    assert!(res.headers().get("content-encoding").is_none());
+    assert_eq!(res.text().await.unwrap(), "hello world");
+}
+
+#[tokio::test]
+async fn gzip_repeated_content_encoding_headers() {
+    let body = gzip_compress(&gzip_compress(b"hello world"));
+    let res = encoded_response(&["gzip", "gzip"], body).await;
+    assert_eq!(res.text().await.unwrap(), "hello world");
+}
+
+#[tokio::test]
+async fn gzip_stacked_with_unsupported_coding() {
+    let body = gzip_compress(b"hello world");
+    let res = encoded_response(&["x-custom, gzip"], body).await;
+    let err = res.text().await.unwrap_err();
+    assert!(err.is_decode());
+    let source = std::error::Error::source(&err).unwrap();
+    assert_eq!(source.to_string(), "unsupported content coding `x-custom`");
+}
+
+#[tokio::test]
+async fn gzip_too_many_content_codings() {
+    let mut body = b"hello world".to_vec();
+    for _ in 0..6 {
+        body = gzip_compress(&body);
+    }
+    let res = encoded_response(&["gzip, gzip, gzip, gzip, gzip, gzip"], body).await;
+    let err = res.text().await.unwrap_err();
+    assert!(err.is_decode());
+    let source = std::error::Error::source(&err).unwrap();
+    assert_eq!(source.to_string(), "too many content codings (6)");
+
+    let mut body = b"hello world".to_vec();
+    for _ in 0..5 {
+        body = gzip_compress(&body);
+    }
+    let res = encoded_response(&["gzip, gzip, gzip, gzip, gzip"], body).await;
+    assert_eq!(res.text().await.unwrap(), "hello world");
+}
+
+#[tokio::test]
+async fn unsupported_coding_is_passed_along() {
+    let res = encoded_response(&["x-custom"], b"hello world".to_vec()).await;
+    assert_eq!(res.headers()["content-encoding"], "x-custom");
+    assert_eq!(res.text().await.unwrap(), "hello world");
+}
+
+#[tokio::test]
+async fn gzip_download_progress_counts_received_and_decoded_bytes() {
+    use std::sync::{Arc, Mutex};
+
+    let content: Vec<u8> = (0..20_000).map(|i| (i % 7) as u8).collect();
+    let gzipped = gzip_compress(&content);
+    let length = gzipped.len() as u64;
+    let server = server::http(move |_req| {
+        let gzipped = gzipped.clone();
+        async move {
+            http::Response::builder()
+                .header("content-encoding", "gzip")
+                .body(gzipped.into())
+                .unwrap()
+        }
+    });
+
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let progress2 = progress.clone();
+    let body = reqwest::Client::new()
+        .get(format!("http://{}/gzip", server.addr()))
+        .on_download_progress(move |received, decoded, total| {
+            progress2.lock().unwrap().push((received, decoded, total))
+        })
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+
+    assert_eq!(body, content);
+    let progress = progress.lock().unwrap();
+    assert!(progress.windows(2).all(|w| w[0].1 <= w[1].1));
+    // the decompressed size is only known once it is all decoded
+    assert_eq!(
+        progress.last(),
+        Some(&(length, content.len() as u64, Some(length)))
+    );
+}
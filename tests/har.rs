@@ -0,0 +1,259 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::sync::Arc;
+
+use reqwest::har::HarRecorder;
+use reqwest::StatusCode;
+use serde_json::Value;
+use support::server;
+
+fn entries(recorder: &HarRecorder) -> Vec<Value> {
+    let har = recorder.to_value();
+    assert_eq!(har["log"]["version"], "1.2");
+    assert_eq!(har["log"]["creator"]["name"], "reqwest");
+    har["log"]["entries"].as_array().unwrap().clone()
+}
+
+fn header<'a>(headers: &'a Value, name: &str) -> Option<&'a str> {
+    headers
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|h| h["name"] == name)
+        .and_then(|h| h["value"].as_str())
+}
+
+#[tokio::test]
+async fn redirect_chain() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |req| async move {
+        if req.uri().path() == "/login" {
+            assert_eq!(req.method(), "POST");
+            http::Response::builder()
+                .status(StatusCode::SEE_OTHER)
+                .header("location", "/home")
+                .header("set-cookie", "session=abc; Path=/; HttpOnly")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            http::Response::builder()
+                .header("content-type", "text/plain")
+                .body("welcome".into())
+                .unwrap()
+        }
+    });
+
+    let recorder = Arc::new(HarRecorder::new());
+    let client = reqwest::Client::builder()
+        .har_recorder(recorder.clone())
+        .build()
+        .unwrap();
+
+    let login = format!("http://{}/login?next=home", server.addr());
+    let res = client
+        .post(&login)
+        .header("cookie", "theme=dark")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body("user=ferris&remember=1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "welcome");
+
+    let entries = entries(&recorder);
+    assert_eq!(entries.len(), 2);
+
+    let first = &entries[0];
+    assert_eq!(first["request"]["method"], "POST");
+    assert_eq!(first["request"]["url"], login);
+    assert_eq!(first["request"]["httpVersion"], "HTTP/1.1");
+    assert_eq!(
+        first["request"]["queryString"],
+        serde_json::json!([{ "name": "next", "value": "home" }])
+    );
+    assert_eq!(
+        first["request"]["cookies"],
+        serde_json::json!([{ "name": "theme", "value": "dark" }])
+    );
+    assert_eq!(first["request"]["bodySize"], 22);
+    assert_eq!(
+        first["request"]["postData"]["text"],
+        "user=ferris&remember=1"
+    );
+    assert_eq!(
+        first["request"]["postData"]["params"][0],
+        serde_json::json!({ "name": "user", "value": "ferris" })
+    );
+    assert_eq!(first["response"]["status"], 303);
+    assert_eq!(first["response"]["statusText"], "See Other");
+    assert_eq!(
+        first["response"]["redirectURL"],
+        format!("http://{}/home", server.addr())
+    );
+    assert_eq!(first["response"]["cookies"][0]["name"], "session");
+    assert_eq!(first["response"]["cookies"][0]["path"], "/");
+    assert_eq!(first["response"]["cookies"][0]["httpOnly"], true);
+    assert_eq!(first["serverIPAddress"], "127.0.0.1");
+    assert!(first["timings"]["connect"].as_f64().unwrap() >= 0.0);
+
+    let second = &entries[1];
+    assert_eq!(second["request"]["method"], "GET");
+    assert_eq!(
+        second["request"]["url"],
+        format!("http://{}/home", server.addr())
+    );
+    assert_eq!(second["response"]["status"], 200);
+    assert_eq!(second["response"]["redirectURL"], "");
+    assert_eq!(second["response"]["content"]["text"], "welcome");
+    assert_eq!(second["response"]["content"]["size"], 7);
+    assert_eq!(second["response"]["content"]["mimeType"], "text/plain");
+    // the connection of the first hop was reused
+    assert_eq!(second["timings"]["connect"], -1);
+    assert!(second["time"].as_f64().unwrap() >= second["timings"]["wait"].as_f64().unwrap());
+    assert!(second["startedDateTime"].as_str().unwrap().ends_with('Z'));
+}
+
+#[tokio::test]
+async fn bodies_are_truncated() {
+    let server = server::http(move |_req| async { http::Response::new("hello world".into()) });
+
+    let recorder = Arc::new(HarRecorder::new().max_body_size(4));
+    let client = reqwest::Client::builder()
+        .har_recorder(recorder.clone())
+        .build()
+        .unwrap();
+
+    let res = client
+        .put(format!("http://{}/", server.addr()))
+        .body(vec![0xff, 0xfe, 0xfd, 0xfc, 0xfb])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "hello world");
+
+    let entries = entries(&recorder);
+    let post_data = &entries[0]["request"]["postData"];
+    assert_eq!(post_data["text"], "//79/A==");
+    assert_eq!(post_data["encoding"], "base64");
+    assert_eq!(post_data["comment"], "truncated to 4 of 5 bytes");
+
+    let content = &entries[0]["response"]["content"];
+    assert_eq!(content["text"], "hell");
+    assert_eq!(content["size"], 11);
+    assert_eq!(content["comment"], "truncated to 4 of 11 bytes");
+}
+
+#[tokio::test]
+async fn oldest_entries_are_dropped() {
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let recorder = Arc::new(HarRecorder::new().max_entries(2));
+    let client = reqwest::Client::builder()
+        .har_recorder(recorder.clone())
+        .build()
+        .unwrap();
+
+    for i in 0..3 {
+        client
+            .get(format!("http://{}/{i}", server.addr()))
+            .send()
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(recorder.len(), 2);
+    let urls = entries(&recorder)
+        .iter()
+        .map(|entry| entry["request"]["url"].as_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        urls,
+        [
+            format!("http://{}/1", server.addr()),
+            format!("http://{}/2", server.addr()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn sensitive_headers_are_redacted() {
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let recorder = Arc::new(HarRecorder::new());
+    let client = reqwest::Client::builder()
+        .har_recorder(recorder.clone())
+        .build()
+        .unwrap();
+
+    client
+        .get(format!("http://{}/", server.addr()))
+        .basic_auth("ferris", Some("secret"))
+        .send()
+        .await
+        .unwrap();
+
+    let entries = entries(&recorder);
+    let headers = &entries[0]["request"]["headers"];
+    assert_eq!(header(headers, "authorization"), Some("REDACTED"));
+}
+
+#[tokio::test]
+async fn failed_request_is_recorded() {
+    let addr = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    };
+
+    let recorder = Arc::new(HarRecorder::new());
+    let client = reqwest::Client::builder()
+        .har_recorder(recorder.clone())
+        .retry(reqwest::retry::Policy::none())
+        .build()
+        .unwrap();
+
+    let err = client
+        .get(format!("http://{addr}/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_connect());
+
+    let entries = entries(&recorder);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["response"]["status"], 0);
+    assert!(entries[0]["_error"].is_string());
+
+    recorder.clear();
+    assert!(recorder.is_empty());
+}
+
+#[tokio::test]
+async fn write_to_file() {
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let recorder = Arc::new(HarRecorder::new());
+    let client = reqwest::Client::builder()
+        .har_recorder(recorder.clone())
+        .build()
+        .unwrap();
+    client
+        .get(format!("http://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap();
+
+    let path = std::env::temp_dir().join(format!("reqwest-test-{}.har", server.addr().port()));
+    recorder.write_to_file(&path).unwrap();
+    let written = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // compare the text, as parsing floats back may not round-trip exactly
+    let mut expected = Vec::new();
+    recorder.write_to(&mut expected).unwrap();
+    assert_eq!(written, expected);
+    let har: Value = serde_json::from_slice(&written).unwrap();
+    assert_eq!(har["log"]["entries"].as_array().unwrap().len(), 1);
+    assert_eq!(recorder.len(), 1);
+}
@@ -289,3 +289,118 @@ async fn http3_request_stream_error() {
         .unwrap();
     assert!(err.is_body());
 }
+
+/// Serve HTTPS over TCP on `addr`, answering "tcp" with an `Alt-Svc`
+/// header advertising HTTP/3 on `h3_port`.
+async fn serve_tcp_with_alt_svc(addr: std::net::SocketAddr, h3_port: u16) {
+    use hyper_util::rt::TokioIo;
+    use std::sync::Arc;
+
+    let cert = std::fs::read("tests/support/server.cert").unwrap().into();
+    let key = std::fs::read("tests/support/server.key")
+        .unwrap()
+        .try_into()
+        .unwrap();
+    let mut tls_config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert], key)
+        .unwrap();
+    tls_config.alpn_protocols = vec![b"http/1.1".into()];
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(tls_config));
+
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (io, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let io = acceptor.accept(io).await.unwrap();
+                let service = hyper::service::service_fn(move |_req| async move {
+                    http::Response::builder()
+                        .header("alt-svc", format!("h3=\":{h3_port}\"; ma=60"))
+                        .body("tcp".to_owned())
+                });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(io), service)
+                    .await;
+            });
+        }
+    });
+}
+
+#[tokio::test]
+async fn http3_alt_svc_upgrade() {
+    let server = server::Http3::new().build(|_| async { http::Response::new("h3".into()) });
+    let port = server.addr().port();
+    serve_tcp_with_alt_svc(server.addr(), port).await;
+
+    let client = reqwest::Client::builder()
+        .use_rustls_tls()
+        .danger_accept_invalid_certs(true)
+        .http3_alt_svc(true)
+        .build()
+        .expect("client builder");
+    let url = format!("https://[::1]:{port}/");
+
+    let res = client.get(&url).send().await.expect("request");
+    assert_eq!(res.version(), http::Version::HTTP_11);
+    assert_eq!(res.text().await.unwrap(), "tcp");
+
+    for _ in 0..2 {
+        let res = client.get(&url).send().await.expect("request");
+        assert_eq!(res.version(), http::Version::HTTP_3);
+        assert_eq!(res.text().await.unwrap(), "h3");
+    }
+
+    // an explicit version is kept
+    let req = client.get(&url).version(http::Version::HTTP_10);
+    let res = req.send().await.expect("request");
+    assert_eq!(res.text().await.unwrap(), "tcp");
+}
+
+#[tokio::test]
+async fn http3_alt_svc_fallback_to_tcp() {
+    let addr = find_free_tcp_addr().await;
+    // nothing listens for QUIC on the advertised port
+    serve_tcp_with_alt_svc(addr, addr.port()).await;
+
+    let client = reqwest::Client::builder()
+        .use_rustls_tls()
+        .danger_accept_invalid_certs(true)
+        .http3_alt_svc(true)
+        .http3_max_idle_timeout(std::time::Duration::from_millis(50))
+        .build()
+        .expect("client builder");
+    let url = format!("https://[::1]:{}/", addr.port());
+
+    for _ in 0..3 {
+        let res = client.get(&url).send().await.expect("request");
+        assert_eq!(res.version(), http::Version::HTTP_11);
+        assert_eq!(res.text().await.unwrap(), "tcp");
+    }
+}
+
+#[tokio::test]
+async fn http3_alt_svc_fallback_when_quic_is_dropped() {
+    let addr = find_free_tcp_addr().await;
+    serve_tcp_with_alt_svc(addr, addr.port()).await;
+    // QUIC packets are received, but never answered
+    let _blackhole = std::net::UdpSocket::bind(addr).expect("bind udp");
+
+    let client = reqwest::Client::builder()
+        .use_rustls_tls()
+        .danger_accept_invalid_certs(true)
+        .http3_alt_svc(true)
+        .build()
+        .expect("client builder");
+    let url = format!("https://[::1]:{}/", addr.port());
+
+    let res = client.get(&url).send().await.expect("request");
+    assert_eq!(res.text().await.unwrap(), "tcp");
+
+    let start = std::time::Instant::now();
+    let res = client.get(&url).send().await.expect("request");
+    assert_eq!(res.version(), http::Version::HTTP_11);
+    assert_eq!(res.text().await.unwrap(), "tcp");
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
@@ -0,0 +1,184 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use reqwest::{retry, Body, StatusCode};
+use support::server;
+use tower::util::{MapRequestLayer, MapResponseLayer};
+
+fn counting_layer(
+    hits: Arc<AtomicUsize>,
+) -> MapRequestLayer<impl Fn(reqwest::Request) -> reqwest::Request + Clone> {
+    MapRequestLayer::new(move |mut req: reqwest::Request| {
+        hits.fetch_add(1, Ordering::SeqCst);
+        req.headers_mut()
+            .insert("x-layer", http::HeaderValue::from_static("yes"));
+        req
+    })
+}
+
+#[tokio::test]
+async fn layer_runs_on_every_redirect_hop() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers()["x-layer"], "yes");
+        if req.uri() == "/redirect" {
+            http::Response::builder()
+                .status(302)
+                .header("location", "/dst")
+                .body(Body::default())
+                .unwrap()
+        } else {
+            assert_eq!(req.uri(), "/dst");
+            http::Response::default()
+        }
+    });
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let client = reqwest::Client::builder()
+        .layer(counting_layer(hits.clone()))
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/redirect", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.url().path(), "/dst");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn layer_runs_on_every_retry() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let attempts2 = attempts.clone();
+    let server = server::http(move |req| {
+        let n = attempts2.fetch_add(1, Ordering::SeqCst);
+        async move {
+            assert_eq!(req.headers()["x-layer"], "yes");
+            let status = if n == 0 {
+                StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                StatusCode::OK
+            };
+            http::Response::builder()
+                .status(status)
+                .body(Body::default())
+                .unwrap()
+        }
+    });
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let client = reqwest::Client::builder()
+        .layer(counting_layer(hits.clone()))
+        .retry(retry::Policy::limited(1).backoff(Duration::ZERO, Duration::ZERO))
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn layer_sees_each_response() {
+    let server = server::http(move |req| async move {
+        if req.uri() == "/redirect" {
+            http::Response::builder()
+                .status(301)
+                .header("location", "/dst")
+                .body(Body::default())
+                .unwrap()
+        } else {
+            http::Response::builder()
+                .header("x-final", "true")
+                .body(Body::default())
+                .unwrap()
+        }
+    });
+
+    let statuses = Arc::new(Mutex::new(Vec::new()));
+    let statuses2 = statuses.clone();
+    let client = reqwest::Client::builder()
+        .layer(MapResponseLayer::new(move |mut res: reqwest::Response| {
+            statuses2.lock().unwrap().push(res.status());
+            res.headers_mut()
+                .insert("x-seen", http::HeaderValue::from_static("yes"));
+            res
+        }))
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/redirect", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+
+    assert_eq!(res.headers()["x-final"], "true");
+    assert_eq!(res.headers()["x-seen"], "yes");
+    assert_eq!(
+        *statuses.lock().unwrap(),
+        [StatusCode::MOVED_PERMANENTLY, StatusCode::OK]
+    );
+}
+
+#[tokio::test]
+async fn layer_error_is_returned() {
+    let client = reqwest::Client::builder()
+        .layer(tower::layer::layer_fn(|_inner| {
+            tower::service_fn(|_req: reqwest::Request| async {
+                Err::<reqwest::Response, _>(Box::<dyn std::error::Error + Send + Sync>::from(
+                    "rejected by layer",
+                ))
+            })
+        }))
+        .build()
+        .unwrap();
+
+    let err = client
+        .get("http://127.0.0.1:1/never")
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_request());
+    assert_eq!(err.url().unwrap().path(), "/never");
+}
+
+#[tokio::test]
+async fn layers_wrap_in_order() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers()["x-order"], "inner");
+        http::Response::default()
+    });
+
+    // The later layer is outermost, so the earlier one sets the header last.
+    let client = reqwest::Client::builder()
+        .layer(MapRequestLayer::new(|mut req: reqwest::Request| {
+            req.headers_mut()
+                .insert("x-order", http::HeaderValue::from_static("inner"));
+            req
+        }))
+        .layer(MapRequestLayer::new(|mut req: reqwest::Request| {
+            req.headers_mut()
+                .insert("x-order", http::HeaderValue::from_static("outer"));
+            req
+        }))
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/order", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+}
@@ -233,3 +233,38 @@ async fn async_impl_file_part() {
     assert_eq!(res.url().as_str(), &url);
     assert_eq!(res.status(), reqwest::StatusCode::OK);
 }
+
+#[tokio::test]
+async fn upload_progress_of_form() {
+    use std::sync::{Arc, Mutex};
+
+    let _ = env_logger::try_init();
+
+    let form = reqwest::multipart::Form::new()
+        .text("foo", "bar")
+        .part("file", reqwest::multipart::Part::bytes(vec![b'x'; 4000]));
+
+    let server = server::http(move |req| async move {
+        let length = req.headers()["content-length"].clone();
+        let full = req.collect().await.unwrap().to_bytes();
+        assert_eq!(length, full.len().to_string());
+        http::Response::new(full.len().to_string().into())
+    });
+
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let progress2 = progress.clone();
+    let res = reqwest::Client::new()
+        .post(format!("http://{}/multipart/progress", server.addr()))
+        .multipart(form)
+        .on_upload_progress(move |sent, total| progress2.lock().unwrap().push((sent, total)))
+        .send()
+        .await
+        .unwrap();
+
+    let received: u64 = res.text().await.unwrap().parse().unwrap();
+    let progress = progress.lock().unwrap();
+    // every part of the form is counted
+    assert!(progress.len() > 1);
+    assert!(progress.iter().all(|&(_, total)| total == Some(received)));
+    assert_eq!(progress.last().unwrap().0, received);
+}
@@ -0,0 +1,246 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use reqwest::header::{HeaderName, ACCEPT};
+use reqwest::replay::{Cassette, Mode};
+use reqwest::StatusCode;
+use support::server;
+
+fn cassette_path(name: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("reqwest-replay-{}", std::process::id()))
+        .join(format!("{name}.json"))
+}
+
+/// A server answering `/items/{n}` with `item n`, and `/old` with a redirect
+/// to `/items/1`, counting its requests.
+fn server() -> (server::Server, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |req| {
+        hits2.fetch_add(1, Ordering::SeqCst);
+        async move {
+            if req.uri().path() == "/old" {
+                return http::Response::builder()
+                    .status(StatusCode::MOVED_PERMANENTLY)
+                    .header("location", "/items/1")
+                    .body(Default::default())
+                    .unwrap();
+            }
+            let item = req.uri().path().trim_start_matches("/items/").to_owned();
+            http::Response::builder()
+                .header("x-token", "server-secret")
+                .body(format!("item {item}").into())
+                .unwrap()
+        }
+    });
+    (server, hits)
+}
+
+#[tokio::test]
+async fn record_then_replay() {
+    let _ = env_logger::try_init();
+
+    let path = cassette_path("record_then_replay");
+    let (server, hits) = server();
+    let url = |path: &str| format!("http://{}{path}", server.addr());
+
+    let cassette = Cassette::record(&path).redact_header(HeaderName::from_static("x-token"));
+    let client = reqwest::Client::builder()
+        .replay(cassette.clone())
+        .build()
+        .unwrap();
+    let res = client
+        .get(url("/old"))
+        .bearer_auth("hunter2")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.url().as_str(), url("/items/1"));
+    assert_eq!(res.headers()["x-token"], "server-secret");
+    assert_eq!(res.text().await.unwrap(), "item 1");
+    let res = client
+        .post(url("/items/2"))
+        .body("new")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "item 2");
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    assert_eq!(cassette.len(), 3);
+    // exchanges are only written once the cassette is saved
+    assert!(!path.exists());
+    cassette.save().unwrap();
+
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    assert!(!recorded.contains("hunter2"));
+    assert!(!recorded.contains("server-secret"));
+
+    let cassette = Cassette::replay(&path).unwrap();
+    assert_eq!(cassette.mode(), Mode::Replay);
+    assert_eq!(cassette.len(), 3);
+    let client = reqwest::Client::builder()
+        .replay(cassette.clone())
+        .build()
+        .unwrap();
+
+    let res = client.get(url("/old")).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.url().as_str(), url("/items/1"));
+    assert_eq!(res.headers()["x-token"], "REDACTED");
+    assert_eq!(res.text().await.unwrap(), "item 1");
+
+    // the body is matched
+    let err = client
+        .post(url("/items/2"))
+        .body("other")
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_request());
+    let res = client
+        .post(url("/items/2"))
+        .body("new")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "item 2");
+
+    // each exchange is replayed once
+    assert_eq!(cassette.remaining(), 0);
+    client.get(url("/items/1")).send().await.unwrap_err();
+
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn replay_matching_rules() {
+    let path = cassette_path("replay_matching_rules");
+    let (server, _hits) = server();
+    let url = format!("http://{}/items/1", server.addr());
+
+    let client = reqwest::Client::builder()
+        .replay(Cassette::record(&path))
+        .build()
+        .unwrap();
+    for accept in ["text/plain", "text/html"] {
+        client
+            .put(&url)
+            .header(ACCEPT, accept)
+            .body(accept)
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+    }
+    // the cassette is saved once it's no longer used
+    drop(client);
+
+    // without matching on the body or headers, exchanges play in order
+    let client = reqwest::Client::builder()
+        .replay(Cassette::replay(&path).unwrap().match_body(false))
+        .build()
+        .unwrap();
+    for _ in 0..2 {
+        let res = client.put(&url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "item 1");
+    }
+
+    let cassette = Cassette::replay(&path)
+        .unwrap()
+        .match_body(false)
+        .match_header(ACCEPT);
+    let client = reqwest::Client::builder()
+        .replay(cassette.clone())
+        .build()
+        .unwrap();
+    client
+        .put(&url)
+        .header(ACCEPT, "text/html")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(cassette.remaining(), 1);
+    client
+        .put(&url)
+        .header(ACCEPT, "application/json")
+        .send()
+        .await
+        .unwrap_err();
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn replay_missing_or_invalid_cassette() {
+    let err = Cassette::replay(cassette_path("missing")).unwrap_err();
+    assert!(err.is_builder());
+
+    let path = cassette_path("invalid");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, r#"{"exchanges": [{"request": {}}]}"#).unwrap();
+    let err = Cassette::replay(&path).unwrap_err();
+    assert!(err.is_builder());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn record_streams_the_response_body() {
+    use futures_util::StreamExt;
+    use http_body_util::StreamBody;
+    use hyper::body::Frame;
+
+    let path = cassette_path("record_streams_the_response_body");
+    let (send_rest, rest) = tokio::sync::oneshot::channel::<()>();
+    let rest = Arc::new(std::sync::Mutex::new(Some(rest)));
+    let server = server::http(move |_req| {
+        let rest = rest.lock().unwrap().take().unwrap();
+        async move {
+            let first = futures_util::stream::once(async {
+                Ok::<_, std::convert::Infallible>(Frame::data(bytes::Bytes::from("first ")))
+            });
+            // the rest is only sent once the first chunk was read
+            let second = futures_util::stream::once(async move {
+                rest.await.unwrap();
+                Ok(Frame::data(bytes::Bytes::from("second")))
+            });
+            http::Response::new(reqwest::Body::wrap(StreamBody::new(first.chain(second))))
+        }
+    });
+
+    let cassette = Cassette::record(&path);
+    let client = reqwest::Client::builder()
+        .replay(cassette.clone())
+        .build()
+        .unwrap();
+    let mut res = client
+        .get(format!("http://{}/stream", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.chunk().await.unwrap().unwrap(), "first ");
+    // nothing is recorded until the body was read to its end
+    assert!(cassette.is_empty());
+
+    send_rest.send(()).unwrap();
+    assert_eq!(res.text().await.unwrap(), "second");
+    assert_eq!(cassette.len(), 1);
+    cassette.save().unwrap();
+
+    let cassette = Cassette::replay(&path).unwrap();
+    let client = reqwest::Client::builder().replay(cassette).build().unwrap();
+    let res = client
+        .get(format!("http://{}/stream", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "first second");
+    std::fs::remove_file(&path).unwrap();
+}
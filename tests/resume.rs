@@ -0,0 +1,268 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::error::Error as _;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use http::header::{CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+use http_body_util::StreamBody;
+use hyper::body::Frame;
+use reqwest::{Body, StatusCode};
+use support::server;
+
+const SIZE: usize = 10_000;
+const CUT: usize = 4_000;
+
+fn content() -> Vec<u8> {
+    (0..SIZE).map(|i| (i % 251) as u8).collect()
+}
+
+/// A body of `data` that fails after `cut` bytes, as if the connection was
+/// lost.
+fn cut_body(data: Vec<u8>, cut: usize) -> Body {
+    use futures_util::StreamExt;
+
+    let head = Bytes::from(data[..cut].to_vec());
+    let chunks = futures_util::stream::once(async move { Ok(Frame::data(head)) }).chain(
+        // fail once the first part was sent
+        futures_util::stream::once(async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                "connection lost",
+            ))
+        }),
+    );
+    Body::wrap(StreamBody::new(chunks))
+}
+
+/// A server for a resource whose first response is cut short, and whose
+/// range requests are answered by `resumed`.
+fn flaky_server<F>(resumed: F) -> (server::Server, Arc<AtomicUsize>)
+where
+    F: Fn(http::Request<hyper::body::Incoming>) -> http::Response<Body>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |req| {
+        hits2.fetch_add(1, Ordering::SeqCst);
+        let resumed = resumed.clone();
+        async move {
+            if req.headers().contains_key(RANGE) {
+                return resumed(req);
+            }
+            http::Response::builder()
+                .header(ETAG, "\"v1\"")
+                .header(CONTENT_LENGTH, SIZE)
+                .body(cut_body(content(), CUT))
+                .unwrap()
+        }
+    });
+    (server, hits)
+}
+
+fn source_chain(err: &reqwest::Error) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        chain.push_str(": ");
+        chain.push_str(&err.to_string());
+        source = err.source();
+    }
+    chain
+}
+
+#[tokio::test]
+async fn resumes_failed_body() {
+    let _ = env_logger::try_init();
+
+    let (server, hits) = flaky_server(|req| {
+        assert_eq!(req.headers()[RANGE], format!("bytes={CUT}-"));
+        assert_eq!(req.headers()[IF_RANGE], "\"v1\"");
+        http::Response::builder()
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(ETAG, "\"v1\"")
+            .header(CONTENT_RANGE, format!("bytes {CUT}-{}/{SIZE}", SIZE - 1))
+            .body(content()[CUT..].to_vec().into())
+            .unwrap()
+    });
+
+    let body = reqwest::Client::new()
+        .get(format!("http://{}/artifact", server.addr()))
+        .resumable(true)
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+
+    assert_eq!(body, content());
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn resumes_more_than_once() {
+    let (server, hits) = flaky_server(|req| {
+        let range = req.headers()[RANGE].to_str().unwrap();
+        let start: usize = range["bytes=".len()..range.len() - 1].parse().unwrap();
+        // each part is cut short again, until the last one
+        let end = (start + CUT).min(SIZE);
+        let body = if end < SIZE {
+            cut_body(content()[start..].to_vec(), CUT)
+        } else {
+            content()[start..].to_vec().into()
+        };
+        http::Response::builder()
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(ETAG, "\"v1\"")
+            .header(CONTENT_RANGE, format!("bytes {start}-{}/{SIZE}", SIZE - 1))
+            .header(CONTENT_LENGTH, SIZE - start)
+            .body(body)
+            .unwrap()
+    });
+
+    let body = reqwest::Client::new()
+        .get(format!("http://{}/artifact", server.addr()))
+        .resumable(true)
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+
+    assert_eq!(body, content());
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn not_resumed_by_default() {
+    let (server, hits) = flaky_server(|_req| unreachable!("resumed without opting in"));
+
+    let err = reqwest::Client::new()
+        .get(format!("http://{}/artifact", server.addr()))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_decode());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn changed_resource_is_not_resumed() {
+    let (server, _hits) = flaky_server(|_req| {
+        // If-Range didn't match, so the whole new resource is sent
+        http::Response::builder()
+            .header(ETAG, "\"v2\"")
+            .body(content().into())
+            .unwrap()
+    });
+
+    let err = reqwest::Client::new()
+        .get(format!("http://{}/artifact", server.addr()))
+        .resumable(true)
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap_err();
+
+    assert!(source_chain(&err).contains("resource changed"));
+}
+
+#[tokio::test]
+async fn ignored_range_is_an_error() {
+    let (server, _hits) = flaky_server(|_req| {
+        http::Response::builder()
+            .header(ETAG, "\"v1\"")
+            .body(content().into())
+            .unwrap()
+    });
+
+    let err = reqwest::Client::new()
+        .get(format!("http://{}/artifact", server.addr()))
+        .resumable(true)
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap_err();
+
+    assert!(source_chain(&err).contains("server ignored the range request"));
+}
+
+#[tokio::test]
+async fn without_validator_is_not_resumed() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |_req| {
+        hits2.fetch_add(1, Ordering::SeqCst);
+        async move {
+            http::Response::builder()
+                .header(CONTENT_LENGTH, SIZE)
+                .body(cut_body(content(), CUT))
+                .unwrap()
+        }
+    });
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/artifact", server.addr()))
+        .resumable(true)
+        .send()
+        .await
+        .unwrap();
+    assert!(res.bytes().await.is_err());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn resumes_stalled_body_after_read_timeout() {
+    use futures_util::StreamExt;
+
+    let server = server::http(move |req| async move {
+        if req.headers().contains_key(RANGE) {
+            return http::Response::builder()
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(ETAG, "\"v1\"")
+                .header(CONTENT_RANGE, format!("bytes {CUT}-{}/{SIZE}", SIZE - 1))
+                .body(content()[CUT..].to_vec().into())
+                .unwrap();
+        }
+        let head = Bytes::from(content()[..CUT].to_vec());
+        let stalled = futures_util::stream::once(async move { Ok(Frame::data(head)) })
+            .chain(futures_util::stream::pending::<Result<_, std::io::Error>>());
+        http::Response::builder()
+            .header(ETAG, "\"v1\"")
+            .header(CONTENT_LENGTH, SIZE)
+            .body(Body::wrap(StreamBody::new(stalled)))
+            .unwrap()
+    });
+
+    let body = reqwest::Client::builder()
+        .read_timeout(std::time::Duration::from_millis(100))
+        .build()
+        .unwrap()
+        .get(format!("http://{}/artifact", server.addr()))
+        .resumable(true)
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+
+    assert_eq!(body, content());
+}
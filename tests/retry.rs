@@ -0,0 +1,229 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::{retry, Body, StatusCode};
+use support::server;
+
+fn fast_policy(max: usize) -> retry::Policy {
+    retry::Policy::limited(max).backoff(Duration::from_millis(1), Duration::from_millis(10))
+}
+
+fn fail_first(count: usize, status: StatusCode) -> (server::Server, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |_req| {
+        let n = hits2.fetch_add(1, Ordering::SeqCst);
+        async move {
+            let status = if n < count { status } else { StatusCode::OK };
+            http::Response::builder()
+                .status(status)
+                .body(Body::default())
+                .unwrap()
+        }
+    });
+    (server, hits)
+}
+
+#[tokio::test]
+async fn retries_status_until_success() {
+    let _ = env_logger::try_init();
+
+    let (server, hits) = fail_first(2, StatusCode::SERVICE_UNAVAILABLE);
+
+    let client = reqwest::Client::builder()
+        .retry(fast_policy(3))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn retries_status_stops_at_max() {
+    let (server, hits) = fail_first(5, StatusCode::TOO_MANY_REQUESTS);
+
+    let client = reqwest::Client::builder()
+        .retry(fast_policy(2))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client.get(&url).send().await.unwrap();
+
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn default_does_not_retry_status() {
+    let (server, hits) = fail_first(1, StatusCode::SERVICE_UNAVAILABLE);
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = reqwest::Client::new().get(&url).send().await.unwrap();
+
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn non_idempotent_status_not_retried() {
+    let (server, hits) = fail_first(1, StatusCode::SERVICE_UNAVAILABLE);
+
+    let client = reqwest::Client::builder()
+        .retry(fast_policy(3))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client.post(&url).body("hello").send().await.unwrap();
+
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn non_idempotent_retry_resends_body() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |req| {
+        let n = hits2.fetch_add(1, Ordering::SeqCst);
+        async move {
+            use http_body_util::BodyExt;
+
+            let body = req.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, "hello");
+
+            let status = if n == 0 {
+                StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                StatusCode::OK
+            };
+            http::Response::builder()
+                .status(status)
+                .body(Body::default())
+                .unwrap()
+        }
+    });
+
+    let client = reqwest::Client::new();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client
+        .post(&url)
+        .body("hello")
+        .retry(fast_policy(1).non_idempotent(true))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn request_policy_overrides_client() {
+    let (server, hits) = fail_first(1, StatusCode::SERVICE_UNAVAILABLE);
+
+    let client = reqwest::Client::builder()
+        .retry(fast_policy(3))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let res = client
+        .get(&url)
+        .retry(retry::Policy::none())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn honors_retry_after() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits2 = hits.clone();
+    let server = server::http(move |_req| {
+        let n = hits2.fetch_add(1, Ordering::SeqCst);
+        async move {
+            if n == 0 {
+                http::Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .header("retry-after", "1")
+                    .body(Body::default())
+                    .unwrap()
+            } else {
+                http::Response::default()
+            }
+        }
+    });
+
+    let client = reqwest::Client::builder()
+        .retry(retry::Policy::limited(1))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let start = std::time::Instant::now();
+    let res = client.get(&url).send().await.unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert!(start.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn retries_connect_errors() {
+    // Reserve a port, then close it, so connecting is refused.
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let client = reqwest::Client::builder()
+        .retry(fast_policy(2))
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let start = std::time::Instant::now();
+    let err = client
+        .get(format!("http://{addr}/retry"))
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_connect());
+    // two backoff delays of at least half a millisecond each
+    assert!(start.elapsed() >= Duration::from_millis(1));
+}
+
+#[tokio::test]
+async fn total_timeout_includes_retries() {
+    let (server, _hits) = fail_first(100, StatusCode::SERVICE_UNAVAILABLE);
+
+    let client = reqwest::Client::builder()
+        .retry(
+            retry::Policy::limited(100)
+                .backoff(Duration::from_millis(50), Duration::from_millis(50))
+                .jitter(false),
+        )
+        .timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/retry", server.addr());
+    let err = client.get(&url).send().await.unwrap_err();
+
+    assert!(err.is_timeout());
+}
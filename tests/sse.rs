@@ -0,0 +1,125 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use reqwest::sse::{Event, EventSource};
+use support::server;
+
+fn event_stream(body: &'static str) -> http::Response<reqwest::Body> {
+    http::Response::builder()
+        .header("content-type", "text/event-stream; charset=utf-8")
+        .body(body.into())
+        .unwrap()
+}
+
+fn message(data: &str, id: Option<&str>) -> Event {
+    Event {
+        event: "message".to_owned(),
+        data: data.to_owned(),
+        id: id.map(str::to_owned),
+        retry: None,
+    }
+}
+
+#[tokio::test]
+async fn response_event_stream() {
+    let server = server::http(move |_req| async {
+        event_stream("\u{feff}: comment\nevent: add\ndata: 1\ndata: 2\n\nid: 3\ndata: {}\r\n\r\ndata: partial")
+    });
+
+    let res = reqwest::get(format!("http://{}/", server.addr()))
+        .await
+        .unwrap();
+    let events = res
+        .event_stream()
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(
+        events,
+        [
+            Event {
+                event: "add".to_owned(),
+                data: "1\n2".to_owned(),
+                id: None,
+                retry: None,
+            },
+            message("{}", Some("3")),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn event_source_reconnects() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let server = server::http(move |req| {
+        let requests = requests.clone();
+        async move {
+            assert_eq!(req.headers()["accept"], "text/event-stream");
+            let last_event_id = req.headers().get("last-event-id");
+            match requests.fetch_add(1, Ordering::SeqCst) {
+                0 => {
+                    assert_eq!(last_event_id, None);
+                    event_stream("retry: 10\nid: 1\ndata: a\n\n")
+                }
+                1 => {
+                    assert_eq!(last_event_id.unwrap(), "1");
+                    event_stream("data: b\n\nid: 2\n")
+                }
+                _ => {
+                    assert_eq!(last_event_id.unwrap(), "2");
+                    http::Response::builder()
+                        .status(http::StatusCode::NO_CONTENT)
+                        .body(reqwest::Body::default())
+                        .unwrap()
+                }
+            }
+        }
+    });
+
+    let client = reqwest::Client::new();
+    let source = EventSource::new(client.get(format!("http://{}/", server.addr())));
+    let events = tokio::time::timeout(Duration::from_secs(1), source.collect::<Vec<_>>())
+        .await
+        .expect("reconnects after the retry delay");
+    let events = events.into_iter().map(Result::unwrap).collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            Event {
+                retry: Some(Duration::from_millis(10)),
+                ..message("a", Some("1"))
+            },
+            message("b", Some("1")),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn event_source_fails_on_other_responses() {
+    let server = server::http(move |req| async move {
+        if req.uri().path() == "/json" {
+            http::Response::new("{}".into())
+        } else {
+            http::Response::builder()
+                .status(http::StatusCode::NOT_FOUND)
+                .body(reqwest::Body::default())
+                .unwrap()
+        }
+    });
+
+    let client = reqwest::Client::new();
+    let mut source = EventSource::new(client.get(format!("http://{}/json", server.addr())));
+    let err = source.next().await.unwrap().unwrap_err();
+    assert!(err.is_decode());
+    assert!(source.next().await.is_none());
+
+    let mut source = EventSource::new(client.get(format!("http://{}/", server.addr())));
+    let err = source.next().await.unwrap().unwrap_err();
+    assert_eq!(err.status(), Some(http::StatusCode::NOT_FOUND));
+    assert!(source.next().await.is_none());
+}
@@ -0,0 +1,228 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::trace::Propagator;
+use reqwest::StatusCode;
+use support::server;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// A recorded span, with its fields formatted as text.
+#[derive(Clone, Debug)]
+struct SpanData {
+    id: u64,
+    name: &'static str,
+    parent: Option<u64>,
+    fields: BTreeMap<&'static str, String>,
+}
+
+impl SpanData {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+}
+
+impl Visit for SpanData {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.fields.insert(field.name(), format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name(), value.to_owned());
+    }
+}
+
+/// Collects every span created while it is the default subscriber.
+#[derive(Clone, Default)]
+struct Collector {
+    next_id: Arc<AtomicU64>,
+    spans: Arc<Mutex<Vec<SpanData>>>,
+}
+
+impl Collector {
+    fn spans(&self, name: &str) -> Vec<SpanData> {
+        let spans = self.spans.lock().unwrap();
+        spans.iter().filter(|s| s.name == name).cloned().collect()
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut span = SpanData {
+            id,
+            name: attrs.metadata().name(),
+            parent: attrs.parent().map(Id::into_u64),
+            fields: BTreeMap::new(),
+        };
+        attrs.record(&mut span);
+        self.spans.lock().unwrap().push(span);
+        Id::from_u64(id)
+    }
+
+    fn record(&self, id: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        if let Some(span) = spans.iter_mut().find(|s| s.id == id.into_u64()) {
+            values.record(span);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[tokio::test]
+async fn request_and_hop_spans() {
+    let server = server::http(move |req| async move {
+        if req.uri().path() == "/start" {
+            http::Response::builder()
+                .status(StatusCode::FOUND)
+                .header("location", "/end")
+                .body(Default::default())
+                .unwrap()
+        } else {
+            http::Response::default()
+        }
+    });
+
+    let collector = Collector::default();
+    let _guard = tracing::subscriber::set_default(collector.clone());
+
+    let start = format!("http://{}/start", server.addr());
+    let end = format!("http://{}/end", server.addr());
+    let res = reqwest::Client::new().get(&start).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    drop(res);
+
+    let requests = collector.spans("HTTP request");
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(request.field("http.request.method"), Some("GET"));
+    assert_eq!(request.field("url.full"), Some(&*start));
+    assert_eq!(request.field("server.address"), Some("127.0.0.1"));
+    assert_eq!(request.field("http.response.status_code"), Some("200"));
+    assert_eq!(request.field("network.protocol.version"), Some("1.1"));
+    assert_eq!(request.field("error.type"), None);
+
+    let hops = collector.spans("HTTP hop");
+    assert_eq!(hops.len(), 2);
+    assert!(hops.iter().all(|hop| hop.parent == Some(request.id)));
+    assert_eq!(hops[0].field("url.full"), Some(&*start));
+    assert_eq!(hops[0].field("http.response.status_code"), Some("302"));
+    assert_eq!(hops[0].field("http.request.resend_count"), None);
+    assert_eq!(hops[1].field("url.full"), Some(&*end));
+    assert_eq!(hops[1].field("http.response.status_code"), Some("200"));
+    assert_eq!(hops[1].field("http.request.resend_count"), Some("1"));
+}
+
+#[tokio::test]
+async fn error_status_and_failure() {
+    let server = server::http(move |_req| async {
+        http::Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Default::default())
+            .unwrap()
+    });
+    let addr = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    };
+
+    let collector = Collector::default();
+    let _guard = tracing::subscriber::set_default(collector.clone());
+
+    let client = reqwest::Client::builder()
+        .retry(reqwest::retry::Policy::none())
+        .build()
+        .unwrap();
+    let res = client
+        .get(format!("http://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let err = client
+        .get(format!("http://{addr}/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_connect());
+
+    let requests = collector.spans("HTTP request");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].field("error.type"), Some("404"));
+    assert_eq!(requests[0].field("otel.status_code"), Some("ERROR"));
+    assert_eq!(requests[1].field("error.type"), Some("connect"));
+    assert_eq!(requests[1].field("http.response.status_code"), None);
+}
+
+#[tokio::test]
+async fn credentials_are_redacted() {
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let collector = Collector::default();
+    let _guard = tracing::subscriber::set_default(collector.clone());
+
+    // `RequestBuilder` moves credentials into the `Authorization` header,
+    // but a `Request` can still carry them in its URL.
+    let url = format!("http://user:secret@{}/", server.addr());
+    let req = reqwest::Request::new(reqwest::Method::GET, url.parse().unwrap());
+    reqwest::Client::new().execute(req).await.unwrap();
+
+    let requests = collector.spans("HTTP request");
+    assert_eq!(
+        requests[0].field("url.full"),
+        Some(&*format!("http://REDACTED:REDACTED@{}/", server.addr()))
+    );
+}
+
+#[tokio::test]
+async fn propagator_injects_hop_context() {
+    struct SpanId;
+
+    impl Propagator for SpanId {
+        fn inject(&self, span: &tracing::Span, headers: &mut HeaderMap) {
+            let id = span.id().unwrap().into_u64();
+            headers.insert("x-span-id", HeaderValue::from(id));
+        }
+    }
+
+    let server = server::http(move |req| async move {
+        let id = req.headers()["x-span-id"].to_str().unwrap().to_owned();
+        http::Response::new(id.into())
+    });
+
+    let collector = Collector::default();
+    let _guard = tracing::subscriber::set_default(collector.clone());
+
+    let client = reqwest::Client::builder()
+        .trace_propagator(Arc::new(SpanId))
+        .build()
+        .unwrap();
+    let res = client
+        .get(format!("http://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    let id = res.text().await.unwrap();
+
+    let hops = collector.spans("HTTP hop");
+    assert_eq!(hops.len(), 1);
+    assert_eq!(id, hops[0].id.to_string());
+}
@@ -0,0 +1,163 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use http::{Request, Response};
+use http_body_util::BodyExt;
+use reqwest::header::{HeaderMap, HeaderValue, LOCATION};
+use reqwest::{Body, StatusCode};
+
+#[tokio::test]
+async fn requests_go_to_transport() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen2 = seen.clone();
+
+    let mut headers = HeaderMap::new();
+    headers.insert("x-api-key", HeaderValue::from_static("secret"));
+    let client = reqwest::Client::builder()
+        .default_headers(headers)
+        .transport(tower::service_fn(move |req: Request<Body>| {
+            let seen = seen2.clone();
+            async move {
+                let (parts, body) = req.into_parts();
+                let body = body.collect().await?.to_bytes();
+                seen.lock().unwrap().push((parts, body));
+                Ok::<_, reqwest::Error>(Response::new("pong".to_owned()))
+            }
+        }))
+        .build()
+        .unwrap();
+
+    let res = client
+        .post("http://api.test/ping")
+        .body("ping")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.url().as_str(), "http://api.test/ping");
+    assert_eq!(res.text().await.unwrap(), "pong");
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    let (parts, body) = &seen[0];
+    assert_eq!(parts.method, "POST");
+    assert_eq!(parts.uri, "http://api.test/ping");
+    assert_eq!(parts.headers["x-api-key"], "secret");
+    assert_eq!(parts.headers["accept"], "*/*");
+    assert_eq!(body, "ping");
+}
+
+#[tokio::test]
+async fn redirects_are_followed() {
+    let paths = Arc::new(Mutex::new(Vec::new()));
+    let paths2 = paths.clone();
+
+    let client = reqwest::Client::builder()
+        .transport(tower::service_fn(move |req: Request<Body>| {
+            paths2.lock().unwrap().push(req.uri().path().to_owned());
+            let res = if req.uri().path() == "/old" {
+                Response::builder()
+                    .status(StatusCode::MOVED_PERMANENTLY)
+                    .header(LOCATION, "/new")
+                    .body(String::new())
+                    .unwrap()
+            } else {
+                Response::new("moved".to_owned())
+            };
+            async move { Ok::<_, Infallible>(res) }
+        }))
+        .build()
+        .unwrap();
+
+    let res = client.get("http://api.test/old").send().await.unwrap();
+    assert_eq!(res.url().as_str(), "http://api.test/new");
+    assert_eq!(res.text().await.unwrap(), "moved");
+    assert_eq!(*paths.lock().unwrap(), ["/old", "/new"]);
+}
+
+#[tokio::test]
+async fn timeout_applies() {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(100))
+        .retry(reqwest::retry::Policy::none())
+        .transport(tower::service_fn(|_req: Request<Body>| async {
+            std::future::pending::<Result<Response<String>, Infallible>>().await
+        }))
+        .build()
+        .unwrap();
+
+    let err = client.get("http://api.test/slow").send().await.unwrap_err();
+    assert!(err.is_timeout());
+}
+
+#[tokio::test]
+async fn transport_errors() {
+    let client = reqwest::Client::builder()
+        .retry(reqwest::retry::Policy::none())
+        .transport(tower::service_fn(|_req: Request<Body>| async {
+            Err::<Response<String>, _>(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "mock refused",
+            ))
+        }))
+        .build()
+        .unwrap();
+
+    let err = client.get("http://api.test/").send().await.unwrap_err();
+    assert!(err.is_request());
+    assert_eq!(err.url().unwrap().as_str(), "http://api.test/");
+}
+
+#[cfg(feature = "cookies")]
+#[tokio::test]
+async fn cookies_are_stored() {
+    let client = reqwest::Client::builder()
+        .cookie_store(true)
+        .transport(tower::service_fn(|req: Request<Body>| {
+            let res = match req.headers().get("cookie") {
+                Some(cookie) => Response::new(cookie.to_str().unwrap().to_owned()),
+                None => Response::builder()
+                    .header("set-cookie", "session=abc")
+                    .body(String::new())
+                    .unwrap(),
+            };
+            async move { Ok::<_, Infallible>(res) }
+        }))
+        .build()
+        .unwrap();
+
+    client.get("http://api.test/login").send().await.unwrap();
+    let res = client.get("http://api.test/me").send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "session=abc");
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn responses_are_decompressed() {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(b"inflated").unwrap();
+    let gzipped = encoder.finish().unwrap();
+
+    let client = reqwest::Client::builder()
+        .transport(tower::service_fn(move |req: Request<Body>| {
+            let accept_encoding = req.headers()["accept-encoding"].to_str().unwrap();
+            assert!(accept_encoding.contains("gzip"));
+            let res = Response::builder()
+                .header("content-encoding", "gzip")
+                .body(http_body_util::Full::new(bytes::Bytes::from(
+                    gzipped.clone(),
+                )))
+                .unwrap();
+            async move { Ok::<_, Infallible>(res) }
+        }))
+        .build()
+        .unwrap();
+
+    let res = client.get("http://api.test/").send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "inflated");
+}
@@ -0,0 +1,365 @@
+#![cfg(not(target_arch = "wasm32"))]
+#![cfg(not(feature = "rustls-tls-manual-roots-no-provider"))]
+mod support;
+use std::error::Error as _;
+use std::future::Future;
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use hyper_util::rt::TokioIo;
+use reqwest::websocket::{CloseFrame, Message};
+use sha1::{Digest, Sha1};
+use support::server;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+type Upgraded = TokioIo<hyper::upgrade::Upgraded>;
+
+/// Accept WebSocket handshakes, with the first offered subprotocol and
+/// the given `Sec-WebSocket-Extensions`, and run `session` on the
+/// upgraded connections.
+fn ws_server<F, Fut>(extensions: Option<&'static str>, session: F) -> server::Server
+where
+    F: Fn(Upgraded) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    server::http(move |req| {
+        let res = handshake(req, extensions, session.clone());
+        async move { res }
+    })
+}
+
+/// Accept the WebSocket handshake `req`, and run `session` on the upgraded
+/// connection.
+fn handshake<F, Fut>(
+    req: http::Request<hyper::body::Incoming>,
+    extensions: Option<&'static str>,
+    session: F,
+) -> http::Response<reqwest::Body>
+where
+    F: FnOnce(Upgraded) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    assert_eq!(req.version(), http::Version::HTTP_11);
+    assert_eq!(req.headers()["connection"], "upgrade");
+    assert_eq!(req.headers()["upgrade"], "websocket");
+    assert_eq!(req.headers()["sec-websocket-version"], "13");
+
+    let mut sha1 = Sha1::new();
+    sha1.update(req.headers()["sec-websocket-key"].as_bytes());
+    sha1.update(b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11");
+    let mut res = http::Response::builder()
+        .status(http::StatusCode::SWITCHING_PROTOCOLS)
+        .header("connection", "Upgrade")
+        .header("upgrade", "websocket")
+        .header(
+            "sec-websocket-accept",
+            BASE64_STANDARD.encode(sha1.finalize()),
+        );
+    if let Some(protocols) = req.headers().get("sec-websocket-protocol") {
+        let protocols = protocols.to_str().unwrap();
+        res = res.header(
+            "sec-websocket-protocol",
+            protocols.split(',').next().unwrap(),
+        );
+    }
+    if let Some(extensions) = extensions {
+        res = res.header("sec-websocket-extensions", extensions);
+    }
+
+    tokio::spawn(async move {
+        session(TokioIo::new(hyper::upgrade::on(req).await.unwrap())).await;
+    });
+    res.body(reqwest::Body::default()).unwrap()
+}
+
+/// Read a frame from the client, returning its first byte and unmasked
+/// payload.
+async fn read_frame<T: AsyncRead + Unpin>(io: &mut T) -> (u8, Vec<u8>) {
+    let mut header = [0; 2];
+    io.read_exact(&mut header).await.unwrap();
+    assert_eq!(header[1] & 0x80, 0x80, "client frames are masked");
+    let len = match header[1] & 0x7f {
+        126 => io.read_u16().await.unwrap() as usize,
+        127 => io.read_u64().await.unwrap() as usize,
+        len => len as usize,
+    };
+    let mut mask = [0; 4];
+    io.read_exact(&mut mask).await.unwrap();
+    let mut payload = vec![0; len];
+    io.read_exact(&mut payload).await.unwrap();
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+    (header[0], payload)
+}
+
+/// Write an unmasked frame, with `first` as its first byte.
+async fn write_frame<T: AsyncWrite + Unpin>(io: &mut T, first: u8, payload: &[u8]) {
+    let mut frame = vec![first];
+    if payload.len() < 126 {
+        frame.push(payload.len() as u8);
+    } else {
+        frame.push(126);
+        frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    }
+    frame.extend_from_slice(payload);
+    io.write_all(&frame).await.unwrap();
+}
+
+/// Echo the frames of the client, until it closes the connection.
+async fn echo(mut io: Upgraded) {
+    loop {
+        let (first, payload) = read_frame(&mut io).await;
+        write_frame(&mut io, first, &payload).await;
+        if first & 0x0f == 0x8 {
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn websocket_echo() {
+    let server = ws_server(None, echo);
+
+    let mut ws = reqwest::Client::new()
+        .websocket(format!("ws://{}/echo", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(ws.protocol(), None);
+
+    ws.send("Hello".into()).await.unwrap();
+    assert_eq!(
+        ws.next().await.unwrap().unwrap(),
+        Message::Text("Hello".into())
+    );
+    let data = vec![7; 1000];
+    ws.send(data.clone().into()).await.unwrap();
+    assert_eq!(
+        ws.next().await.unwrap().unwrap(),
+        Message::Binary(data.into())
+    );
+
+    let close = CloseFrame {
+        code: 1000,
+        reason: "done".into(),
+    };
+    ws.close(Some(close)).await.unwrap();
+}
+
+/// A `wss://` server whose TLS handshake offers HTTP/2 as well, but which
+/// only accepts WebSocket handshakes over HTTP/1.1.
+#[cfg(feature = "__rustls")]
+async fn wss_server() -> std::net::SocketAddr {
+    use std::sync::Arc;
+
+    let cert = std::fs::read("tests/support/server.cert").unwrap().into();
+    let key = std::fs::read("tests/support/server.key")
+        .unwrap()
+        .try_into()
+        .unwrap();
+    let mut tls_config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert], key)
+        .unwrap();
+    tls_config.alpn_protocols = vec![b"h2".into(), b"http/1.1".into()];
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(tls_config));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (io, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let io = acceptor.accept(io).await.unwrap();
+                assert_eq!(io.get_ref().1.alpn_protocol(), Some(&b"http/1.1"[..]));
+                let service = hyper::service::service_fn(|req| async move {
+                    Ok::<_, std::convert::Infallible>(handshake(req, None, echo))
+                });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(io), service)
+                    .with_upgrades()
+                    .await;
+            });
+        }
+    });
+    addr
+}
+
+#[cfg(feature = "__rustls")]
+#[tokio::test]
+async fn websocket_over_tls() {
+    let addr = wss_server().await;
+
+    let client = reqwest::Client::builder()
+        .use_rustls_tls()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let mut ws = client
+        .websocket(format!("wss://localhost:{}/", addr.port()))
+        .send()
+        .await
+        .unwrap();
+
+    ws.send("Hello".into()).await.unwrap();
+    assert_eq!(
+        ws.next().await.unwrap().unwrap(),
+        Message::Text("Hello".into())
+    );
+}
+
+#[tokio::test]
+async fn websocket_permessage_deflate() {
+    let server = ws_server(
+        Some("permessage-deflate; server_max_window_bits=15"),
+        |mut io| async move {
+            // the client compresses, and the same stream can be decompressed
+            for _ in 0..2 {
+                let (first, payload) = read_frame(&mut io).await;
+                assert_eq!(first, 0xc1);
+                assert!(payload.len() < 100);
+                write_frame(&mut io, first, &payload).await;
+            }
+            // an uncompressed message is allowed too
+            write_frame(&mut io, 0x81, b"plain").await;
+        },
+    );
+
+    let mut ws = reqwest::Client::new()
+        .websocket(format!("ws://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap();
+
+    let text = "compressible ".repeat(100);
+    for _ in 0..2 {
+        ws.send(text.as_str().into()).await.unwrap();
+        assert_eq!(
+            ws.next().await.unwrap().unwrap(),
+            Message::Text(text.clone())
+        );
+    }
+    assert_eq!(
+        ws.next().await.unwrap().unwrap(),
+        Message::Text("plain".into())
+    );
+}
+
+#[tokio::test]
+async fn websocket_without_permessage_deflate() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.headers().get("sec-websocket-extensions"), None);
+        http::Response::builder()
+            .status(http::StatusCode::BAD_REQUEST)
+            .body(reqwest::Body::default())
+            .unwrap()
+    });
+
+    let err = reqwest::Client::new()
+        .websocket(format!("ws://{}/", server.addr()))
+        .permessage_deflate(false)
+        .send()
+        .await
+        .unwrap_err();
+    let source = err.source().unwrap().to_string();
+    assert!(source.contains("400 Bad Request"), "{source}");
+}
+
+#[tokio::test]
+async fn websocket_subprotocol() {
+    let server = ws_server(None, echo);
+
+    let ws = reqwest::Client::new()
+        .websocket(format!("ws://{}/", server.addr()))
+        .protocols(["chat.v2", "chat.v1"])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(ws.protocol(), Some("chat.v2"));
+}
+
+#[tokio::test]
+async fn websocket_answers_ping_and_close() {
+    let server = ws_server(None, |mut io| async move {
+        write_frame(&mut io, 0x89, b"ping").await;
+        assert_eq!(read_frame(&mut io).await, (0x8a, b"ping".to_vec()));
+
+        write_frame(&mut io, 0x88, b"\x03\xe9going away").await;
+        assert_eq!(read_frame(&mut io).await, (0x88, b"\x03\xe9".to_vec()));
+    });
+
+    let mut ws = reqwest::Client::new()
+        .websocket(format!("ws://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        ws.next().await.unwrap().unwrap(),
+        Message::Ping("ping".into())
+    );
+    let close = CloseFrame {
+        code: 1001,
+        reason: "going away".into(),
+    };
+    assert_eq!(
+        ws.next().await.unwrap().unwrap(),
+        Message::Close(Some(close))
+    );
+    assert!(ws.next().await.is_none());
+    assert!(ws.send("late".into()).await.is_err());
+}
+
+#[tokio::test]
+async fn websocket_max_message_size() {
+    let server = ws_server(None, |mut io| async move {
+        write_frame(&mut io, 0x82, &[0; 200]).await;
+        let _ = read_frame(&mut io).await;
+    });
+
+    let mut ws = reqwest::Client::new()
+        .websocket(format!("ws://{}/", server.addr()))
+        .max_message_size(100)
+        .send()
+        .await
+        .unwrap();
+
+    let err = ws.next().await.unwrap().unwrap_err();
+    assert!(err.is_body());
+}
+
+#[tokio::test]
+async fn websocket_invalid_accept_key() {
+    let server = server::http(move |_req| async {
+        http::Response::builder()
+            .status(http::StatusCode::SWITCHING_PROTOCOLS)
+            .header("connection", "upgrade")
+            .header("upgrade", "websocket")
+            .header("sec-websocket-accept", "invalid")
+            .body(reqwest::Body::default())
+            .unwrap()
+    });
+
+    let err = reqwest::Client::new()
+        .websocket(format!("ws://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "invalid `Sec-WebSocket-Accept` header"
+    );
+}
+
+#[tokio::test]
+async fn websocket_bad_scheme() {
+    let err = reqwest::Client::new()
+        .websocket("ftp://example.com/")
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_builder());
+}
@@ -566,3 +566,24 @@ async fn test_chunked_fragmented_response_with_extra_bytes() {
     assert!(err.is_decode());
     assert!(start.elapsed() >= DELAY_BETWEEN_RESPONSE_PARTS - DELAY_MARGIN);
 }
+
+#[tokio::test]
+async fn zstd_request_body() {
+    let server = server::http(move |req| async move {
+        use http_body_util::BodyExt;
+
+        assert_eq!(req.headers()["content-encoding"], "zstd");
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(zstd_crate::decode_all(&body[..]).unwrap(), b"hello world");
+        http::Response::default()
+    });
+
+    let res = reqwest::Client::new()
+        .post(format!("http://{}/upload", server.addr()))
+        .body("hello world")
+        .compress(reqwest::Encoding::Zstd)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+}